@@ -0,0 +1,61 @@
+#![allow(dead_code)]
+
+use crate::index::{read_workdir_blob, Index, IndexEntry};
+use crate::repository::{RepError, Repository};
+
+/// all files below a folder of the working directory (relative to the root)
+fn collect_files(rep: &Repository, dir: &str, files: &mut Vec<String>) -> Result<(), RepError> {
+    let full = rep.get_workdir().clone() + "/" + dir;
+    let io_error = |e| RepError::Io(full.clone(), e);
+
+    for entry in std::fs::read_dir(&full).map_err(io_error)? {
+        let entry = entry.map_err(io_error)?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name == ".git" {
+            continue;
+        }
+
+        let path = if dir.is_empty() {
+            name
+        } else {
+            dir.to_string() + "/" + &name
+        };
+
+        if entry.file_type().map_err(io_error)?.is_dir() {
+            collect_files(rep, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// stage files and everything inside of folders, tracked files which are gone are removed
+pub fn add(rep: &Repository, files: &[String]) -> Result<(), RepError> {
+    let mut index = Index::load(rep)?;
+
+    let mut paths = vec![];
+    for file in files {
+        let path = rep.relative_path(file);
+        let full = rep.get_workdir().clone() + "/" + &path;
+
+        if std::path::Path::new(&full).is_dir() {
+            collect_files(rep, &path, &mut paths)?;
+        } else if std::path::Path::new(&full).exists() {
+            paths.push(path);
+        } else if index.get_entries().iter().any(|e| e.get_name() == path) {
+            // the file got deleted
+            index.remove(&path);
+        } else {
+            return Err(RepError::InvalidPathspec(file.clone()));
+        }
+    }
+
+    for path in paths {
+        let sha = read_workdir_blob(rep, &path)?.save(rep)?;
+        index.add(IndexEntry::from_workdir(rep, &path, &sha));
+    }
+
+    index.save(rep)
+}
@@ -0,0 +1,749 @@
+#![allow(dead_code)]
+
+use std::io::Write;
+
+use chrono::{Datelike, Local, TimeZone, Timelike};
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc, GzBuilder};
+
+use crate::attributes::{Attributes, State};
+use crate::diff::is_binary;
+use crate::objects::{Object, KVLM};
+use crate::oid::ObjectId;
+use crate::pathspec::Pathspec;
+use crate::repository::{RepError, Repository};
+use crate::sequencer::short;
+use crate::signature::{DateFormat, Signature};
+
+/// the archive formats, like `git archive --list`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl Format {
+    pub const NAMES: [&'static str; 4] = ["tar", "tgz", "tar.gz", "zip"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "tar" => Some(Self::Tar),
+            "tgz" | "tar.gz" => Some(Self::TarGz),
+            "zip" => Some(Self::Zip),
+            _ => None,
+        }
+    }
+
+    /// the format matching the extension of an output file
+    pub fn from_filename(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .filter(|ext| name.ends_with(&format!(".{}", ext)))
+            .max_by_key(|ext| ext.len())
+            .and_then(|ext| Self::parse(ext))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub format: Format,
+    /// prepended to every path, usually ending with a slash
+    pub prefix: String,
+}
+
+/// a file, symlink or folder to put in the archive
+struct Entry {
+    path: String,
+    mode: u32,
+    hash: ObjectId,
+    /// the path inside of the tree, for the attributes
+    tree_path: String,
+}
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+const S_IFGITLINK: u32 = 0o160000;
+
+/// like git the permissions given to everyone are restricted by tar.umask (002)
+const TAR_UMASK: u32 = 0o002;
+
+/// tar writes in records of 512 bytes, grouped in blocks of 20 records
+const RECORD_SIZE: usize = 512;
+const BLOCK_SIZE: usize = RECORD_SIZE * 20;
+
+/// a commit or tree to archive, the commit id goes in the pax header / zip comment
+struct Source {
+    commit: Option<ObjectId>,
+    tree: ObjectId,
+    /// the commit time, or the current time for trees
+    mtime: i64,
+}
+
+/// a pax extended header record "<length> <key>=<value>\n", the length includes itself
+fn ext_header_record(key: &str, value: &[u8]) -> Vec<u8> {
+    let len = key.len() + value.len() + 3;
+    let mut total = len + 1;
+    while total != len + total.to_string().len() {
+        total = len + total.to_string().len();
+    }
+
+    let mut record = format!("{} {}=", total, key).into_bytes();
+    record.extend_from_slice(value);
+    record.push(b'\n');
+    record
+}
+
+/// writes a tar stream like git archive does
+struct TarWriter<W: Write> {
+    out: W,
+    mtime: i64,
+    /// bytes written into the current block
+    offset: usize,
+}
+
+impl<W: Write> TarWriter<W> {
+    fn write_padded(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.out.write_all(data)?;
+        let padding = (RECORD_SIZE - data.len() % RECORD_SIZE) % RECORD_SIZE;
+        self.out.write_all(&vec![0; padding])?;
+        self.offset = (self.offset + data.len() + padding) % BLOCK_SIZE;
+        Ok(())
+    }
+
+    /// a ustar header, name / linkname / prefix have to fit already
+    fn header(
+        &mut self,
+        typeflag: u8,
+        name: &[u8],
+        prefix: &[u8],
+        link: &[u8],
+        mode: u32,
+        size: usize,
+    ) -> std::io::Result<()> {
+        let mut header = [0u8; RECORD_SIZE];
+        let mut field = |offset: usize, value: &[u8]| {
+            header[offset..offset + value.len()].copy_from_slice(value);
+        };
+
+        field(0, name);
+        field(100, format!("{:07o}", mode & 0o7777).as_bytes());
+        field(108, b"0000000");
+        field(116, b"0000000");
+        let size = match typeflag {
+            b'0' | b'x' | b'g' => size,
+            _ => 0,
+        };
+        field(124, format!("{:011o}", size).as_bytes());
+        field(136, format!("{:011o}", self.mtime.max(0)).as_bytes());
+        field(156, &[typeflag]);
+        field(157, link);
+        field(257, b"ustar\0");
+        field(263, b"00");
+        field(265, b"root");
+        field(297, b"root");
+        field(329, b"0000000");
+        field(337, b"0000000");
+        field(345, prefix);
+
+        // the checksum is computed with its own field filled with spaces
+        header[148..156].copy_from_slice(b"        ");
+        let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..156].copy_from_slice(format!("{:07o}\0", checksum).as_bytes());
+
+        self.write_padded(&header)
+    }
+
+    /// the pax_global_header with the id of the archived commit
+    fn global_header(&mut self, commit: &ObjectId) -> std::io::Result<()> {
+        let record = ext_header_record("comment", commit.to_string().as_bytes());
+        self.header(b'g', b"pax_global_header", b"", b"", 0o100666, record.len())?;
+        self.write_padded(&record)
+    }
+
+    fn entry(&mut self, entry: &Entry, data: &[u8]) -> std::io::Result<()> {
+        let path = entry.path.as_bytes();
+        let (typeflag, mode) = match entry.mode & S_IFMT {
+            S_IFDIR | S_IFGITLINK => (b'5', (entry.mode | 0o777) & !TAR_UMASK),
+            S_IFLNK => (b'2', entry.mode | 0o777),
+            _ => match entry.mode & 0o100 {
+                0 => (b'0', (entry.mode | 0o666) & !TAR_UMASK),
+                _ => (b'0', (entry.mode | 0o777) & !TAR_UMASK),
+            },
+        };
+
+        // long paths are split into prefix and name, or stored in an extended header
+        let mut ext_header = vec![];
+        let mut name = path.to_vec();
+        let mut prefix = vec![];
+        if path.len() > 100 {
+            let split = path_prefix(path, 155);
+            let rest = path.len() - split - 1;
+            if split > 0 && rest <= 100 {
+                prefix = path[..split].to_vec();
+                name = path[split + 1..].to_vec();
+            } else {
+                name = format!("{}.data", entry.hash).into_bytes();
+                ext_header.extend(ext_header_record("path", path));
+            }
+        }
+
+        let mut link = vec![];
+        if typeflag == b'2' {
+            match data.len() > 100 {
+                true => {
+                    link = format!("see {}.paxheader", entry.hash).into_bytes();
+                    ext_header.extend(ext_header_record("linkpath", data));
+                }
+                false => link = data.to_vec(),
+            }
+        }
+
+        if !ext_header.is_empty() {
+            let name = format!("{}.paxheader", entry.hash);
+            self.header(b'x', name.as_bytes(), b"", b"", 0o100666, ext_header.len())?;
+            self.write_padded(&ext_header)?;
+        }
+        self.header(typeflag, &name, &prefix, &link, mode, data.len())?;
+        if typeflag == b'0' && !data.is_empty() {
+            self.write_padded(data)?;
+        }
+        Ok(())
+    }
+
+    /// fill the last block with zeros, at least two empty records end the archive
+    fn finish(mut self) -> std::io::Result<W> {
+        let tail = BLOCK_SIZE - self.offset;
+        self.out.write_all(&vec![0; tail])?;
+        if tail < 2 * RECORD_SIZE {
+            self.out.write_all(&[0; BLOCK_SIZE])?;
+        }
+        Ok(self.out)
+    }
+}
+
+/// the length of the part of a path before the slash it can be split at
+fn path_prefix(path: &[u8], max: usize) -> usize {
+    let mut i = path.len();
+    if i > 1 && path[i - 1] == b'/' {
+        i -= 1;
+    }
+    i = i.min(max);
+    loop {
+        if i == 0 {
+            return 0;
+        }
+        i -= 1;
+        if i == 0 || path[i] == b'/' {
+            return i;
+        }
+    }
+}
+
+/// the date and time of the local timezone in the format of ms-dos
+fn dos_time(time: i64) -> (u16, u16) {
+    let time = Local
+        .timestamp_opt(time, 0)
+        .single()
+        .unwrap_or_else(|| Local.timestamp_opt(0, 0).unwrap());
+    let date =
+        ((time.year() - 1980).max(0) as u16) << 9 | (time.month() as u16) << 5 | time.day() as u16;
+    let clock =
+        (time.hour() as u16) << 11 | (time.minute() as u16) << 5 | (time.second() / 2) as u16;
+    (date, clock)
+}
+
+/// write a zip file like git archive does
+fn write_zip(
+    out: &mut impl Write,
+    entries: &[Entry],
+    source: &Source,
+    content: impl Fn(&Entry) -> std::io::Result<Vec<u8>>,
+) -> std::io::Result<()> {
+    let (date, time) = dos_time(source.mtime);
+    // the extended timestamp field with the modification time
+    let mut extra = vec![0x55, 0x54, 5, 0, 1];
+    extra.extend_from_slice(&(source.mtime as u32).to_le_bytes());
+
+    let mut offset = 0usize;
+    let mut directory = vec![];
+    for entry in entries {
+        let is_dir = matches!(entry.mode & S_IFMT, S_IFDIR | S_IFGITLINK);
+        let is_link = entry.mode & S_IFMT == S_IFLNK;
+        let path = &entry.path;
+
+        let mut creator: u16 = 0;
+        let data = content(entry)?;
+        let data = data.as_slice();
+        let external = match (is_dir, is_link) {
+            (true, _) => 16,
+            (false, true) => (entry.mode | 0o777) << 16,
+            (false, false) if entry.mode & 0o111 != 0 => entry.mode << 16,
+            (false, false) => 0,
+        };
+        if is_link || (!is_dir && entry.mode & 0o111 != 0) {
+            creator = 0x0317;
+        }
+        let text = !is_dir && !is_binary(data);
+
+        let mut crc = Crc::new();
+        crc.update(data);
+
+        // small files which don't get smaller are stored as they are
+        let mut method: u16 = 0;
+        let mut compressed = data.to_vec();
+        if !is_dir && !is_link && !data.is_empty() {
+            let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+            encoder.write_all(data)?;
+            let deflated = encoder.finish()?;
+            if deflated.len() < data.len() {
+                method = 8;
+                compressed = deflated;
+            }
+        }
+        // like git the version needed to extract is always 1.0
+        let version: u16 = 10;
+        let flags: u16 = match path.is_ascii() {
+            true => 0,
+            false => 0x800,
+        };
+
+        let mut common = vec![];
+        common.extend_from_slice(&version.to_le_bytes());
+        common.extend_from_slice(&flags.to_le_bytes());
+        common.extend_from_slice(&method.to_le_bytes());
+        common.extend_from_slice(&time.to_le_bytes());
+        common.extend_from_slice(&date.to_le_bytes());
+        common.extend_from_slice(&crc.sum().to_le_bytes());
+        common.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(path.len() as u16).to_le_bytes());
+        common.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+
+        let mut local = vec![0x50, 0x4b, 0x03, 0x04];
+        local.extend_from_slice(&common);
+        local.extend_from_slice(path.as_bytes());
+        local.extend_from_slice(&extra);
+        out.write_all(&local)?;
+        out.write_all(&compressed)?;
+
+        directory.extend_from_slice(&[0x50, 0x4b, 0x01, 0x02]);
+        directory.extend_from_slice(&creator.to_le_bytes());
+        directory.extend_from_slice(&common);
+        directory.extend_from_slice(&0u16.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes());
+        directory.extend_from_slice(&(text as u16).to_le_bytes());
+        directory.extend_from_slice(&external.to_le_bytes());
+        directory.extend_from_slice(&(offset as u32).to_le_bytes());
+        directory.extend_from_slice(path.as_bytes());
+        directory.extend_from_slice(&extra);
+
+        offset += local.len() + compressed.len();
+    }
+    out.write_all(&directory)?;
+
+    let comment = source.commit.map(|c| c.to_string()).unwrap_or_default();
+    let mut end = vec![0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0];
+    end.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    end.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    end.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    end.extend_from_slice(&(offset as u32).to_le_bytes());
+    end.extend_from_slice(&(comment.len() as u16).to_le_bytes());
+    end.extend_from_slice(comment.as_bytes());
+    out.write_all(&end)
+}
+
+/// expand the $Format:...$ placeholders of files with the export-subst attribute
+fn expand_subst(rep: &Repository, commit: &ObjectId, data: &[u8]) -> Vec<u8> {
+    let text = String::from_utf8_lossy(data);
+    let mut result = String::new();
+    let mut rest = text.as_ref();
+
+    while let Some(start) = rest.find("$Format:") {
+        let format = &rest[start + 8..];
+        let end = match format.find('$') {
+            Some(end) => end,
+            None => break,
+        };
+        result.push_str(&rest[..start]);
+        result.push_str(&format_commit(rep, commit, &format[..end]));
+        rest = &format[end + 1..];
+    }
+    result.push_str(rest);
+
+    result.into_bytes()
+}
+
+/// the placeholders of git log --pretty=format: which make sense in a file
+fn format_commit(rep: &Repository, hash: &ObjectId, format: &str) -> String {
+    let commit = match Object::load(rep, hash) {
+        Ok(Object::Commit(c)) => c,
+        _ => return format.to_string(),
+    };
+    let message = commit.get_message();
+    let (subject, body) = match message.split_once("\n\n") {
+        Some((subject, body)) => (subject.replace('\n', " "), body.to_string()),
+        None => (message.trim_end().replace('\n', " "), String::new()),
+    };
+    let parents = commit.get_parents();
+    let person = |signature: Signature, c: char| -> Option<String> {
+        let date = |format: DateFormat| signature.format_date(&format);
+        Some(match c {
+            'n' => signature.name.clone(),
+            'e' => signature.email.clone(),
+            'd' => date(DateFormat::Default),
+            'D' => date(DateFormat::Rfc),
+            't' => signature.seconds.to_string(),
+            'i' => date(DateFormat::Iso),
+            'I' => date(DateFormat::IsoStrict),
+            's' => date(DateFormat::Short),
+            'r' => date(DateFormat::Relative),
+            _ => return None,
+        })
+    };
+
+    let mut result = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        let expanded = match chars.next() {
+            Some('H') => Some(hash.to_string()),
+            Some('h') => Some(short(hash)),
+            Some('T') => Some(commit.get_tree().to_string()),
+            Some('t') => Some(short(&commit.get_tree())),
+            Some('P') => Some(
+                parents
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" "),
+            ),
+            Some('p') => Some(parents.iter().map(short).collect::<Vec<String>>().join(" ")),
+            Some('s') => Some(subject.clone()),
+            Some('b') => Some(body.clone()),
+            Some('B') => Some(message.clone()),
+            Some('n') => Some("\n".to_string()),
+            Some('%') => Some("%".to_string()),
+            Some(who @ ('a' | 'c')) => {
+                let signature = match who {
+                    'a' => commit.get_author(),
+                    _ => commit.get_committer(),
+                };
+                let field = chars.next();
+                match field.and_then(|f| person(signature, f)) {
+                    Some(value) => Some(value),
+                    None => Some(format!(
+                        "%{}{}",
+                        who,
+                        field.map(String::from).unwrap_or_default()
+                    )),
+                }
+            }
+            // unknown placeholders are kept as they are
+            Some(other) => Some(format!("%{}", other)),
+            None => Some("%".to_string()),
+        };
+        result.push_str(&expanded.unwrap_or_default());
+    }
+
+    result
+}
+
+/// the files and folders of a tree, in the order of the tree with folders before their content
+fn collect_entries(
+    rep: &Repository,
+    tree: &ObjectId,
+    base: &str,
+    attributes: &Attributes,
+    pathspec: &Pathspec,
+    entries: &mut Vec<Entry>,
+) -> Result<bool, RepError> {
+    let tree = match Object::load(rep, tree)? {
+        Object::Tree(t) => t,
+        _ => return Err(RepError::BadObject("tree".to_string(), tree.to_string())),
+    };
+
+    let mut found = false;
+    for object in tree.get_objects() {
+        let path = base.to_string() + object.get_name();
+        if attributes.get(&path, "export-ignore") == Some(State::Set) {
+            continue;
+        }
+        let mode = u32::from_str_radix(object.get_mode(), 8).unwrap_or(S_IFREG | 0o644);
+
+        if object.is_tree() {
+            if !pathspec.matches_dir(&path) {
+                continue;
+            }
+            // the folder is only included if something inside of it is
+            let position = entries.len();
+            entries.push(Entry {
+                path: path.clone() + "/",
+                mode,
+                hash: *object.get_hash(),
+                tree_path: path.clone(),
+            });
+            let inside = collect_entries(
+                rep,
+                object.get_hash(),
+                &(path + "/"),
+                attributes,
+                pathspec,
+                entries,
+            )?;
+            if !inside && entries.len() == position + 1 {
+                entries.pop();
+            }
+            found |= inside;
+            continue;
+        }
+        if !pathspec.matches(&path) {
+            continue;
+        }
+
+        entries.push(Entry {
+            path: match mode & S_IFMT {
+                S_IFGITLINK => path.clone() + "/",
+                _ => path.clone(),
+            },
+            mode,
+            hash: *object.get_hash(),
+            tree_path: path,
+        });
+        found = true;
+    }
+
+    Ok(found)
+}
+
+/// write an archive of a tree-ish to out
+pub fn archive(
+    rep: &Repository,
+    tree_ish: &str,
+    paths: &[String],
+    opts: &Options,
+    out: &mut impl Write,
+) -> Result<(), RepError> {
+    let invalid = || RepError::InvalidReference(tree_ish.to_string());
+    let hash = rep.ref_resolve(tree_ish).map_err(|_| invalid())?;
+    let commit = rep.peel(&hash, "commit");
+    let tree = rep.peel(&hash, "tree").ok_or_else(invalid)?;
+    let mtime = match &commit {
+        Some(commit) => match Object::load(rep, commit)? {
+            Object::Commit(c) => c.get_committer().seconds,
+            _ => 0,
+        },
+        None => chrono::Utc::now().timestamp(),
+    };
+
+    let attributes = Attributes::for_tree(rep, &tree);
+    // like git only the current folder is archived when run in a subfolder
+    let cwd = rep.relative_path(".");
+    let paths = match paths.is_empty() {
+        true => vec![".".to_string()],
+        false => paths.to_vec(),
+    };
+    let pathspec = Pathspec::parse(rep, &paths)?;
+
+    let mut entries = vec![];
+    collect_entries(rep, &tree, "", &attributes, &pathspec, &mut entries)?;
+
+    let unmatched = pathspec.unmatched(entries.iter().map(|e| e.path.trim_end_matches('/')));
+    if let Some(spec) = unmatched.into_iter().find(|s| s != ".") {
+        return Err(RepError::InvalidPathspec(spec));
+    }
+
+    // paths are relative to the current folder
+    let base = match cwd.is_empty() {
+        true => String::new(),
+        false => cwd + "/",
+    };
+    let mut entries = entries
+        .into_iter()
+        .filter(|e| e.path.starts_with(&base) && e.path != base)
+        .map(|e| Entry {
+            path: opts.prefix.clone() + &e.path[base.len()..],
+            ..e
+        })
+        .collect::<Vec<Entry>>();
+
+    // the prefix itself is a folder too
+    if opts.prefix.ends_with('/') {
+        entries.insert(
+            0,
+            Entry {
+                path: opts.prefix.clone(),
+                mode: S_IFDIR | 0o777,
+                hash: tree,
+                tree_path: String::new(),
+            },
+        );
+    }
+
+    // the content is only loaded while writing, with $Format:...$ expanded for export-subst
+    let content = |entry: &Entry| -> std::io::Result<Vec<u8>> {
+        let data = match entry.mode & S_IFMT {
+            S_IFDIR | S_IFGITLINK => return Ok(vec![]),
+            _ => Object::load(rep, &entry.hash)
+                .map_err(std::io::Error::other)?
+                .get_data()
+                .clone(),
+        };
+        let subst = attributes.get(&entry.tree_path, "export-subst") == Some(State::Set);
+        match &commit {
+            Some(commit) if subst && entry.mode & S_IFMT == S_IFREG => {
+                Ok(expand_subst(rep, commit, &data))
+            }
+            _ => Ok(data),
+        }
+    };
+
+    let source = Source {
+        commit,
+        tree,
+        mtime,
+    };
+    let result = match opts.format {
+        Format::Tar => write_tar(out, &entries, &source, content),
+        Format::TarGz => {
+            let level = Compression::default();
+            let mut encoder = GzBuilder::new().operating_system(3).write(out, level);
+            write_tar(&mut encoder, &entries, &source, content)
+                .and_then(|_| encoder.finish().map(|_| ()))
+        }
+        Format::Zip => write_zip(out, &entries, &source, content),
+    };
+    result.map_err(|e| match e.kind() {
+        std::io::ErrorKind::BrokenPipe => RepError::Output(e),
+        _ => RepError::InvalidArgument(e.to_string()),
+    })
+}
+
+fn write_tar(
+    out: &mut impl Write,
+    entries: &[Entry],
+    source: &Source,
+    content: impl Fn(&Entry) -> std::io::Result<Vec<u8>>,
+) -> std::io::Result<()> {
+    let mut tar = TarWriter {
+        out,
+        mtime: source.mtime,
+        offset: 0,
+    };
+    if let Some(commit) = &source.commit {
+        tar.global_header(commit)?;
+    }
+    for entry in entries {
+        tar.entry(entry, &content(entry)?)?;
+    }
+    tar.finish().map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the names and type flags of the headers in a tar stream
+    fn tar_headers(tar: &[u8]) -> Vec<(String, u8)> {
+        let mut headers = vec![];
+        let mut offset = 0;
+        while offset + RECORD_SIZE <= tar.len() && tar[offset] != 0 {
+            let header = &tar[offset..offset + RECORD_SIZE];
+            let name = String::from_utf8_lossy(&header[..100]);
+            let size = std::str::from_utf8(&header[124..135]).unwrap();
+            let size = usize::from_str_radix(size.trim_matches('\0'), 8).unwrap();
+            headers.push((name.trim_end_matches('\0').to_string(), header[156]));
+            offset += RECORD_SIZE + size.div_ceil(RECORD_SIZE) * RECORD_SIZE;
+        }
+        headers
+    }
+
+    #[test]
+    fn formats_are_chosen_by_name() {
+        assert_eq!(Format::parse("tgz"), Some(Format::TarGz));
+        assert_eq!(Format::parse("7z"), None);
+        assert_eq!(Format::from_filename("out.tar.gz"), Some(Format::TarGz));
+        assert_eq!(Format::from_filename("out.zip"), Some(Format::Zip));
+        assert_eq!(Format::from_filename("out.txt"), None);
+    }
+
+    #[test]
+    fn pax_records_count_their_own_length() {
+        let record = ext_header_record("comment", &[b'0'; 40]);
+        assert!(record.starts_with(b"52 comment=0"));
+        assert_eq!(record.len(), 52);
+        let record = ext_header_record("path", &[b'a'; 93]);
+        assert!(record.starts_with(b"103 path=a"));
+        assert_eq!(record.len(), 103);
+    }
+
+    #[test]
+    fn long_paths_are_split_at_a_slash() {
+        assert_eq!(path_prefix(b"a/b/c", 155), 3);
+        assert_eq!(path_prefix(b"dir/", 155), 0);
+        assert_eq!(path_prefix(b"abc/def", 2), 0);
+    }
+
+    #[test]
+    fn trees_are_written_with_their_attributes() {
+        let rep = Repository::temporary("archive-tar");
+        let attributes = "secret.txt export-ignore\nsubst.txt export-subst\n";
+        let commit = rep.commit_files(
+            &[
+                (".gitattributes", attributes),
+                ("a.txt", "a\n"),
+                ("dir/b.txt", "b\n"),
+                ("secret.txt", "secret\n"),
+                ("subst.txt", "commit $Format:%H$ by $Format:%an$\n"),
+            ],
+            "first",
+        );
+        let tree = rep.peel(&commit, "tree").unwrap();
+
+        let mut entries = vec![];
+        let pathspec = Pathspec::parse(&rep, &[":/".to_string()]).unwrap();
+        let attributes = Attributes::for_tree(&rep, &tree);
+        collect_entries(&rep, &tree, "", &attributes, &pathspec, &mut entries).unwrap();
+        let paths = entries
+            .iter()
+            .map(|e| e.path.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            paths,
+            [".gitattributes", "a.txt", "dir/", "dir/b.txt", "subst.txt"]
+        );
+
+        let data = expand_subst(&rep, &commit, b"commit $Format:%H$ by $Format:%an$\n");
+        assert_eq!(data, format!("commit {} by T\n", commit).into_bytes());
+
+        let source = Source {
+            commit: Some(commit),
+            tree,
+            mtime: 1700000000,
+        };
+        let mut tar = vec![];
+        let content = |entry: &Entry| match entry.mode & S_IFMT {
+            S_IFDIR => Ok(vec![]),
+            _ => Ok(Object::load(&rep, &entry.hash).unwrap().get_data().clone()),
+        };
+        write_tar(&mut tar, &entries, &source, content).unwrap();
+        assert_eq!(tar.len() % BLOCK_SIZE, 0);
+        assert_eq!(
+            tar_headers(&tar),
+            [
+                ("pax_global_header".to_string(), b'g'),
+                (".gitattributes".to_string(), b'0'),
+                ("a.txt".to_string(), b'0'),
+                ("dir/".to_string(), b'5'),
+                ("dir/b.txt".to_string(), b'0'),
+                ("subst.txt".to_string(), b'0'),
+            ]
+        );
+    }
+}
@@ -0,0 +1,274 @@
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::diff::is_binary;
+use crate::ignore::wildmatch;
+use crate::merge::tree_files;
+use crate::objects::Object;
+use crate::oid::ObjectId;
+use crate::repository::Repository;
+
+/// the state of an attribute for a path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum State {
+    /// `attr`
+    Set,
+    /// `-attr`
+    Unset,
+    /// `attr=value`
+    Value(String),
+}
+
+/// a line of a .gitattributes file
+#[derive(Debug, Clone)]
+struct Rule {
+    /// folder of the .gitattributes file relative to the root, "" for the root
+    base: String,
+    pattern: String,
+    /// patterns containing a slash only match relative to their base
+    anchored: bool,
+    attributes: Vec<(String, State)>,
+}
+
+/// the attributes of .gitattributes files, .git/info/attributes and core.attributesFile
+#[derive(Default)]
+pub struct Attributes {
+    rules: Vec<Rule>,
+}
+
+impl Attributes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// all attribute files which apply to a path (relative to the root)
+    /// later files have a higher priority: global, root, sub folders and .git/info/attributes
+    pub fn for_path(rep: &Repository, path: &str) -> Self {
+        let mut attributes = Self::new();
+
+        if let Some(file) = rep.config_get("core", "attributesFile") {
+            let file = match file.strip_prefix("~/") {
+                Some(rest) => std::env::var("HOME").unwrap_or_default() + "/" + rest,
+                None => file,
+            };
+            attributes.add_file("", &file);
+        }
+
+        let mut base = String::new();
+        attributes.add_file("", &(rep.get_workdir().clone() + "/.gitattributes"));
+        for part in path.split('/').take(path.matches('/').count()) {
+            base = match base.is_empty() {
+                true => part.to_string(),
+                false => base + "/" + part,
+            };
+            let file = rep.get_workdir().clone() + "/" + &base + "/.gitattributes";
+            attributes.add_file(&base, &file);
+        }
+
+        attributes.add_file("", &(rep.get_gitdir().clone() + "/info/attributes"));
+        attributes
+    }
+
+    /// the attributes of the .gitattributes files inside a tree and .git/info/attributes,
+    /// used when the working directory doesn't matter like for archives
+    pub fn for_tree(rep: &Repository, tree: &ObjectId) -> Self {
+        let mut attributes = Self::new();
+
+        if let Some(file) = rep.config_get("core", "attributesFile") {
+            let file = match file.strip_prefix("~/") {
+                Some(rest) => std::env::var("HOME").unwrap_or_default() + "/" + rest,
+                None => file,
+            };
+            attributes.add_file("", &file);
+        }
+
+        let mut files = tree_files(rep, Some(tree))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(path, _)| path == ".gitattributes" || path.ends_with("/.gitattributes"))
+            .collect::<Vec<(String, (String, ObjectId))>>();
+        files.sort_by_key(|(path, _)| path.matches('/').count());
+        for (path, (_, hash)) in files {
+            let base = path.rsplit_once('/').map(|(base, _)| base).unwrap_or("");
+            if let Ok(object) = Object::load(rep, &hash) {
+                attributes.add_data(base, &String::from_utf8_lossy(object.get_data()));
+            }
+        }
+
+        attributes.add_file("", &(rep.get_gitdir().clone() + "/info/attributes"));
+        attributes
+    }
+
+    pub fn add_file(&mut self, base: &str, path: &str) {
+        if let Ok(content) = std::fs::read_to_string(path) {
+            self.add_data(base, &content);
+        }
+    }
+
+    /// add the rules of the content of a .gitattributes file, base is the folder it is in
+    pub fn add_data(&mut self, base: &str, content: &str) {
+        for line in content.lines() {
+            let mut parts = line.split_whitespace();
+            let pattern = match parts.next() {
+                Some(p) if !p.starts_with('#') => p,
+                _ => continue,
+            };
+
+            let mut attributes = vec![];
+            for attr in parts {
+                let (name, value) = if let Some(name) = attr.strip_prefix('-') {
+                    (name, State::Unset)
+                } else if let Some((name, value)) = attr.split_once('=') {
+                    (name, State::Value(value.to_string()))
+                } else {
+                    (attr, State::Set)
+                };
+
+                // the builtin binary macro
+                if name == "binary" && value == State::Set {
+                    for name in ["diff", "merge", "text"] {
+                        attributes.push((name.to_string(), State::Unset));
+                    }
+                }
+                attributes.push((name.to_string(), value));
+            }
+
+            self.rules.push(Rule {
+                base: base.to_string(),
+                pattern: pattern.trim_start_matches('/').to_string(),
+                anchored: pattern.contains('/'),
+                attributes,
+            });
+        }
+    }
+
+    /// the value of an attribute for a path, None if it is unspecified
+    pub fn get(&self, path: &str, name: &str) -> Option<State> {
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rule| rule.matches(path))
+            .find_map(|rule| {
+                rule.attributes
+                    .iter()
+                    .rev()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, v)| v.clone())
+            })
+    }
+}
+
+impl Rule {
+    fn matches(&self, path: &str) -> bool {
+        let relative = match self.base.as_str() {
+            "" => path,
+            base => match path.strip_prefix(base).and_then(|r| r.strip_prefix('/')) {
+                Some(relative) => relative,
+                None => return false,
+            },
+        };
+
+        match self.anchored {
+            true => wildmatch(&self.pattern, relative, true),
+            false => {
+                let name = relative.rsplit('/').next().unwrap_or(relative);
+                wildmatch(&self.pattern, name, false)
+            }
+        }
+    }
+}
+
+/// run the clean command of a filter driver on the data
+fn run_filter(command: &str, path: &str, data: &[u8]) -> Option<Vec<u8>> {
+    let command = command.replace("%f", path);
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+
+    // write in a thread so a filter producing output early cant block us
+    let mut stdin = child.stdin.take()?;
+    let input = data.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+
+    let output = child.wait_with_output().ok()?;
+    let _ = writer.join();
+    output.status.success().then_some(output.stdout)
+}
+
+/// apply the filter, text / eol and ident attributes to file content before it gets stored
+pub fn convert_to_git(rep: &Repository, path: &str, data: Vec<u8>) -> Vec<u8> {
+    let attributes = Attributes::for_path(rep, path);
+    let mut data = data;
+
+    if let Some(State::Value(driver)) = attributes.get(path, "filter") {
+        if let Some(clean) = rep.config_get(&format!("filter \"{}\"", driver), "clean") {
+            match run_filter(&clean, path, &data) {
+                Some(cleaned) => data = cleaned,
+                None => errln!("error: external filter '{}' failed", clean),
+            }
+        }
+    }
+
+    let autocrlf = rep
+        .config_get("core", "autocrlf")
+        .map(|v| v == "true" || v == "input")
+        .unwrap_or(false);
+    let text = match attributes.get(path, "text") {
+        Some(State::Unset) => false,
+        Some(State::Set) => true,
+        Some(State::Value(v)) if v == "auto" => !is_binary(&data),
+        // an eol attribute marks the file as text
+        _ if attributes.get(path, "eol").is_some() => true,
+        _ => autocrlf && !is_binary(&data),
+    };
+    if text {
+        data = crlf_to_lf(&data);
+    }
+
+    if attributes.get(path, "ident") == Some(State::Set) {
+        data = collapse_ident(&data);
+    }
+
+    data
+}
+
+fn crlf_to_lf(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    for (i, &b) in data.iter().enumerate() {
+        if b == b'\r' && data.get(i + 1) == Some(&b'\n') {
+            continue;
+        }
+        result.push(b);
+    }
+    result
+}
+
+/// turn expanded "$Id: <sha> $" keywords back into "$Id$"
+fn collapse_ident(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut i = 0;
+
+    while i < data.len() {
+        if data[i..].starts_with(b"$Id:") {
+            let end = data[i + 4..]
+                .iter()
+                .position(|&b| b == b'$' || b == b'\n')
+                .map(|p| i + 4 + p);
+            if let Some(end) = end.filter(|&e| data[e] == b'$') {
+                result.extend_from_slice(b"$Id$");
+                i = end + 1;
+                continue;
+            }
+        }
+        result.push(data[i]);
+        i += 1;
+    }
+
+    result
+}
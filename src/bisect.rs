@@ -0,0 +1,964 @@
+#![allow(dead_code)]
+
+use std::collections::HashSet;
+use std::process::Command;
+
+use crate::diff::{diff_trees, format_stat};
+use crate::index::{update_workdir, Index};
+use crate::objects::KVLM;
+use crate::oid::ObjectId;
+use crate::pathspec::Pathspec;
+use crate::repository::{RepError, Repository};
+use crate::revwalk::{ancestors, resolve_commit, rev_list};
+use crate::sequencer::{load_commit, short, subject};
+use crate::signature::DateFormat;
+
+/// the files git keeps the bisect state in, below .git
+const STATE_FILES: [&str; 9] = [
+    "BISECT_EXPECTED_REV",
+    "BISECT_ANCESTORS_OK",
+    "BISECT_LOG",
+    "BISECT_TERMS",
+    "BISECT_NAMES",
+    "BISECT_RUN",
+    "BISECT_START",
+    "BISECT_HEAD",
+    "BISECT_FIRST_PARENT",
+];
+
+/// the names of the two states, bad / good unless changed with --term-new / --term-old
+struct Terms {
+    new: String,
+    old: String,
+}
+
+impl Terms {
+    fn load(rep: &Repository) -> Self {
+        let content = read_state(rep, "BISECT_TERMS").unwrap_or_default();
+        let mut lines = content.lines();
+        match (lines.next(), lines.next()) {
+            (Some(new), Some(old)) => Self {
+                new: new.to_string(),
+                old: old.to_string(),
+            },
+            _ => Self {
+                new: "bad".to_string(),
+                old: "good".to_string(),
+            },
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        self.new == "bad" && self.old == "good"
+    }
+
+    /// the state a command marks commits with, bad / good can also be called new / old
+    fn state_of(&self, command: &str) -> Option<State> {
+        let default = self.is_default();
+        if command == self.new || (default && command == "new") {
+            Some(State::New)
+        } else if command == self.old || (default && command == "old") {
+            Some(State::Old)
+        } else if command == "skip" {
+            Some(State::Skip)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    New,
+    Old,
+    Skip,
+}
+
+/// what happened after commits got marked
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    /// a bad and a good commit are still needed
+    Waiting,
+    /// the next commit to test is checked out
+    Testing,
+    /// the first bad commit is found
+    Found,
+    /// the first bad commit is one of the skipped ones
+    OnlySkipped,
+    /// something went wrong, the message was already printed
+    Failed,
+}
+
+fn state_path(rep: &Repository, name: &str) -> String {
+    rep.get_gitdir().clone() + "/" + name
+}
+
+fn read_state(rep: &Repository, name: &str) -> Option<String> {
+    std::fs::read_to_string(state_path(rep, name)).ok()
+}
+
+fn write_state(rep: &Repository, name: &str, content: &str) {
+    std::fs::write(state_path(rep, name), content).unwrap();
+}
+
+fn append_log(rep: &Repository, line: &str) {
+    let log = read_state(rep, "BISECT_LOG").unwrap_or_default();
+    write_state(rep, "BISECT_LOG", &(log + line + "\n"));
+}
+
+fn is_bisecting(rep: &Repository) -> bool {
+    read_state(rep, "BISECT_START").is_some()
+}
+
+/// quote an argument for the shell like git does in BISECT_LOG
+fn sq_quote(arg: &str) -> String {
+    let quoted = arg.replace('\'', "'\\''").replace('!', "'\\!'");
+    format!("'{}'", quoted)
+}
+
+/// split a line of BISECT_LOG into words, undoing sq_quote
+fn sq_split(line: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                    word.push(c);
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    words
+}
+
+/// remove the state files and refs/bisect
+fn clean_state(rep: &Repository) {
+    for file in STATE_FILES {
+        let _ = std::fs::remove_file(state_path(rep, file));
+    }
+    for (refname, _) in rep.get_refs() {
+        if refname.starts_with("refs/bisect/") {
+            rep.delete_ref(&refname);
+        }
+    }
+}
+
+/// check out a commit with a detached HEAD, refuses to overwrite local changes
+fn checkout(rep: &Repository, hash: &ObjectId) -> Result<bool, RepError> {
+    let tree = rep
+        .peel(hash, "tree")
+        .ok_or(RepError::InvalidReference(hash.to_string()))?;
+    let current = Index::load(rep)?;
+    let mut target = Index::from_tree(rep, &tree)?;
+
+    let (old, new) = (current.to_map(), target.to_map());
+    let overwritten = current
+        .get_modified(rep)
+        .into_iter()
+        .filter(|p| old.get(p) != new.get(p))
+        .collect::<Vec<String>>();
+    if !overwritten.is_empty() {
+        errln!(
+            "error: Your local changes to the following files would be overwritten by checkout:"
+        );
+        for path in overwritten {
+            errln!("\t{}", path);
+        }
+        errln!("Please commit your changes or stash them before you switch branches.");
+        errln!("Aborting");
+        return Ok(false);
+    }
+
+    update_workdir(rep, &current, &mut target)?;
+    target.save(rep)?;
+    rep.detach_head(hash)?;
+    Ok(true)
+}
+
+/// the marked commits as (new, olds, skipped)
+fn marked(rep: &Repository, terms: &Terms) -> (Option<ObjectId>, Vec<ObjectId>, Vec<ObjectId>) {
+    let new = "refs/bisect/".to_string() + &terms.new;
+    let old = format!("refs/bisect/{}-", terms.old);
+    let mut bad = None;
+    let mut goods = vec![];
+    let mut skipped = vec![];
+
+    for (refname, hash) in rep.get_refs() {
+        if refname == new {
+            bad = Some(hash);
+        } else if refname.starts_with(&old) {
+            goods.push(hash);
+        } else if refname.starts_with("refs/bisect/skip-") {
+            skipped.push(hash);
+        }
+    }
+
+    (bad, goods, skipped)
+}
+
+/// record the state of a commit in refs/bisect and the log
+fn write_mark(
+    rep: &Repository,
+    terms: &Terms,
+    state: State,
+    hash: &ObjectId,
+    log_command: bool,
+) -> Result<(), RepError> {
+    let (term, refname) = match state {
+        State::New => (terms.new.as_str(), "refs/bisect/".to_string() + &terms.new),
+        State::Old => (
+            terms.old.as_str(),
+            format!("refs/bisect/{}-{}", terms.old, hash),
+        ),
+        State::Skip => ("skip", format!("refs/bisect/skip-{}", hash)),
+    };
+    rep.update_ref(&refname, hash)?;
+
+    append_log(
+        rep,
+        &format!("# {}: [{}] {}", term, hash, subject(rep, hash)),
+    );
+    if log_command {
+        append_log(rep, &format!("git bisect {} {}", term, hash));
+    }
+    Ok(())
+}
+
+/// the number of steps bisecting that many commits roughly takes
+fn estimate_steps(all: usize) -> usize {
+    if all < 3 {
+        return 0;
+    }
+    let n = usize::BITS - 1 - all.leading_zeros();
+    let e = 1usize << n;
+    let x = all - e;
+    match e < 3 * x {
+        true => n as usize,
+        false => n as usize - 1,
+    }
+}
+
+/// show a commit like git show --stat --summary
+fn show_commit(rep: &Repository, hash: &ObjectId) -> Result<(), RepError> {
+    let commit = load_commit(rep, hash)?;
+    let parents = commit.get_parents();
+
+    outln!("commit {}", hash)?;
+    if parents.len() > 1 {
+        let parents = parents.iter().map(short).collect::<Vec<String>>();
+        outln!("Merge: {}", parents.join(" "))?;
+    }
+    let author = commit.get_author();
+    outln!("Author: {}", author.identity())?;
+    outln!("Date:   {}", author.format_date(&DateFormat::Default))?;
+    outln!()?;
+    for line in commit.get_message().trim_end().lines() {
+        outln!("    {}", line)?;
+    }
+    outln!()?;
+
+    let parent_tree = parents.first().and_then(|p| rep.peel(p, "tree"));
+    let changes = diff_trees(rep, parent_tree.as_ref(), Some(&commit.get_tree()))?;
+    out!("{}", format_stat(rep, &changes)?)?;
+    for (path, old, new) in changes.iter() {
+        match (old, new) {
+            (None, Some((mode, _))) => outln!(" create mode {} {}", mode, path)?,
+            (Some((mode, _)), None) => outln!(" delete mode {} {}", mode, path)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn plural(n: usize, word: &str) -> String {
+    match n {
+        1 => format!("{} {}", n, word),
+        _ => format!("{} {}s", n, word),
+    }
+}
+
+/// check if a commit changes a file selected by the pathspec compared to its first parent
+fn changes_paths(rep: &Repository, hash: &ObjectId, pathspec: &Pathspec) -> Result<bool, RepError> {
+    let commit = load_commit(rep, hash)?;
+    let parent_tree = commit
+        .get_parents()
+        .first()
+        .and_then(|p| rep.peel(p, "tree"));
+    Ok(
+        diff_trees(rep, parent_tree.as_ref(), Some(&commit.get_tree()))?
+            .iter()
+            .any(|(path, _, _)| pathspec.matches(path)),
+    )
+}
+
+/// how many of the candidates each candidate reaches, in the order of the candidates
+fn weights(rep: &Repository, candidates: &[ObjectId]) -> Vec<usize> {
+    let set: HashSet<&ObjectId> = candidates.iter().collect();
+    candidates
+        .iter()
+        .map(|c| {
+            ancestors(rep, std::slice::from_ref(c))
+                .iter()
+                .filter(|a| set.contains(a))
+                .count()
+        })
+        .collect()
+}
+
+/// the commit splitting the candidates in two halves of the same size, and how many it reaches
+fn best_bisection(rep: &Repository, candidates: &[ObjectId]) -> (usize, Option<ObjectId>) {
+    let all = candidates.len();
+    let set: HashSet<&ObjectId> = candidates.iter().collect();
+    let weights = weights(rep, candidates);
+
+    // like git the oldest commit wins a tie, and the first one found half way is taken,
+    // except for those without parents to test
+    let mut best: Option<(usize, usize)> = None;
+    for (i, candidate) in candidates.iter().enumerate().rev() {
+        let distance = weights[i].min(all - weights[i]);
+        if best.is_none_or(|(d, _)| distance > d) {
+            best = Some((distance, i));
+        }
+        let has_parents = load_commit(rep, candidate)
+            .map(|c| c.get_parents().iter().any(|p| set.contains(p)))
+            .unwrap_or_default();
+        if has_parents && (2 * weights[i]).abs_diff(all) <= 1 {
+            best = Some((distance, i));
+            break;
+        }
+    }
+
+    match best {
+        Some((_, i)) => (weights[i], Some(candidates[i])),
+        None => (0, None),
+    }
+}
+
+/// like best_bisection when commits are skipped, git sorts the candidates by their distance
+/// and, if the best one is skipped, moves away from it by a pseudo random amount
+fn best_not_skipped(
+    rep: &Repository,
+    candidates: &[ObjectId],
+    skipped: &[ObjectId],
+) -> (usize, Option<ObjectId>) {
+    let all = candidates.len();
+    let weights = weights(rep, candidates);
+
+    let mut sorted = (0..all).collect::<Vec<usize>>();
+    sorted.sort_by(|&a, &b| {
+        let distance = |i: usize| weights[i].min(all - weights[i]);
+        distance(b)
+            .cmp(&distance(a))
+            .then(candidates[a].cmp(&candidates[b]))
+    });
+    let reaches = weights[sorted[0]];
+
+    let first = &candidates[sorted[0]];
+    if !skipped.contains(first) {
+        return (reaches, Some(*first));
+    }
+
+    let untested = sorted
+        .iter()
+        .map(|&i| &candidates[i])
+        .filter(|c| !skipped.contains(c))
+        .collect::<Vec<&ObjectId>>();
+    let bad = &candidates[0];
+
+    let count = untested.len() as u32;
+    let prn = get_prn(count);
+    let index = ((count * prn / PRN_MODULO) as usize * sqrti(prn)) / sqrti(PRN_MODULO);
+    let chosen = match untested.get(index) {
+        Some(&c) if c != bad => Some(c),
+        Some(_) if index > 0 => Some(untested[index - 1]),
+        _ => untested.first().copied(),
+    };
+
+    (reaches, chosen.cloned())
+}
+
+const PRN_MODULO: u32 = 32768;
+
+/// the pseudo random number generator git uses to move away from skipped commits
+fn get_prn(count: u32) -> u32 {
+    let count = count.wrapping_mul(1103515245).wrapping_add(12345);
+    (count / 65536) % PRN_MODULO
+}
+
+/// the integer square root as git computes it
+fn sqrti(val: u32) -> usize {
+    if val == 0 {
+        return 0;
+    }
+    let val = val as f32;
+    let mut x = val;
+    loop {
+        let y = (x + val / x) / 2.0;
+        let d = (y - x).abs();
+        x = y;
+        if d < 0.5 {
+            return x as usize;
+        }
+    }
+}
+
+/// check out the next commit to test or report the first bad commit
+fn next(rep: &Repository, terms: &Terms) -> Result<Outcome, RepError> {
+    let (bad, goods, skipped) = marked(rep, terms);
+    // like git the status always talks about good and bad, whatever the terms are
+    let bad = match (bad, goods.is_empty()) {
+        (None, true) => Err("waiting for both good and bad commits".to_string()),
+        (Some(_), true) => Err("waiting for good commit(s), bad commit known".to_string()),
+        (None, false) => Err(format!(
+            "waiting for bad commit, {} known",
+            plural(goods.len(), "good commit")
+        )),
+        (Some(bad), false) => Ok(bad),
+    };
+    let bad = match bad {
+        Ok(bad) => bad,
+        Err(status) => {
+            outln!("status: {}", status)?;
+            append_log(rep, &format!("# status: {}", status));
+            return Ok(Outcome::Waiting);
+        }
+    };
+
+    // with paths only the commits changing them are tested
+    let mut names = sq_split(&read_state(rep, "BISECT_NAMES").unwrap_or_default());
+    names.retain(|name| name != "--");
+    let pathspec = Pathspec::parse(rep, &names)?;
+    let mut candidates = vec![];
+    for commit in rev_list(rep, std::slice::from_ref(&bad), &goods) {
+        if commit == bad || pathspec.is_empty() || changes_paths(rep, &commit, &pathspec)? {
+            candidates.push(commit);
+        }
+    }
+    if candidates.is_empty() {
+        outln!(
+            "Some {} revs are not ancestors of the {} rev.",
+            terms.old,
+            terms.new
+        )?;
+        outln!("git bisect cannot work properly in this case.")?;
+        outln!("Maybe you mistook {} and {} revs?", terms.old, terms.new)?;
+        return Ok(Outcome::Failed);
+    }
+
+    let all = candidates.len();
+    let (reaches, chosen) = match skipped.is_empty() {
+        true => best_bisection(rep, &candidates),
+        false => best_not_skipped(rep, &candidates, &skipped),
+    };
+
+    let chosen = match chosen {
+        Some(chosen) if chosen != bad => chosen,
+        _ => {
+            let untested = candidates
+                .iter()
+                .filter(|c| skipped.contains(c))
+                .collect::<Vec<&ObjectId>>();
+            if !untested.is_empty() {
+                outln!("There are only 'skipped' commits left to test.")?;
+                outln!("The first {} commit could be any of:", terms.new)?;
+                for commit in untested {
+                    outln!("{}", commit)?;
+                }
+                outln!("{}", bad)?;
+                outln!("We cannot bisect more!")?;
+                return Ok(Outcome::OnlySkipped);
+            }
+
+            outln!("{} is the first {} commit", bad, terms.new)?;
+            show_commit(rep, &bad)?;
+            append_log(
+                rep,
+                &format!(
+                    "# first {} commit: [{}] {}",
+                    terms.new,
+                    bad,
+                    subject(rep, &bad)
+                ),
+            );
+            return Ok(Outcome::Found);
+        }
+    };
+
+    if !checkout(rep, &chosen)? {
+        return Ok(Outcome::Failed);
+    }
+    write_state(rep, "BISECT_EXPECTED_REV", &format!("{}\n", chosen));
+
+    let left = all - reaches - 1;
+    outln!(
+        "Bisecting: {} left to test after this (roughly {})",
+        plural(left, "revision"),
+        plural(estimate_steps(all), "step")
+    )?;
+    outln!("[{}] {}", chosen, subject(rep, &chosen))?;
+
+    Ok(Outcome::Testing)
+}
+
+/// bisect start [--term-new=<term> --term-old=<term>] [<bad> [<good>...]] [-- <paths>]
+fn start(rep: &Repository, args: &[String]) -> Result<Outcome, RepError> {
+    let mut terms = Terms {
+        new: "bad".to_string(),
+        old: "good".to_string(),
+    };
+    let mut revs = vec![];
+    let mut paths = vec![];
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        let mut option = |names: &[&str]| -> Option<String> {
+            for name in names {
+                if let Some(value) = arg.strip_prefix(&format!("{}=", name)) {
+                    return Some(value.to_string());
+                }
+                if arg == name {
+                    return args_iter.next().cloned();
+                }
+            }
+            None
+        };
+
+        if let Some(term) = option(&["--term-new", "--term-bad"]) {
+            terms.new = term;
+        } else if let Some(term) = option(&["--term-old", "--term-good"]) {
+            terms.old = term;
+        } else if arg == "--" {
+            // like git the separator is kept in BISECT_NAMES
+            paths.push(arg.clone());
+            paths.extend(args_iter.by_ref().cloned());
+        } else if arg == "--no-checkout" || arg == "--first-parent" {
+            return Err(RepError::InvalidArgument(format!(
+                "{} is not supported",
+                arg
+            )));
+        } else if arg.starts_with("--") {
+            return Err(RepError::InvalidArgument(format!(
+                "unrecognized option: '{}'",
+                arg
+            )));
+        } else {
+            let hash = resolve_commit(rep, arg)
+                .map_err(|_| RepError::InvalidArgument(format!("Bad rev input: {}", arg)))?;
+            revs.push(hash);
+        }
+    }
+
+    if terms.new == terms.old {
+        return Err(RepError::InvalidArgument(
+            "please use two different terms".to_string(),
+        ));
+    }
+
+    // restarting keeps the place to go back to
+    let head = match read_state(rep, "BISECT_START") {
+        Some(head) => head.trim().to_string(),
+        None => match rep.get_head_ref() {
+            Some(refname) => refname.trim_start_matches("refs/heads/").to_string(),
+            None => rep.ref_resolve("HEAD")?.to_string(),
+        },
+    };
+    clean_state(rep);
+
+    write_state(rep, "BISECT_START", &(head + "\n"));
+    write_state(
+        rep,
+        "BISECT_TERMS",
+        &format!("{}\n{}\n", terms.new, terms.old),
+    );
+    let names = paths.iter().map(|p| " ".to_string() + &sq_quote(p));
+    write_state(rep, "BISECT_NAMES", &(names.collect::<String>() + "\n"));
+
+    for (i, hash) in revs.iter().enumerate() {
+        let state = if i == 0 { State::New } else { State::Old };
+        write_mark(rep, &terms, state, hash, false)?;
+    }
+    let quoted = args.iter().map(|a| sq_quote(a)).collect::<Vec<String>>();
+    let command = match quoted.is_empty() {
+        true => "git bisect start".to_string(),
+        false => format!("git bisect start {}", quoted.join(" ")),
+    };
+    append_log(rep, &command);
+
+    next(rep, &terms)
+}
+
+/// mark commits (HEAD by default) as new, old or skipped
+fn mark(
+    rep: &Repository,
+    state: State,
+    revs: &[String],
+    auto_next: bool,
+) -> Result<Outcome, RepError> {
+    let terms = Terms::load(rep);
+    let revs = match revs.is_empty() {
+        true => vec!["HEAD".to_string()],
+        false => revs.to_vec(),
+    };
+    if state == State::New && revs.len() > 1 {
+        return Err(RepError::InvalidArgument(format!(
+            "'git bisect {}' can take only one argument.",
+            terms.new
+        )));
+    }
+
+    let mut hashes = vec![];
+    for rev in revs.iter() {
+        let hash = resolve_commit(rep, rev)
+            .map_err(|_| RepError::InvalidArgument(format!("Bad rev input: {}", rev)))?;
+        hashes.push(hash);
+    }
+    for hash in hashes {
+        write_mark(rep, &terms, state, &hash, true)?;
+    }
+
+    match auto_next {
+        true => next(rep, &terms),
+        false => Ok(Outcome::Waiting),
+    }
+}
+
+/// go back to where bisecting started (or to a commit) and remove the state
+fn reset(rep: &Repository, commit: Option<&String>) -> Result<(), RepError> {
+    let start = match read_state(rep, "BISECT_START") {
+        Some(start) => start.trim().to_string(),
+        None => {
+            outln!("We are not bisecting.")?;
+            return Ok(());
+        }
+    };
+    let target = commit.cloned().unwrap_or(start);
+
+    let branch = rep
+        .full_ref_name(&target)
+        .filter(|r| r.starts_with("refs/heads/"));
+    let hash = resolve_commit(rep, &target)?;
+
+    let previous = rep.ref_resolve("HEAD")?;
+    if !checkout(rep, &hash)? {
+        return Ok(());
+    }
+
+    match branch {
+        Some(branch) => {
+            rep.attach_head(&branch)?;
+            if previous != hash {
+                outln!(
+                    "Previous HEAD position was {} {}",
+                    short(&previous),
+                    subject(rep, &previous)
+                )?;
+            }
+            outln!(
+                "Switched to branch '{}'",
+                branch.trim_start_matches("refs/heads/")
+            )?;
+        }
+        None => outln!("HEAD is now at {} {}", short(&hash), subject(rep, &hash))?,
+    }
+
+    clean_state(rep);
+    Ok(())
+}
+
+/// redo the steps of a bisect log
+fn replay(rep: &Repository, file: &str) -> Result<(), RepError> {
+    let log = std::fs::read_to_string(file).map_err(|_| {
+        RepError::InvalidArgument(format!("cannot read file '{}' for replaying", file))
+    })?;
+
+    if is_bisecting(rep) {
+        reset(rep, None)?;
+    }
+
+    for line in log.lines() {
+        let words = sq_split(line);
+        let rest = match words.as_slice() {
+            [git, bisect, rest @ ..] if git == "git" && bisect == "bisect" => rest,
+            [git_bisect, rest @ ..] if git_bisect == "git-bisect" => rest,
+            _ => continue,
+        };
+        let (command, args) = match rest.split_first() {
+            Some(split) => split,
+            None => continue,
+        };
+
+        if command == "start" {
+            start(rep, args)?;
+            continue;
+        }
+        match Terms::load(rep).state_of(command) {
+            Some(state) => {
+                mark(rep, state, args, false)?;
+            }
+            None => {
+                return Err(RepError::InvalidArgument(
+                    "?? what are you talking about?".to_string(),
+                ))
+            }
+        }
+    }
+
+    next(rep, &Terms::load(rep))?;
+    Ok(())
+}
+
+/// run a command on every commit to test, 0 is old, 125 skips and 1-127 is new
+fn run(rep: &Repository, command: &[String]) -> Result<(), RepError> {
+    if command.is_empty() {
+        return Err(RepError::InvalidArgument(
+            "bisect run failed: no command provided.".to_string(),
+        ));
+    }
+    let terms = Terms::load(rep);
+    let (bad, goods, _) = marked(rep, &terms);
+    if bad.is_none() || goods.is_empty() {
+        return Err(RepError::InvalidArgument(format!(
+            "You need to give me at least one {} and {} revision.\n\
+             You can use \"git bisect {}\" and \"git bisect {}\" for that.",
+            terms.new, terms.old, terms.new, terms.old
+        )));
+    }
+
+    // like git the arguments are quoted and given to the shell, a single one is run as it is
+    let command = match command {
+        [script] => script.clone(),
+        _ => command
+            .iter()
+            .map(|a| sq_quote(a))
+            .collect::<Vec<String>>()
+            .join(" "),
+    };
+    loop {
+        outln!("running {}", command)?;
+        let status = Command::new("sh").arg("-c").arg(&command).status();
+        let code = match status.ok().and_then(|s| s.code()) {
+            Some(code) if (0..128).contains(&code) => code,
+            code => {
+                outln!(
+                    "bisect run failed: exit code {} from '{}' is < 0 or >= 128",
+                    code.unwrap_or(-1),
+                    command
+                )?;
+                return Ok(());
+            }
+        };
+
+        let state = match code {
+            0 => State::Old,
+            125 => State::Skip,
+            _ => State::New,
+        };
+        match mark(rep, state, &[], true)? {
+            Outcome::Testing => continue,
+            Outcome::Found => {
+                outln!("bisect found first bad commit")?;
+                return Ok(());
+            }
+            Outcome::OnlySkipped => {
+                outln!("bisect run cannot continue any more")?;
+                return Ok(());
+            }
+            _ => {
+                outln!(
+                    "bisect run failed: 'git bisect {}' exited with error",
+                    command
+                )?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// bisect <start | <term> | skip | reset | log | replay | run | terms> [<args>]
+pub fn bisect(rep: &Repository, args: &[String]) -> Result<(), RepError> {
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => {
+            outln!("usage: kyu bisect [start | bad | good | new | old | skip | reset | log | replay | run | terms]")?;
+            return Ok(());
+        }
+    };
+
+    if command == "start" {
+        start(rep, args)?;
+        return Ok(());
+    }
+    if !is_bisecting(rep) && !["reset", "log", "replay"].contains(&command) {
+        outln!("You need to start by \"git bisect start\"")?;
+        return Ok(());
+    }
+
+    match command {
+        "reset" => reset(rep, args.first()),
+        "log" => {
+            match read_state(rep, "BISECT_LOG") {
+                Some(log) => out!("{}", log)?,
+                None => {
+                    return Err(RepError::InvalidArgument(
+                        "We are not bisecting.".to_string(),
+                    ))
+                }
+            }
+            Ok(())
+        }
+        "replay" => match args.first() {
+            Some(file) => replay(rep, file),
+            None => Err(RepError::InvalidArgument("no logfile given".to_string())),
+        },
+        "run" => run(rep, args),
+        "terms" => {
+            let terms = Terms::load(rep);
+            outln!(
+                "Your current terms are {} for the old state\nand {} for the new state.",
+                terms.old,
+                terms.new
+            )?;
+            Ok(())
+        }
+        command => match Terms::load(rep).state_of(command) {
+            Some(state) => mark(rep, state, args, true).map(|_| ()),
+            None => Err(RepError::InvalidArgument(format!(
+                "unknown command: '{}'",
+                command
+            ))),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// five commits, a.txt is broken from the third one on
+    fn history(name: &str) -> (Repository, Vec<ObjectId>) {
+        let rep = Repository::temporary(name);
+        let commits = ["fine", "fine", "broken", "broken", "broken"]
+            .iter()
+            .enumerate()
+            .map(|(i, content)| {
+                let files = [("a.txt", *content), ("n.txt", &i.to_string())];
+                rep.commit_files(&files, &format!("commit {}", i))
+            })
+            .collect();
+        (rep, commits)
+    }
+
+    fn first_bad(rep: &Repository) -> ObjectId {
+        rep.ref_resolve("refs/bisect/bad").unwrap()
+    }
+
+    #[test]
+    fn the_first_bad_commit_is_found() {
+        let (rep, commits) = history("bisect-manual");
+        let range = [commits[4].to_string(), commits[0].to_string()];
+
+        let mut outcome = start(&rep, &range).unwrap();
+        while outcome == Outcome::Testing {
+            let content = std::fs::read_to_string(rep.get_workdir().clone() + "/a.txt").unwrap();
+            let state = match content.as_str() {
+                "broken" => State::New,
+                _ => State::Old,
+            };
+            outcome = mark(&rep, state, &[], true).unwrap();
+        }
+        assert_eq!(outcome, Outcome::Found);
+        assert_eq!(first_bad(&rep), commits[2]);
+        assert!(read_state(&rep, "BISECT_LOG")
+            .unwrap()
+            .contains(&format!("# first bad commit: [{}]", commits[2])));
+
+        reset(&rep, None).unwrap();
+        assert!(!is_bisecting(&rep));
+        assert_eq!(rep.get_head_ref(), Some("refs/heads/master".to_string()));
+        assert_eq!(rep.ref_resolve("HEAD").unwrap(), commits[4]);
+        assert!(rep.ref_resolve("refs/bisect/bad").is_err());
+    }
+
+    #[test]
+    fn run_marks_commits_by_the_exit_code() {
+        let (rep, commits) = history("bisect-run");
+        let range = [commits[4].to_string(), commits[0].to_string()];
+        start(&rep, &range).unwrap();
+
+        let script = format!("grep -q fine {}/a.txt", rep.get_workdir());
+        run(&rep, &[script]).unwrap();
+        assert_eq!(first_bad(&rep), commits[2]);
+
+        assert!(matches!(
+            mark(&rep, State::New, &["HEAD".into(), "HEAD~1".into()], false),
+            Err(RepError::InvalidArgument(_))
+        ));
+        assert!(matches!(run(&rep, &[]), Err(RepError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn terms_can_be_renamed() {
+        let terms = Terms {
+            new: "broken".to_string(),
+            old: "fixed".to_string(),
+        };
+        assert_eq!(terms.state_of("broken"), Some(State::New));
+        assert_eq!(terms.state_of("fixed"), Some(State::Old));
+        assert_eq!(terms.state_of("bad"), None);
+        assert_eq!(terms.state_of("skip"), Some(State::Skip));
+
+        let rep = Repository::in_memory();
+        let terms = Terms::load(&rep);
+        assert!(terms.is_default());
+        assert_eq!(terms.state_of("new"), Some(State::New));
+    }
+
+    #[test]
+    fn log_arguments_are_quoted_for_the_shell() {
+        let args = ["plain", "it's", "wow!", "two words"];
+        let line = args.map(sq_quote).join(" ");
+        assert_eq!(line, "'plain' 'it'\\''s' 'wow'\\!'' 'two words'");
+        assert_eq!(sq_split(&line), args);
+        assert_eq!(
+            sq_split("git bisect  good  abc"),
+            ["git", "bisect", "good", "abc"]
+        );
+    }
+
+    #[test]
+    fn helpers_compute_like_git() {
+        assert_eq!(estimate_steps(2), 0);
+        assert_eq!(estimate_steps(1024), 9);
+        assert_eq!(estimate_steps(1500), 10);
+        assert_eq!((sqrti(0), sqrti(16), sqrti(10)), (0, 4, 3));
+        assert!(get_prn(7) < PRN_MODULO);
+    }
+}
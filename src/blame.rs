@@ -0,0 +1,932 @@
+#![allow(dead_code)]
+
+use regex::Regex;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::io::Write;
+
+use crate::diff::{diff, matches, split_lines, Edit};
+use crate::mailmap::Mailmap;
+use crate::merge::tree_files;
+use crate::objects::{Object, KVLM};
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+use crate::revwalk::{commit_time, resolve_commit};
+use crate::signature::{DateFormat, Signature};
+
+/// the pseudo commit for lines which only exist in the working directory
+const WORKTREE: ObjectId = ObjectId::NULL;
+
+/// lines moved inside a file need this many alphanumeric characters to be detected
+const MOVE_SCORE: usize = 20;
+/// lines copied from other files need this many alphanumeric characters to be detected
+const COPY_SCORE: usize = 40;
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// line ranges like 10,20 or 10,+5 or /regex/,/regex/ (-L)
+    pub ranges: Vec<String>,
+    /// show the commit info only once per commit (--porcelain)
+    pub porcelain: bool,
+    /// show the commit info for every line (--line-porcelain)
+    pub line_porcelain: bool,
+    /// ignore whitespace when comparing lines (-w)
+    pub ignore_whitespace: bool,
+    /// commits whose changes are passed on to their parent (--ignore-rev)
+    pub ignore_revs: Vec<String>,
+    /// files with commits to ignore, blame.ignoreRevsFile is used as well (--ignore-revs-file)
+    pub ignore_revs_files: Vec<String>,
+    /// detect lines moved inside the file (-M)
+    pub moves: bool,
+    /// detect lines copied from other files, more often searches more files (-C)
+    pub copies: u8,
+    /// dont treat root commits as boundaries (--root)
+    pub show_root: bool,
+}
+
+/// a file in a commit which lines can be blamed on
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Origin {
+    commit: ObjectId,
+    path: String,
+}
+
+/// a line of the final file which is currently blamed on a suspect
+#[derive(Debug, Clone, Copy)]
+struct Line {
+    /// index in the final file
+    target: usize,
+    /// index in the file of the suspect
+    source: usize,
+}
+
+/// where a line of the final file comes from
+#[derive(Debug, Clone)]
+struct Blamed {
+    origin: Origin,
+    source: usize,
+}
+
+/// what is shown about a commit
+struct CommitInfo {
+    author: Signature,
+    committer: Signature,
+    summary: String,
+    boundary: bool,
+}
+
+struct Blame<'a> {
+    rep: &'a Repository,
+    opts: Options,
+    mailmap: Mailmap,
+    ignored: HashSet<ObjectId>,
+    /// the content of the working directory file if it is blamed
+    worktree: Option<Vec<u8>>,
+    /// all files of a commit, only needed to find renames and copies
+    trees: HashMap<ObjectId, BTreeMap<String, (String, ObjectId)>>,
+    /// (mode, sha) of the file of an origin, None if the commit doesn't have it
+    entries: HashMap<Origin, Option<(String, ObjectId)>>,
+    /// the parent file a suspect was compared with
+    previous: HashMap<Origin, Origin>,
+}
+
+impl Blame<'_> {
+    fn parents(&self, commit: &ObjectId) -> Vec<ObjectId> {
+        if *commit == WORKTREE {
+            return self.rep.ref_resolve("HEAD").into_iter().collect();
+        }
+        match Object::load(self.rep, commit) {
+            Ok(Object::Commit(c)) => c.get_parents().to_vec(),
+            _ => vec![],
+        }
+    }
+
+    fn time(&self, commit: &ObjectId) -> i64 {
+        match *commit {
+            WORKTREE => i64::MAX,
+            _ => commit_time(self.rep, commit),
+        }
+    }
+
+    /// path -> (mode, sha) of the files of a commit
+    fn files(
+        &mut self,
+        commit: &ObjectId,
+    ) -> Result<&BTreeMap<String, (String, ObjectId)>, RepError> {
+        if !self.trees.contains_key(commit) {
+            let tree = match Object::load(self.rep, commit)? {
+                Object::Commit(c) => Some(c.get_tree()),
+                _ => None,
+            };
+            let files = tree_files(self.rep, tree.as_ref())?;
+            self.trees.insert(*commit, files);
+        }
+        Ok(&self.trees[commit])
+    }
+
+    /// (mode, sha) of the file of an origin, only the trees along its path are loaded
+    fn entry(&mut self, origin: &Origin) -> Result<Option<(String, ObjectId)>, RepError> {
+        if let Some(entry) = self.entries.get(origin) {
+            return Ok(entry.clone());
+        }
+        let entry = match Object::load(self.rep, &origin.commit)? {
+            Object::Commit(c) => match Object::load(self.rep, &c.get_tree())? {
+                Object::Tree(tree) => tree.find(self.rep, &origin.path)?,
+                _ => None,
+            },
+            _ => None,
+        };
+        self.entries.insert(origin.clone(), entry.clone());
+        Ok(entry)
+    }
+
+    fn blob(&mut self, origin: &Origin) -> Result<Option<ObjectId>, RepError> {
+        if origin.commit == WORKTREE {
+            return Ok(None);
+        }
+        Ok(self.entry(origin)?.map(|(_, sha)| sha))
+    }
+
+    fn content(&mut self, origin: &Origin) -> Result<Vec<u8>, RepError> {
+        if origin.commit == WORKTREE {
+            return Ok(self.worktree.clone().unwrap_or_default());
+        }
+        match self.blob(origin)? {
+            Some(sha) => Ok(Object::load(self.rep, &sha)?.get_data().clone()),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// the lines of a file as they are compared, without whitespace for -w
+    fn keys(&self, content: &[u8]) -> Vec<Vec<u8>> {
+        split_lines(content)
+            .into_iter()
+            .map(|line| match self.opts.ignore_whitespace {
+                true => line
+                    .iter()
+                    .filter(|b| !b.is_ascii_whitespace())
+                    .copied()
+                    .collect(),
+                false => line.strip_suffix(b"\n").unwrap_or(line).to_vec(),
+            })
+            .collect()
+    }
+
+    /// the compared lines of the file of an origin
+    fn lines(&mut self, origin: &Origin) -> Result<Vec<Vec<u8>>, RepError> {
+        let content = self.content(origin)?;
+        Ok(self.keys(&content))
+    }
+
+    /// the file in the parent the suspect came from, following renames
+    fn parent_origin(
+        &mut self,
+        origin: &Origin,
+        parent: &ObjectId,
+    ) -> Result<Option<Origin>, RepError> {
+        let same_path = Origin {
+            commit: *parent,
+            path: origin.path.clone(),
+        };
+        if self.entry(&same_path)?.is_some() {
+            return Ok(Some(same_path));
+        }
+
+        // a renamed file is one that only exists in the parent and has mostly the same lines
+        let ours = self.lines(origin)?;
+        let current: HashSet<String> = match origin.commit {
+            WORKTREE => HashSet::new(),
+            commit => self.files(&commit)?.keys().cloned().collect(),
+        };
+        let candidates: Vec<String> = self
+            .files(parent)?
+            .keys()
+            .filter(|p| !current.contains(*p))
+            .cloned()
+            .collect();
+
+        let mut best: Option<(usize, String)> = None;
+        for candidate in candidates {
+            let theirs = self.lines(&Origin {
+                commit: *parent,
+                path: candidate.clone(),
+            })?;
+            let common = matches(&ours, &theirs).iter().flatten().count();
+            let similar = common * 2 >= ours.len().max(theirs.len()) && common > 0;
+            if similar && best.as_ref().is_none_or(|(c, _)| common > *c) {
+                best = Some((common, candidate));
+            }
+        }
+
+        Ok(best.map(|(_, path)| Origin {
+            commit: *parent,
+            path,
+        }))
+    }
+
+    /// assign the lines of a suspect to its parents, returns the lines which stay with it
+    fn pass_blame(
+        &mut self,
+        origin: &Origin,
+        lines: Vec<Line>,
+        queue: &mut BTreeMap<Origin, Vec<Line>>,
+    ) -> Result<Vec<Line>, RepError> {
+        let boundary = self.is_boundary(&origin.commit);
+        if boundary {
+            return Ok(lines);
+        }
+
+        let mut remaining = lines;
+        let ours = self.lines(origin)?;
+        let mut first_parent: Option<(Origin, Vec<Vec<u8>>)> = None;
+
+        for parent in self.parents(&origin.commit) {
+            if remaining.is_empty() {
+                break;
+            }
+            let parent_origin = match self.parent_origin(origin, &parent)? {
+                Some(p) => p,
+                None => continue,
+            };
+            self.previous
+                .entry(origin.clone())
+                .or_insert(parent_origin.clone());
+
+            // an unchanged file passes all of its lines
+            let blob = self.blob(origin)?;
+            if blob.is_some() && blob == self.blob(&parent_origin)? {
+                queue
+                    .entry(parent_origin)
+                    .or_default()
+                    .append(&mut remaining);
+                break;
+            }
+
+            let theirs = self.lines(&parent_origin)?;
+            let matched = matches(&ours, &theirs);
+            let mut kept = vec![];
+            for line in remaining {
+                match matched.get(line.source).copied().flatten() {
+                    Some(source) => queue.entry(parent_origin.clone()).or_default().push(Line {
+                        target: line.target,
+                        source,
+                    }),
+                    None => kept.push(line),
+                }
+            }
+            remaining = kept;
+
+            if first_parent.is_none() {
+                first_parent = Some((parent_origin, theirs));
+            }
+        }
+
+        let (parent_origin, theirs) = match first_parent {
+            Some(first) => first,
+            None => return Ok(remaining),
+        };
+
+        if self.opts.moves || self.opts.copies > 0 {
+            remaining = find_moved(&ours, &theirs, remaining, MOVE_SCORE, |line| {
+                queue.entry(parent_origin.clone()).or_default().push(line)
+            });
+        }
+
+        if self.opts.copies > 0 {
+            for path in self.copy_candidates(origin, &parent_origin)? {
+                let source = Origin {
+                    commit: parent_origin.commit,
+                    path,
+                };
+                let theirs = self.lines(&source)?;
+                remaining = find_moved(&ours, &theirs, remaining, COPY_SCORE, |line| {
+                    queue.entry(source.clone()).or_default().push(line)
+                });
+            }
+        }
+
+        // the lines of an ignored commit are guessed to come from the same place in the parent
+        if self.ignored.contains(&origin.commit) {
+            let guessed = guess_lines(&ours, &theirs);
+            let mut kept = vec![];
+            for line in remaining {
+                match guessed.get(line.source).copied().flatten() {
+                    Some(source) => queue.entry(parent_origin.clone()).or_default().push(Line {
+                        target: line.target,
+                        source,
+                    }),
+                    None => kept.push(line),
+                }
+            }
+            remaining = kept;
+        }
+
+        Ok(remaining)
+    }
+
+    /// the files of the parent that lines could have been copied from
+    fn copy_candidates(
+        &mut self,
+        origin: &Origin,
+        parent: &Origin,
+    ) -> Result<Vec<String>, RepError> {
+        let parent_files = self.files(&parent.commit)?.clone();
+        let candidates = parent_files
+            .iter()
+            .filter(|(path, _)| **path != parent.path);
+
+        // once only searches files changed in the same commit, more often all files
+        let candidates = match (self.opts.copies, origin.commit) {
+            (1, WORKTREE) => vec![],
+            (1, commit) => {
+                let files = self.files(&commit)?.clone();
+                candidates
+                    .filter(|(path, entry)| files.get(*path).is_some_and(|e| e != *entry))
+                    .map(|(path, _)| path.clone())
+                    .collect()
+            }
+            _ => candidates.map(|(path, _)| path.clone()).collect(),
+        };
+        Ok(candidates)
+    }
+
+    fn is_boundary(&self, commit: &ObjectId) -> bool {
+        *commit != WORKTREE && !self.opts.show_root && self.parents(commit).is_empty()
+    }
+
+    fn info(&self, origin: &Origin) -> Result<CommitInfo, RepError> {
+        if origin.commit == WORKTREE {
+            let author = Signature::now("Not Committed Yet", "not.committed.yet");
+            return Ok(CommitInfo {
+                committer: author.clone(),
+                author,
+                summary: format!("Version of {} from {}", origin.path, origin.path),
+                boundary: false,
+            });
+        }
+
+        let commit = match Object::load(self.rep, &origin.commit)? {
+            Object::Commit(c) => c,
+            _ => {
+                return Err(RepError::BadObject(
+                    "commit".to_string(),
+                    origin.commit.to_string(),
+                ))
+            }
+        };
+        Ok(CommitInfo {
+            author: self.mailmap.apply(&commit.get_author()),
+            committer: self.mailmap.apply(&commit.get_committer()),
+            summary: subject(&commit.get_message()),
+            boundary: self.is_boundary(&origin.commit),
+        })
+    }
+}
+
+/// the first paragraph of a message on one line
+fn subject(message: &str) -> String {
+    message
+        .trim_start_matches('\n')
+        .split("\n\n")
+        .next()
+        .unwrap_or("")
+        .lines()
+        .map(|l| l.trim())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// the number of alphanumeric characters in lines, blocks need a minimum to count as moved
+fn score(lines: &[Vec<u8>]) -> usize {
+    lines
+        .iter()
+        .map(|l| l.iter().filter(|b| b.is_ascii_alphanumeric()).count())
+        .sum()
+}
+
+/// find blocks of lines which appear anywhere in theirs, found lines are given to assign
+fn find_moved(
+    ours: &[Vec<u8>],
+    theirs: &[Vec<u8>],
+    lines: Vec<Line>,
+    min_score: usize,
+    mut assign: impl FnMut(Line),
+) -> Vec<Line> {
+    let mut lines = lines;
+    lines.sort_by_key(|l| l.source);
+
+    let mut remaining = vec![];
+    let mut pending = vec![lines];
+
+    while let Some(block) = pending.pop() {
+        if block.is_empty() {
+            continue;
+        }
+
+        // the longest run of consecutive lines of the block which appears in theirs
+        let mut best = (0, 0, 0);
+        for start in 0..block.len() {
+            for (t, _) in theirs.iter().enumerate() {
+                let mut len = 0;
+                while start + len < block.len()
+                    && t + len < theirs.len()
+                    && block[start + len].source == block[start].source + len
+                    && ours[block[start + len].source] == theirs[t + len]
+                {
+                    len += 1;
+                }
+                if len > best.2 {
+                    best = (start, t, len);
+                }
+            }
+        }
+
+        let (start, t, len) = best;
+        let first = block.get(start).map(|l| l.source).unwrap_or(0);
+        if len == 0 || score(&ours[first..first + len]) < min_score {
+            remaining.extend(block);
+            continue;
+        }
+
+        for (i, line) in block[start..start + len].iter().enumerate() {
+            assign(Line {
+                target: line.target,
+                source: t + i,
+            });
+        }
+        pending.push(block[..start].to_vec());
+        pending.push(block[start + len..].to_vec());
+    }
+
+    remaining
+}
+
+/// map changed lines to the line at the same offset in the changed part of the parent
+fn guess_lines(ours: &[Vec<u8>], theirs: &[Vec<u8>]) -> Vec<Option<usize>> {
+    let mut result = vec![None; ours.len()];
+    let mut deleted = vec![];
+    let mut inserted = 0;
+
+    for edit in diff(theirs, ours) {
+        match edit {
+            Edit::Equal(i, j) => {
+                result[j] = Some(i);
+                deleted.clear();
+                inserted = 0;
+            }
+            Edit::Delete(i) => deleted.push(i),
+            Edit::Insert(j) => {
+                result[j] = deleted.get(inserted).copied();
+                inserted += 1;
+            }
+        }
+    }
+
+    result
+}
+
+/// the hashes in a file of commits to ignore, comments start with #
+fn read_ignore_file(rep: &Repository, path: &str) -> Vec<ObjectId> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or("").trim())
+        .filter(|l| !l.is_empty())
+        .filter_map(|l| resolve_commit(rep, l).ok())
+        .collect()
+}
+
+/// find the line a -L position refers to, regexes search from the start line
+fn parse_position(position: &str, lines: &[&[u8]], start: usize) -> Result<usize, RepError> {
+    let invalid = || RepError::InvalidRange(position.to_string());
+
+    if let Some(pattern) = position.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+        let regex = Regex::new(pattern).map_err(|_| invalid())?;
+        return lines
+            .iter()
+            .enumerate()
+            .skip(start)
+            .find(|(_, l)| regex.is_match(&String::from_utf8_lossy(l)))
+            .map(|(i, _)| i)
+            .ok_or(invalid());
+    }
+
+    match position.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n - 1),
+        _ => Err(invalid()),
+    }
+}
+
+/// parse a -L range into the indices of the first and last line
+fn parse_range(range: &str, lines: &[&[u8]]) -> Result<(usize, usize), RepError> {
+    let invalid = || RepError::InvalidRange(range.to_string());
+    let (start, end) = match range.split_once(',') {
+        // a regex can contain a comma, only split after the closing slash
+        Some(_) if range.starts_with('/') => {
+            let close = range[1..].find('/').ok_or(invalid())? + 1;
+            match range[close + 1..].strip_prefix(',') {
+                Some(end) => (&range[..=close], end),
+                None => (range, ""),
+            }
+        }
+        Some(parts) => parts,
+        None => (range, ""),
+    };
+
+    let start = parse_position(start, lines, 0)?;
+    if start >= lines.len() {
+        return Err(RepError::InvalidRange(format!(
+            "file has only {} lines",
+            lines.len()
+        )));
+    }
+
+    let last = lines.len() - 1;
+    let end = if end.is_empty() {
+        last
+    } else if let Some(count) = end.strip_prefix('+') {
+        let count = count.parse::<usize>().map_err(|_| invalid())?;
+        start + count.max(1) - 1
+    } else if let Some(count) = end.strip_prefix('-') {
+        // a negative count goes backwards from the start line
+        let count = count.parse::<usize>().map_err(|_| invalid())?;
+        let first = (start + 1).saturating_sub(count.max(1));
+        return Ok((first, start));
+    } else {
+        parse_position(end, lines, start + 1)?
+    };
+
+    let (start, end) = if end < start {
+        (end, start)
+    } else {
+        (start, end)
+    };
+    Ok((start, end.min(last)))
+}
+
+/// show which commit last changed every line of a file, the working directory file without a revision
+pub fn blame(
+    rep: &Repository,
+    rev: Option<&str>,
+    file: &str,
+    opts: Options,
+) -> Result<(), RepError> {
+    let path = rep.relative_path(file);
+
+    let (commit, worktree) = match rev {
+        Some(rev) => (resolve_commit(rep, rev)?, None),
+        None => {
+            let content = std::fs::read(rep.get_workdir().clone() + "/" + &path).map_err(|_| {
+                RepError::NoSuchPath(path.clone(), "the working directory".to_string())
+            })?;
+            (WORKTREE, Some(content))
+        }
+    };
+
+    let mut ignored: Vec<ObjectId> = vec![];
+    for rev in opts.ignore_revs.iter() {
+        ignored.push(resolve_commit(rep, rev)?);
+    }
+    let mut files = opts.ignore_revs_files.clone();
+    if let Some(file) = rep.config_get("blame", "ignoreRevsFile") {
+        files.push(rep.get_workdir().clone() + "/" + &file);
+    }
+    for file in files {
+        ignored.extend(read_ignore_file(rep, &file));
+    }
+
+    let show_root = opts.show_root
+        || rep
+            .config_get("blame", "showRoot")
+            .is_some_and(|v| v == "true");
+
+    let mut blame = Blame {
+        rep,
+        opts: Options { show_root, ..opts },
+        mailmap: Mailmap::load(rep),
+        ignored: ignored.into_iter().collect(),
+        worktree,
+        trees: HashMap::new(),
+        entries: HashMap::new(),
+        previous: HashMap::new(),
+    };
+
+    let origin = Origin {
+        commit,
+        path: path.clone(),
+    };
+    if commit != WORKTREE && blame.blob(&origin)?.is_none() {
+        return Err(RepError::NoSuchPath(
+            path,
+            rev.unwrap_or("HEAD").to_string(),
+        ));
+    }
+
+    let content = blame.content(&origin)?;
+    let final_lines = split_lines(&content);
+
+    // only the lines of the -L ranges are blamed
+    let mut selected = vec![false; final_lines.len()];
+    if blame.opts.ranges.is_empty() {
+        selected.iter_mut().for_each(|s| *s = true);
+    }
+    for range in blame.opts.ranges.clone() {
+        let (start, end) = parse_range(&range, &final_lines)?;
+        selected[start..=end].iter_mut().for_each(|s| *s = true);
+    }
+
+    let lines = (0..final_lines.len())
+        .filter(|&i| selected[i])
+        .map(|i| Line {
+            target: i,
+            source: i,
+        })
+        .collect::<Vec<Line>>();
+
+    // newer suspects are handled first so lines reach merge bases through every path
+    let mut suspects: BTreeMap<Origin, Vec<Line>> = BTreeMap::new();
+    suspects.insert(origin, lines);
+    let mut heap = BinaryHeap::new();
+    heap.push((blame.time(&commit), commit));
+
+    let mut result: Vec<Option<Blamed>> = vec![None; final_lines.len()];
+    while let Some((_, commit)) = heap.pop() {
+        let origins: Vec<Origin> = suspects
+            .keys()
+            .filter(|o| o.commit == commit)
+            .cloned()
+            .collect();
+
+        for origin in origins {
+            let lines = match suspects.remove(&origin) {
+                Some(lines) if !lines.is_empty() => lines,
+                _ => continue,
+            };
+
+            let mut passed = BTreeMap::new();
+            for line in blame.pass_blame(&origin, lines, &mut passed)? {
+                result[line.target] = Some(Blamed {
+                    origin: origin.clone(),
+                    source: line.source,
+                });
+            }
+
+            for (parent, mut lines) in passed {
+                if !suspects.contains_key(&parent) {
+                    heap.push((blame.time(&parent.commit), parent.commit));
+                }
+                suspects.entry(parent).or_default().append(&mut lines);
+            }
+        }
+    }
+
+    output(&blame, &path, &final_lines, &result)
+}
+
+/// consecutive lines blamed on the same origin as (first final line, count)
+fn entries(result: &[Option<Blamed>]) -> Vec<(usize, usize)> {
+    let mut entries: Vec<(usize, usize)> = vec![];
+
+    for (i, blamed) in result.iter().enumerate() {
+        let blamed = match blamed {
+            Some(b) => b,
+            None => continue,
+        };
+        if let Some((start, count)) = entries.last_mut() {
+            let prev = result[*start].as_ref().unwrap();
+            if *start + *count == i
+                && prev.origin == blamed.origin
+                && prev.source + *count == blamed.source
+            {
+                *count += 1;
+                continue;
+            }
+        }
+        entries.push((i, 1));
+    }
+
+    entries
+}
+
+fn output(
+    blame: &Blame,
+    path: &str,
+    lines: &[&[u8]],
+    result: &[Option<Blamed>],
+) -> Result<(), RepError> {
+    let mut infos: HashMap<ObjectId, CommitInfo> = HashMap::new();
+    for blamed in result.iter().flatten() {
+        if let Entry::Vacant(entry) = infos.entry(blamed.origin.commit) {
+            entry.insert(blame.info(&blamed.origin)?);
+        }
+    }
+
+    let mut out = std::io::stdout().lock();
+    let line_text = |i: usize| {
+        let mut line = lines[i].to_vec();
+        if !line.ends_with(b"\n") {
+            line.push(b'\n');
+        }
+        line
+    };
+
+    if blame.opts.porcelain || blame.opts.line_porcelain {
+        // the filename is repeated for commits which touched several paths
+        let mut paths: HashMap<&ObjectId, HashSet<&str>> = HashMap::new();
+        for blamed in result.iter().flatten() {
+            paths
+                .entry(&blamed.origin.commit)
+                .or_default()
+                .insert(&blamed.origin.path);
+        }
+
+        let repeat = blame.opts.line_porcelain;
+        let mut shown = HashSet::new();
+        for (start, count) in entries(result) {
+            let blamed = result[start].as_ref().unwrap();
+            let origin = &blamed.origin;
+            let info = &infos[&origin.commit];
+
+            for i in 0..count {
+                write!(
+                    out,
+                    "{} {} {}",
+                    origin.commit,
+                    blamed.source + i + 1,
+                    start + i + 1
+                )?;
+                if i == 0 {
+                    write!(out, " {}", count).map_err(RepError::Output)?;
+                }
+                writeln!(out).map_err(RepError::Output)?;
+
+                // the details of a commit are shown once, the filename also when it changes
+                if i == 0 || repeat {
+                    let details = repeat || shown.insert(origin.commit);
+                    if details {
+                        porcelain_details(&mut out, info)?;
+                    }
+                    if details || paths[&origin.commit].len() > 1 {
+                        if let Some(previous) = blame.previous.get(origin) {
+                            writeln!(out, "previous {} {}", previous.commit, previous.path)
+                                .map_err(RepError::Output)?;
+                        }
+                        writeln!(out, "filename {}", origin.path).map_err(RepError::Output)?;
+                    }
+                }
+
+                out.write_all(b"\t").map_err(RepError::Output)?;
+                out.write_all(&line_text(start + i))
+                    .map_err(RepError::Output)?;
+            }
+        }
+        return Ok(());
+    }
+
+    // the path is shown when lines come from an older name or another file
+    let show_path = result.iter().flatten().any(|b| b.origin.path != path);
+    let path_width = result
+        .iter()
+        .flatten()
+        .map(|b| b.origin.path.chars().count())
+        .max()
+        .unwrap_or(0);
+    let author_width = infos
+        .values()
+        .map(|i| i.author.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let number_width = lines.len().to_string().len();
+
+    for (i, blamed) in result.iter().enumerate() {
+        let blamed = match blamed {
+            Some(b) => b,
+            None => continue,
+        };
+        let info = &infos[&blamed.origin.commit];
+
+        let hash = match info.boundary {
+            true => format!("^{:.7}", blamed.origin.commit),
+            false => format!("{:.8}", blamed.origin.commit),
+        };
+        let mut line = hash;
+        if show_path {
+            line += &format!(" {:<width$}", blamed.origin.path, width = path_width);
+        }
+        line += &format!(
+            " ({:<width$} {} {:>number$}) ",
+            info.author.name,
+            info.author.format_date(&DateFormat::Iso),
+            i + 1,
+            width = author_width,
+            number = number_width
+        );
+
+        out.write_all(line.as_bytes()).map_err(RepError::Output)?;
+        out.write_all(&line_text(i)).map_err(RepError::Output)?;
+    }
+
+    Ok(())
+}
+
+fn porcelain_details(out: &mut impl Write, info: &CommitInfo) -> Result<(), RepError> {
+    for (role, signature) in [("author", &info.author), ("committer", &info.committer)] {
+        writeln!(out, "{} {}", role, signature.name).map_err(RepError::Output)?;
+        writeln!(out, "{}-mail <{}>", role, signature.email).map_err(RepError::Output)?;
+        writeln!(out, "{}-time {}", role, signature.seconds).map_err(RepError::Output)?;
+        writeln!(out, "{}-tz {}", role, signature.timezone()).map_err(RepError::Output)?;
+    }
+    writeln!(out, "summary {}", info.summary).map_err(RepError::Output)?;
+    if info.boundary {
+        writeln!(out, "boundary").map_err(RepError::Output)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<Vec<u8>> {
+        text.split(' ').map(|l| l.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn ranges_are_parsed_like_git() {
+        let file = ["a", "b", "foo, bar", "d", "e"].map(str::as_bytes);
+        let range = |r: &str| parse_range(r, &file).ok();
+        assert_eq!(range("2,4"), Some((1, 3)));
+        assert_eq!(range("4,2"), Some((1, 3)));
+        assert_eq!(range("3"), Some((2, 4)));
+        assert_eq!(range("2,+2"), Some((1, 2)));
+        assert_eq!(range("4,-2"), Some((2, 3)));
+        assert_eq!(range("2,+9"), Some((1, 4)));
+        assert_eq!(range("/foo, bar/,+1"), Some((2, 2)));
+        assert_eq!(range("/b/,/d/"), Some((1, 3)));
+        assert_eq!(range("0"), None);
+        assert_eq!(range("6"), None);
+        assert_eq!(range("/nothing/"), None);
+    }
+
+    #[test]
+    fn changed_lines_are_guessed_at_the_same_offset() {
+        assert_eq!(
+            guess_lines(&lines("a X c"), &lines("a b c")),
+            vec![Some(0), Some(1), Some(2)]
+        );
+        assert_eq!(
+            guess_lines(&lines("a new c"), &lines("a c")),
+            vec![Some(0), None, Some(1)]
+        );
+    }
+
+    #[test]
+    fn moved_blocks_are_found_with_enough_content() {
+        let ours = lines("x first_moved_line second_moved_line");
+        let theirs = lines("first_moved_line second_moved_line z");
+        let suspects = vec![
+            Line {
+                target: 1,
+                source: 1,
+            },
+            Line {
+                target: 2,
+                source: 2,
+            },
+        ];
+
+        let mut assigned = vec![];
+        let remaining = find_moved(&ours, &theirs, suspects.clone(), 20, |l| {
+            assigned.push((l.target, l.source))
+        });
+        assert!(remaining.is_empty());
+        assigned.sort();
+        assert_eq!(assigned, vec![(1, 0), (2, 1)]);
+
+        let remaining = find_moved(&ours, &theirs, suspects, 1000, |_| panic!());
+        assert_eq!(remaining.len(), 2);
+    }
+
+    #[test]
+    fn consecutive_lines_of_an_origin_form_one_entry() {
+        let origin = |commit: &str| Origin {
+            commit: commit.repeat(40).parse().unwrap(),
+            path: "a.txt".to_string(),
+        };
+        let blamed = |commit: &str, source: usize| {
+            Some(Blamed {
+                origin: origin(commit),
+                source,
+            })
+        };
+        let result = vec![
+            blamed("a", 0),
+            blamed("a", 1),
+            blamed("b", 2),
+            blamed("a", 5),
+            blamed("a", 6),
+        ];
+        assert_eq!(entries(&result), vec![(0, 2), (2, 1), (3, 2)]);
+        assert_eq!(subject("\nfirst\nline\n\nbody\n"), "first line");
+    }
+}
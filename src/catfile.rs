@@ -0,0 +1,201 @@
+#![allow(dead_code)]
+
+use std::io::{BufRead, Write};
+
+use crate::objects::Object;
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+
+/// what cat-file shows about a single object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// the type of the object (-t)
+    Type,
+    /// the size of the object (-s)
+    Size,
+    /// the content, trees are shown like ls-tree (-p)
+    Pretty,
+    /// only check that the object exists (-e)
+    Exists,
+    /// the raw content of the object peeled to a type (cat-file <type> <object>)
+    Typed(String),
+}
+
+/// resolve an object name to the id of an existing object
+fn resolve(rep: &Repository, name: &str) -> Result<ObjectId, RepError> {
+    let hash = rep.ref_resolve(name)?;
+    match Object::exists(rep, &hash) {
+        true => Ok(hash),
+        false => Err(RepError::InvalidReference(name.to_string())),
+    }
+}
+
+/// show information about an object, returns false if -e didnt find the object
+pub fn cat_file(rep: &Repository, query: Query, name: &str) -> Result<bool, RepError> {
+    if query == Query::Exists {
+        // a valid name of a missing object is not an error, it just doesnt exist
+        let hash = rep.ref_resolve(name)?;
+        return Ok(Object::exists(rep, &hash));
+    }
+
+    let hash = resolve(rep, name)?;
+    let mut out = std::io::stdout().lock();
+
+    match query {
+        Query::Type => {
+            let (object_type, _) = rep.objects().read_header(&hash)?;
+            writeln!(out, "{}", object_type).map_err(RepError::Output)?;
+        }
+        Query::Size => {
+            let (_, size) = rep.objects().read_header(&hash)?;
+            writeln!(out, "{}", size).map_err(RepError::Output)?;
+        }
+        Query::Pretty => {
+            out.write_all(&Object::load(rep, &hash)?.pretty())
+                .map_err(RepError::Output)?;
+        }
+        Query::Typed(object_type) => {
+            // tags are peeled and commits are peeled to their tree like git does
+            let hash = rep
+                .peel(&hash, &object_type)
+                .ok_or(RepError::InvalidObjectType(name.to_string()))?;
+            out.write_all(Object::load(rep, &hash)?.get_data())
+                .map_err(RepError::Output)?;
+        }
+        Query::Exists => {}
+    }
+
+    Ok(true)
+}
+
+/// replace the placeholders of a --batch format for an object
+fn expand(format: &str, id: &ObjectId, object_type: &str, size: usize, rest: &str) -> String {
+    format
+        .replace("%(objectname)", &id.to_string())
+        .replace("%(objecttype)", object_type)
+        .replace("%(objectsize)", &size.to_string())
+        .replace("%(rest)", rest)
+}
+
+/// answer object names from stdin (or all objects) with a line in the format and with contents the raw data
+pub fn batch(
+    rep: &Repository,
+    format: Option<&str>,
+    contents: bool,
+    all_objects: bool,
+) -> Result<(), RepError> {
+    let format = format.unwrap_or("%(objectname) %(objecttype) %(objectsize)");
+    let mut out = std::io::stdout().lock();
+
+    // --batch-check only needs the headers, the content is only read for --batch
+    let mut show = |name: &str, rest: &str| -> Result<(), RepError> {
+        let loaded = resolve(rep, name).and_then(|hash| match contents {
+            true => {
+                let (object_type, data) = rep.objects().read(&hash)?;
+                Ok((hash, object_type, data.len(), Some(data)))
+            }
+            false => {
+                let (object_type, size) = rep.objects().read_header(&hash)?;
+                Ok((hash, object_type, size, None))
+            }
+        });
+        let (hash, object_type, size, data) = match loaded {
+            Ok(loaded) => loaded,
+            Err(_) => {
+                writeln!(out, "{} missing", name).map_err(RepError::Output)?;
+                return Ok(());
+            }
+        };
+
+        writeln!(out, "{}", expand(format, &hash, &object_type, size, rest))
+            .map_err(RepError::Output)?;
+        if let Some(data) = data {
+            out.write_all(&data).map_err(RepError::Output)?;
+            out.write_all(b"\n").map_err(RepError::Output)?;
+        }
+        // the answers have to arrive while the caller is still writing names
+        out.flush().map_err(RepError::Output)?;
+        Ok(())
+    };
+
+    if all_objects {
+        for hash in rep.objects().iterate() {
+            show(&hash.to_string(), "")?;
+        }
+        return Ok(());
+    }
+
+    for line in std::io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        // the rest of the line is only split off if the format uses it
+        let (name, rest) = match format.contains("%(rest)") {
+            true => line
+                .trim_start()
+                .split_once(char::is_whitespace)
+                .map(|(n, r)| (n.to_string(), r.trim_start().to_string()))
+                .unwrap_or((line.trim().to_string(), String::new())),
+            false => (line.clone(), String::new()),
+        };
+        show(&name, &rest)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::Blob;
+
+    const MISSING: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn missing_objects_are_only_an_error_without_e() {
+        let rep = Repository::in_memory();
+        let blob = Blob::from_data(b"hello\n".to_vec()).save(&rep).unwrap();
+
+        assert!(cat_file(&rep, Query::Exists, &blob.to_string()).unwrap());
+        assert!(!cat_file(&rep, Query::Exists, MISSING).unwrap());
+        assert!(matches!(
+            cat_file(&rep, Query::Type, MISSING),
+            Err(RepError::InvalidReference(_))
+        ));
+        assert!(matches!(
+            cat_file(&rep, Query::Exists, "no-such-name"),
+            Err(RepError::InvalidReference(_))
+        ));
+        assert_eq!(resolve(&rep, &format!("{:.7}", blob)).unwrap(), blob);
+    }
+
+    #[test]
+    fn typed_objects_have_to_peel_to_the_type() {
+        let rep = Repository::in_memory();
+        let blob = Blob::from_data(b"hello\n".to_vec()).save(&rep).unwrap();
+        assert!(matches!(
+            cat_file(&rep, Query::Typed("tree".to_string()), &blob.to_string()),
+            Err(RepError::InvalidObjectType(_))
+        ));
+    }
+
+    #[test]
+    fn batch_formats_are_expanded() {
+        let id = MISSING.parse::<ObjectId>().unwrap();
+        assert_eq!(
+            expand(
+                "%(objectname) %(objecttype) %(objectsize)",
+                &id,
+                "blob",
+                6,
+                ""
+            ),
+            format!("{} blob 6", MISSING)
+        );
+        assert_eq!(
+            expand("%(objecttype) %(rest) %(unknown)", &id, "tree", 0, "src/"),
+            "tree src/ %(unknown)"
+        );
+    }
+}
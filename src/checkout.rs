@@ -0,0 +1,44 @@
+#![allow(dead_code)]
+
+use crate::index::write_workdir_file;
+use crate::objects::Object;
+use crate::pathspec::Pathspec;
+use crate::repository::{RepError, Repository};
+
+/// write the files of the tree of a commit or ref which match the paths to the working
+/// directory, without paths everything below the current directory is checked out
+pub fn checkout(rep: &Repository, commit_or_ref: &str, paths: &[String]) -> Result<(), RepError> {
+    let paths = match paths.is_empty() {
+        true => vec![".".to_string()],
+        false => paths.to_vec(),
+    };
+    let pathspec = Pathspec::parse(rep, &paths)?;
+
+    let tree = rep
+        .ref_resolve(commit_or_ref)
+        .ok()
+        .and_then(|hash| rep.peel(&hash, "tree"))
+        .ok_or_else(|| RepError::InvalidReference(commit_or_ref.to_string()))?;
+    let tree = match Object::load(rep, &tree)? {
+        Object::Tree(t) => t,
+        _ => return Err(RepError::BadObject("tree".to_string(), tree.to_string())),
+    };
+
+    // like git nothing is written if any of the paths doesn't match
+    let files = tree.matching(rep, "", &pathspec)?;
+    let unmatched = pathspec.unmatched(files.iter().map(|(path, _, _)| path.as_str()));
+    if !unmatched.is_empty() {
+        let message = unmatched
+            .iter()
+            .map(|spec| format!("pathspec '{}' did not match any file(s) known to git", spec))
+            .collect::<Vec<String>>()
+            .join("\nerror: ");
+        return Err(RepError::InvalidArgument(message));
+    }
+
+    for (path, mode, sha) in files {
+        write_workdir_file(rep, &path, &mode, &sha)?;
+        outln!("checked out: {}", path)?;
+    }
+    Ok(())
+}
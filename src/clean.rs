@@ -0,0 +1,485 @@
+#![allow(dead_code)]
+
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+
+use crate::ignore::{wildmatch, Ignore};
+use crate::index::Index;
+use crate::pathspec::{display_path, Pathspec};
+use crate::repository::{RepError, Repository};
+
+/// which untracked files are removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ignored {
+    /// only the files which are not ignored
+    #[default]
+    Respect,
+    /// ignored files too (-x)
+    Include,
+    /// only the ignored files (-X)
+    Only,
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// only show what would be removed (-n)
+    pub dry_run: bool,
+    /// -f, given twice nested repositories are removed too
+    pub force: u8,
+    /// remove untracked folders too (-d)
+    pub directories: bool,
+    pub ignored: Ignored,
+    /// additional ignore patterns (-e), they apply even with -x
+    pub excludes: Vec<String>,
+    /// choose what to remove interactively (-i)
+    pub interactive: bool,
+    /// don't print the names of removed files (-q)
+    pub quiet: bool,
+}
+
+/// walks the working directory and collects what gets removed
+struct Cleaner<'a> {
+    rep: &'a Repository,
+    opts: &'a Options,
+    tracked: HashSet<String>,
+    /// the folders containing tracked files
+    tracked_dirs: HashSet<String>,
+    /// the rules of .gitignore files and -e, or only -e with -x
+    ignore: Ignore,
+    pathspec: Pathspec,
+}
+
+impl Cleaner<'_> {
+    fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        self.ignore.is_ignored(path, is_dir)
+    }
+
+    fn is_repository(&self, path: &str) -> bool {
+        std::path::Path::new(&(self.rep.get_workdir().clone() + "/" + path + "/.git")).exists()
+    }
+
+    /// the entries of a folder as (path, is_dir), sorted by name
+    fn read_dir(&mut self, dir: &str) -> Vec<(String, bool)> {
+        let full = match dir.is_empty() {
+            true => self.rep.get_workdir().clone(),
+            false => self.rep.get_workdir().clone() + "/" + dir,
+        };
+        if !dir.is_empty() && self.opts.ignored != Ignored::Include {
+            self.ignore.add_file(dir, &(full.clone() + "/.gitignore"));
+        }
+
+        let mut entries = match std::fs::read_dir(&full) {
+            Ok(entries) => entries.filter_map(|e| e.ok()).collect::<Vec<_>>(),
+            Err(_) => return vec![],
+        };
+        entries.sort_by_key(|e| e.file_name());
+
+        entries
+            .into_iter()
+            .filter(|e| e.file_name() != ".git")
+            .map(|e| {
+                let name = e.file_name().to_string_lossy().to_string();
+                let path = match dir.is_empty() {
+                    true => name,
+                    false => dir.to_string() + "/" + &name,
+                };
+                (path, e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+            })
+            .collect()
+    }
+
+    /// check if an untracked file is removed
+    fn removes_file(&self, path: &str) -> bool {
+        let ignored = self.is_ignored(path, false);
+        match self.opts.ignored {
+            Ignored::Only => ignored,
+            _ => !ignored,
+        }
+    }
+
+    /// collect what to remove below a folder, folders end with a slash
+    fn collect(&mut self, dir: &str, removed: &mut Vec<String>) {
+        for (path, is_dir) in self.read_dir(dir) {
+            if !is_dir {
+                if !self.tracked.contains(&path)
+                    && self.pathspec.matches(&path)
+                    && self.removes_file(&path)
+                {
+                    removed.push(path);
+                }
+                continue;
+            }
+
+            // folders with tracked files or only partly selected by the paths are entered
+            if self.tracked_dirs.contains(&path) || self.tracked.contains(&path) {
+                if !self.tracked.contains(&path) {
+                    self.collect(&path, removed);
+                }
+                continue;
+            }
+            if !self.pathspec.matches(&path) {
+                if self.pathspec.matches_dir(&path) {
+                    self.collect(&path, removed);
+                }
+                continue;
+            }
+
+            self.collect_untracked_dir(&path, removed);
+        }
+    }
+
+    /// an untracked folder is removed as a whole if everything inside of it would be removed,
+    /// returns whether that is the case
+    fn collect_untracked_dir(&mut self, path: &str, removed: &mut Vec<String>) -> bool {
+        // without -d only -X looks into untracked folders, for the ignored files of folders
+        // which aren't ignored completely
+        if !self.opts.directories && self.opts.ignored != Ignored::Only {
+            return false;
+        }
+        if self.is_repository(path) && self.opts.force < 2 {
+            return false;
+        }
+
+        if self.is_ignored(path, true) {
+            if self.opts.ignored == Ignored::Only {
+                if self.opts.directories {
+                    removed.push(path.to_string() + "/");
+                }
+                return true;
+            }
+            return false;
+        }
+
+        let mut inside = vec![];
+        let mut complete = true;
+        let entries = self.read_dir(path);
+        if entries.is_empty() && self.opts.ignored == Ignored::Only {
+            return false;
+        }
+        for (child, is_dir) in entries {
+            let removes = match is_dir {
+                true => self.collect_untracked_dir(&child, &mut inside),
+                false => {
+                    let removes = self.removes_file(&child);
+                    if removes {
+                        inside.push(child);
+                    }
+                    removes
+                }
+            };
+            complete &= removes;
+        }
+
+        match (complete, self.opts.directories) {
+            (true, true) => removed.push(path.to_string() + "/"),
+            (true, false) => {}
+            (false, true) => removed.extend(inside),
+            (false, false) => removed.extend(inside.into_iter().filter(|p| !p.ends_with('/'))),
+        }
+        complete
+    }
+}
+
+/// an item relative to the current folder, folders keep their trailing slash
+fn shown(item: &str, prefix: &str) -> String {
+    let path = display_path(item.trim_end_matches('/'), prefix);
+    match item.ends_with('/') {
+        true => path + "/",
+        false => path,
+    }
+}
+
+/// ask which of the items to remove, None if the user quit or the output got closed
+fn interactive(items: Vec<String>, prefix: &str) -> Option<Vec<String>> {
+    let mut items = items;
+    let mut input = std::io::stdin().lock().lines().map_while(Result::ok);
+    let mut prompt = |text: &str| -> Option<String> {
+        out!("{}", text).ok()?;
+        std::io::stdout().flush().ok()?;
+        input.next().map(|l| l.trim().to_string())
+    };
+
+    loop {
+        if items.is_empty() {
+            outln!("No more files to clean, exiting.").ok()?;
+            return Some(items);
+        }
+        outln!(
+            "Would remove the following item{}:",
+            if items.len() == 1 { "" } else { "s" }
+        )
+        .ok()?;
+        for item in items.iter() {
+            outln!("  {}", shown(item, prefix)).ok()?;
+        }
+        outln!("*** Commands ***").ok()?;
+        outln!("    1: clean                2: filter by pattern    3: select by numbers").ok()?;
+        outln!("    4: ask each             5: quit                 6: help").ok()?;
+
+        let command = prompt("What now> ")?;
+        match command.as_str() {
+            "1" | "c" | "clean" => return Some(items),
+            "2" | "f" | "filter by pattern" => loop {
+                let patterns = prompt("Input ignore patterns>> ")?;
+                if patterns.is_empty() {
+                    break;
+                }
+                let before = items.len();
+                items.retain(|item| {
+                    let name = shown(item, prefix);
+                    !patterns
+                        .split_whitespace()
+                        .any(|p| wildmatch(p, &name, false))
+                });
+                if items.len() == before {
+                    outln!("WARNING: Cannot find items matched by: {}", patterns).ok()?;
+                }
+                for item in items.iter() {
+                    outln!("  {}", shown(item, prefix)).ok()?;
+                }
+            },
+            "3" | "s" | "select by numbers" => {
+                for (i, item) in items.iter().enumerate() {
+                    outln!("  {:>2}: {}", i + 1, shown(item, prefix)).ok()?;
+                }
+                let selection = prompt("Select items to delete>> ")?;
+                let mut chosen = HashSet::new();
+                for part in selection.split([' ', ',']).filter(|p| !p.is_empty()) {
+                    if part == "*" {
+                        chosen.extend(1..=items.len());
+                        continue;
+                    }
+                    let (start, end) = match part.split_once('-') {
+                        Some((start, "")) => (start.parse().ok(), Some(items.len())),
+                        Some((start, end)) => (start.parse().ok(), end.parse().ok()),
+                        None => (part.parse().ok(), part.parse().ok()),
+                    };
+                    match (start, end) {
+                        (Some(start), Some(end)) => chosen.extend(start..=end),
+                        _ => outln!("Huh ({})?", part).ok()?,
+                    }
+                }
+                return Some(
+                    items
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(i, _)| chosen.contains(&(i + 1)))
+                        .map(|(_, item)| item)
+                        .collect(),
+                );
+            }
+            "4" | "a" | "ask each" => {
+                let mut chosen = vec![];
+                for item in items {
+                    let name = shown(&item, prefix);
+                    let answer = prompt(&format!("Remove {} [y/N]? ", name)).unwrap_or_default();
+                    if answer.to_lowercase().starts_with('y') {
+                        chosen.push(item);
+                    }
+                }
+                return Some(chosen);
+            }
+            "5" | "q" | "quit" => {
+                outln!("Bye.").ok()?;
+                return None;
+            }
+            "6" | "h" | "help" | "?" => {
+                outln!("clean               - start cleaning").ok()?;
+                outln!("filter by pattern   - exclude items from deletion").ok()?;
+                outln!("select by numbers   - select items to be deleted by numbers").ok()?;
+                outln!("ask each            - confirm each deletion (like \"rm -i\")").ok()?;
+                outln!("quit                - stop cleaning").ok()?;
+                outln!("help                - this screen").ok()?;
+                outln!("?                   - help for prompt selection").ok()?;
+            }
+            _ => outln!("Huh ({})?", command).ok()?,
+        }
+    }
+}
+
+/// remove the untracked files (and folders with -d) selected by the paths
+pub fn clean(rep: &Repository, paths: &[String], opts: &Options) -> Result<(), RepError> {
+    let require_force = rep
+        .config_get("clean", "requireForce")
+        .map(|v| v != "false")
+        .unwrap_or(true);
+    if require_force && opts.force == 0 && !opts.dry_run && !opts.interactive {
+        return Err(RepError::InvalidArgument(
+            "clean.requireForce defaults to true and neither -i, -n, nor -f given; refusing to clean"
+                .to_string(),
+        ));
+    }
+
+    let index = Index::load(rep)?;
+    let tracked = index
+        .get_entries()
+        .iter()
+        .map(|e| e.get_name().to_string())
+        .collect::<HashSet<String>>();
+    let mut tracked_dirs = HashSet::new();
+    for name in tracked.iter() {
+        let mut dir = name.as_str();
+        while let Some((parent, _)) = dir.rsplit_once('/') {
+            if !tracked_dirs.insert(parent.to_string()) {
+                break;
+            }
+            dir = parent;
+        }
+    }
+
+    let mut ignore = match opts.ignored {
+        Ignored::Include => Ignore::new(),
+        _ => Ignore::load(rep),
+    };
+    for pattern in opts.excludes.iter() {
+        ignore.add_pattern("", pattern, "command line");
+    }
+
+    // like git only the current folder is cleaned without paths
+    let paths = match paths.is_empty() {
+        true => vec![".".to_string()],
+        false => paths.to_vec(),
+    };
+    let mut cleaner = Cleaner {
+        rep,
+        opts,
+        tracked,
+        tracked_dirs,
+        ignore,
+        pathspec: Pathspec::parse(rep, &paths)?,
+    };
+    let mut removed = vec![];
+    cleaner.collect("", &mut removed);
+    removed.sort();
+
+    let prefix = rep.relative_path(".");
+    if opts.interactive && !opts.dry_run {
+        removed = match interactive(removed, &prefix) {
+            Some(removed) => removed,
+            None => return Ok(()),
+        };
+    }
+
+    for item in removed {
+        if opts.dry_run {
+            outln!("Would remove {}", shown(&item, &prefix))?;
+            continue;
+        }
+
+        let path = rep.get_workdir().clone() + "/" + item.trim_end_matches('/');
+        let result = match item.ends_with('/') {
+            true => std::fs::remove_dir_all(&path),
+            false => std::fs::remove_file(&path),
+        };
+        match result {
+            Ok(()) if !opts.quiet => outln!("Removing {}", shown(&item, &prefix))?,
+            Ok(()) => {}
+            Err(e) => errln!("warning: failed to remove {}: {}", shown(&item, &prefix), e),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(rep: &Repository, name: &str) {
+        let path = std::path::Path::new(rep.get_workdir()).join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "content\n").unwrap();
+    }
+
+    fn exists(rep: &Repository, name: &str) -> bool {
+        std::path::Path::new(rep.get_workdir()).join(name).exists()
+    }
+
+    fn run(rep: &Repository, opts: Options) -> Result<(), RepError> {
+        clean(rep, &[":/".to_string()], &opts)
+    }
+
+    fn forced() -> Options {
+        Options {
+            force: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn force_is_required() {
+        let rep = Repository::temporary("clean-force");
+        rep.commit_files(&[("a.txt", "a\n")], "first");
+        write(&rep, "u.txt");
+
+        assert!(matches!(
+            run(&rep, Options::default()),
+            Err(RepError::InvalidArgument(_))
+        ));
+        let dry_run = Options {
+            dry_run: true,
+            ..Default::default()
+        };
+        run(&rep, dry_run).unwrap();
+        assert!(exists(&rep, "u.txt"));
+
+        run(&rep, forced()).unwrap();
+        assert!(!exists(&rep, "u.txt") && exists(&rep, "a.txt"));
+    }
+
+    #[test]
+    fn ignored_files_and_folders_need_options() {
+        let rep = Repository::temporary("clean-ignored");
+        rep.commit_files(&[(".gitignore", "*.log\n"), ("a.txt", "a\n")], "first");
+        for name in ["u.txt", "debug.log", "keep.txt", "dir/x.txt"] {
+            write(&rep, name);
+        }
+
+        let opts = Options {
+            excludes: vec!["keep.txt".to_string()],
+            ..forced()
+        };
+        run(&rep, opts).unwrap();
+        assert!(!exists(&rep, "u.txt"));
+        assert!(exists(&rep, "debug.log") && exists(&rep, "keep.txt") && exists(&rep, "dir/x.txt"));
+
+        let opts = Options {
+            ignored: Ignored::Only,
+            ..forced()
+        };
+        run(&rep, opts).unwrap();
+        assert!(!exists(&rep, "debug.log") && exists(&rep, "keep.txt"));
+
+        let opts = Options {
+            directories: true,
+            ignored: Ignored::Include,
+            ..forced()
+        };
+        run(&rep, opts).unwrap();
+        assert!(!exists(&rep, "dir") && !exists(&rep, "keep.txt"));
+        assert!(exists(&rep, "a.txt") && exists(&rep, ".gitignore"));
+    }
+
+    #[test]
+    fn nested_repositories_need_a_second_force() {
+        let rep = Repository::temporary("clean-nested");
+        rep.commit_files(&[("a.txt", "a\n")], "first");
+        write(&rep, "nested/.git/HEAD");
+        write(&rep, "nested/file.txt");
+
+        let opts = Options {
+            directories: true,
+            ..forced()
+        };
+        run(&rep, opts).unwrap();
+        assert!(exists(&rep, "nested/file.txt"));
+
+        let opts = Options {
+            directories: true,
+            force: 2,
+            ..Default::default()
+        };
+        run(&rep, opts).unwrap();
+        assert!(!exists(&rep, "nested"));
+    }
+}
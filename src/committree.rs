@@ -0,0 +1,77 @@
+#![allow(dead_code)]
+
+use std::io::Read;
+
+use crate::objects::Commit;
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+
+/// the message of a commit where every -m and -F is a paragraph, a file - is stdin,
+/// without any the whole message is read from stdin
+pub fn read_message(messages: &[String], files: &[String]) -> Result<String, RepError> {
+    let read_stdin = || {
+        let mut content = String::new();
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .map(|_| content)
+    };
+
+    let mut paragraphs = messages.to_vec();
+    for file in files {
+        let content = match file.as_str() {
+            "-" => read_stdin(),
+            _ => std::fs::read_to_string(file),
+        };
+        match content {
+            Ok(content) => paragraphs.push(content),
+            Err(_) => {
+                return Err(RepError::InvalidArgument(format!(
+                    "could not read log file '{}'",
+                    file
+                )))
+            }
+        }
+    }
+
+    match paragraphs.is_empty() {
+        true => read_stdin().map_err(|e| RepError::Io("stdin".to_string(), e)),
+        false => Ok(paragraphs
+            .iter()
+            .map(|p| p.trim_end_matches('\n').to_string() + "\n")
+            .collect::<Vec<String>>()
+            .join("\n")),
+    }
+}
+
+/// create a commit of a tree with the parents, a parent given twice is only used once,
+/// the message is only read once the tree and parents are known to exist
+pub fn commit_tree(
+    rep: &Repository,
+    tree: &str,
+    parents: &[String],
+    messages: &[String],
+    files: &[String],
+) -> Result<ObjectId, RepError> {
+    let peel = |name: &str, object_type: &str| {
+        rep.ref_resolve(name)
+            .ok()
+            .and_then(|hash| rep.peel(&hash, object_type))
+            .ok_or_else(|| RepError::InvalidReference(name.to_string()))
+    };
+    let tree = peel(tree, "tree")?;
+
+    let mut parent_hashes: Vec<ObjectId> = vec![];
+    for parent in parents {
+        let hash = peel(parent, "commit")?;
+        if parent_hashes.contains(&hash) {
+            errln!("error: duplicate parent {} ignored", hash);
+            continue;
+        }
+        parent_hashes.push(hash);
+    }
+
+    let message = read_message(messages, files)?;
+    let author = rep.get_signature("AUTHOR")?;
+    let committer = rep.get_signature("COMMITTER")?;
+    Commit::create(&tree, &parent_hashes, &author, &committer, &message).save(rep)
+}
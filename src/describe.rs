@@ -0,0 +1,552 @@
+#![allow(dead_code)]
+
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::ignore::wildmatch;
+use crate::index::Index;
+use crate::merge::tree_files;
+use crate::objects::{Object, KVLM};
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+use crate::revwalk::{ancestors, commit_time, resolve_commit};
+
+/// going to the second parent of a merge counts like this many commits
+const MERGE_TRAVERSAL_WEIGHT: usize = 65535;
+
+/// describe stops looking after finding this many tagged commits
+const MAX_CANDIDATES: usize = 10;
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// use any ref, not only tags (--all)
+    pub all: bool,
+    /// use lightweight tags too (--tags)
+    pub tags: bool,
+    /// always show the distance and the abbreviated hash (--long)
+    pub long: bool,
+    /// length of the abbreviated hash, 0 only shows the tag
+    pub abbrev: usize,
+    /// mark to append if the working directory has changes (--dirty)
+    pub dirty: Option<String>,
+    /// only use tags matching one of these globs (--match)
+    pub matches: Vec<String>,
+    /// dont use tags matching one of these globs (--exclude)
+    pub excludes: Vec<String>,
+    /// find the tag that comes after the commit instead (--contains)
+    pub contains: bool,
+    /// show the abbreviated hash if nothing describes the commit (--always)
+    pub always: bool,
+}
+
+/// a ref which can be used to describe a commit
+struct Candidate {
+    name: String,
+    /// annotated tags are preferred over lightweight tags over other refs
+    priority: u8,
+    /// the tagger date of annotated tags, newer tags win
+    date: i64,
+}
+
+/// the name of a ref without refs/tags/, refs/heads/ or refs/remotes/ to match patterns against
+fn short_ref(refname: &str) -> &str {
+    ["refs/tags/", "refs/heads/", "refs/remotes/"]
+        .iter()
+        .find_map(|prefix| refname.strip_prefix(prefix))
+        .unwrap_or(refname)
+}
+
+fn is_annotated(rep: &Repository, hash: &ObjectId) -> bool {
+    matches!(Object::load(rep, hash), Ok(Object::Tag(_)))
+}
+
+/// the tagger date of a tag object, the commit date for everything else
+fn tag_date(rep: &Repository, hash: &ObjectId) -> i64 {
+    match Object::load(rep, hash) {
+        Ok(Object::Tag(t)) => t.get_tagger().map(|t| t.seconds).unwrap_or(0),
+        _ => commit_time(rep, hash),
+    }
+}
+
+/// the refs describe can use by the commit they point to
+/// also returns whether lightweight tags were skipped, for the hint
+fn candidates(rep: &Repository, opts: &Options) -> (HashMap<ObjectId, Candidate>, bool) {
+    let mut names: HashMap<ObjectId, Candidate> = HashMap::new();
+    let mut unannotated = false;
+
+    for (refname, hash) in rep.get_refs() {
+        let is_tag = refname.starts_with("refs/tags/");
+        if !is_tag && !opts.all {
+            continue;
+        }
+
+        let short = short_ref(&refname);
+        if !opts.matches.is_empty() && !opts.matches.iter().any(|p| wildmatch(p, short, false)) {
+            continue;
+        }
+        if opts.excludes.iter().any(|p| wildmatch(p, short, false)) {
+            continue;
+        }
+
+        let annotated = is_tag && is_annotated(rep, &hash);
+        if is_tag && !annotated && !opts.tags && !opts.all {
+            unannotated = true;
+            continue;
+        }
+
+        let commit = match rep.peel(&hash, "commit") {
+            Some(commit) => commit,
+            None => continue,
+        };
+        let candidate = Candidate {
+            name: match opts.all {
+                true => refname.trim_start_matches("refs/").to_string(),
+                false => short.to_string(),
+            },
+            priority: match (is_tag, annotated) {
+                (true, true) => 2,
+                (true, false) => 1,
+                _ => 0,
+            },
+            date: match annotated {
+                true => tag_date(rep, &hash),
+                false => 0,
+            },
+        };
+
+        let better = match names.get(&commit) {
+            Some(existing) => {
+                candidate.priority > existing.priority
+                    || (candidate.priority == existing.priority && candidate.date > existing.date)
+            }
+            None => true,
+        };
+        if better {
+            names.insert(commit, candidate);
+        }
+    }
+
+    (names, unannotated)
+}
+
+/// check if the index or the working directory differ from HEAD
+fn is_dirty(rep: &Repository) -> Result<bool, RepError> {
+    let index = Index::load(rep)?;
+    let head_tree = rep
+        .ref_resolve("HEAD")
+        .ok()
+        .and_then(|h| rep.peel(&h, "tree"));
+
+    Ok(!index.get_conflicts().is_empty()
+        || tree_files(rep, head_tree.as_ref()).ok() != Some(index.to_map())
+        || !index.get_modified(rep).is_empty())
+}
+
+/// describe a commit by the closest tag reachable from it
+fn describe_commit(
+    rep: &Repository,
+    hash: &ObjectId,
+    names: &HashMap<ObjectId, Candidate>,
+    opts: &Options,
+) -> Option<String> {
+    let abbrev = |hash: &ObjectId| format!("{:.*}", opts.abbrev.clamp(4, 40), hash);
+
+    if let Some(candidate) = names.get(hash) {
+        return Some(match opts.long && opts.abbrev > 0 {
+            true => format!("{}-0-g{}", candidate.name, abbrev(hash)),
+            false => candidate.name.clone(),
+        });
+    }
+
+    // walk the history newest first and keep the first tagged commits
+    let mut found = vec![];
+    let mut seen = HashSet::new();
+    let mut queue = BinaryHeap::new();
+    queue.push((commit_time(rep, hash), *hash));
+    seen.insert(*hash);
+
+    while let Some((_, commit)) = queue.pop() {
+        if names.contains_key(&commit) {
+            found.push(commit);
+            if found.len() >= MAX_CANDIDATES {
+                break;
+            }
+            // commits behind a tag are described by that tag already
+            continue;
+        }
+        if let Ok(Object::Commit(c)) = Object::load(rep, &commit) {
+            for parent in c.get_parents() {
+                if seen.insert(*parent) {
+                    queue.push((commit_time(rep, parent), *parent));
+                }
+            }
+        }
+    }
+
+    // the best tag is the one with the fewest commits on top of it
+    let history = ancestors(rep, &[*hash]);
+    let (depth, tagged) = found
+        .iter()
+        .map(|tagged| {
+            let behind = ancestors(rep, std::slice::from_ref(tagged));
+            (history.difference(&behind).count(), tagged)
+        })
+        .min_by_key(|(depth, _)| *depth)?;
+
+    let name = &names[tagged].name;
+    Some(match opts.abbrev {
+        0 => name.clone(),
+        _ => format!("{}-{}-g{}", name, depth, abbrev(hash)),
+    })
+}
+
+/// describe commits by tags, the working directory (HEAD) if no commits are given
+pub fn describe(rep: &Repository, commits: &[String], opts: Options) -> Result<(), RepError> {
+    if opts.contains {
+        let name_opts = NameOptions {
+            tags: !opts.all,
+            refs: opts
+                .matches
+                .iter()
+                .map(|p| format!("refs/tags/{}", p))
+                .collect(),
+            excludes: opts
+                .excludes
+                .iter()
+                .map(|p| format!("refs/tags/{}", p))
+                .collect(),
+            name_only: true,
+            no_undefined: true,
+            always: opts.always,
+        };
+        let commits = match commits.is_empty() {
+            true => vec!["HEAD".to_string()],
+            false => commits.to_vec(),
+        };
+        return name_rev(rep, &commits, name_opts);
+    }
+
+    if opts.dirty.is_some() && !commits.is_empty() {
+        return Err(RepError::InvalidArgument(
+            "option '--dirty' and commit-ishes cannot be used together".to_string(),
+        ));
+    }
+
+    let (names, unannotated) = candidates(rep, &opts);
+    let dirty = match &opts.dirty {
+        Some(mark) if is_dirty(rep)? => mark.clone(),
+        _ => String::new(),
+    };
+
+    let commits = match commits.is_empty() {
+        true => vec!["HEAD".to_string()],
+        false => commits.to_vec(),
+    };
+    for commit in commits {
+        let hash = resolve_commit(rep, &commit)?;
+
+        let description = match describe_commit(rep, &hash, &names, &opts) {
+            Some(description) => description,
+            None if opts.always => format!("{:.*}", opts.abbrev.clamp(4, 40), hash),
+            None if names.is_empty() && !unannotated => {
+                return Err(RepError::NoNames("No names found, cannot describe anything.".to_string()))
+            }
+            None if unannotated => {
+                return Err(RepError::NoNames(format!(
+                    "No annotated tags can describe '{}'.\nHowever, there were unannotated tags: try --tags.",
+                    hash
+                )))
+            }
+            None => {
+                return Err(RepError::NoNames(format!(
+                    "No tags can describe '{}'.\nTry --always, or create some tags.",
+                    hash
+                )))
+            }
+        };
+        outln!("{}{}", description, dirty)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct NameOptions {
+    /// only use tags (--tags)
+    pub tags: bool,
+    /// only use refs matching one of these globs (--refs)
+    pub refs: Vec<String>,
+    /// dont use refs matching one of these globs (--exclude)
+    pub excludes: Vec<String>,
+    /// only print the name (--name-only)
+    pub name_only: bool,
+    /// fail for commits which cant be named (--no-undefined)
+    pub no_undefined: bool,
+    /// show the abbreviated hash for commits which cant be named (--always)
+    pub always: bool,
+}
+
+/// a name of a commit relative to a ref like tags/v1.0~2^2
+#[derive(Debug, Clone)]
+struct RevName {
+    tip: String,
+    /// number of first parent steps from the tip
+    generation: usize,
+    /// merges count much more than first parents
+    distance: usize,
+    from_tag: bool,
+    /// tagger date of the tip, names based on older tags are preferred
+    date: i64,
+}
+
+impl RevName {
+    fn is_better_than(&self, other: &RevName) -> bool {
+        if self.from_tag && other.from_tag {
+            return other.date > self.date
+                || (other.date == self.date && other.distance > self.distance);
+        }
+        if self.from_tag != other.from_tag {
+            return self.from_tag;
+        }
+        if self.distance != other.distance {
+            return other.distance > self.distance;
+        }
+        other.generation > self.generation
+    }
+
+    /// the tip without the ^0 git appends to peeled tags
+    fn base(&self) -> &str {
+        self.tip.strip_suffix("^0").unwrap_or(&self.tip)
+    }
+
+    fn to_name(&self) -> String {
+        match self.generation {
+            0 => self.tip.clone(),
+            n => format!("{}~{}", self.base(), n),
+        }
+    }
+}
+
+/// check a glob against the full refname and every path suffix of it
+/// returns where the match starts, refs matched by a suffix are shown abbreviated
+fn subpath_matches(pattern: &str, refname: &str) -> Option<usize> {
+    refname
+        .char_indices()
+        .filter(|(i, c)| *i == 0 || *c == '/')
+        .map(|(i, c)| if c == '/' { i + 1 } else { i })
+        .find(|&start| wildmatch(pattern, &refname[start..], false))
+}
+
+/// name every commit reachable from the refs relative to the best ref
+fn name_commits(rep: &Repository, opts: &NameOptions) -> HashMap<ObjectId, RevName> {
+    let mut tips = vec![];
+
+    for (refname, hash) in rep.get_refs() {
+        if opts.tags && !refname.starts_with("refs/tags/") {
+            continue;
+        }
+        let matched: Vec<usize> = opts
+            .refs
+            .iter()
+            .filter_map(|p| subpath_matches(p, &refname))
+            .collect();
+        if !opts.refs.is_empty() && matched.is_empty() {
+            continue;
+        }
+        if opts
+            .excludes
+            .iter()
+            .any(|p| subpath_matches(p, &refname).is_some())
+        {
+            continue;
+        }
+        let abbreviate = (opts.tags && opts.name_only) || matched.iter().any(|&m| m > 0);
+        let commit = match rep.peel(&hash, "commit") {
+            Some(commit) => commit,
+            None => continue,
+        };
+
+        let mut tip = match abbreviate {
+            true => short_ref(&refname).to_string(),
+            false => refname
+                .strip_prefix("refs/heads/")
+                .or_else(|| refname.strip_prefix("refs/"))
+                .unwrap_or(&refname)
+                .to_string(),
+        };
+        let peeled = commit != hash;
+        if peeled {
+            tip.push_str("^0");
+        }
+
+        tips.push((
+            commit,
+            RevName {
+                tip,
+                generation: 0,
+                distance: 0,
+                from_tag: refname.starts_with("refs/tags/"),
+                date: tag_date(rep, &hash),
+            },
+        ));
+    }
+
+    // tags first and older ones before newer ones
+    tips.sort_by_key(|(_, name)| (!name.from_tag, name.date));
+
+    let mut names: HashMap<ObjectId, RevName> = HashMap::new();
+    for (commit, name) in tips {
+        let mut stack = vec![(commit, name)];
+        while let Some((commit, name)) = stack.pop() {
+            if let Some(existing) = names.get(&commit) {
+                if !name.is_better_than(existing) {
+                    continue;
+                }
+            }
+            names.insert(commit, name.clone());
+
+            let parents = match Object::load(rep, &commit) {
+                Ok(Object::Commit(c)) => c.get_parents().to_vec(),
+                _ => vec![],
+            };
+            // the first parent is handled first, so it is pushed last
+            for (i, parent) in parents.into_iter().enumerate().rev() {
+                let parent_name = match i {
+                    0 => RevName {
+                        generation: name.generation + 1,
+                        distance: name.distance + 1,
+                        ..name.clone()
+                    },
+                    n => RevName {
+                        tip: match name.generation {
+                            0 => format!("{}^{}", name.base(), n + 1),
+                            g => format!("{}~{}^{}", name.base(), g, n + 1),
+                        },
+                        generation: 0,
+                        distance: name.distance + MERGE_TRAVERSAL_WEIGHT,
+                        ..name.clone()
+                    },
+                };
+                stack.push((parent, parent_name));
+            }
+        }
+    }
+
+    names
+}
+
+/// show names of commits relative to refs like tags/v1.0~2
+pub fn name_rev(rep: &Repository, commits: &[String], opts: NameOptions) -> Result<(), RepError> {
+    let names = name_commits(rep, &opts);
+
+    for commit in commits {
+        let hash = match resolve_commit(rep, commit) {
+            Ok(hash) => hash,
+            Err(_) => {
+                outln!("Could not get sha1 for {}. Skipping.", commit)?;
+                continue;
+            }
+        };
+        let name = match names.get(&hash) {
+            Some(name) => name.to_name(),
+            None if opts.always => format!("{:.7}", hash),
+            None if opts.no_undefined => {
+                return Err(RepError::NoNames(format!("cannot describe '{}'", hash)))
+            }
+            None => "undefined".to_string(),
+        };
+
+        match opts.name_only {
+            true => outln!("{}", name)?,
+            false => outln!("{} {}", commit, name)?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// three commits on master with the lightweight tag v1.0 on the first one
+    fn history(name: &str) -> (Repository, [ObjectId; 3]) {
+        let rep = Repository::temporary(name);
+        let first = rep.commit_files(&[("a.txt", "1\n")], "first");
+        let second = rep.commit_files(&[("a.txt", "2\n")], "second");
+        let third = rep.commit_files(&[("a.txt", "3\n")], "third");
+        rep.update_ref("refs/tags/v1.0", &first).unwrap();
+        (rep, [first, second, third])
+    }
+
+    fn options() -> Options {
+        Options {
+            tags: true,
+            abbrev: 7,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn commits_are_described_by_the_closest_tag() {
+        let (rep, [first, _, third]) = history("describe-closest");
+        let opts = options();
+        let (names, unannotated) = candidates(&rep, &opts);
+        assert!(!unannotated);
+
+        assert_eq!(
+            describe_commit(&rep, &first, &names, &opts).unwrap(),
+            "v1.0"
+        );
+        assert_eq!(
+            describe_commit(&rep, &third, &names, &opts).unwrap(),
+            format!("v1.0-2-g{:.7}", third)
+        );
+        let long = Options {
+            long: true,
+            ..options()
+        };
+        assert_eq!(
+            describe_commit(&rep, &first, &names, &long).unwrap(),
+            format!("v1.0-0-g{:.7}", first)
+        );
+
+        // lightweight tags are only used with --tags
+        let (names, unannotated) = candidates(&rep, &Options::default());
+        assert!(names.is_empty() && unannotated);
+        let excluded = Options {
+            excludes: vec!["v1.*".to_string()],
+            ..options()
+        };
+        assert!(candidates(&rep, &excluded).0.is_empty());
+    }
+
+    #[test]
+    fn the_working_directory_can_be_dirty() {
+        let (rep, _) = history("describe-dirty");
+        assert!(!is_dirty(&rep).unwrap());
+        std::fs::write(rep.get_workdir().clone() + "/a.txt", "changed\n").unwrap();
+        assert!(is_dirty(&rep).unwrap());
+    }
+
+    #[test]
+    fn commits_are_named_relative_to_refs() {
+        let (rep, [first, second, third]) = history("describe-name-rev");
+        rep.update_ref("refs/tags/v2.0", &third).unwrap();
+
+        let names = name_commits(&rep, &NameOptions::default());
+        // tags win over branches, older tags over newer ones
+        assert_eq!(names[&first].to_name(), "tags/v1.0");
+        assert_eq!(names[&second].to_name(), "tags/v2.0~1");
+
+        let opts = NameOptions {
+            refs: vec!["heads/*".to_string()],
+            ..Default::default()
+        };
+        let names = name_commits(&rep, &opts);
+        assert_eq!(names[&first].to_name(), "master~2");
+
+        assert_eq!(subpath_matches("v1.*", "refs/tags/v1.0"), Some(10));
+        assert_eq!(subpath_matches("refs/tags/*", "refs/tags/v1.0"), Some(0));
+        assert_eq!(subpath_matches("v2.*", "refs/tags/v1.0"), None);
+    }
+}
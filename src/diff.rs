@@ -0,0 +1,493 @@
+#![allow(dead_code)]
+
+use crate::merge::tree_files;
+use crate::objects::Object;
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+
+/// a single step of an edit script turning a into b
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// line a[i] is equal to line b[j]
+    Equal(usize, usize),
+    /// line a[i] got removed
+    Delete(usize),
+    /// line b[j] got added
+    Insert(usize),
+}
+
+/// split data into lines, the line endings are kept so the lines can be joined again
+pub fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    let mut lines = vec![];
+    let mut start = 0;
+
+    for (i, b) in data.iter().enumerate() {
+        if *b == b'\n' {
+            lines.push(&data[start..=i]);
+            start = i + 1;
+        }
+    }
+    if start < data.len() {
+        lines.push(&data[start..]);
+    }
+
+    lines
+}
+
+/// compute the shortest edit script between a and b with the myers algorithm
+pub fn diff<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    // the common prefix and suffix dont need to go through the algorithm
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut edits = (0..prefix)
+        .map(|i| Edit::Equal(i, i))
+        .collect::<Vec<Edit>>();
+
+    let middle = myers(&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    edits.extend(middle.into_iter().map(|e| match e {
+        Edit::Equal(i, j) => Edit::Equal(i + prefix, j + prefix),
+        Edit::Delete(i) => Edit::Delete(i + prefix),
+        Edit::Insert(j) => Edit::Insert(j + prefix),
+    }));
+
+    let (a_end, b_end) = (a.len() - suffix, b.len() - suffix);
+    edits.extend((0..suffix).map(|i| Edit::Equal(a_end + i, b_end + i)));
+
+    edits
+}
+
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+
+    // v[k] is the furthest x reached on diagonal k, trace keeps v for every d to walk back
+    let mut v = vec![0isize; 2 * max + 3];
+    let mut trace = vec![];
+
+    'outer: for d in 0..=max as isize {
+        trace.push(v.clone());
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+
+            if x >= n && y >= m {
+                break 'outer;
+            }
+            k += 2;
+        }
+    }
+
+    // walk back through the trace to build the edit script
+    let mut edits = vec![];
+    let (mut x, mut y) = (n, m);
+
+    for d in (1..trace.len() as isize).rev() {
+        let v = &trace[d as usize];
+        let k = x - y;
+        let idx = (k + offset) as usize;
+
+        let prev_k = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Equal(x as usize, y as usize));
+        }
+
+        if x == prev_x {
+            y -= 1;
+            edits.push(Edit::Insert(y as usize));
+        } else {
+            x -= 1;
+            edits.push(Edit::Delete(x as usize));
+        }
+    }
+
+    while x > 0 && y > 0 {
+        x -= 1;
+        y -= 1;
+        edits.push(Edit::Equal(x as usize, y as usize));
+    }
+
+    edits.reverse();
+    edits
+}
+
+/// for every line of a the index of the matching line in b (if the line is kept)
+pub fn matches<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Option<usize>> {
+    let mut result = vec![None; a.len()];
+
+    for edit in diff(a, b) {
+        if let Edit::Equal(i, j) = edit {
+            result[i] = Some(j);
+        }
+    }
+
+    result
+}
+
+/// a changed file between two trees as (path, old (mode, sha), new (mode, sha))
+pub type FileChange = (
+    String,
+    Option<(String, ObjectId)>,
+    Option<(String, ObjectId)>,
+);
+
+/// the files which differ between two trees, None is the empty tree
+pub fn diff_trees(
+    rep: &Repository,
+    old: Option<&ObjectId>,
+    new: Option<&ObjectId>,
+) -> Result<Vec<FileChange>, RepError> {
+    let old = tree_files(rep, old)?;
+    let new = tree_files(rep, new)?;
+
+    let mut paths = old.keys().chain(new.keys()).collect::<Vec<&String>>();
+    paths.sort();
+    paths.dedup();
+
+    Ok(paths
+        .into_iter()
+        .filter(|p| old.get(*p) != new.get(*p))
+        .map(|p| (p.clone(), old.get(p).cloned(), new.get(p).cloned()))
+        .collect())
+}
+
+/// git treats files with a null byte in the first 8000 bytes as binary
+pub fn is_binary(data: &[u8]) -> bool {
+    data.iter().take(8000).any(|&b| b == 0)
+}
+
+fn read_blob(rep: &Repository, entry: &Option<(String, ObjectId)>) -> Result<Vec<u8>, RepError> {
+    let data = match entry {
+        Some((mode, sha)) if mode != "160000" => Object::load(rep, sha)?.get_data().clone(),
+        Some((_, sha)) => format!("Subproject commit {}\n", sha).into_bytes(),
+        None => vec![],
+    };
+    Ok(data)
+}
+
+/// the hunks of a unified diff between two file contents
+pub fn unified(old: &[u8], new: &[u8], context: usize) -> String {
+    let a = split_lines(old);
+    let b = split_lines(new);
+    let edits = diff(&a, &b);
+
+    // the position in a and b before every edit
+    let mut positions = vec![];
+    let (mut x, mut y) = (0, 0);
+    for edit in edits.iter() {
+        positions.push((x, y));
+        match edit {
+            Edit::Equal(_, _) => {
+                x += 1;
+                y += 1;
+            }
+            Edit::Delete(_) => x += 1,
+            Edit::Insert(_) => y += 1,
+        }
+    }
+    positions.push((x, y));
+
+    let is_change = |e: &Edit| !matches!(e, Edit::Equal(_, _));
+    let mut result = String::new();
+    let mut i = 0;
+
+    while i < edits.len() {
+        if !is_change(&edits[i]) {
+            i += 1;
+            continue;
+        }
+
+        // extend the hunk as long as the next change is close enough to share the context
+        let start = i.saturating_sub(context);
+        let mut last_change = i;
+        let mut j = i;
+        while j < edits.len() {
+            if is_change(&edits[j]) {
+                last_change = j;
+            } else if j - last_change > 2 * context {
+                break;
+            }
+            j += 1;
+        }
+        let end = (last_change + context + 1).min(edits.len());
+
+        let (a_start, b_start) = positions[start];
+        let (a_end, b_end) = positions[end];
+        let range = |start: usize, count: usize| match count {
+            0 => format!("{},0", start),
+            1 => format!("{}", start + 1),
+            _ => format!("{},{}", start + 1, count),
+        };
+        result.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(a_start, a_end - a_start),
+            range(b_start, b_end - b_start)
+        ));
+
+        for edit in edits[start..end].iter() {
+            let (prefix, line) = match *edit {
+                Edit::Equal(i, _) => (' ', a[i]),
+                Edit::Delete(i) => ('-', a[i]),
+                Edit::Insert(j) => ('+', b[j]),
+            };
+            result.push(prefix);
+            result.push_str(&String::from_utf8_lossy(line));
+            if !line.ends_with(b"\n") {
+                result.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+
+        i = end;
+    }
+
+    result
+}
+
+/// the patch of a list of changes like git diff prints it
+pub fn format_patch(rep: &Repository, changes: &[FileChange]) -> Result<String, RepError> {
+    let mut result = String::new();
+    let short = |sha: &Option<(String, ObjectId)>| match sha {
+        Some((_, sha)) => format!("{:.7}", sha),
+        None => "0000000".to_string(),
+    };
+
+    for (path, old, new) in changes {
+        result.push_str(&format!("diff --git a/{} b/{}\n", path, path));
+
+        match (old, new) {
+            (None, Some((mode, _))) => result.push_str(&format!("new file mode {}\n", mode)),
+            (Some((mode, _)), None) => result.push_str(&format!("deleted file mode {}\n", mode)),
+            (Some((old_mode, _)), Some((new_mode, _))) if old_mode != new_mode => {
+                result.push_str(&format!("old mode {}\nnew mode {}\n", old_mode, new_mode));
+            }
+            _ => {}
+        }
+
+        let same_content = old.as_ref().map(|o| &o.1) == new.as_ref().map(|n| &n.1);
+        if same_content {
+            continue;
+        }
+
+        let mode = match (old, new) {
+            (Some((old_mode, _)), Some((new_mode, _))) if old_mode == new_mode => {
+                format!(" {}", old_mode)
+            }
+            _ => String::new(),
+        };
+        result.push_str(&format!("index {}..{}{}\n", short(old), short(new), mode));
+
+        let old_data = read_blob(rep, old)?;
+        let new_data = read_blob(rep, new)?;
+        let old_name = old.as_ref().map(|_| format!("a/{}", path));
+        let new_name = new.as_ref().map(|_| format!("b/{}", path));
+        let old_name = old_name.unwrap_or("/dev/null".to_string());
+        let new_name = new_name.unwrap_or("/dev/null".to_string());
+
+        if is_binary(&old_data) || is_binary(&new_data) {
+            result.push_str(&format!(
+                "Binary files {} and {} differ\n",
+                old_name, new_name
+            ));
+            continue;
+        }
+
+        result.push_str(&format!("--- {}\n+++ {}\n", old_name, new_name));
+        result.push_str(&unified(&old_data, &new_data, 3));
+    }
+
+    Ok(result)
+}
+
+/// the number of added and removed lines of a change, None for binary files
+pub fn count_changes(
+    rep: &Repository,
+    change: &FileChange,
+) -> Result<Option<(usize, usize)>, RepError> {
+    let old = read_blob(rep, &change.1)?;
+    let new = read_blob(rep, &change.2)?;
+    if is_binary(&old) || is_binary(&new) {
+        return Ok(None);
+    }
+
+    let edits = diff(&split_lines(&old), &split_lines(&new));
+    let added = edits
+        .iter()
+        .filter(|e| matches!(e, Edit::Insert(_)))
+        .count();
+    let removed = edits
+        .iter()
+        .filter(|e| matches!(e, Edit::Delete(_)))
+        .count();
+    Ok(Some((added, removed)))
+}
+
+/// a diffstat like git diff --stat
+pub fn format_stat(rep: &Repository, changes: &[FileChange]) -> Result<String, RepError> {
+    let counts = changes
+        .iter()
+        .map(|c| count_changes(rep, c))
+        .collect::<Result<Vec<Option<(usize, usize)>>, RepError>>()?;
+
+    let name_width = changes.iter().map(|c| c.0.len()).max().unwrap_or(0);
+    let max_changes = counts
+        .iter()
+        .map(|c| c.map(|(a, r)| a + r).unwrap_or(0))
+        .max()
+        .unwrap_or(0);
+    let count_width = max_changes.to_string().len();
+    // the graph is scaled down if it would get too wide
+    let scale = |n: usize| match max_changes > 50 {
+        true => (n * 50).div_ceil(max_changes),
+        false => n,
+    };
+
+    let mut result = String::new();
+    let (mut insertions, mut deletions) = (0, 0);
+
+    for (change, count) in changes.iter().zip(counts.iter()) {
+        match count {
+            Some((added, removed)) => {
+                insertions += added;
+                deletions += removed;
+                result.push_str(&format!(
+                    " {:name_width$} | {:>count_width$} {}{}\n",
+                    change.0,
+                    added + removed,
+                    "+".repeat(scale(*added)),
+                    "-".repeat(scale(*removed)),
+                ));
+            }
+            None => result.push_str(&format!(" {:name_width$} | Bin\n", change.0)),
+        }
+    }
+
+    let plural = |n: usize, word: &str| match n {
+        1 => format!("{} {}", n, word),
+        _ => format!("{} {}s", n, word),
+    };
+    result.push_str(&format!(" {} changed", plural(changes.len(), "file")));
+    if insertions > 0 || deletions == 0 {
+        result.push_str(&format!(", {}(+)", plural(insertions, "insertion")));
+    }
+    if deletions > 0 {
+        result.push_str(&format!(", {}(-)", plural(deletions, "deletion")));
+    }
+    result.push('\n');
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// apply an edit script to a and check that it gives b
+    fn check_script(a: &[char], b: &[char], edits: &[Edit]) {
+        let mut result = vec![];
+        let (mut x, mut y) = (0, 0);
+        for edit in edits {
+            match *edit {
+                Edit::Equal(i, j) => {
+                    assert_eq!((i, j), (x, y));
+                    assert_eq!(a[i], b[j]);
+                    result.push(a[i]);
+                    x += 1;
+                    y += 1;
+                }
+                Edit::Delete(i) => {
+                    assert_eq!(i, x);
+                    x += 1;
+                }
+                Edit::Insert(j) => {
+                    assert_eq!(j, y);
+                    result.push(b[j]);
+                    y += 1;
+                }
+            }
+        }
+        assert_eq!((x, result.as_slice()), (a.len(), b));
+    }
+
+    fn changes(edits: &[Edit]) -> usize {
+        edits
+            .iter()
+            .filter(|e| !matches!(e, Edit::Equal(_, _)))
+            .count()
+    }
+
+    #[test]
+    fn myers_finds_the_shortest_edit_script() {
+        let a = "ABCABBA".chars().collect::<Vec<char>>();
+        let b = "CBABAC".chars().collect::<Vec<char>>();
+        let edits = diff(&a, &b);
+        check_script(&a, &b, &edits);
+        assert_eq!(changes(&edits), 5);
+    }
+
+    #[test]
+    fn empty_and_equal_sides_are_diffed() {
+        let a = "abc".chars().collect::<Vec<char>>();
+        for (a, b) in [
+            (&a[..], &[][..]),
+            (&[][..], &a[..]),
+            (&a[..], &a[..]),
+            (&[][..], &[][..]),
+        ] {
+            let edits = diff(a, b);
+            check_script(a, b, &edits);
+            assert_eq!(changes(&edits), a.len().abs_diff(b.len()));
+        }
+    }
+
+    #[test]
+    fn kept_lines_are_matched() {
+        let a = ["a", "b", "c"];
+        let b = ["a", "c", "d"];
+        assert_eq!(matches(&a, &b), vec![Some(0), None, Some(1)]);
+    }
+
+    #[test]
+    fn lines_keep_their_endings() {
+        assert_eq!(split_lines(b"a\nb"), vec![&b"a\n"[..], &b"b"[..]]);
+        assert!(split_lines(b"").is_empty());
+    }
+
+    #[test]
+    fn hunks_are_formatted_like_git() {
+        let old = b"1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = b"1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\neleven";
+        assert_eq!(
+            unified(old, new, 1),
+            "@@ -2,3 +2,3 @@\n 2\n-3\n+three\n 4\n\
+             @@ -10 +10,2 @@\n 10\n+eleven\n\\ No newline at end of file\n"
+        );
+        assert_eq!(unified(old, old, 3), "");
+    }
+}
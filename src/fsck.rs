@@ -0,0 +1,848 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::index::Index;
+use crate::objects::Kvlm;
+use crate::odb::{Location, StoredObject};
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+
+/// the bits of the exit code of fsck like git uses them
+pub const ERROR_OBJECT: u8 = 0x01;
+pub const ERROR_REACHABLE: u8 = 0x02;
+pub const ERROR_PACK: u8 = 0x04;
+pub const ERROR_REFS: u8 = 0x08;
+
+/// a problem found in the content of an object, named like the message ids of git fsck
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub id: &'static str,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(id: &'static str, message: &str) -> Self {
+        Self {
+            id,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.id, self.message)
+    }
+}
+
+/// check the content of an object of a given type
+pub fn check_object(object_type: &str, data: &[u8]) -> Result<(), Problem> {
+    match object_type {
+        "blob" => Ok(()),
+        "tree" => check_tree(data),
+        "commit" => check_commit(data),
+        "tag" => check_tag(data),
+        _ => Err(Problem::new("badType", "invalid object type")),
+    }
+}
+
+fn is_hex(hash: &str) -> bool {
+    hash.len() == 40
+        && hash
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// the header lines of a commit or tag up to the empty line before the message
+fn header_lines(data: &[u8]) -> Vec<&[u8]> {
+    let end = data
+        .windows(2)
+        .position(|w| w == b"\n\n")
+        .map(|p| p + 1)
+        .unwrap_or(data.len());
+
+    data[..end]
+        .split(|&b| b == b'\n')
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+/// check a line like "author Name <email> 1700000000 +0100"
+fn check_ident(ident: &[u8], role: &str) -> Result<(), Problem> {
+    let bad = |id: &'static str, what: &str| {
+        Err(Problem::new(
+            id,
+            &format!("invalid {} line - {}", role, what),
+        ))
+    };
+
+    let open = match ident.iter().position(|&b| b == b'<') {
+        Some(open) => open,
+        None => return bad("missingEmail", "missing email"),
+    };
+    if open == 0 || ident[open - 1] != b' ' {
+        return bad("missingSpaceBeforeEmail", "missing space before email");
+    }
+    let close = match ident[open..].iter().position(|&b| b == b'>') {
+        Some(close) => open + close,
+        None => return bad("badEmail", "bad email"),
+    };
+    if ident[open + 1..close].contains(&b'<') {
+        return bad("badName", "bad name");
+    }
+
+    let rest = &ident[close + 1..];
+    let rest = match rest.strip_prefix(b" ") {
+        Some(rest) => rest,
+        None => return bad("missingSpaceBeforeDate", "missing space before date"),
+    };
+    let (date, tz) = match rest.iter().position(|&b| b == b' ') {
+        Some(space) => (&rest[..space], &rest[space + 1..]),
+        None => return bad("badDate", "bad date"),
+    };
+    if date.is_empty() || !date.iter().all(|b| b.is_ascii_digit()) {
+        return bad("badDate", "bad date");
+    }
+    if date.len() > 1 && date[0] == b'0' {
+        return bad("zeroPaddedDate", "zero-padded date");
+    }
+    let valid_tz = tz.len() == 5
+        && (tz[0] == b'+' || tz[0] == b'-')
+        && tz[1..].iter().all(|b| b.is_ascii_digit());
+    if !valid_tz {
+        return bad("badTimezone", "bad time zone");
+    }
+
+    Ok(())
+}
+
+/// a commit needs a tree, valid parents, an author and a committer in this order
+pub fn check_commit(data: &[u8]) -> Result<(), Problem> {
+    let lines = header_lines(data);
+    let mut lines = lines.iter().peekable();
+
+    match lines.next().and_then(|l| l.strip_prefix(b"tree ")) {
+        Some(tree) if is_hex(&String::from_utf8_lossy(tree)) => {}
+        Some(_) => {
+            return Err(Problem::new(
+                "badTreeSha1",
+                "invalid 'tree' line format - bad sha1",
+            ))
+        }
+        None => {
+            return Err(Problem::new(
+                "missingTree",
+                "invalid format - expected 'tree' line",
+            ))
+        }
+    }
+
+    while let Some(parent) = lines.peek().and_then(|l| l.strip_prefix(b"parent ")) {
+        if !is_hex(&String::from_utf8_lossy(parent)) {
+            return Err(Problem::new(
+                "badParentSha1",
+                "invalid 'parent' line format - bad sha1",
+            ));
+        }
+        lines.next();
+    }
+
+    for role in ["author", "committer"] {
+        let prefix = format!("{} ", role);
+        match lines.next().and_then(|l| l.strip_prefix(prefix.as_bytes())) {
+            Some(ident) => check_ident(ident, role)?,
+            None => {
+                let id = match role {
+                    "author" => "missingAuthor",
+                    _ => "missingCommitter",
+                };
+                return Err(Problem::new(
+                    id,
+                    &format!("invalid format - expected '{}' line", role),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// a tag needs an object, its type, a name and usually a tagger
+pub fn check_tag(data: &[u8]) -> Result<(), Problem> {
+    let lines = header_lines(data);
+    let mut lines = lines.iter();
+
+    match lines.next().and_then(|l| l.strip_prefix(b"object ")) {
+        Some(object) if is_hex(&String::from_utf8_lossy(object)) => {}
+        Some(_) => {
+            return Err(Problem::new(
+                "badObjectSha1",
+                "invalid 'object' line format - bad sha1",
+            ))
+        }
+        None => {
+            return Err(Problem::new(
+                "missingObject",
+                "invalid format - expected 'object' line",
+            ))
+        }
+    }
+
+    match lines.next().and_then(|l| l.strip_prefix(b"type ")) {
+        Some(b"blob") | Some(b"tree") | Some(b"commit") | Some(b"tag") => {}
+        Some(_) => return Err(Problem::new("badType", "invalid 'type' value")),
+        None => {
+            return Err(Problem::new(
+                "missingTypeEntry",
+                "invalid format - expected 'type' line",
+            ))
+        }
+    }
+
+    match lines.next().and_then(|l| l.strip_prefix(b"tag ")) {
+        Some(name) if !name.is_empty() => {}
+        _ => {
+            return Err(Problem::new(
+                "missingTagEntry",
+                "invalid format - expected 'tag' line",
+            ))
+        }
+    }
+
+    // very old tags dont have a tagger, so it is optional
+    if let Some(tagger) = lines.next().and_then(|l| l.strip_prefix(b"tagger ")) {
+        check_ident(tagger, "tagger")?;
+    }
+
+    Ok(())
+}
+
+/// the entries of a tree as (mode, name, sha), an error if it can not be parsed
+pub fn parse_tree(data: &[u8]) -> Result<Vec<(String, Vec<u8>, ObjectId)>, Problem> {
+    let mut entries = vec![];
+    let mut rest = data;
+    let malformed = || Problem::new("badTree", "cannot be parsed as a tree");
+
+    while !rest.is_empty() {
+        let space = rest.iter().position(|&b| b == b' ').ok_or_else(malformed)?;
+        let mode = std::str::from_utf8(&rest[..space]).map_err(|_| malformed())?;
+        if mode.is_empty() || !mode.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+            return Err(malformed());
+        }
+        rest = &rest[space + 1..];
+
+        let null = rest.iter().position(|&b| b == 0).ok_or_else(malformed)?;
+        let name = rest[..null].to_vec();
+        rest = &rest[null + 1..];
+
+        if rest.len() < 20 {
+            return Err(malformed());
+        }
+        let sha = ObjectId::from_bytes(&rest[..20]).ok_or_else(malformed)?;
+        rest = &rest[20..];
+
+        entries.push((mode.to_string(), name, sha));
+    }
+
+    Ok(entries)
+}
+
+/// trees need valid modes and names and have to be sorted like git sorts them
+pub fn check_tree(data: &[u8]) -> Result<(), Problem> {
+    let entries = parse_tree(data)?;
+
+    // git compares names as if trees had a trailing slash
+    let sort_key = |mode: &str, name: &[u8]| {
+        let mut key = name.to_vec();
+        if mode == "40000" {
+            key.push(b'/');
+        }
+        key
+    };
+
+    for (i, (mode, name, _)) in entries.iter().enumerate() {
+        if mode.starts_with('0') {
+            return Err(Problem::new(
+                "zeroPaddedFilemode",
+                "contains zero-padded file modes",
+            ));
+        }
+        if !matches!(
+            mode.as_str(),
+            "100644" | "100755" | "100664" | "120000" | "40000" | "160000"
+        ) {
+            return Err(Problem::new("badFilemode", "contains bad file modes"));
+        }
+
+        match name.as_slice() {
+            b"" => return Err(Problem::new("emptyName", "contains empty pathname")),
+            b"." => return Err(Problem::new("hasDot", "contains '.'")),
+            b".." => return Err(Problem::new("hasDotdot", "contains '..'")),
+            n if n.eq_ignore_ascii_case(b".git") => {
+                return Err(Problem::new("hasDotgit", "contains '.git'"))
+            }
+            n if n.contains(&b'/') => {
+                return Err(Problem::new("fullPathname", "contains full pathnames"))
+            }
+            _ => {}
+        }
+
+        if i > 0 {
+            let (prev_mode, prev_name, _) = &entries[i - 1];
+            if prev_name == name {
+                return Err(Problem::new(
+                    "duplicateEntries",
+                    "contains duplicate file entries",
+                ));
+            }
+            if sort_key(prev_mode, prev_name) > sort_key(mode, name) {
+                return Err(Problem::new("treeNotSorted", "not properly sorted"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct Options {
+    /// report unreachable objects instead of only the dangling ones
+    pub unreachable: bool,
+    pub dangling: bool,
+    /// use the reflogs as starting points too
+    pub reflogs: bool,
+    /// write the dangling objects to .git/lost-found
+    pub lost_found: bool,
+    /// report root commits
+    pub root: bool,
+    /// report tagged objects
+    pub tags: bool,
+}
+
+/// what fsck knows about an object, objects which are only linked to have no type yet
+#[derive(Debug, Default)]
+struct Node {
+    object_type: String,
+    exists: bool,
+    reachable: bool,
+    /// linked to by any existing object
+    used: bool,
+    /// the linked objects as (hash, expected type)
+    links: Vec<(ObjectId, String)>,
+}
+
+struct Checker<'a> {
+    rep: &'a Repository,
+    opts: &'a Options,
+    nodes: HashMap<ObjectId, Node>,
+    pending: Vec<ObjectId>,
+    errors: u8,
+}
+
+/// the objects an object links to as (hash, type), malformed hashes are left out
+/// since check_object reports them
+pub fn links(object_type: &str, data: &[u8]) -> Vec<(ObjectId, String)> {
+    fn parse(hash: &[u8]) -> Option<ObjectId> {
+        std::str::from_utf8(hash).ok()?.parse().ok()
+    }
+
+    match object_type {
+        "commit" => {
+            let kvlm = Kvlm::parse(data);
+            let tree = kvlm
+                .get("tree")
+                .and_then(parse)
+                .map(|t| (t, "tree".to_string()));
+            let parents = kvlm
+                .get_all("parent")
+                .into_iter()
+                .filter_map(parse)
+                .map(|p| (p, "commit".to_string()));
+            tree.into_iter().chain(parents).collect()
+        }
+        "tag" => {
+            let kvlm = Kvlm::parse(data);
+            match (kvlm.get("object").and_then(parse), kvlm.get_str("type")) {
+                (Some(object), Some(object_type)) => vec![(object, object_type)],
+                _ => vec![],
+            }
+        }
+        "tree" => parse_tree(data)
+            .unwrap_or_default()
+            .into_iter()
+            // submodule commits are in another repository
+            .filter(|(mode, _, _)| mode != "160000")
+            .map(|(mode, _, hash)| {
+                let object_type = match mode.as_str() {
+                    "40000" => "tree",
+                    _ => "blob",
+                };
+                (hash, object_type.to_string())
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+impl Checker<'_> {
+    /// paths are shown relative to the root of the working tree like git does
+    fn display_path(&self, path: &str) -> String {
+        match path.strip_prefix(&(self.rep.get_workdir().clone() + "/")) {
+            Some(relative) => relative.to_string(),
+            None => path.to_string(),
+        }
+    }
+
+    /// check the content of an object read from the object store and remember its links
+    fn add_object(
+        &mut self,
+        hash: &ObjectId,
+        object_type: &str,
+        data: &[u8],
+    ) -> Result<(), RepError> {
+        if self.nodes.get(hash).is_some_and(|n| n.exists) {
+            return Ok(());
+        }
+        if let Err(problem) = check_object(object_type, data) {
+            errln!("error in {} {}: {}", object_type, hash, problem);
+            self.errors |= ERROR_OBJECT;
+        }
+
+        if self.opts.root && object_type == "commit" && Kvlm::parse(data).get("parent").is_none() {
+            outln!("root {}", hash)?;
+        }
+        if self.opts.tags && object_type == "tag" {
+            let kvlm = Kvlm::parse(data);
+            if let (Some(object), Some(tagged), Some(name)) = (
+                kvlm.get_str("object"),
+                kvlm.get_str("type"),
+                kvlm.get_str("tag"),
+            ) {
+                outln!("tagged {} {} ({}) in {}", tagged, object, name, hash)?;
+            }
+        }
+
+        let links = links(object_type, data);
+        for (link, _) in links.iter() {
+            self.nodes.entry(*link).or_default().used = true;
+        }
+        let node = self.nodes.entry(*hash).or_default();
+        node.object_type = object_type.to_string();
+        node.exists = true;
+        node.links = links;
+        Ok(())
+    }
+
+    /// rehash and check every loose object (or object kept in memory)
+    fn check_loose(&mut self, stored: &[StoredObject]) -> Result<(), RepError> {
+        for object in stored.iter() {
+            let hash = object.id;
+            let path = match &object.location {
+                Location::Loose(path) => self.display_path(path),
+                Location::Memory => hash.to_string(),
+                Location::Packed(..) => continue,
+            };
+            let (object_type, data) = match self.rep.objects().read_stored(object) {
+                Ok(object) => object,
+                Err(_) => {
+                    errln!("error: unable to unpack header of {}", path);
+                    errln!("error: {}: object corrupt or missing: {}", hash, path);
+                    self.errors |= ERROR_OBJECT;
+                    continue;
+                }
+            };
+
+            let real = ObjectId::hash(&object_type, &data);
+            if real != hash {
+                errln!("error: {}: hash-path mismatch, found at: {}", real, path);
+                self.errors |= ERROR_OBJECT;
+                continue;
+            }
+            self.add_object(&hash, &object_type, &data)?;
+        }
+        Ok(())
+    }
+
+    /// verify the checksums of the packs and rehash every packed object
+    fn check_packs(&mut self, stored: &[StoredObject]) -> Result<(), RepError> {
+        for problem in self.rep.objects().verify() {
+            errln!("error: {}", self.display_path(&problem));
+            self.errors |= ERROR_PACK;
+        }
+
+        for object in stored.iter() {
+            let (hash, path, offset) = match &object.location {
+                Location::Packed(path, offset) => (object.id, self.display_path(path), offset),
+                _ => continue,
+            };
+            let (object_type, data) = match self.rep.objects().read_stored(object) {
+                Ok(object) => object,
+                Err(_) => {
+                    errln!(
+                        "error: cannot unpack {} from {} at offset {}",
+                        hash,
+                        path,
+                        offset
+                    );
+                    self.errors |= ERROR_PACK;
+                    continue;
+                }
+            };
+            if ObjectId::hash(&object_type, &data) != hash {
+                errln!("error: packed {} from {} is corrupt", hash, path);
+                self.errors |= ERROR_PACK;
+                continue;
+            }
+            self.add_object(&hash, &object_type, &data)?;
+        }
+        Ok(())
+    }
+
+    /// mark an object as reachable, parent is the object linking to it as (type, hash)
+    fn mark(
+        &mut self,
+        hash: &ObjectId,
+        object_type: &str,
+        parent: Option<(&str, &ObjectId)>,
+    ) -> Result<(), RepError> {
+        let node = self.nodes.entry(*hash).or_default();
+        if node.exists && node.object_type != object_type {
+            if let Some((parent_type, parent_hash)) = parent {
+                errln!(
+                    "error in {} {}: wrong object type in link",
+                    parent_type,
+                    parent_hash
+                );
+                self.errors |= ERROR_OBJECT;
+            }
+        }
+        if node.object_type.is_empty() {
+            node.object_type = object_type.to_string();
+        }
+        if node.reachable {
+            return Ok(());
+        }
+        node.reachable = true;
+
+        if !node.exists {
+            if let Some((parent_type, parent_hash)) = parent {
+                outln!("broken link from {:>7} {}", parent_type, parent_hash)?;
+                outln!("              to {:>7} {}", object_type, hash)?;
+                self.errors |= ERROR_REACHABLE;
+            }
+            return Ok(());
+        }
+        self.pending.push(*hash);
+        Ok(())
+    }
+
+    /// mark a starting point of the walk, its type is taken from the object itself
+    fn mark_root(&mut self, hash: &ObjectId) -> Result<bool, RepError> {
+        let object_type = match self.nodes.get(hash) {
+            Some(node) if node.exists => node.object_type.clone(),
+            _ => return Ok(false),
+        };
+        self.mark(hash, &object_type, None)?;
+        Ok(true)
+    }
+
+    /// the refs, HEAD, the reflogs and the index
+    fn mark_default_roots(&mut self) -> Result<(), RepError> {
+        if let Ok(head) = self.rep.ref_resolve("HEAD") {
+            if !self.mark_root(&head)? {
+                errln!("error: HEAD: invalid sha1 pointer {}", head);
+                self.errors |= ERROR_REFS;
+            }
+        }
+        for (name, hash) in self.rep.get_refs() {
+            if !self.mark_root(&hash)? {
+                errln!("error: {}: invalid sha1 pointer {}", name, hash);
+                self.errors |= ERROR_REACHABLE;
+            }
+        }
+
+        if self.opts.reflogs {
+            for name in self.rep.reflog_names() {
+                for entry in self.rep.read_reflog(&name) {
+                    for hash in [entry.old, entry.new] {
+                        if hash.is_null() {
+                            continue;
+                        }
+                        if !self.mark_root(&hash)? {
+                            errln!("error: {}: invalid reflog entry {}", name, hash);
+                            self.errors |= ERROR_REACHABLE;
+                        }
+                    }
+                }
+            }
+        }
+
+        let index = Index::load(self.rep)?;
+        for entry in index.get_entries() {
+            if entry.get_mode() != "160000" {
+                self.mark(entry.get_hash(), "blob", None)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn traverse(&mut self) -> Result<(), RepError> {
+        while let Some(hash) = self.pending.pop() {
+            let node = &self.nodes[&hash];
+            let object_type = node.object_type.clone();
+            for (link, link_type) in node.links.clone() {
+                self.mark(&link, &link_type, Some((&object_type, &hash)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// report missing, unreachable and dangling objects
+    fn report(&mut self) -> Result<(), RepError> {
+        let mut hashes = self.nodes.keys().copied().collect::<Vec<ObjectId>>();
+        hashes.sort();
+
+        for hash in hashes {
+            let node = &self.nodes[&hash];
+            if node.reachable && !node.exists {
+                outln!("missing {} {}", node.object_type, hash)?;
+                self.errors |= ERROR_REACHABLE;
+                continue;
+            }
+            if node.reachable || !node.exists {
+                continue;
+            }
+
+            if self.opts.unreachable {
+                outln!("unreachable {} {}", node.object_type, hash)?;
+                continue;
+            }
+            if node.used {
+                continue;
+            }
+            if self.opts.dangling {
+                outln!("dangling {} {}", node.object_type, hash)?;
+            }
+            if self.opts.lost_found {
+                self.write_lost_found(&hash, &node.object_type)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// commits go to lost-found/commit as their hash, blobs to lost-found/other with their content
+    fn write_lost_found(&self, hash: &ObjectId, object_type: &str) -> Result<(), RepError> {
+        let folder = match object_type {
+            "commit" => "commit",
+            _ => "other",
+        };
+        let dir = self.rep.get_gitdir().clone() + "/lost-found/" + folder;
+        let _ = std::fs::create_dir_all(&dir);
+
+        let content = match object_type {
+            "blob" => read_object(self.rep, hash)
+                .map(|(_, data)| data)
+                .unwrap_or_default(),
+            _ => format!("{}\n", hash).into_bytes(),
+        };
+        if let Err(e) = std::fs::write(format!("{}/{}", dir, hash), content) {
+            errln!("error: could not write '{}/{}': {}", dir, hash, e);
+        }
+        Ok(())
+    }
+}
+
+/// read an object from the loose objects or the packs without panicking
+fn read_object(rep: &Repository, hash: &ObjectId) -> Option<(String, Vec<u8>)> {
+    rep.objects().read(hash).ok()
+}
+
+/// verify every object of the repository and that everything reachable is there,
+/// the result are the error bits for the exit code
+pub fn fsck(rep: &Repository, objects: &[String], opts: &Options) -> Result<u8, RepError> {
+    let mut checker = Checker {
+        rep,
+        opts,
+        nodes: HashMap::new(),
+        pending: vec![],
+        errors: 0,
+    };
+    let stored = rep.objects().stored();
+    checker.check_loose(&stored)?;
+    checker.check_packs(&stored)?;
+
+    match objects.is_empty() {
+        true => checker.mark_default_roots()?,
+        false => {
+            for object in objects {
+                let hash = rep.ref_resolve(object)?;
+                if !checker.mark_root(&hash)? {
+                    errln!("error: {}: object missing", hash);
+                    checker.errors |= ERROR_REACHABLE;
+                }
+            }
+        }
+    }
+    checker.traverse()?;
+    checker.report()?;
+
+    Ok(checker.errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::Tree;
+
+    const TREE: &str = "9d4c3a46a6a04d5624fdb47ed91eeba7dfd0c950";
+    const PARENT: &str = "70ce96057278e39600d477912c672b9ef28dccf4";
+
+    fn options() -> Options {
+        Options {
+            unreachable: false,
+            dangling: true,
+            reflogs: true,
+            lost_found: false,
+            root: false,
+            tags: false,
+        }
+    }
+
+    fn problem(result: Result<(), Problem>) -> &'static str {
+        result.err().map(|p| p.id).unwrap_or("ok")
+    }
+
+    fn tree_entry(mode: &str, name: &str) -> Vec<u8> {
+        let mut entry = format!("{} {}\0", mode, name).into_bytes();
+        entry.extend([0x11; 20]);
+        entry
+    }
+
+    #[test]
+    fn commit_headers_are_checked_in_order() {
+        let commit = |headers: &str| check_commit(format!("{}\nmessage\n", headers).as_bytes());
+        let ident = "T <t@x> 1700000000 +0000";
+        let valid = format!(
+            "tree {}\nparent {}\nauthor {}\ncommitter {}\n",
+            TREE, PARENT, ident, ident
+        );
+        assert_eq!(problem(commit(&valid)), "ok");
+        assert_eq!(
+            problem(commit(&valid.replacen("tree 9", "tree x", 1))),
+            "badTreeSha1"
+        );
+        assert_eq!(
+            problem(commit(&format!("author {}\n", ident))),
+            "missingTree"
+        );
+        let no_committer = format!("tree {}\nauthor {}\n", TREE, ident);
+        assert_eq!(problem(commit(&no_committer)), "missingCommitter");
+
+        let with_author =
+            |author: &str| format!("tree {}\nauthor {}\ncommitter {}\n", TREE, author, ident);
+        assert_eq!(
+            problem(commit(&with_author("T t@x> 1 +0000"))),
+            "missingEmail"
+        );
+        assert_eq!(
+            problem(commit(&with_author("T<t@x> 1 +0000"))),
+            "missingSpaceBeforeEmail"
+        );
+        assert_eq!(
+            problem(commit(&with_author("T <t@x> 01 +0000"))),
+            "zeroPaddedDate"
+        );
+        assert_eq!(
+            problem(commit(&with_author("T <t@x> 1 0000"))),
+            "badTimezone"
+        );
+    }
+
+    #[test]
+    fn tags_and_trees_are_checked() {
+        let tag = |headers: String| check_tag(format!("{}\nmessage\n", headers).as_bytes());
+        let object = format!("object {}\ntype commit\n", TREE);
+        assert_eq!(problem(tag(object.clone() + "tag v1.0\n")), "ok");
+        assert_eq!(
+            problem(tag(object.replace("commit", "thing") + "tag v1.0\n")),
+            "badType"
+        );
+        assert_eq!(problem(tag(object.clone())), "missingTagEntry");
+        let tagger = object + "tag v1.0\ntagger T <t@x> 1 +0000\n";
+        assert_eq!(problem(tag(tagger)), "ok");
+
+        let tree = |entries: &[(&str, &str)]| {
+            let data = entries
+                .iter()
+                .flat_map(|(m, n)| tree_entry(m, n))
+                .collect::<Vec<u8>>();
+            check_tree(&data)
+        };
+        assert_eq!(problem(tree(&[("100644", "a"), ("40000", "b")])), "ok");
+        assert_eq!(problem(tree(&[("040000", "a")])), "zeroPaddedFilemode");
+        assert_eq!(problem(tree(&[("100600", "a")])), "badFilemode");
+        assert_eq!(problem(tree(&[("100644", ".GIT")])), "hasDotgit");
+        assert_eq!(problem(tree(&[("100644", "a/b")])), "fullPathname");
+        assert_eq!(
+            problem(tree(&[("100644", "b"), ("100644", "a")])),
+            "treeNotSorted"
+        );
+        // the folder a sorts like "a/", so after a.txt
+        assert_eq!(
+            problem(tree(&[("40000", "a"), ("100644", "a.txt")])),
+            "treeNotSorted"
+        );
+        assert_eq!(
+            problem(tree(&[("100644", "a"), ("100644", "a")])),
+            "duplicateEntries"
+        );
+        assert_eq!(problem(check_tree(b"100644 a\0short")), "badTree");
+    }
+
+    #[test]
+    fn links_of_objects_are_listed() {
+        let commit = format!("tree {}\nparent {}\n\nmessage\n", TREE, PARENT);
+        let links = links("commit", commit.as_bytes());
+        assert_eq!(
+            links,
+            vec![
+                (TREE.parse().unwrap(), "tree".to_string()),
+                (PARENT.parse().unwrap(), "commit".to_string())
+            ]
+        );
+
+        let mut tree = tree_entry("40000", "dir");
+        tree.extend(tree_entry("160000", "submodule"));
+        let links = crate::fsck::links("tree", &tree);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].1, "tree");
+    }
+
+    #[test]
+    fn missing_and_broken_objects_are_reported() {
+        let rep = Repository::temporary("fsck-missing");
+        let commit = rep.commit_files(&[("a.txt", "a\n")], "first");
+        assert_eq!(fsck(&rep, &[], &options()).unwrap(), 0);
+
+        // a commit on HEAD whose tree links to a blob which doesn't exist
+        let missing = "1111111111111111111111111111111111111111".parse().unwrap();
+        let tree = Tree::from_entries(vec![("100644".into(), "gone.txt".into(), missing)])
+            .save(&rep)
+            .unwrap();
+        let data = String::from_utf8(rep.objects().read(&commit).unwrap().1)
+            .unwrap()
+            .replace(
+                &rep.peel(&commit, "tree").unwrap().to_string(),
+                &tree.to_string(),
+            );
+        let broken = rep.objects().write("commit", data.as_bytes()).unwrap();
+        rep.update_head(&broken).unwrap();
+        assert_eq!(fsck(&rep, &[], &options()).unwrap(), ERROR_REACHABLE);
+
+        rep.update_head(&commit).unwrap();
+        rep.objects().write("commit", b"tree nothing\n").unwrap();
+        assert_eq!(fsck(&rep, &[], &options()).unwrap(), ERROR_OBJECT);
+    }
+}
@@ -0,0 +1,762 @@
+#![allow(dead_code)]
+
+use std::io::Write;
+
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgAction, CommandFactory, FromArgMatches};
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::diff::is_binary;
+use crate::index::Index;
+use crate::merge::tree_files;
+use crate::objects::Object;
+use crate::oid::ObjectId;
+use crate::pathspec::{display_path, Pathspec};
+use crate::repository::{RepError, Repository};
+
+/// how patterns are interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    /// basic regular expressions like grep (-G), the default
+    #[default]
+    Basic,
+    /// extended regular expressions (-E)
+    Extended,
+    /// perl compatible regular expressions (-P)
+    Perl,
+    /// the patterns are plain strings (-F)
+    Fixed,
+}
+
+/// what happens with files containing a null byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Binary {
+    /// only say that the file matches
+    #[default]
+    Matches,
+    /// don't search in them (-I)
+    Skip,
+    /// search them like text (-a)
+    Text,
+}
+
+/// a part of the boolean expression given with -e, --and, --or, --not and parentheses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Pattern(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub syntax: Syntax,
+    pub ignore_case: bool,
+    /// patterns only match whole words (-w)
+    pub word: bool,
+    /// select the lines not matching (-v)
+    pub invert: bool,
+    pub line_number: bool,
+    /// only show the names of matching files (-l)
+    pub files_with_matches: bool,
+    /// only show the names of files without a match (-L)
+    pub files_without_match: bool,
+    /// show the number of matching lines per file (-c)
+    pub count: bool,
+    /// don't show the file names (-h)
+    pub no_filename: bool,
+    /// lines of context before and after a match (-B, -A, -C)
+    pub before: usize,
+    pub after: usize,
+    pub binary: Binary,
+    /// search the blobs of the index instead of the working tree (--cached)
+    pub cached: bool,
+    pub threads: Option<usize>,
+    pub tokens: Vec<Token>,
+}
+
+/// the expression a line has to match
+enum Expr {
+    Pattern(Regex),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/// a file to search, with the name it is shown as
+struct Source {
+    name: String,
+    blob: Blob,
+}
+
+enum Blob {
+    Worktree(String),
+    Object(ObjectId),
+}
+
+/// the arguments of grep, the order of -e, --and, --or, --not and parentheses is taken
+/// from their indices so the flags for them only record where they were given
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "kyu grep",
+    no_binary_name = true,
+    disable_help_flag = true,
+    args_override_self = true
+)]
+struct Args {
+    /// a pattern of the expression
+    #[arg(short = 'e', value_name = "pattern", allow_hyphen_values = true)]
+    patterns: Vec<String>,
+    /// both expressions around it have to match
+    #[arg(long, action = ArgAction::Append, num_args = 0, default_missing_value = "true")]
+    and: Vec<bool>,
+    /// one of the expressions around it has to match
+    #[arg(long, action = ArgAction::Append, num_args = 0, default_missing_value = "true")]
+    or: Vec<bool>,
+    /// the expression after it must not match
+    #[arg(long, action = ArgAction::Append, num_args = 0, default_missing_value = "true")]
+    not: Vec<bool>,
+    #[arg(short = 'G', long)]
+    basic_regexp: bool,
+    #[arg(short = 'E', long)]
+    extended_regexp: bool,
+    #[arg(short = 'P', long)]
+    perl_regexp: bool,
+    #[arg(short = 'F', long)]
+    fixed_strings: bool,
+    #[arg(short = 'i', long)]
+    ignore_case: bool,
+    #[arg(short = 'w', long)]
+    word_regexp: bool,
+    #[arg(short = 'v', long)]
+    invert_match: bool,
+    #[arg(short = 'n', long)]
+    line_number: bool,
+    #[arg(short = 'l', long, visible_alias = "name-only")]
+    files_with_matches: bool,
+    #[arg(short = 'L', long)]
+    files_without_match: bool,
+    #[arg(short = 'c', long)]
+    count: bool,
+    /// don't show the file names
+    #[arg(short = 'h')]
+    no_filename: bool,
+    /// show the file names
+    #[arg(short = 'H')]
+    with_filename: bool,
+    /// don't search in binary files
+    #[arg(short = 'I')]
+    skip_binary: bool,
+    #[arg(short = 'a', long)]
+    text: bool,
+    #[arg(long)]
+    cached: bool,
+    #[arg(short = 'A', long, value_name = "n")]
+    after_context: Option<usize>,
+    #[arg(short = 'B', long, value_name = "n")]
+    before_context: Option<usize>,
+    #[arg(short = 'C', long, value_name = "n")]
+    context: Option<usize>,
+    #[arg(long, value_name = "n")]
+    threads: Option<usize>,
+    /// the pattern when there is no -e, parentheses and the trees or paths
+    args: Vec<String>,
+    /// the paths after --
+    #[arg(last = true)]
+    paths: Vec<String>,
+}
+
+/// parse the arguments of grep: options, the expression and the tree-ishs or paths after it
+pub fn parse_args(args: &[String]) -> Result<(Options, Vec<String>), clap::Error> {
+    let matches = Args::command().try_get_matches_from(args)?;
+    let parsed = Args::from_arg_matches(&matches)?;
+
+    // the index of the last time an argument was given, None when it wasn't
+    let last = |id: &str| match matches.value_source(id) {
+        Some(ValueSource::CommandLine) => matches.indices_of(id).and_then(|i| i.max()),
+        _ => None,
+    };
+    let indices = |id: &str| match last(id) {
+        Some(_) => matches
+            .indices_of(id)
+            .map(|i| i.collect())
+            .unwrap_or_default(),
+        None => vec![],
+    };
+    // of the arguments overriding each other the one given last wins
+    let latest = |ids: &[&'static str]| -> Option<&'static str> {
+        ids.iter()
+            .filter_map(|id| last(id).map(|i| (i, *id)))
+            .max()
+            .map(|(_, id)| id)
+    };
+
+    let mut opts = Options {
+        syntax: match latest(&[
+            "basic_regexp",
+            "extended_regexp",
+            "perl_regexp",
+            "fixed_strings",
+        ]) {
+            Some("extended_regexp") => Syntax::Extended,
+            Some("perl_regexp") => Syntax::Perl,
+            Some("fixed_strings") => Syntax::Fixed,
+            _ => Syntax::Basic,
+        },
+        ignore_case: parsed.ignore_case,
+        word: parsed.word_regexp,
+        invert: parsed.invert_match,
+        line_number: parsed.line_number,
+        files_with_matches: parsed.files_with_matches,
+        files_without_match: parsed.files_without_match,
+        count: parsed.count,
+        no_filename: latest(&["no_filename", "with_filename"]) == Some("no_filename"),
+        before: match latest(&["before_context", "context"]) {
+            Some("before_context") => parsed.before_context.unwrap_or_default(),
+            _ => parsed.context.unwrap_or_default(),
+        },
+        after: match latest(&["after_context", "context"]) {
+            Some("after_context") => parsed.after_context.unwrap_or_default(),
+            _ => parsed.context.unwrap_or_default(),
+        },
+        binary: match latest(&["skip_binary", "text"]) {
+            Some("skip_binary") => Binary::Skip,
+            Some("text") => Binary::Text,
+            _ => Binary::Matches,
+        },
+        cached: parsed.cached,
+        threads: parsed.threads,
+        tokens: vec![],
+    };
+
+    let mut tokens: Vec<(usize, Token)> = vec![];
+    let patterns = indices("patterns").into_iter().zip(parsed.patterns);
+    tokens.extend(patterns.map(|(i, pattern)| (i, Token::Pattern(pattern))));
+    tokens.extend(indices("and").into_iter().map(|i| (i, Token::And)));
+    tokens.extend(indices("or").into_iter().map(|i| (i, Token::Or)));
+    tokens.extend(indices("not").into_iter().map(|i| (i, Token::Not)));
+
+    let mut rest = vec![];
+    for (i, arg) in indices("args").into_iter().zip(parsed.args) {
+        match arg.as_str() {
+            "(" => tokens.push((i, Token::Open)),
+            ")" => tokens.push((i, Token::Close)),
+            _ => rest.push(arg),
+        }
+    }
+    tokens.sort_by_key(|(i, _)| *i);
+    opts.tokens = tokens.into_iter().map(|(_, token)| token).collect();
+
+    // without -e the first argument is the pattern
+    if opts.tokens.is_empty() {
+        if rest.is_empty() {
+            let mut command = Args::command();
+            return Err(command.error(ErrorKind::MissingRequiredArgument, "no pattern given"));
+        }
+        opts.tokens.push(Token::Pattern(rest.remove(0)));
+    }
+    // clap drops the --, grep needs it to tell the trees from the paths
+    if !parsed.paths.is_empty() || args.last().is_some_and(|a| a == "--") {
+        rest.push("--".to_string());
+        rest.extend(parsed.paths);
+    }
+
+    Ok((opts, rest))
+}
+
+/// turn a basic regular expression into the syntax of the regex crate,
+/// in basic ones ( ) | { } + ? are special only with a backslash
+fn basic_to_extended(pattern: &str) -> String {
+    let mut result = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c) if "(){}|+?".contains(c) => result.push(c),
+                Some(c) => {
+                    result.push('\\');
+                    result.push(c);
+                }
+                None => result.push_str("\\\\"),
+            },
+            c if "(){}|+?".contains(c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn compile_pattern(pattern: &str, opts: &Options) -> Result<Regex, RepError> {
+    let pattern = match opts.syntax {
+        Syntax::Basic => basic_to_extended(pattern),
+        Syntax::Extended => pattern.to_string(),
+        // the regex crate has no lookaround or backreferences, so perl patterns are refused
+        // like git does when it is built without pcre
+        Syntax::Perl => {
+            return Err(RepError::InvalidArgument(
+                "cannot use Perl-compatible regexes when not compiled with USE_LIBPCRE".to_string(),
+            ))
+        }
+        Syntax::Fixed => regex::escape(pattern),
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(opts.ignore_case)
+        .multi_line(true)
+        .build()
+        .map_err(|e| RepError::InvalidArgument(format!("command line, '{}': {}", pattern, e)))
+}
+
+/// parse the tokens like git: --or binds weaker than --and, which binds weaker than --not,
+/// patterns without an operator between them are or-ed
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    opts: &'a Options,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn or(&mut self) -> Result<Expr, RepError> {
+        let left = self.and()?;
+        match self.peek() {
+            None | Some(Token::Close) => Ok(left),
+            Some(token) => {
+                if *token == Token::Or {
+                    self.pos += 1;
+                }
+                Ok(Expr::Or(Box::new(left), Box::new(self.or()?)))
+            }
+        }
+    }
+
+    fn and(&mut self) -> Result<Expr, RepError> {
+        let left = self.not()?;
+        match self.peek() {
+            Some(Token::And) => {
+                self.pos += 1;
+                Ok(Expr::And(Box::new(left), Box::new(self.and()?)))
+            }
+            _ => Ok(left),
+        }
+    }
+
+    fn not(&mut self) -> Result<Expr, RepError> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.not()?)))
+            }
+            _ => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, RepError> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Pattern(pattern)) => {
+                Ok(Expr::Pattern(compile_pattern(&pattern, self.opts)?))
+            }
+            Some(Token::Open) => {
+                if self.peek().is_none() {
+                    return Err(RepError::InvalidArgument(
+                        "unmatched parenthesis".to_string(),
+                    ));
+                }
+                let expr = self.or()?;
+                match self.peek() {
+                    Some(Token::Close) => {
+                        self.pos += 1;
+                        Ok(expr)
+                    }
+                    _ => Err(RepError::InvalidArgument(
+                        "unmatched parenthesis".to_string(),
+                    )),
+                }
+            }
+            Some(Token::Not) | Some(Token::And) | Some(Token::Or) | Some(Token::Close) => Err(
+                RepError::InvalidArgument("incomplete pattern expression".to_string()),
+            ),
+            None => Err(RepError::InvalidArgument(
+                "incomplete pattern expression".to_string(),
+            )),
+        }
+    }
+}
+
+fn is_word_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+impl Expr {
+    fn parse(opts: &Options) -> Result<Self, RepError> {
+        let mut parser = Parser {
+            tokens: &opts.tokens,
+            pos: 0,
+            opts,
+        };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(_) => Err(RepError::InvalidArgument(
+                "unmatched parenthesis".to_string(),
+            )),
+        }
+    }
+
+    fn matches(&self, line: &[u8], word: bool) -> bool {
+        match self {
+            Expr::Pattern(regex) if !word => regex.is_match(line),
+            Expr::Pattern(regex) => {
+                // like git a match which isn't a whole word is retried one character later
+                let mut start = 0;
+                while let Some(m) = regex.find_at(line, start) {
+                    let before = m.start() == 0 || !is_word_char(line[m.start() - 1]);
+                    let after = m.end() == line.len() || !is_word_char(line[m.end()]);
+                    if before && after && !m.is_empty() {
+                        return true;
+                    }
+                    if m.start() >= line.len() {
+                        return false;
+                    }
+                    start = m.start() + 1;
+                }
+                false
+            }
+            Expr::Not(expr) => !expr.matches(line, word),
+            Expr::And(left, right) => left.matches(line, word) && right.matches(line, word),
+            Expr::Or(left, right) => left.matches(line, word) || right.matches(line, word),
+        }
+    }
+}
+
+struct Grep<'a> {
+    rep: &'a Repository,
+    opts: &'a Options,
+    expr: Expr,
+}
+
+impl Grep<'_> {
+    fn read(&self, blob: &Blob) -> Option<Vec<u8>> {
+        match blob {
+            Blob::Worktree(path) => {
+                let path = self.rep.get_workdir().clone() + "/" + path;
+                let metadata = std::fs::symlink_metadata(&path).ok()?;
+                match metadata.is_symlink() {
+                    true => Some(
+                        std::fs::read_link(&path)
+                            .ok()?
+                            .to_string_lossy()
+                            .as_bytes()
+                            .to_vec(),
+                    ),
+                    false => std::fs::read(&path).ok(),
+                }
+            }
+            Blob::Object(hash) => Some(Object::load(self.rep, hash).ok()?.get_data().clone()),
+        }
+    }
+
+    /// search a file, returns the output and whether something matched
+    fn search(&self, source: &Source) -> (Vec<u8>, bool) {
+        let opts = self.opts;
+        let mut out = vec![];
+        let data = match self.read(&source.blob) {
+            Some(data) => data,
+            None => return (out, false),
+        };
+        let binary = opts.binary != Binary::Text && is_binary(&data);
+        if binary && opts.binary == Binary::Skip {
+            return (out, false);
+        }
+
+        let mut lines = data.split(|&b| b == b'\n').collect::<Vec<&[u8]>>();
+        if data.ends_with(b"\n") {
+            lines.pop();
+        }
+        let selected = lines
+            .iter()
+            .map(|line| self.expr.matches(line, opts.word) != opts.invert)
+            .collect::<Vec<bool>>();
+        let count = selected.iter().filter(|&&s| s).count();
+
+        if opts.files_without_match {
+            if count == 0 {
+                writeln!(out, "{}", source.name).unwrap();
+            }
+            return (out, count == 0);
+        }
+        if count == 0 {
+            return (out, false);
+        }
+        if opts.files_with_matches {
+            writeln!(out, "{}", source.name).unwrap();
+            return (out, true);
+        }
+        if opts.count {
+            match opts.no_filename {
+                true => writeln!(out, "{}", count).unwrap(),
+                false => writeln!(out, "{}:{}", source.name, count).unwrap(),
+            }
+            return (out, true);
+        }
+        if binary {
+            writeln!(out, "Binary file {} matches", source.name).unwrap();
+            return (out, true);
+        }
+
+        // the lines to show with their context, separated by -- where lines are left out
+        let mut last_shown: Option<usize> = None;
+        let mut after_left = 0;
+        for (i, line) in lines.iter().enumerate() {
+            let show = match selected[i] {
+                true => {
+                    after_left = opts.after;
+                    true
+                }
+                false => {
+                    let before = selected[i + 1..].iter().take(opts.before).any(|&s| s);
+                    let after = after_left > 0;
+                    after_left = after_left.saturating_sub(1);
+                    before || after
+                }
+            };
+            if !show {
+                continue;
+            }
+
+            let context = opts.before > 0 || opts.after > 0;
+            if context && last_shown.is_some_and(|l| l + 1 != i) {
+                writeln!(out, "--").unwrap();
+            }
+            last_shown = Some(i);
+
+            let separator = match selected[i] {
+                true => ":",
+                false => "-",
+            };
+            if !opts.no_filename {
+                write!(out, "{}{}", source.name, separator).unwrap();
+            }
+            if opts.line_number {
+                write!(out, "{}{}", i + 1, separator).unwrap();
+            }
+            out.extend_from_slice(line);
+            out.push(b'\n');
+        }
+
+        (out, true)
+    }
+
+    /// search all files, with several threads, and print the results in order
+    fn run(&self, sources: &[Source]) -> Result<bool, RepError> {
+        let threads = self
+            .opts
+            .threads
+            .filter(|&t| t > 0)
+            .or_else(|| {
+                self.rep
+                    .config_get("grep", "threads")
+                    .and_then(|t| t.parse::<usize>().ok())
+                    .filter(|&t| t > 0)
+            })
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+        let chunk_size = sources.len().div_ceil(threads).max(1);
+
+        let results = std::thread::scope(|scope| {
+            let workers = sources
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || chunk.iter().map(|s| self.search(s)).collect::<Vec<_>>())
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|w| w.join().unwrap())
+                .collect::<Vec<(Vec<u8>, bool)>>()
+        });
+
+        let context = self.opts.before > 0 || self.opts.after > 0;
+        let mut stdout = std::io::stdout().lock();
+        let mut printed = false;
+        let mut found = false;
+        for (out, matched) in results {
+            found |= matched;
+            if out.is_empty() {
+                continue;
+            }
+            // groups of lines in different files are separated too
+            if context && printed && !out.starts_with(b"Binary file ") {
+                writeln!(stdout, "--").map_err(RepError::Output)?;
+            }
+            stdout.write_all(&out).map_err(RepError::Output)?;
+            printed = true;
+        }
+
+        Ok(found)
+    }
+}
+
+/// search the working tree, the index (--cached) or trees for lines matching the expression,
+/// returns whether something matched
+pub fn grep(rep: &Repository, opts: &Options, args: &[String]) -> Result<bool, RepError> {
+    let expr = Expr::parse(opts)?;
+
+    // arguments before -- are trees as long as they resolve, everything else are paths
+    let mut trees = vec![];
+    let mut paths = vec![];
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        if arg == "--" {
+            paths.extend(args_iter.by_ref().cloned());
+            break;
+        }
+        let tree = rep.ref_resolve(arg).ok().and_then(|h| rep.peel(&h, "tree"));
+        match (tree, paths.is_empty()) {
+            (Some(tree), true) => trees.push((arg.clone(), tree)),
+            _ if std::path::Path::new(arg).exists() => paths.push(arg.clone()),
+            _ => {
+                return Err(RepError::InvalidArgument(format!(
+                    "ambiguous argument '{}': unknown revision or path not in the working tree.",
+                    arg
+                )))
+            }
+        }
+    }
+    if !trees.is_empty() && opts.cached {
+        return Err(RepError::InvalidArgument(
+            "--cached cannot be used with a tree".to_string(),
+        ));
+    }
+
+    // like git only the current directory is searched without paths
+    if paths.is_empty() {
+        paths.push(".".to_string());
+    }
+    let pathspec = Pathspec::parse(rep, &paths)?;
+    let prefix = rep.relative_path(".");
+
+    let mut sources = vec![];
+    if trees.is_empty() {
+        for (path, (mode, hash)) in Index::load(rep)?.to_map() {
+            if mode == "160000" || !pathspec.matches(&path) {
+                continue;
+            }
+            let blob = match opts.cached {
+                true => Blob::Object(hash),
+                false => Blob::Worktree(path.clone()),
+            };
+            let name = display_path(&path, &prefix);
+            sources.push(Source { name, blob });
+        }
+    }
+    for (name, tree) in trees {
+        // like git HEAD gives HEAD:path and HEAD:dir gives HEAD:dir:path
+        let name = match name.ends_with(':') || name.ends_with('/') {
+            true => name,
+            false => name + ":",
+        };
+        for (path, (mode, hash)) in tree_files(rep, Some(&tree))? {
+            if mode == "160000" || !pathspec.matches(&path) {
+                continue;
+            }
+            let name = name.clone() + &display_path(&path, &prefix);
+            sources.push(Source {
+                name,
+                blob: Blob::Object(hash),
+            });
+        }
+    }
+
+    let grep = Grep { rep, opts, expr };
+    grep.run(&sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split(' ').map(String::from).collect()
+    }
+
+    #[test]
+    fn bundled_short_options_are_split() {
+        let (opts, rest) = parse_args(&args("-iA3 foo")).unwrap();
+        assert!(opts.ignore_case);
+        assert_eq!((opts.before, opts.after), (0, 3));
+        assert_eq!(opts.tokens, vec![Token::Pattern("foo".to_string())]);
+        assert!(rest.is_empty());
+
+        let (opts, _) = parse_args(&args("-efoo")).unwrap();
+        assert_eq!(opts.tokens, vec![Token::Pattern("foo".to_string())]);
+        // values and paths after -- are never split
+        let (opts, rest) = parse_args(&args("-e -in -- -ab")).unwrap();
+        assert!(!opts.ignore_case);
+        assert_eq!(opts.tokens, vec![Token::Pattern("-in".to_string())]);
+        assert_eq!(rest, args("-- -ab"));
+    }
+
+    #[test]
+    fn expression_keeps_its_order() {
+        let (opts, rest) = parse_args(&args("--not ( -e a --or -e b ) HEAD")).unwrap();
+        assert_eq!(
+            opts.tokens,
+            vec![
+                Token::Not,
+                Token::Open,
+                Token::Pattern("a".to_string()),
+                Token::Or,
+                Token::Pattern("b".to_string()),
+                Token::Close
+            ]
+        );
+        assert_eq!(rest, args("HEAD"));
+        assert!(parse_args(&args("HEAD --")).unwrap().1 == args("--"));
+        assert!(parse_args(&args("-i")).is_err());
+    }
+
+    #[test]
+    fn last_option_wins() {
+        let (opts, _) = parse_args(&args("-E -F -h -H -C1 -A2 foo")).unwrap();
+        assert_eq!(opts.syntax, Syntax::Fixed);
+        assert!(!opts.no_filename);
+        assert_eq!((opts.before, opts.after), (1, 2));
+        let (opts, _) = parse_args(&args("-P foo")).unwrap();
+        assert!(compile_pattern("foo", &opts).is_err());
+    }
+
+    #[test]
+    fn bundled_options_are_parsed() {
+        let (opts, rest) = parse_args(&args("-inwC2 -e foo --and -e bar HEAD")).unwrap();
+        assert!(opts.ignore_case && opts.line_number && opts.word);
+        assert_eq!((opts.before, opts.after), (2, 2));
+        assert_eq!(
+            opts.tokens,
+            vec![
+                Token::Pattern("foo".to_string()),
+                Token::And,
+                Token::Pattern("bar".to_string())
+            ]
+        );
+        assert_eq!(rest, args("HEAD"));
+
+        let (opts, rest) = parse_args(&args("-lv pattern -- src")).unwrap();
+        assert!(opts.files_with_matches && opts.invert);
+        assert_eq!(opts.tokens, vec![Token::Pattern("pattern".to_string())]);
+        assert_eq!(rest, args("-- src"));
+
+        assert!(parse_args(&args("-ix foo")).is_err());
+        assert!(parse_args(&args("-Ax foo")).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+#![allow(dead_code)]
+
+use std::io::{BufRead, Read};
+
+use crate::attributes;
+use crate::fsck;
+use crate::objects::Object;
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+
+#[derive(Debug)]
+pub struct Options {
+    pub object_type: String,
+    /// store the objects instead of only computing their ids (-w)
+    pub write: bool,
+    /// allow any type and content which fsck would reject
+    pub literally: bool,
+    /// the path whose attribute filters are applied, instead of the path of the file
+    pub path: Option<String>,
+    pub no_filters: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            object_type: "blob".to_string(),
+            write: false,
+            literally: false,
+            path: None,
+            no_filters: false,
+        }
+    }
+}
+
+/// the id of content as an object, path is the file it belongs to for the attribute filters
+pub fn hash_data(
+    rep: Option<&Repository>,
+    data: Vec<u8>,
+    path: Option<&str>,
+    opts: &Options,
+) -> Result<ObjectId, RepError> {
+    let path = opts.path.as_deref().or(path);
+    let data = match (rep, path) {
+        (Some(rep), Some(path)) if !opts.no_filters && opts.object_type == "blob" => {
+            attributes::convert_to_git(rep, &rep.relative_path(path), data)
+        }
+        _ => data,
+    };
+
+    if !opts.literally {
+        if let Err(problem) = fsck::check_object(&opts.object_type, &data) {
+            return Err(RepError::FailsFsck(problem.to_string()));
+        }
+    }
+
+    match rep {
+        Some(rep) if opts.write => Object::save_raw(rep, &opts.object_type, &data),
+        _ => Ok(ObjectId::hash(&opts.object_type, &data)),
+    }
+}
+
+/// print the ids of stdin and the files, with stdin_paths the files are also read from stdin,
+/// hashing without writing also works outside of a repository
+pub fn hash_object(
+    rep: Option<&Repository>,
+    files: &[String],
+    stdin: bool,
+    stdin_paths: bool,
+    opts: &Options,
+) -> Result<(), RepError> {
+    if opts.write && rep.is_none() {
+        return Err(RepError::NotARepository);
+    }
+    let known_type = ["blob", "tree", "commit", "tag"].contains(&opts.object_type.as_str());
+    if !known_type && !opts.literally {
+        return Err(RepError::InvalidObjectType(opts.object_type.clone()));
+    }
+
+    if stdin {
+        let mut data = vec![];
+        std::io::stdin()
+            .read_to_end(&mut data)
+            .map_err(|e| RepError::Io("stdin".to_string(), e))?;
+        outln!("{}", hash_data(rep, data, None, opts)?)?;
+    }
+
+    let files = match stdin_paths {
+        true => std::io::stdin()
+            .lock()
+            .lines()
+            .map_while(Result::ok)
+            .chain(files.iter().cloned())
+            .collect(),
+        false => files.to_vec(),
+    };
+    for file in files {
+        let data = std::fs::read(&file).map_err(|e| {
+            RepError::InvalidArgument(format!("could not open '{}' for reading: {}", file, e))
+        })?;
+        outln!("{}", hash_data(rep, data, Some(&file), opts)?)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_is_hashed_without_a_repository() {
+        let id = hash_data(None, b"hi\n".to_vec(), None, &Options::default()).unwrap();
+        assert_eq!(id.to_string(), "45b983be36b73c0788dc9cbcb76cbb80fc7bb057");
+    }
+
+    #[test]
+    fn malformed_objects_are_refused_unless_literally() {
+        let opts = Options {
+            object_type: "commit".to_string(),
+            ..Options::default()
+        };
+        assert!(matches!(
+            hash_data(None, b"junk".to_vec(), None, &opts),
+            Err(RepError::FailsFsck(_))
+        ));
+
+        let opts = Options {
+            literally: true,
+            ..opts
+        };
+        assert!(hash_data(None, b"junk".to_vec(), None, &opts).is_ok());
+    }
+}
@@ -0,0 +1,278 @@
+#![allow(dead_code)]
+
+use crate::repository::Repository;
+
+/// a single line of a .gitignore file
+#[derive(Debug, Clone)]
+struct Pattern {
+    /// folder of the .gitignore file relative to the root, "" for the root
+    base: String,
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+    /// patterns containing a slash only match relative to their base
+    anchored: bool,
+    /// where the pattern comes from ("file:line") for check-ignore -v
+    source: String,
+}
+
+/// matches paths against .gitignore, .git/info/exclude and core.excludesFile
+#[derive(Default)]
+pub struct Ignore {
+    patterns: Vec<Pattern>,
+}
+
+impl Ignore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the global excludes, .git/info/exclude and the .gitignore in the root
+    /// the .gitignore files of sub folders get added with add_file while walking
+    pub fn load(rep: &Repository) -> Self {
+        let mut ignore = Self::new();
+
+        if let Some(file) = rep.config_get("core", "excludesFile") {
+            let file = match file.strip_prefix("~/") {
+                Some(rest) => std::env::var("HOME").unwrap_or_default() + "/" + rest,
+                None => file,
+            };
+            ignore.add_file("", &file);
+        }
+        ignore.add_file("", &(rep.get_gitdir().clone() + "/info/exclude"));
+        ignore.add_file("", &(rep.get_workdir().clone() + "/.gitignore"));
+
+        ignore
+    }
+
+    /// add the patterns of a file, base is the folder they are relative to
+    pub fn add_file(&mut self, base: &str, path: &str) {
+        if let Ok(content) = std::fs::read_to_string(path) {
+            for (i, line) in content.lines().enumerate() {
+                self.add_pattern(base, line, &format!("{}:{}", path, i + 1));
+            }
+        }
+    }
+
+    pub fn add_pattern(&mut self, base: &str, line: &str, source: &str) {
+        // trailing spaces are ignored unless they are escaped
+        let mut line = line.trim_end_matches(['\r', '\n']).to_string();
+        while line.ends_with(' ') && !line.ends_with("\\ ") {
+            line.pop();
+        }
+
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest.to_string()),
+            None => (
+                false,
+                line.strip_prefix('\\').map(String::from).unwrap_or(line),
+            ),
+        };
+
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest.to_string()),
+            None => (false, line),
+        };
+
+        let anchored = line.contains('/');
+        let pattern = line.trim_start_matches('/').to_string();
+
+        self.patterns.push(Pattern {
+            base: base.to_string(),
+            pattern,
+            negated,
+            dir_only,
+            anchored,
+            source: source.to_string(),
+        });
+    }
+
+    /// the last pattern matching the path itself (parent folders are not checked)
+    fn matching(&self, path: &str, is_dir: bool) -> Option<&Pattern> {
+        self.patterns.iter().rev().find(|p| {
+            if p.dir_only && !is_dir {
+                return false;
+            }
+
+            let relative = match p.base.as_str() {
+                "" => path,
+                base => match path.strip_prefix(base).and_then(|r| r.strip_prefix('/')) {
+                    Some(relative) => relative,
+                    None => return false,
+                },
+            };
+
+            if p.anchored {
+                wildmatch(&p.pattern, relative, true)
+            } else {
+                let name = relative.rsplit('/').next().unwrap_or(relative);
+                wildmatch(&p.pattern, name, false)
+            }
+        })
+    }
+
+    /// check if a path (relative to the root) is ignored, a file inside of an ignored folder is ignored too
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        self.explain(path, is_dir)
+            .map(|(negated, _, _)| !negated)
+            .unwrap_or(false)
+    }
+
+    /// the pattern deciding about a path as (negated, pattern, source)
+    pub fn explain(&self, path: &str, is_dir: bool) -> Option<(bool, String, String)> {
+        // a file can not be re-included if a parent folder is excluded
+        let mut prefix = String::new();
+        for part in path.split('/').take(path.matches('/').count()) {
+            prefix = if prefix.is_empty() {
+                part.to_string()
+            } else {
+                prefix + "/" + part
+            };
+
+            if let Some(p) = self.matching(&prefix, true) {
+                if !p.negated {
+                    return Some(describe(p));
+                }
+            }
+        }
+
+        self.matching(path, is_dir).map(describe)
+    }
+}
+
+fn describe(p: &Pattern) -> (bool, String, String) {
+    let mut pattern = p.pattern.clone();
+    if p.anchored && !pattern.contains('/') {
+        pattern.insert(0, '/');
+    }
+    if p.dir_only {
+        pattern.push('/');
+    }
+    if p.negated {
+        pattern.insert(0, '!');
+    }
+    (p.negated, pattern, p.source.clone())
+}
+
+/// match a text against a shell glob like git's wildmatch
+/// with pathname set, * and ? dont match a slash but ** matches across folders
+pub fn wildmatch(pattern: &str, text: &str, pathname: bool) -> bool {
+    match_bytes(pattern.as_bytes(), text.as_bytes(), pathname)
+}
+
+fn match_bytes(p: &[u8], t: &[u8], pathname: bool) -> bool {
+    let (mut pi, mut ti) = (0, 0);
+
+    while pi < p.len() {
+        match p[pi] {
+            b'*' => {
+                let double = p.get(pi + 1) == Some(&b'*');
+                if double && pathname {
+                    let rest = &p[pi + 2..];
+                    // "**/" also matches no folder at all
+                    if let Some(rest) = rest.strip_prefix(b"/") {
+                        if match_bytes(rest, &t[ti..], pathname) {
+                            return true;
+                        }
+                        return (ti..t.len())
+                            .filter(|&i| t[i] == b'/')
+                            .any(|i| match_bytes(rest, &t[i + 1..], pathname));
+                    }
+                    return (ti..=t.len()).any(|i| match_bytes(rest, &t[i..], pathname));
+                }
+
+                let rest = &p[pi + 1..];
+                for i in ti..=t.len() {
+                    if match_bytes(rest, &t[i..], pathname) {
+                        return true;
+                    }
+                    if i < t.len() && pathname && t[i] == b'/' {
+                        return false;
+                    }
+                }
+                return false;
+            }
+            b'?' => {
+                if ti >= t.len() || (pathname && t[ti] == b'/') {
+                    return false;
+                }
+            }
+            b'[' => {
+                if ti >= t.len() {
+                    return false;
+                }
+                match match_class(&p[pi..], t[ti]) {
+                    Some((true, len)) => {
+                        pi += len;
+                        ti += 1;
+                        continue;
+                    }
+                    Some((false, _)) => return false,
+                    // no closing bracket, treat it as a normal character
+                    None => {
+                        if t[ti] != b'[' {
+                            return false;
+                        }
+                    }
+                }
+            }
+            b'\\' if pi + 1 < p.len() => {
+                pi += 1;
+                if ti >= t.len() || t[ti] != p[pi] {
+                    return false;
+                }
+            }
+            c => {
+                if ti >= t.len() || t[ti] != c {
+                    return false;
+                }
+            }
+        }
+        pi += 1;
+        ti += 1;
+    }
+
+    ti == t.len()
+}
+
+/// match a character class like [a-z] or [!0-9], returns (matched, length of the class)
+fn match_class(p: &[u8], c: u8) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(p.get(i), Some(b'!') | Some(b'^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let start = i;
+    while i < p.len() {
+        if p[i] == b']' && i > start {
+            return Some((matched != negated, i + 1));
+        }
+
+        let mut lo = p[i];
+        if lo == b'\\' && i + 1 < p.len() {
+            i += 1;
+            lo = p[i];
+        }
+
+        if p.get(i + 1) == Some(&b'-') && p.get(i + 2).map(|&c| c != b']').unwrap_or(false) {
+            let hi = p[i + 2];
+            if lo <= c && c <= hi {
+                matched = true;
+            }
+            i += 3;
+        } else {
+            if lo == c {
+                matched = true;
+            }
+            i += 1;
+        }
+    }
+
+    None
+}
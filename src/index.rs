@@ -0,0 +1,538 @@
+#![allow(dead_code)]
+
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+use crate::ignore::Ignore;
+use crate::objects::{Blob, Object, Tree};
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+
+/// the staging area stored in .git/index (format version 2 / 3)
+#[derive(Clone)]
+pub struct Index {
+    version: u32,
+    entries: Vec<IndexEntry>,
+}
+
+#[derive(Clone)]
+pub struct IndexEntry {
+    ctime: (u32, u32),
+    mtime: (u32, u32),
+    dev: u32,
+    ino: u32,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    size: u32,
+    sha: ObjectId,
+    stage: u8,
+    name: String,
+}
+
+impl IndexEntry {
+    /// an entry without any stat information, e.g. for files taken from a tree
+    pub fn new(name: &str, mode: u32, sha: &ObjectId, stage: u8) -> Self {
+        Self {
+            ctime: (0, 0),
+            mtime: (0, 0),
+            dev: 0,
+            ino: 0,
+            mode,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            sha: *sha,
+            stage,
+            name: name.to_string(),
+        }
+    }
+
+    /// an entry with the stat information of the file in the working directory
+    pub fn from_workdir(rep: &Repository, name: &str, sha: &ObjectId) -> Self {
+        let path = rep.get_workdir().clone() + "/" + name;
+        let meta = std::fs::symlink_metadata(&path).expect("Couldnt stat file");
+
+        let mode = if meta.file_type().is_symlink() {
+            0o120000
+        } else if meta.permissions().mode() & 0o111 != 0 {
+            0o100755
+        } else {
+            0o100644
+        };
+
+        Self {
+            ctime: (meta.ctime() as u32, meta.ctime_nsec() as u32),
+            mtime: (meta.mtime() as u32, meta.mtime_nsec() as u32),
+            dev: meta.dev() as u32,
+            ino: meta.ino() as u32,
+            mode,
+            uid: meta.uid(),
+            gid: meta.gid(),
+            size: meta.size() as u32,
+            sha: *sha,
+            stage: 0,
+            name: name.to_string(),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_hash(&self) -> &ObjectId {
+        &self.sha
+    }
+
+    pub fn get_stage(&self) -> u8 {
+        self.stage
+    }
+
+    /// the mode as it is written in trees (e.g. 100644)
+    pub fn get_mode(&self) -> String {
+        format!("{:o}", self.mode)
+    }
+
+    /// check the working directory file against the entry
+    /// the cheap stat comparison is tried first and the content is only hashed if that fails
+    pub fn is_modified(&self, rep: &Repository) -> bool {
+        let path = rep.get_workdir().clone() + "/" + &self.name;
+        let meta = match std::fs::symlink_metadata(&path) {
+            Ok(meta) => meta,
+            Err(_) => return true,
+        };
+
+        if meta.size() as u32 == self.size
+            && meta.mtime() as u32 == self.mtime.0
+            && meta.mtime_nsec() as u32 == self.mtime.1
+        {
+            return false;
+        }
+
+        // a file which can't be read anymore counts as modified
+        !hash_workdir_file(rep, &self.name).is_ok_and(|sha| sha == self.sha)
+    }
+}
+
+impl Default for Index {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Index {
+    pub fn new() -> Self {
+        Self {
+            version: 2,
+            entries: vec![],
+        }
+    }
+
+    /// load the index of the repository, a missing index file is an empty index
+    pub fn load(rep: &Repository) -> Result<Self, RepError> {
+        let path = rep.get_gitdir().clone() + "/index";
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(RepError::Io(path, e)),
+        };
+
+        Self::from_data(&data)
+    }
+
+    fn from_data(data: &[u8]) -> Result<Self, RepError> {
+        let u32_at = |pos: usize| {
+            data.get(pos..pos + 4)
+                .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+                .ok_or(RepError::CorruptIndex)
+        };
+
+        if data.get(0..4) != Some(b"DIRC") {
+            return Err(RepError::CorruptIndex);
+        }
+
+        let version = u32_at(4)?;
+        if version != 2 && version != 3 {
+            return Err(RepError::UnsupportedIndexVersion(version));
+        }
+
+        let count = u32_at(8)?;
+        let mut entries = vec![];
+        let mut pos = 12;
+
+        for _ in 0..count {
+            let start = pos;
+            let fixed = data.get(pos..pos + 62).ok_or(RepError::CorruptIndex)?;
+            let sha = ObjectId::from_bytes(&fixed[40..60]).ok_or(RepError::CorruptIndex)?;
+            let flags = u16::from_be_bytes([fixed[60], fixed[61]]);
+            pos += 62;
+
+            // version 3 entries may have a second flags field
+            if flags & 0x4000 != 0 {
+                pos += 2;
+            }
+
+            let null = data
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&x| x == 0x00))
+                .ok_or(RepError::CorruptIndex)?;
+            let name = String::from_utf8_lossy(&data[pos..pos + null]).to_string();
+            pos += null;
+
+            // entries are padded with 1-8 null bytes to a multiple of 8
+            pos = start + (pos - start + 8) / 8 * 8;
+
+            entries.push(IndexEntry {
+                ctime: (u32_at(start)?, u32_at(start + 4)?),
+                mtime: (u32_at(start + 8)?, u32_at(start + 12)?),
+                dev: u32_at(start + 16)?,
+                ino: u32_at(start + 20)?,
+                mode: u32_at(start + 24)?,
+                uid: u32_at(start + 28)?,
+                gid: u32_at(start + 32)?,
+                size: u32_at(start + 36)?,
+                sha,
+                stage: ((flags >> 12) & 0x3) as u8,
+                name,
+            });
+        }
+
+        // extensions (like the cached tree) are ignored, they get dropped when saving
+
+        Ok(Self { version, entries })
+    }
+
+    /// write the index back to .git/index
+    pub fn save(&self, rep: &Repository) -> Result<(), RepError> {
+        let mut data = vec![];
+        data.extend_from_slice(b"DIRC");
+        data.extend_from_slice(&2u32.to_be_bytes());
+        data.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+
+        for e in self.entries.iter() {
+            let start = data.len();
+            for n in [
+                e.ctime.0, e.ctime.1, e.mtime.0, e.mtime.1, e.dev, e.ino, e.mode, e.uid, e.gid,
+                e.size,
+            ] {
+                data.extend_from_slice(&n.to_be_bytes());
+            }
+            data.extend_from_slice(e.sha.as_bytes());
+
+            let flags = ((e.stage as u16) << 12) | (e.name.len().min(0xfff) as u16);
+            data.extend_from_slice(&flags.to_be_bytes());
+            data.extend_from_slice(e.name.as_bytes());
+
+            let len = data.len() - start;
+            data.resize(start + (len + 8) / 8 * 8, 0);
+        }
+
+        let mut hasher = Sha1::new();
+        hasher.update(&data);
+        data.extend_from_slice(&hasher.finalize());
+
+        let path = rep.get_gitdir().clone() + "/index";
+        std::fs::write(&path, data).map_err(|e| RepError::Io(path, e))
+    }
+
+    /// an index matching the content of a tree (without stat information)
+    pub fn from_tree(rep: &Repository, tree: &ObjectId) -> Result<Self, RepError> {
+        let mut index = Self::new();
+
+        if let Object::Tree(t) = Object::load(rep, tree)? {
+            for (path, mode, sha) in t.flatten(rep, "")? {
+                let mode = u32::from_str_radix(&mode, 8).unwrap_or(0o100644);
+                index.add(IndexEntry::new(&path, mode, &sha, 0));
+            }
+        }
+
+        Ok(index)
+    }
+
+    pub fn get_entries(&self) -> &Vec<IndexEntry> {
+        &self.entries
+    }
+
+    /// the stage 0 entry of a path
+    pub fn get(&self, name: &str) -> Option<&IndexEntry> {
+        self.entries.iter().find(|e| e.name == name && e.stage == 0)
+    }
+
+    /// add an entry, replacing all existing entries (of every stage) with the same name
+    /// except when a conflict stage gets added, then only the same stage is replaced
+    pub fn add(&mut self, entry: IndexEntry) {
+        self.entries.retain(|e| {
+            e.name != entry.name || (entry.stage != 0 && e.stage != entry.stage && e.stage != 0)
+        });
+
+        let pos = self
+            .entries
+            .iter()
+            .position(|e| (e.name.as_bytes(), e.stage) > (entry.name.as_bytes(), entry.stage))
+            .unwrap_or(self.entries.len());
+        self.entries.insert(pos, entry);
+    }
+
+    /// remove every stage of a path
+    pub fn remove(&mut self, name: &str) {
+        self.entries.retain(|e| e.name != name);
+    }
+
+    /// paths which have unmerged entries
+    pub fn get_conflicts(&self) -> Vec<String> {
+        let mut conflicts = self
+            .entries
+            .iter()
+            .filter(|e| e.stage != 0)
+            .map(|e| e.name.clone())
+            .collect::<Vec<String>>();
+        conflicts.dedup();
+        conflicts
+    }
+
+    /// the stage 0 entries as path -> (mode, sha)
+    pub fn to_map(&self) -> BTreeMap<String, (String, ObjectId)> {
+        self.entries
+            .iter()
+            .filter(|e| e.stage == 0)
+            .map(|e| (e.name.clone(), (e.get_mode(), e.sha)))
+            .collect()
+    }
+
+    /// tracked files whose working directory content differs from the index
+    pub fn get_modified(&self, rep: &Repository) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|e| e.stage == 0 && e.is_modified(rep))
+            .map(|e| e.name.clone())
+            .collect()
+    }
+
+    /// write the index as tree objects and return the id of the root tree
+    pub fn write_tree(&self, rep: &Repository) -> Result<ObjectId, RepError> {
+        if !self.get_conflicts().is_empty() {
+            return Err(RepError::InvalidArgument(
+                "cannot write a tree from an index with conflicts".to_string(),
+            ));
+        }
+
+        let entries = self
+            .entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.get_mode(), e.sha))
+            .collect::<Vec<_>>();
+
+        Self::write_subtree(rep, &entries)
+    }
+
+    fn write_subtree(
+        rep: &Repository,
+        entries: &[(&str, String, ObjectId)],
+    ) -> Result<ObjectId, RepError> {
+        let mut tree_entries = vec![];
+        let mut i = 0;
+
+        while i < entries.len() {
+            let (name, mode, sha) = &entries[i];
+            match name.split_once('/') {
+                Some((dir, _)) => {
+                    // collect everything inside of this directory
+                    let prefix = dir.to_string() + "/";
+                    let children = entries[i..]
+                        .iter()
+                        .take_while(|(n, _, _)| n.starts_with(&prefix))
+                        .map(|(n, m, s)| (&n[prefix.len()..], m.clone(), *s))
+                        .collect::<Vec<_>>();
+                    i += children.len();
+
+                    let sha = Self::write_subtree(rep, &children)?;
+                    tree_entries.push(("40000".to_string(), dir.to_string(), sha));
+                }
+                None => {
+                    tree_entries.push((mode.clone(), name.to_string(), *sha));
+                    i += 1;
+                }
+            }
+        }
+
+        Tree::from_entries(tree_entries).save(rep)
+    }
+}
+
+/// update the working directory from the files of one index to the files of another
+/// unchanged files are left alone, the stat information of `to` gets refreshed for written files
+pub fn update_workdir(rep: &Repository, from: &Index, to: &mut Index) -> Result<(), RepError> {
+    let old = from.to_map();
+    let new = to.to_map();
+
+    for path in old.keys().filter(|p| !new.contains_key(*p)) {
+        remove_workdir_file(rep, path);
+    }
+
+    for (path, (mode, sha)) in new.iter() {
+        if old.get(path) == Some(&(mode.clone(), *sha)) {
+            // keep the stat information so the file doesnt need to be hashed again
+            if let Some(entry) = from.get(path) {
+                to.add(entry.clone());
+            }
+            continue;
+        }
+
+        write_workdir_file(rep, path, mode, sha)?;
+        to.add(IndexEntry::from_workdir(rep, path, sha));
+    }
+
+    Ok(())
+}
+
+/// make the index and the working directory match a tree, dropping all local changes
+pub fn reset_workdir(rep: &Repository, tree: &ObjectId) -> Result<(), RepError> {
+    let mut current = Index::load(rep)?;
+    let mut target = Index::from_tree(rep, tree)?;
+
+    // modified and conflicting files are treated as unknown so they get overwritten or removed
+    let dirty = current
+        .get_modified(rep)
+        .into_iter()
+        .chain(current.get_conflicts())
+        .collect::<Vec<String>>();
+    for path in dirty {
+        current.remove(&path);
+        if target.get(&path).is_none() {
+            remove_workdir_file(rep, &path);
+        }
+    }
+
+    update_workdir(rep, &current, &mut target)?;
+    target.save(rep)?;
+    Ok(())
+}
+
+/// write a blob from the repository to the working directory
+pub fn write_workdir_file(
+    rep: &Repository,
+    path: &str,
+    mode: &str,
+    sha: &ObjectId,
+) -> Result<(), RepError> {
+    let file = rep.get_workdir().clone() + "/" + path;
+
+    // submodules are not checked out
+    if mode == "160000" {
+        let _ = std::fs::create_dir_all(&file);
+        return Ok(());
+    }
+
+    if let Some(parent) = std::path::Path::new(&file).parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = std::fs::remove_file(&file);
+
+    let data = Object::load(rep, sha)?.get_data().clone();
+    if mode == "120000" {
+        let target = String::from_utf8_lossy(&data).to_string();
+        return std::os::unix::fs::symlink(target, &file).map_err(|e| RepError::Io(file, e));
+    }
+
+    std::fs::write(&file, data).map_err(|e| RepError::Io(file.clone(), e))?;
+    let permissions = if mode == "100755" { 0o755 } else { 0o644 };
+    let _ = std::fs::set_permissions(&file, std::fs::Permissions::from_mode(permissions));
+    Ok(())
+}
+
+/// remove a file from the working directory together with its then empty parent folders
+pub fn remove_workdir_file(rep: &Repository, path: &str) {
+    let file = rep.get_workdir().clone() + "/" + path;
+    let _ = std::fs::remove_file(&file);
+
+    let mut dir = std::path::Path::new(&file).parent();
+    while let Some(d) = dir {
+        if d.to_string_lossy().len() <= rep.get_workdir().len() || std::fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
+
+/// files of the working directory which are not tracked by the index
+/// ignored files are skipped unless include_ignored is set, folders of nested repositories are never entered
+pub fn untracked_files(rep: &Repository, index: &Index, include_ignored: bool) -> Vec<String> {
+    let tracked = index
+        .entries
+        .iter()
+        .map(|e| e.name.as_str())
+        .collect::<std::collections::HashSet<&str>>();
+
+    let mut ignore = Ignore::load(rep);
+    let mut files = vec![];
+    collect_untracked(rep, "", &tracked, &mut ignore, include_ignored, &mut files);
+    files
+}
+
+fn collect_untracked(
+    rep: &Repository,
+    dir: &str,
+    tracked: &std::collections::HashSet<&str>,
+    ignore: &mut Ignore,
+    include_ignored: bool,
+    files: &mut Vec<String>,
+) {
+    let full = rep.get_workdir().clone() + "/" + dir;
+    if !dir.is_empty() {
+        ignore.add_file(dir, &(full.clone() + "/.gitignore"));
+    }
+
+    let mut entries = match std::fs::read_dir(&full) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).collect::<Vec<_>>(),
+        Err(_) => return,
+    };
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name == ".git" {
+            continue;
+        }
+        let path = if dir.is_empty() {
+            name
+        } else {
+            dir.to_string() + "/" + &name
+        };
+
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if !include_ignored && !tracked.contains(path.as_str()) && ignore.is_ignored(&path, is_dir)
+        {
+            continue;
+        }
+
+        if is_dir {
+            let nested_repository = std::path::Path::new(
+                &(full.clone() + "/" + &entry.file_name().to_string_lossy() + "/.git"),
+            )
+            .exists();
+            if !nested_repository {
+                collect_untracked(rep, &path, tracked, ignore, include_ignored, files);
+            }
+        } else if !tracked.contains(path.as_str()) {
+            files.push(path);
+        }
+    }
+}
+
+/// the blob id of a file in the working directory
+pub fn hash_workdir_file(rep: &Repository, name: &str) -> Result<ObjectId, RepError> {
+    Ok(read_workdir_blob(rep, name)?.id())
+}
+
+/// read a working directory file as a blob, symlinks are stored as their target
+pub fn read_workdir_blob(rep: &Repository, name: &str) -> Result<Object, RepError> {
+    let path = rep.get_workdir().clone() + "/" + name;
+
+    match std::fs::read_link(&path) {
+        Ok(target) => Ok(Blob::from_data(
+            target.to_string_lossy().as_bytes().to_vec(),
+        )),
+        Err(_) => Blob::from_file(&path),
+    }
+}
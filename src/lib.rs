@@ -0,0 +1,113 @@
+//! A reimplementation of git, usable as a library.
+//!
+//! A [Repository] is found from a directory, objects are loaded from it by their hash
+//! and the headers of commits and tags are read with the [KVLM] trait:
+//!
+//! ```no_run
+//! use kyu::{Object, Repository, KVLM};
+//!
+//! let rep = Repository::load(None)?;
+//! let head = rep.ref_resolve("HEAD")?;
+//! if let Object::Commit(commit) = Object::load(&rep, &head)? {
+//!     println!("{} {}", head, commit.get_message());
+//! }
+//! # Ok::<(), kyu::RepError>(())
+//! ```
+
+/// println! to a locked stdout which returns the error as RepError::Output instead of
+/// panicking, so a closed pipe like in `kyu log | head` can end a command quietly
+macro_rules! outln {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        writeln!(std::io::stdout().lock(), $($arg)*).map_err($crate::repository::RepError::Output)
+    }};
+}
+
+/// print! like [outln]
+macro_rules! out {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        write!(std::io::stdout().lock(), $($arg)*).map_err($crate::repository::RepError::Output)
+    }};
+}
+
+/// eprintln! for the error:, warning: and hint: lines, they go to stderr so the output of
+/// a command stays usable, a stderr which can't be written to is ignored like git does
+macro_rules! errln {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        let _ = writeln!(std::io::stderr().lock(), $($arg)*);
+    }};
+}
+
+pub mod index;
+pub mod objects;
+pub mod odb;
+pub mod oid;
+pub mod pack;
+pub mod pathspec;
+pub mod repository;
+pub mod revwalk;
+pub mod signature;
+
+// the commands of the kyu binary, they print their output and are not a stable API
+#[doc(hidden)]
+pub mod add;
+#[doc(hidden)]
+pub mod archive;
+#[doc(hidden)]
+pub mod attributes;
+#[doc(hidden)]
+pub mod bisect;
+#[doc(hidden)]
+pub mod blame;
+#[doc(hidden)]
+pub mod catfile;
+#[doc(hidden)]
+pub mod checkout;
+#[doc(hidden)]
+pub mod clean;
+#[doc(hidden)]
+pub mod committree;
+#[doc(hidden)]
+pub mod describe;
+#[doc(hidden)]
+pub mod diff;
+#[doc(hidden)]
+pub mod fsck;
+#[doc(hidden)]
+pub mod grep;
+#[doc(hidden)]
+pub mod hashobject;
+#[doc(hidden)]
+pub mod ignore;
+#[doc(hidden)]
+pub mod logscreen;
+#[doc(hidden)]
+pub mod lstree;
+#[doc(hidden)]
+pub mod mailmap;
+#[doc(hidden)]
+pub mod merge;
+#[doc(hidden)]
+pub mod mktree;
+#[doc(hidden)]
+pub mod prune;
+#[doc(hidden)]
+pub mod rebase;
+#[doc(hidden)]
+pub mod reset;
+#[doc(hidden)]
+pub mod sequencer;
+#[doc(hidden)]
+pub mod shortlog;
+#[doc(hidden)]
+pub mod stash;
+#[doc(hidden)]
+pub mod writetree;
+
+pub use objects::{Blob, Commit, Object, Tag, Tree, KVLM};
+pub use odb::ObjectDatabase;
+pub use oid::ObjectId;
+pub use repository::{RepError, Repository};
+pub use signature::Signature;
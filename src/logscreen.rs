@@ -1,74 +1,79 @@
-use chrono::{Datelike, FixedOffset, TimeZone, Utc};
 use colored::*;
 use std::collections::VecDeque;
+use std::io::Write;
 
+use crate::mailmap::Mailmap;
 use crate::objects::{Commit, Object, KVLM};
-use crate::repository::Repository;
+use crate::repository::{RepError, Repository};
+use crate::signature::DateFormat;
 
-pub fn display_log(commit: Commit, rep: Repository) {
+pub fn display_log(
+    commit: Commit,
+    rep: Repository,
+    date_format: &DateFormat,
+) -> Result<(), RepError> {
+    let mailmap = match rep.config_get("log", "mailmap").as_deref() {
+        Some("false") => Mailmap::new(),
+        _ => Mailmap::load(&rep),
+    };
+
+    let mut out = std::io::stdout().lock();
     let mut commits_to_visit: VecDeque<Commit> = VecDeque::new();
     commits_to_visit.push_back(commit);
 
     while !commits_to_visit.is_empty() {
         let commit = commits_to_visit.pop_front().unwrap();
-        let commit = Object::load(&rep, &commit.hash());
+        let commit = Object::load(&rep, &commit.id())?;
         let commit = match commit {
             Object::Commit(c) => c,
-            _ => panic!("head should be a commit object"),
+            _ => {
+                return Err(RepError::BadObject(
+                    "commit".to_string(),
+                    commit.id().to_string(),
+                ))
+            }
         };
 
-        println!("{} {}", "commit".cyan(), commit.hash().blue());
+        writeln!(
+            out,
+            "{} {}",
+            "commit".cyan(),
+            commit.id().to_string().blue()
+        )?;
 
         if commit.get_parents().len() > 1 {
-            println!(
+            writeln!(
+                out,
                 "Merge: {} -> {}",
                 commit.get_parents()[0],
                 commit.get_parents()[1]
-            );
+            )?;
         }
 
-        let author_data = commit.get_author();
-        let author_data = author_data.split(" ").collect::<Vec<&str>>();
-        let author = author_data[0..2].join(" ");
-        println!("Author: {}", author);
-
-        if author_data.len() > 3 {
-            let date = author_data[2].parse::<i64>();
-            let offset = author_data[3].parse::<i32>().unwrap_or_else(|_| 0);
-            let offset = FixedOffset::east_opt(offset * 36).unwrap(); // / 100 * 3600 = * 36
+        let author = mailmap.apply(&commit.get_author());
+        writeln!(out, "Author: {}", author.identity()).map_err(RepError::Output)?;
+        writeln!(out, "Date:   {}", author.format_date(date_format)).map_err(RepError::Output)?;
 
-            match date {
-                Ok(date) => {
-                    let date = Utc.timestamp_opt(date, 0).unwrap();
-                    let date = date.with_timezone(&offset);
-                    println!(
-                        "Date: {}, {} {} {} {} {}",
-                        date.weekday(),
-                        date.day(),
-                        date.format("%b"),
-                        date.year(),
-                        date.time(),
-                        date.timezone(),
-                    );
-                }
-                Err(_) => {
-                    println!("Date: unknown");
-                }
-            }
+        writeln!(out).map_err(RepError::Output)?;
+        for line in commit.get_message().trim_end().lines() {
+            writeln!(out, "    {}", line).map_err(RepError::Output)?;
         }
-
-        println!();
-        println!("    {}", commit.get_message());
-        println!();
+        writeln!(out).map_err(RepError::Output)?;
 
         for parent in commit.get_parents() {
-            let parent = Object::load(&rep, &parent);
-            let parent = match parent {
+            let parent = match Object::load(&rep, parent)? {
                 Object::Commit(c) => c,
-                _ => panic!("head should be a commit object"),
+                _ => {
+                    return Err(RepError::BadObject(
+                        "commit".to_string(),
+                        parent.to_string(),
+                    ))
+                }
             };
 
             commits_to_visit.push_back(parent);
         }
     }
+
+    Ok(())
 }
@@ -0,0 +1,337 @@
+#![allow(dead_code)]
+
+use std::io::Write;
+
+use crate::objects::{Object, Tree, TreeEntry};
+use crate::oid::ObjectId;
+use crate::pathspec::{display_path, quote_path};
+use crate::repository::{RepError, Repository};
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// recurse into sub trees (-r)
+    pub recursive: bool,
+    /// show trees even when recursing into them (-t)
+    pub show_trees: bool,
+    /// only show trees (-d)
+    pub only_trees: bool,
+    /// show the size of blobs (-l)
+    pub long: bool,
+    pub name_only: bool,
+    pub object_only: bool,
+    /// terminate lines with NUL and dont quote paths (-z)
+    pub null_terminated: bool,
+    /// show paths relative to the root instead of the current directory
+    pub full_name: bool,
+    /// ignore the current directory completely
+    pub full_tree: bool,
+    /// length of the abbreviated object names
+    pub abbrev: Option<usize>,
+    /// a custom format with %(objectmode), %(objecttype), %(objectname), %(objectsize) and %(path)
+    pub format: Option<String>,
+}
+
+/// lists the entries of a tree like git ls-tree
+struct Lister<'a> {
+    rep: &'a Repository,
+    opts: Options,
+    /// the paths to show relative to the root, a trailing slash shows the content of a folder
+    paths: Vec<String>,
+    /// the current directory relative to the root
+    prefix: String,
+    out: std::io::StdoutLock<'static>,
+}
+
+impl Lister<'_> {
+    /// check if an entry is selected by one of the paths
+    fn is_selected(&self, path: &str, is_tree: bool) -> bool {
+        self.paths.iter().any(|p| {
+            let folder = p.trim_end_matches('/');
+            folder.is_empty()
+                || (path == folder && !(is_tree && p.ends_with('/')))
+                || path.starts_with(&(folder.to_string() + "/"))
+        })
+    }
+
+    /// check if the entries of a tree have to be listed
+    fn descend(&self, path: &str) -> bool {
+        self.paths.iter().any(|p| {
+            let folder = p.trim_end_matches('/');
+            // the path points to something inside of this tree
+            folder.starts_with(&(path.to_string() + "/"))
+                || (path == folder && p.ends_with('/'))
+                || (self.opts.recursive
+                    && (folder.is_empty()
+                        || path == folder
+                        || path.starts_with(&(folder.to_string() + "/"))))
+        })
+    }
+
+    fn list(&mut self, tree: &Tree, prefix: &str) -> Result<(), RepError> {
+        for entry in tree.get_objects() {
+            let path = prefix.to_string() + entry.get_name();
+            let is_tree = entry.is_tree();
+
+            if is_tree && self.descend(&path) {
+                if self.opts.show_trees || self.opts.only_trees {
+                    self.show(entry, &path)?;
+                }
+                if let Some(t) = entry.load_tree(self.rep)? {
+                    self.list(&t, &(path + "/"))?;
+                }
+            } else if self.is_selected(&path, is_tree) && (is_tree || !self.opts.only_trees) {
+                self.show(entry, &path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn show(&mut self, entry: &TreeEntry, path: &str) -> Result<(), RepError> {
+        let format = match &self.opts.format {
+            Some(format) => format.clone(),
+            None if self.opts.name_only => "%(path)".to_string(),
+            None if self.opts.object_only => "%(objectname)".to_string(),
+            None if self.opts.long => {
+                "%(objectmode) %(objecttype) %(objectname) %(objectsize:padded)%x09%(path)"
+                    .to_string()
+            }
+            None => "%(objectmode) %(objecttype) %(objectname)%x09%(path)".to_string(),
+        };
+
+        let mut line = self.expand(&format, entry, path);
+        line.push(if self.opts.null_terminated {
+            '\0'
+        } else {
+            '\n'
+        });
+        self.out
+            .write_all(line.as_bytes())
+            .map_err(RepError::Output)?;
+        Ok(())
+    }
+
+    /// replace the placeholders of a format string for an entry
+    fn expand(&self, format: &str, entry: &TreeEntry, path: &str) -> String {
+        let mut result = String::new();
+        let mut rest = format;
+
+        while let Some(pos) = rest.find('%') {
+            result.push_str(&rest[..pos]);
+            rest = &rest[pos..];
+
+            if let Some(hex) = rest.strip_prefix("%x").and_then(|r| r.get(..2)) {
+                if let Ok(b) = u8::from_str_radix(hex, 16) {
+                    result.push(b as char);
+                    rest = &rest[4..];
+                    continue;
+                }
+            }
+            if let Some(r) = rest.strip_prefix("%%") {
+                result.push('%');
+                rest = r;
+                continue;
+            }
+
+            let placeholder = rest
+                .strip_prefix("%(")
+                .and_then(|r| r.split_once(')'))
+                .map(|(name, _)| name);
+            let value = match placeholder {
+                Some("objectmode") => format!("{:0>6}", entry.get_mode()),
+                Some("objecttype") => entry.get_type().to_string(),
+                Some("objectname") => self.abbrev(entry.get_hash()),
+                Some("objectsize") => self.size(entry),
+                Some("objectsize:padded") => format!("{:>7}", self.size(entry)),
+                Some("path") => self.path(path),
+                _ => {
+                    // not a placeholder, keep the percent sign
+                    result.push('%');
+                    rest = &rest[1..];
+                    continue;
+                }
+            };
+            result.push_str(&value);
+            rest = &rest[placeholder.unwrap().len() + 3..];
+        }
+
+        result.push_str(rest);
+        result
+    }
+
+    fn abbrev(&self, id: &ObjectId) -> String {
+        match self.opts.abbrev {
+            Some(n) => format!("{:.*}", n.clamp(4, 40), id),
+            None => id.to_string(),
+        }
+    }
+
+    /// the size of a blob, trees and submodules have no size
+    fn size(&self, entry: &TreeEntry) -> String {
+        match entry.get_type() {
+            "blob" => match self.rep.objects().read_header(entry.get_hash()) {
+                Ok((_, size)) => size.to_string(),
+                Err(_) => "-".to_string(),
+            },
+            _ => "-".to_string(),
+        }
+    }
+
+    fn path(&self, path: &str) -> String {
+        let path = match self.opts.full_name || self.opts.full_tree {
+            true => path.to_string(),
+            false => display_path(path, &self.prefix),
+        };
+        match self.opts.null_terminated {
+            true => path,
+            false => quote_path(&path),
+        }
+    }
+}
+
+/// list the content of a tree-ish, paths are relative to the current directory
+pub fn ls_tree(
+    rep: &Repository,
+    tree_ish: &str,
+    paths: &[String],
+    opts: Options,
+) -> Result<(), RepError> {
+    let hash = rep.ref_resolve(tree_ish)?;
+    let tree = rep
+        .peel(&hash, "tree")
+        .ok_or(RepError::InvalidReference(tree_ish.to_string()))?;
+    let tree = match Object::load(rep, &tree)? {
+        Object::Tree(t) => t,
+        _ => return Err(RepError::InvalidReference(tree_ish.to_string())),
+    };
+
+    let prefix = match opts.full_tree {
+        true => String::new(),
+        false => rep.relative_path("."),
+    };
+
+    // without paths the current directory is listed
+    let paths = match paths.is_empty() {
+        true if prefix.is_empty() => vec![String::new()],
+        true => vec![prefix.clone() + "/"],
+        false => paths
+            .iter()
+            .map(|p| {
+                let path = match opts.full_tree {
+                    true => p.trim_start_matches('/').to_string(),
+                    false => rep.relative_path(p),
+                };
+                // keep the trailing slash, it lists the content of a folder
+                match p.ends_with('/') && !path.is_empty() {
+                    true => path + "/",
+                    false => path,
+                }
+            })
+            .collect(),
+    };
+
+    let mut lister = Lister {
+        rep,
+        opts,
+        paths,
+        prefix,
+        out: std::io::stdout().lock(),
+    };
+    lister.list(&tree, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::Blob;
+
+    /// a tree with a.txt and dir/b.txt
+    fn tree(rep: &Repository) -> Tree {
+        let blob = Blob::from_data(b"hello\n".to_vec()).save(rep).unwrap();
+        let entry = |mode: &str, name: &str, id: ObjectId| (mode.to_string(), name.to_string(), id);
+        let dir = Tree::from_entries(vec![entry("100644", "b.txt", blob)])
+            .save(rep)
+            .unwrap();
+        let root = Tree::from_entries(vec![
+            entry("100644", "a.txt", blob),
+            entry("40000", "dir", dir),
+        ])
+        .save(rep)
+        .unwrap();
+        match Object::load(rep, &root).unwrap() {
+            Object::Tree(t) => t,
+            _ => unreachable!(),
+        }
+    }
+
+    fn new_lister<'a>(rep: &'a Repository, paths: &[&str], opts: Options) -> Lister<'a> {
+        Lister {
+            rep,
+            opts,
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+            prefix: String::new(),
+            out: std::io::stdout().lock(),
+        }
+    }
+
+    #[test]
+    fn formats_are_expanded() {
+        let rep = Repository::in_memory();
+        let tree = tree(&rep);
+        let [a, dir] = [&tree.get_objects()[0], &tree.get_objects()[1]];
+        let blob = a.get_hash().to_string();
+
+        let opts = Options {
+            abbrev: Some(7),
+            ..Default::default()
+        };
+        let lister = new_lister(&rep, &[""], opts);
+        let long = "%(objectmode) %(objecttype) %(objectname) %(objectsize:padded)%x09%(path)";
+        assert_eq!(
+            lister.expand(long, a, "a.txt"),
+            format!("100644 blob {:.7}       6\ta.txt", blob)
+        );
+        assert_eq!(
+            lister.expand(
+                "%(objectmode) %(objectsize) %(path) 100%% %(unknown)",
+                dir,
+                "dir"
+            ),
+            "040000 - dir 100% %(unknown)"
+        );
+    }
+
+    #[test]
+    fn paths_select_entries_and_trees_to_enter() {
+        let rep = Repository::in_memory();
+        let lister = new_lister(&rep, &["dir"], Options::default());
+        assert!(lister.is_selected("dir", true) && !lister.descend("dir"));
+        assert!(!lister.is_selected("a.txt", false));
+
+        // a trailing slash lists the content of the folder
+        let lister = new_lister(&rep, &["dir/"], Options::default());
+        assert!(!lister.is_selected("dir", true) && lister.descend("dir"));
+        assert!(lister.is_selected("dir/b.txt", false));
+
+        let opts = Options {
+            recursive: true,
+            ..Default::default()
+        };
+        let lister = new_lister(&rep, &[""], opts);
+        assert!(lister.descend("dir") && lister.is_selected("a.txt", false));
+    }
+
+    #[test]
+    fn paths_are_quoted_unless_null_terminated() {
+        let rep = Repository::in_memory();
+        assert_eq!(
+            new_lister(&rep, &[""], Options::default()).path("tab\there"),
+            "\"tab\\there\""
+        );
+        let opts = Options {
+            null_terminated: true,
+            ..Default::default()
+        };
+        assert_eq!(new_lister(&rep, &[""], opts).path("tab\there"), "tab\there");
+    }
+}
@@ -0,0 +1,238 @@
+#![allow(dead_code)]
+
+use crate::objects::Object;
+use crate::repository::{RepError, Repository};
+use crate::signature::Signature;
+
+/// a line of a .mailmap file, commit name and email are what is found in commits
+#[derive(Debug, Clone)]
+struct Entry {
+    proper_name: Option<String>,
+    proper_email: Option<String>,
+    commit_name: Option<String>,
+    commit_email: String,
+}
+
+/// maps the names and emails found in commits to the canonical ones
+#[derive(Default)]
+pub struct Mailmap {
+    entries: Vec<Entry>,
+}
+
+impl Mailmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// .mailmap of the working directory, mailmap.blob and mailmap.file, later ones win
+    pub fn load(rep: &Repository) -> Self {
+        let mut mailmap = Self::new();
+
+        if let Ok(content) = std::fs::read_to_string(rep.get_workdir().clone() + "/.mailmap") {
+            mailmap.add_data(&content);
+        }
+
+        if let Some(blob) = rep.config_get("mailmap", "blob") {
+            let object = rep
+                .ref_resolve(&blob)
+                .ok()
+                .and_then(|hash| Object::load(rep, &hash).ok());
+            if let Some(object) = object.filter(|o| o.get_type() == "blob") {
+                mailmap.add_data(&String::from_utf8_lossy(object.get_data()));
+            }
+        }
+
+        if let Some(file) = rep.config_get("mailmap", "file") {
+            let file = match file.strip_prefix("~/") {
+                Some(rest) => std::env::var("HOME").unwrap_or_default() + "/" + rest,
+                None => file,
+            };
+            if let Ok(content) = std::fs::read_to_string(file) {
+                mailmap.add_data(&content);
+            }
+        }
+
+        mailmap
+    }
+
+    /// add the mappings of the content of a mailmap file
+    pub fn add_data(&mut self, content: &str) {
+        for line in content.lines() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            if let Some(entry) = parse_line(line) {
+                self.entries.push(entry);
+            }
+        }
+    }
+
+    /// the proper name and email for a name and email found in a commit
+    pub fn map(&self, name: &str, email: &str) -> (String, String) {
+        // entries for the same name and email are merged, later ones override earlier fields
+        let merge = |commit_name: Option<&str>| {
+            let mut found = false;
+            let mut proper: (Option<String>, Option<String>) = (None, None);
+            for entry in self.entries.iter() {
+                let same_name = match (&entry.commit_name, commit_name) {
+                    (Some(a), Some(b)) => a.to_lowercase() == b.to_lowercase(),
+                    (None, None) => true,
+                    _ => false,
+                };
+                if !same_name || !entry.commit_email.eq_ignore_ascii_case(email) {
+                    continue;
+                }
+                found = true;
+                if entry.proper_name.is_some() {
+                    proper.0 = entry.proper_name.clone();
+                }
+                if entry.proper_email.is_some() {
+                    proper.1 = entry.proper_email.clone();
+                }
+            }
+            found.then_some(proper)
+        };
+
+        // an entry for this exact name is preferred over one for the email only
+        match merge(Some(name)).or_else(|| merge(None)) {
+            Some((proper_name, proper_email)) => (
+                proper_name.unwrap_or(name.to_string()),
+                proper_email.unwrap_or(email.to_string()),
+            ),
+            None => (name.to_string(), email.to_string()),
+        }
+    }
+
+    /// the proper "Name <email>" of a contact given as "Name <email>" or "<email>",
+    /// like check-mailmap shows it
+    pub fn check(&self, contact: &str) -> Result<String, RepError> {
+        let contact = contact.trim();
+        let (name, rest) = contact.split_once('<').unwrap_or(("", contact));
+        let email = match rest.strip_suffix('>') {
+            Some(email) if contact.contains('<') => email,
+            _ => {
+                return Err(RepError::InvalidArgument(format!(
+                    "unable to parse contact: {}",
+                    contact
+                )))
+            }
+        };
+
+        let (name, email) = self.map(name.trim(), email);
+        match name.is_empty() {
+            true => Ok(format!("<{}>", email)),
+            false => Ok(format!("{} <{}>", name, email)),
+        }
+    }
+
+    /// a signature with the proper name and email, the date stays the same
+    pub fn apply(&self, signature: &Signature) -> Signature {
+        let (name, email) = self.map(&signature.name, &signature.email);
+        Signature {
+            name,
+            email,
+            ..signature.clone()
+        }
+    }
+}
+
+/// split "Name <email> rest" into the name, the email and the rest
+fn split_contact(text: &str) -> Option<(Option<String>, String, &str)> {
+    let open = text.find('<')?;
+    let close = open + text[open..].find('>')?;
+
+    let name = text[..open].trim();
+    let name = (!name.is_empty()).then(|| name.to_string());
+    Some((name, text[open + 1..close].to_string(), &text[close + 1..]))
+}
+
+/// the four forms of mailmap lines:
+/// Proper Name <commit@email>
+/// <proper@email> <commit@email>
+/// Proper Name <proper@email> <commit@email>
+/// Proper Name <proper@email> Commit Name <commit@email>
+fn parse_line(line: &str) -> Option<Entry> {
+    let (proper_name, first_email, rest) = split_contact(line)?;
+
+    let entry = match split_contact(rest) {
+        Some((commit_name, commit_email, _)) => Entry {
+            proper_name,
+            proper_email: Some(first_email),
+            commit_name,
+            commit_email,
+        },
+        None => Entry {
+            proper_name,
+            proper_email: None,
+            commit_name: None,
+            commit_email: first_email,
+        },
+    };
+
+    Some(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAILMAP: &str = "# comment
+Proper Name <commit@email>
+<proper@email> <other@email>
+Both Name <both@email> <old@email>
+Joe Dev <joe@dev> Joe <joe@old>
+Jane <jane@new> <JANE@old>
+";
+
+    fn mailmap() -> Mailmap {
+        let mut mailmap = Mailmap::new();
+        mailmap.add_data(MAILMAP);
+        mailmap
+    }
+
+    #[test]
+    fn all_four_line_forms_are_mapped() {
+        let mailmap = mailmap();
+        let map = |name: &str, email: &str| mailmap.map(name, email);
+        assert_eq!(
+            map("X", "commit@email"),
+            ("Proper Name".into(), "commit@email".into())
+        );
+        assert_eq!(map("X", "other@email"), ("X".into(), "proper@email".into()));
+        assert_eq!(
+            map("", "old@email"),
+            ("Both Name".into(), "both@email".into())
+        );
+        assert_eq!(map("Joe", "joe@old"), ("Joe Dev".into(), "joe@dev".into()));
+        // the commit name has to match for the last form
+        assert_eq!(
+            map("Joseph", "joe@old"),
+            ("Joseph".into(), "joe@old".into())
+        );
+    }
+
+    #[test]
+    fn emails_are_compared_without_case() {
+        let mailmap = mailmap();
+        assert_eq!(
+            mailmap.map("J", "jane@old"),
+            ("Jane".into(), "jane@new".into())
+        );
+        assert_eq!(
+            mailmap.map("X", "COMMIT@email"),
+            ("Proper Name".into(), "COMMIT@email".into())
+        );
+    }
+
+    #[test]
+    fn contacts_are_checked_like_check_mailmap() {
+        let mailmap = mailmap();
+        assert_eq!(
+            mailmap.check(" <old@email> ").unwrap(),
+            "Both Name <both@email>"
+        );
+        assert_eq!(mailmap.check("A <a@b>").unwrap(), "A <a@b>");
+        assert!(mailmap.check("a@b").is_err());
+        assert!(mailmap.check("A <a@b").is_err());
+    }
+}
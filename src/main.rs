@@ -1,10 +1,15 @@
-use clap::{Parser, Subcommand, ValueEnum};
-use objects::{Blob, Object, KVLM};
-use repository::{RepError, Repository};
-
-mod logscreen;
-mod objects;
-mod repository;
+use clap::{Parser, Subcommand};
+use kyu::index::Index;
+use kyu::objects::Object;
+use kyu::pathspec::Pathspec;
+use kyu::repository::{self, RepError, Repository};
+use kyu::sequencer::{self, Action};
+use kyu::{
+    add, archive, bisect, blame, catfile, checkout, clean, committree, describe, fsck, grep,
+    hashobject, logscreen, lstree, mailmap, mktree, prune, rebase, reset, shortlog, signature,
+    stash, writetree,
+};
+use std::io::{ErrorKind, IsTerminal, Write};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -13,161 +18,1339 @@ struct Args {
     command: Commands,
 }
 
-#[derive(ValueEnum, Debug, Clone, PartialEq, Eq)]
-enum ObjectType {
-    Blob,
-    Commit,
-    Tree,
-    Tag,
+/// flags shared by cherry-pick and revert
+#[derive(clap::Args, Debug)]
+struct SequencerArgs {
+    /// Commits or ranges (A..B) to apply
+    commits: Vec<String>,
+
+    /// Only update the index and working directory without committing
+    #[arg(short = 'n', long)]
+    no_commit: bool,
+
+    /// Commit the resolved conflicts and continue
+    #[arg(long = "continue")]
+    continue_: bool,
+
+    /// Skip the current commit and continue
+    #[arg(long)]
+    skip: bool,
+
+    /// Cancel the operation and go back to the original HEAD
+    #[arg(long)]
+    abort: bool,
+}
+
+/// flags of stash push, they can also be given to stash directly
+#[derive(clap::Args, Debug)]
+struct StashPushArgs {
+    /// Also stash untracked files
+    #[arg(short = 'u', long)]
+    include_untracked: bool,
+
+    /// Also stash untracked and ignored files
+    #[arg(short, long)]
+    all: bool,
+
+    /// Keep the staged changes in the index and working directory
+    #[arg(short, long)]
+    keep_index: bool,
+
+    #[arg(short, long)]
+    message: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum StashCommand {
+    /// Save the local changes and reset to HEAD (the default)
+    Push(StashPushArgs),
+    /// List the stash entries
+    List,
+    /// Show the changes recorded in a stash entry
+    Show {
+        /// Show the changes as a patch instead of a diffstat
+        #[arg(short, long)]
+        patch: bool,
+
+        stash: Option<String>,
+    },
+    /// Apply a stash entry on top of the working directory
+    Apply {
+        /// Also restore the staged changes
+        #[arg(long)]
+        index: bool,
+
+        stash: Option<String>,
+    },
+    /// Apply a stash entry and remove it
+    Pop {
+        #[arg(long)]
+        index: bool,
+
+        stash: Option<String>,
+    },
+    /// Remove a stash entry
+    Drop { stash: Option<String> },
+    /// Create a branch where the stash was made and apply it there
+    Branch { name: String, stash: Option<String> },
+    /// Remove all stash entries
+    Clear,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
+    /// Add file contents to the index
     Add {
         files: Vec<String>,
     },
-    /// Print the contents of a blob object
+    /// Print the contents, type or size of objects
+    #[command(group = clap::ArgGroup::new("query").args(["t", "s", "p", "e"]))]
     CatFile {
-        #[arg(value_enum)]
-        object_type: ObjectType,
+        /// Show the type of the object
+        #[arg(short)]
+        t: bool,
 
-        hash: String,
+        /// Show the size of the object
+        #[arg(short)]
+        s: bool,
+
+        /// Pretty print the content of the object
+        #[arg(short)]
+        p: bool,
+
+        /// Exit with status 1 if the object doesnt exist
+        #[arg(short)]
+        e: bool,
+
+        /// Print info and content of the objects named on stdin
+        #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "")]
+        batch: Option<String>,
+
+        /// Print info of the objects named on stdin
+        #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "")]
+        batch_check: Option<String>,
+
+        /// Show all objects instead of reading names from stdin
+        #[arg(long)]
+        batch_all_objects: bool,
+
+        /// <type> <object> or just <object> with -t, -s, -p or -e
+        args: Vec<String>,
     },
     Commit {
         #[arg(short, long)]
         message: Option<String>,
     },
     CheckIgnore,
-    /// Set the current branch / specified folders / files to a specific commit / tree
+    /// Show the canonical names and emails of contacts according to the mailmap
+    CheckMailmap {
+        /// Contacts like "Name <email>" or "<email>"
+        contacts: Vec<String>,
+
+        /// Also read contacts from stdin, one per line
+        #[arg(long)]
+        stdin: bool,
+    },
+    /// Create a commit object from a tree without touching the working directory
+    CommitTree {
+        tree: String,
+
+        /// Parent commits
+        #[arg(short)]
+        p: Vec<String>,
+
+        /// Message paragraphs
+        #[arg(short)]
+        m: Vec<String>,
+
+        /// Read the message from a file, - is stdin
+        #[arg(short = 'F')]
+        file: Vec<String>,
+    },
+    /// Apply the changes introduced by existing commits
+    CherryPick {
+        #[command(flatten)]
+        args: SequencerArgs,
+
+        /// Append a line that says which commit was cherry picked
+        #[arg(short = 'x')]
+        record_origin: bool,
+    },
+    /// Set the current folder / specified paths to a specific commit / tree
     Checkout {
         commit: String,
 
-        folder: Option<String>,
+        /// Pathspecs of the files to check out, defaults to the current folder
+        paths: Vec<String>,
     },
-    /// Convert an file into a blob object
+    /// Compute the object id of files and optionally store them
     HashObject {
-        path: String,
+        files: Vec<String>,
 
+        /// Write the objects into the object database
         #[arg(short, long)]
         write: bool,
 
-        #[arg(long, value_enum, name = "type")]
-        type_: Option<ObjectType>,
+        /// The type of the objects (blob, tree, commit or tag)
+        #[arg(short = 't', long = "type", default_value = "blob")]
+        object_type: String,
+
+        /// Read the object from stdin
+        #[arg(long, conflicts_with = "stdin_paths")]
+        stdin: bool,
+
+        /// Read the paths of the files from stdin, one per line
+        #[arg(long)]
+        stdin_paths: bool,
+
+        /// Skip the checks of the content and allow any type
+        #[arg(long)]
+        literally: bool,
+
+        /// Apply the filters as if the content was at this path
+        #[arg(long, conflicts_with = "no_filters")]
+        path: Option<String>,
+
+        /// Hash the content as it is without any filters
+        #[arg(long)]
+        no_filters: bool,
+    },
+    /// Print the lines matching a pattern in the working tree, the index or trees
+    #[command(disable_help_flag = true)]
+    Grep {
+        /// options, patterns combined with -e, --and, --or, --not and ( ), trees and paths
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+        args: Vec<String>,
     },
     /// Initialize a new git repository
     Init {
         path: Option<String>,
     },
+    /// Create a tar or zip archive of the files of a tree
+    Archive {
+        /// tar, tgz, tar.gz or zip, guessed from the output file name by default
+        #[arg(long)]
+        format: Option<String>,
+
+        /// Prepend this to every path in the archive
+        #[arg(long, default_value = "")]
+        prefix: String,
+
+        /// Write the archive to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// List the supported formats
+        #[arg(short, long)]
+        list: bool,
+
+        #[arg(required_unless_present = "list")]
+        tree_ish: Option<String>,
+
+        paths: Vec<String>,
+    },
+    /// Verify the objects of the repository and their connectivity
+    Fsck {
+        /// Report all unreachable objects instead of only the dangling ones
+        #[arg(long)]
+        unreachable: bool,
+
+        /// Don't report dangling objects
+        #[arg(long)]
+        no_dangling: bool,
+
+        /// Don't use the reflogs as starting points
+        #[arg(long)]
+        no_reflogs: bool,
+
+        /// Write dangling objects to .git/lost-found, implies --no-reflogs
+        #[arg(long)]
+        lost_found: bool,
+
+        /// Report root commits
+        #[arg(long)]
+        root: bool,
+
+        /// Report tagged objects
+        #[arg(long)]
+        tags: bool,
+
+        /// Start the connectivity check at these objects instead of the refs, reflogs and index
+        objects: Vec<String>,
+    },
+    /// Count the loose objects and the disk space they use
+    CountObjects {
+        /// Also show the packs, objects which can be pruned and garbage files
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// Show sizes in a human readable format
+        #[arg(short = 'H', long)]
+        human_readable: bool,
+    },
+    /// Remove unreachable loose objects
+    Prune {
+        /// Only show what would be removed
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Show the removed objects
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// Only remove loose objects older than this date (e.g. 2.weeks.ago)
+        #[arg(long)]
+        expire: Option<String>,
+
+        /// Keep the objects reachable from these too
+        heads: Vec<String>,
+    },
+    /// Remove untracked files from the working tree
+    Clean {
+        /// Only show what would be removed
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Required unless clean.requireForce is false, given twice nested repositories are removed too
+        #[arg(short, long, action = clap::ArgAction::Count)]
+        force: u8,
+
+        /// Remove untracked directories too
+        #[arg(short)]
+        d: bool,
+
+        /// Remove ignored files too
+        #[arg(short = 'x', conflicts_with = "only_ignored")]
+        x: bool,
+
+        /// Remove only ignored files
+        #[arg(short = 'X')]
+        only_ignored: bool,
+
+        /// Additional ignore patterns
+        #[arg(short, long = "exclude")]
+        e: Vec<String>,
+
+        /// Choose the files to remove interactively
+        #[arg(short, long)]
+        interactive: bool,
+
+        /// Don't print the names of removed files
+        #[arg(short, long)]
+        quiet: bool,
+
+        paths: Vec<String>,
+    },
+    /// Find the commit that introduced a bug by binary search
+    Bisect {
+        /// start, bad, good, skip, reset, log, replay, run or terms and their arguments
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Show which commit last changed each line of a file
+    Blame {
+        /// Only blame these line ranges: <start>,<end>, <start>,+<count> or /regex/
+        #[arg(short = 'L')]
+        ranges: Vec<String>,
+
+        /// Show the commit info only once per commit in a machine readable format
+        #[arg(short, long)]
+        porcelain: bool,
+
+        /// Show the commit info for every line in a machine readable format
+        #[arg(long)]
+        line_porcelain: bool,
+
+        /// Ignore whitespace when comparing lines
+        #[arg(short)]
+        w: bool,
+
+        /// Pass the changes of a commit on to its parent
+        #[arg(long)]
+        ignore_rev: Vec<String>,
+
+        /// Ignore the commits listed in a file
+        #[arg(long)]
+        ignore_revs_file: Vec<String>,
+
+        /// Detect lines moved inside the file
+        #[arg(short = 'M')]
+        moves: bool,
+
+        /// Detect lines copied from other files, repeat to search more files
+        #[arg(short = 'C', action = clap::ArgAction::Count)]
+        copies: u8,
+
+        /// Dont treat root commits as boundaries
+        #[arg(long)]
+        root: bool,
+
+        /// [<rev>] <file>, the working directory file is blamed without a revision
+        #[arg(required = true, num_args = 1..=2)]
+        args: Vec<String>,
+    },
+    /// Describe a commit by the closest tag reachable from it
+    Describe {
+        /// Use any ref instead of only tags
+        #[arg(long)]
+        all: bool,
+
+        /// Use lightweight tags too
+        #[arg(long)]
+        tags: bool,
+
+        /// Always show the distance and the abbreviated hash
+        #[arg(long)]
+        long: bool,
+
+        /// Length of the abbreviated hash, 0 only shows the tag
+        #[arg(long, default_value_t = 7, require_equals = true)]
+        abbrev: usize,
+
+        /// Append a mark if the working directory has changes
+        #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "-dirty")]
+        dirty: Option<String>,
+
+        /// Only use tags matching the glob
+        #[arg(long = "match")]
+        matches: Vec<String>,
+
+        /// Dont use tags matching the glob
+        #[arg(long)]
+        exclude: Vec<String>,
+
+        /// Find the first tag containing the commit
+        #[arg(long)]
+        contains: bool,
+
+        /// Show the abbreviated hash if no tag describes the commit
+        #[arg(long)]
+        always: bool,
+
+        /// Commits to describe, HEAD if none are given
+        commits: Vec<String>,
+    },
+    /// Name commits relative to refs
+    NameRev {
+        /// Only use tags
+        #[arg(long)]
+        tags: bool,
+
+        /// Only use refs matching the glob
+        #[arg(long)]
+        refs: Vec<String>,
+
+        /// Dont use refs matching the glob
+        #[arg(long)]
+        exclude: Vec<String>,
+
+        /// Only print the names
+        #[arg(long)]
+        name_only: bool,
+
+        /// Fail for commits which cant be named
+        #[arg(long)]
+        no_undefined: bool,
+
+        /// Show the abbreviated hash for commits which cant be named
+        #[arg(long)]
+        always: bool,
+
+        #[arg(required = true)]
+        commits: Vec<String>,
+    },
+    /// Summarize the commits per author
+    Shortlog {
+        /// Only show the number of commits per author
+        #[arg(short, long)]
+        summary: bool,
+
+        /// Sort by the number of commits instead of the name
+        #[arg(short, long)]
+        numbered: bool,
+
+        /// Show the email of the authors
+        #[arg(short, long)]
+        email: bool,
+
+        /// Group by committer instead of author
+        #[arg(short, long)]
+        committer: bool,
+
+        /// Group by author, committer or trailer:<key>
+        #[arg(long)]
+        group: Vec<String>,
+
+        /// Revisions to summarize, the log is read from stdin if none are given and stdin is not a terminal
+        revisions: Vec<String>,
+    },
     /// Show the commit history
     Log {
         #[arg(default_value = "HEAD")]
         commit: String,
+
+        /// Format of dates: default, iso, iso-strict, rfc, relative, short, unix, raw or format:<strftime>
+        #[arg(long, default_value = "default")]
+        date: String,
     },
     LsFiles,
+    /// List the contents of a tree object
     LsTree {
-        hash: String,
+        /// Recurse into sub trees
+        #[arg(short)]
+        r: bool,
+
+        /// Show trees when recursing
+        #[arg(short)]
+        t: bool,
+
+        /// Only show trees
+        #[arg(short)]
+        d: bool,
+
+        /// Show the size of blobs
+        #[arg(short, long)]
+        long: bool,
+
+        /// Terminate entries with NUL instead of newline
+        #[arg(short)]
+        z: bool,
+
+        /// Only show the paths
+        #[arg(long, alias = "name-status")]
+        name_only: bool,
+
+        /// Only show the object names
+        #[arg(long)]
+        object_only: bool,
+
+        /// Show paths relative to the root
+        #[arg(long)]
+        full_name: bool,
+
+        /// Ignore the current directory
+        #[arg(long)]
+        full_tree: bool,
+
+        /// Abbreviate the object names
+        #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "7")]
+        abbrev: Option<usize>,
+
+        /// Format of the lines, e.g. "%(objectmode) %(objecttype) %(objectname)%x09%(path)"
+        #[arg(long)]
+        format: Option<String>,
+
+        /// The tree, commit or tag to list
+        tree_ish: String,
+
+        /// Only show these paths
+        paths: Vec<String>,
+    },
+    /// Build a tree object from ls-tree formatted lines on stdin
+    Mktree {
+        /// Read NUL terminated lines
+        #[arg(short)]
+        z: bool,
+
+        /// Allow objects which are not in the repository
+        #[arg(long)]
+        missing: bool,
+
+        /// Build multiple trees separated by empty lines
+        #[arg(long)]
+        batch: bool,
+    },
+    /// Reapply commits on top of another base
+    Rebase {
+        /// Upstream branch to compare against
+        upstream: Option<String>,
+
+        /// Switch to this branch before rebasing
+        branch: Option<String>,
+
+        /// Starting point for the new commits, defaults to upstream
+        #[arg(long)]
+        onto: Option<String>,
+
+        /// Edit the list of commits with the sequence editor
+        #[arg(short, long)]
+        interactive: bool,
+
+        /// Move fixup! and squash! commits after their target
+        #[arg(long)]
+        autosquash: bool,
+
+        /// Run a command after each commit
+        #[arg(short = 'x', long)]
+        exec: Vec<String>,
+
+        #[arg(long = "continue")]
+        continue_: bool,
+
+        #[arg(long)]
+        skip: bool,
+
+        #[arg(long)]
+        abort: bool,
+    },
+    /// Move the current branch to another commit or unstage files
+    Reset {
+        /// Only move the branch, keep the index and working directory
+        #[arg(long, group = "mode")]
+        soft: bool,
+
+        /// Also reset the index (the default)
+        #[arg(long, group = "mode")]
+        mixed: bool,
+
+        /// Also reset the index and the working directory
+        #[arg(long, group = "mode")]
+        hard: bool,
+
+        /// The commit to reset to (defaults to HEAD) followed by paths
+        targets: Vec<String>,
+
+        /// Paths to reset in the index
+        #[arg(last = true)]
+        paths: Vec<String>,
+    },
+    /// Restore files in the working directory or the index
+    Restore {
+        /// Restore the index (from HEAD by default)
+        #[arg(short = 'S', long)]
+        staged: bool,
+
+        /// Restore the working directory (the default without --staged)
+        #[arg(short = 'W', long)]
+        worktree: bool,
+
+        /// Take the files from this commit or tree
+        #[arg(short, long)]
+        source: Option<String>,
+
+        #[arg(required = true)]
+        paths: Vec<String>,
     },
     RevParse,
+    /// Revert the changes introduced by existing commits
+    Revert {
+        #[command(flatten)]
+        args: SequencerArgs,
+    },
     Rm {
         files: Vec<String>,
     },
     ShowRef {
         reference: Option<String>,
     },
+    /// Stash the changes of the working directory away
+    #[command(args_conflicts_with_subcommands = true)]
+    Stash {
+        #[command(subcommand)]
+        command: Option<StashCommand>,
+
+        #[command(flatten)]
+        push: StashPushArgs,
+    },
     Status,
     Tag,
+    /// Write the index as a tree object
+    WriteTree {
+        /// Only write the tree of this folder
+        #[arg(long)]
+        prefix: Option<String>,
+
+        /// Dont check that the objects of the index exist
+        #[arg(long)]
+        missing_ok: bool,
+    },
+}
+
+/// exit code of errors that stop a command, like git
+const FATAL: i32 = 128;
+/// exit code of a command line that could not be understood
+const USAGE: i32 = 1;
+
+/// println! which ends the command like [fail] when the output can't be written
+macro_rules! outln {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        if let Err(e) = writeln!(std::io::stdout().lock(), $($arg)*) {
+            fail(e.into());
+        }
+    }};
+}
+
+/// eprintln! which ignores a stderr that can't be written to, like git does
+macro_rules! errln {
+    ($($arg:tt)*) => {{
+        let _ = writeln!(std::io::stderr().lock(), $($arg)*);
+    }};
+}
+
+/// print an error the way git does and stop with the fatal exit code
+fn fatal(message: impl std::fmt::Display) -> ! {
+    errln!("fatal: {}", message);
+    std::process::exit(FATAL);
+}
+
+/// print an error which doesn't need the fatal exit code and stop
+fn error(message: impl std::fmt::Display) -> ! {
+    errln!("error: {}", message);
+    std::process::exit(1);
+}
+
+/// a closed output pipe, like in `kyu log | head`, ends the command quietly, other errors are fatal
+fn fail(error: RepError) -> ! {
+    match error {
+        RepError::Output(e) if e.kind() == ErrorKind::BrokenPipe => std::process::exit(0),
+        e => fatal(e),
+    }
+}
+
+/// the missing identity is reported with a hint on how to set it
+fn identity_unknown() -> ! {
+    errln!("fatal: unable to auto-detect email address");
+    errln!("hint: set user.name and user.email with git config");
+    std::process::exit(FATAL);
+}
+
+/// the repository of the current directory, being outside of one is fatal
+fn load_repository() -> Repository {
+    Repository::load(None).unwrap_or_else(|e| fail(e))
 }
 
 fn main() {
-    let args = Args::parse();
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            let _ = e.print();
+            std::process::exit(if e.use_stderr() { USAGE } else { 0 });
+        }
+    };
 
     match args.command {
         Commands::Init { path } => init(path),
         Commands::CatFile {
-            object_type: _,
-            hash,
-        } => cat_file(&hash),
-        Commands::HashObject { path, write, type_ } => hash_object(path, write, type_),
-        Commands::Log { commit } => log(commit),
-        Commands::LsTree { hash } => cat_file(&hash),
-        Commands::Checkout { commit, folder } => checkout(commit, folder),
+            t,
+            s,
+            p,
+            e,
+            batch,
+            batch_check,
+            batch_all_objects,
+            args,
+        } => {
+            let query = match (t, s, p, e) {
+                (true, _, _, _) => Some(catfile::Query::Type),
+                (_, true, _, _) => Some(catfile::Query::Size),
+                (_, _, true, _) => Some(catfile::Query::Pretty),
+                (_, _, _, true) => Some(catfile::Query::Exists),
+                _ => None,
+            };
+            match (batch, batch_check) {
+                (Some(format), _) => cat_file_batch(format, true, batch_all_objects),
+                (_, Some(format)) => cat_file_batch(format, false, batch_all_objects),
+                _ => cat_file(query, args),
+            }
+        }
+        Commands::HashObject {
+            files,
+            write,
+            object_type,
+            stdin,
+            stdin_paths,
+            literally,
+            path,
+            no_filters,
+        } => {
+            let opts = hashobject::Options {
+                object_type,
+                write,
+                literally,
+                path,
+                no_filters,
+            };
+            hash_object(files, stdin, stdin_paths, opts)
+        }
+        Commands::Archive {
+            format,
+            prefix,
+            output,
+            list,
+            tree_ish,
+            paths,
+        } => match list {
+            true => archive::Format::NAMES.iter().for_each(|f| outln!("{}", f)),
+            false => archive(tree_ish.unwrap(), paths, format, prefix, output),
+        },
+        Commands::Fsck {
+            unreachable,
+            no_dangling,
+            no_reflogs,
+            lost_found,
+            root,
+            tags,
+            objects,
+        } => {
+            let opts = fsck::Options {
+                unreachable,
+                dangling: !no_dangling,
+                reflogs: !no_reflogs && !lost_found,
+                lost_found,
+                root,
+                tags,
+            };
+            fsck(objects, opts)
+        }
+        Commands::CountObjects {
+            verbose,
+            human_readable,
+        } => count_objects(verbose, human_readable),
+        Commands::Prune {
+            dry_run,
+            verbose,
+            expire,
+            heads,
+        } => prune(heads, dry_run, verbose, expire),
+        Commands::Clean {
+            dry_run,
+            force,
+            d,
+            x,
+            only_ignored,
+            e,
+            interactive,
+            quiet,
+            paths,
+        } => {
+            let ignored = match (x, only_ignored) {
+                (true, _) => clean::Ignored::Include,
+                (_, true) => clean::Ignored::Only,
+                _ => clean::Ignored::Respect,
+            };
+            let opts = clean::Options {
+                dry_run,
+                force,
+                directories: d,
+                ignored,
+                excludes: e,
+                interactive,
+                quiet,
+            };
+            clean(paths, opts)
+        }
+        Commands::Bisect { args } => bisect(args),
+        Commands::Grep { args } => grep(args),
+        Commands::Blame {
+            ranges,
+            porcelain,
+            line_porcelain,
+            w,
+            ignore_rev,
+            ignore_revs_file,
+            moves,
+            copies,
+            root,
+            args,
+        } => {
+            let opts = blame::Options {
+                ranges,
+                porcelain,
+                line_porcelain,
+                ignore_whitespace: w,
+                ignore_revs: ignore_rev,
+                ignore_revs_files: ignore_revs_file,
+                moves,
+                copies,
+                show_root: root,
+            };
+            blame(args, opts)
+        }
+        Commands::Describe {
+            all,
+            tags,
+            long,
+            abbrev,
+            dirty,
+            matches,
+            exclude,
+            contains,
+            always,
+            commits,
+        } => {
+            let opts = describe::Options {
+                all,
+                tags,
+                long,
+                abbrev,
+                dirty,
+                matches,
+                excludes: exclude,
+                contains,
+                always,
+            };
+            describe(commits, opts)
+        }
+        Commands::NameRev {
+            tags,
+            refs,
+            exclude,
+            name_only,
+            no_undefined,
+            always,
+            commits,
+        } => {
+            let opts = describe::NameOptions {
+                tags,
+                refs,
+                excludes: exclude,
+                name_only,
+                no_undefined,
+                always,
+            };
+            name_rev(commits, opts)
+        }
+        Commands::Shortlog {
+            summary,
+            numbered,
+            email,
+            committer,
+            group,
+            revisions,
+        } => shortlog(summary, numbered, email, committer, group, revisions),
+        Commands::Log { commit, date } => log(commit, date),
+        Commands::WriteTree { prefix, missing_ok } => write_tree(prefix, missing_ok),
+        Commands::CommitTree { tree, p, m, file } => commit_tree(tree, p, m, file),
+        Commands::Mktree { z, missing, batch } => mktree(mktree::Options {
+            null_terminated: z,
+            allow_missing: missing,
+            batch,
+        }),
+        Commands::LsTree {
+            r,
+            t,
+            d,
+            long,
+            z,
+            name_only,
+            object_only,
+            full_name,
+            full_tree,
+            abbrev,
+            format,
+            tree_ish,
+            paths,
+        } => {
+            let opts = lstree::Options {
+                recursive: r,
+                show_trees: t,
+                only_trees: d,
+                long,
+                name_only,
+                object_only,
+                null_terminated: z,
+                full_name,
+                full_tree,
+                abbrev,
+                format,
+            };
+            ls_tree(&tree_ish, &paths, opts)
+        }
+        Commands::Checkout { commit, paths } => checkout(commit, paths),
         Commands::ShowRef { reference } => show_ref(reference),
-        _ => println!("Not implemented yet"),
+        Commands::CheckMailmap { contacts, stdin } => check_mailmap(contacts, stdin),
+        Commands::Add { files } => add(files),
+        Commands::Stash { command, push } => stash(command.unwrap_or(StashCommand::Push(push))),
+        Commands::CherryPick {
+            args,
+            record_origin,
+        } => sequence(Action::Pick, args, record_origin),
+        Commands::Reset {
+            soft,
+            mixed: _,
+            hard,
+            targets,
+            paths,
+        } => {
+            let mode = match (soft, hard) {
+                (true, _) => reset::Mode::Soft,
+                (_, true) => reset::Mode::Hard,
+                _ => reset::Mode::Mixed,
+            };
+            reset(mode, targets, paths)
+        }
+        Commands::Restore {
+            staged,
+            worktree,
+            source,
+            paths,
+        } => restore(source, staged, worktree || !staged, paths),
+        Commands::Revert { args } => sequence(Action::Revert, args, false),
+        Commands::Rebase {
+            upstream,
+            branch,
+            onto,
+            interactive,
+            autosquash,
+            exec,
+            continue_,
+            skip,
+            abort,
+        } => {
+            let opts = rebase::Options {
+                onto,
+                interactive,
+                autosquash,
+                exec,
+            };
+            rebase(upstream, branch, opts, continue_, skip, abort)
+        }
+        _ => outln!("Not implemented yet"),
     }
 }
 
 fn init(path: Option<String>) {
-    let rep = repository::Repository::init(path);
-
-    if rep.is_err() {
-        println!("Failed to initialize repository: {:?}", rep.err().unwrap());
-        return;
-    }
-
-    let rep = rep.ok().unwrap();
+    let rep = repository::Repository::init(path).unwrap_or_else(|e| fail(e));
 
-    println!(
+    outln!(
         "Initialized new repository in the directory: {}",
         rep.get_workdir()
     );
 }
 
-fn cat_file(hash: &str) {
-    let rep = Repository::load(None).unwrap();
-    // TODO: Implement loading with short hashes etc.
-    let obj = Object::load(&rep, hash);
+fn cat_file(query: Option<catfile::Query>, args: Vec<String>) {
+    let rep = load_repository();
 
-    println!("{}", obj.cat());
+    let (query, name) = match (query, args.as_slice()) {
+        (Some(query), [name]) => (query, name),
+        (None, [object_type, name]) => match object_type.as_str() {
+            "blob" | "commit" | "tree" | "tag" => {
+                (catfile::Query::Typed(object_type.clone()), name)
+            }
+            _ => fatal(format!("invalid object type \"{}\"", object_type)),
+        },
+        _ => {
+            errln!("usage: kyu cat-file (-t | -s | -e | -p | <type>) <object>");
+            std::process::exit(USAGE);
+        }
+    };
+
+    match catfile::cat_file(&rep, query, name) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(RepError::InvalidObjectType(_)) => fatal(format!("git cat-file {}: bad file", name)),
+        Err(RepError::InvalidReference(_)) | Err(RepError::NoCommitsInBranch(_)) => {
+            fatal(format!("Not a valid object name {}", name))
+        }
+        Err(e) => fail(e),
+    }
 }
 
-fn hash_object(path: String, write: bool, object_type: Option<ObjectType>) {
-    if object_type.is_some() && object_type.unwrap() != ObjectType::Blob {
-        unimplemented!("Only blobs can be hashed for now")
+fn cat_file_batch(format: String, contents: bool, all_objects: bool) {
+    let rep = load_repository();
+    let format = Some(format).filter(|f| !f.is_empty());
+
+    catfile::batch(&rep, format.as_deref(), contents, all_objects).unwrap_or_else(|e| fail(e));
+}
+
+fn ls_tree(tree_ish: &str, paths: &[String], opts: lstree::Options) {
+    let rep = load_repository();
+
+    match lstree::ls_tree(&rep, tree_ish, paths, opts) {
+        Ok(()) => {}
+        Err(RepError::NoCommitsInBranch(_)) | Err(RepError::InvalidReference(_)) => {
+            fatal(format!("Not a valid object name {}", tree_ish))
+        }
+        Err(e) => fail(e),
     }
+}
 
-    let rep = Repository::load(None).unwrap();
-    let obj = Blob::from_file(&path);
+fn hash_object(files: Vec<String>, stdin: bool, stdin_paths: bool, opts: hashobject::Options) {
+    let rep = Repository::load(None).ok();
+    match hashobject::hash_object(rep.as_ref(), &files, stdin, stdin_paths, &opts) {
+        Ok(()) => {}
+        Err(RepError::InvalidObjectType(object_type)) => {
+            fatal(format!("invalid object type \"{}\"", object_type))
+        }
+        Err(RepError::InvalidArgument(message)) => fatal(message),
+        Err(e @ RepError::FailsFsck(_)) => {
+            errln!("error: {}", e);
+            fatal("refusing to create malformed object")
+        }
+        Err(e) => fail(e),
+    }
+}
+
+fn write_tree(prefix: Option<String>, missing_ok: bool) {
+    let rep = load_repository();
+    match writetree::write_tree(&rep, prefix.as_deref(), missing_ok) {
+        Ok(tree) => outln!("{}", tree),
+        Err(RepError::InvalidArgument(message)) => fatal(message),
+        Err(e) => fail(e),
+    }
+}
 
-    if write {
-        obj.save(&rep);
+fn commit_tree(tree: String, parents: Vec<String>, messages: Vec<String>, files: Vec<String>) {
+    let rep = load_repository();
+    match committree::commit_tree(&rep, &tree, &parents, &messages, &files) {
+        Ok(commit) => outln!("{}", commit),
+        Err(RepError::InvalidReference(name)) => fatal(format!("not a valid object name {}", name)),
+        Err(RepError::InvalidArgument(message)) => fatal(message),
+        Err(RepError::InvalidDate(date)) => fatal(format!("invalid date format: {}", date)),
+        Err(RepError::IdentityUnknown) => identity_unknown(),
+        Err(e) => fail(e),
     }
+}
 
-    println!("{}", obj.hash());
+fn mktree(opts: mktree::Options) {
+    let rep = load_repository();
+    match mktree::mktree(&rep, &opts) {
+        Ok(()) => {}
+        Err(RepError::InvalidArgument(message)) => fatal(message),
+        Err(e) => fail(e),
+    }
 }
 
-fn log(commit: String) {
-    let rep = Repository::load(None).unwrap();
-    let hash = rep.ref_resolve(&commit);
+fn check_mailmap(contacts: Vec<String>, stdin: bool) {
+    let rep = load_repository();
+    let mailmap = mailmap::Mailmap::load(&rep);
 
-    match hash {
-        Ok(hash) => {
-            let commit = Object::load(&rep, &hash);
-            let commit = match commit {
-                Object::Commit(c) => c,
-                _ => panic!("head should be a commit object"),
-            };
+    let check = |contact: &str| match mailmap.check(contact) {
+        Ok(contact) => outln!("{}", contact),
+        Err(RepError::InvalidArgument(message)) => fatal(message),
+        Err(e) => fail(e),
+    };
 
-            logscreen::display_log(commit, rep);
+    contacts.iter().for_each(|contact| check(contact));
+    if stdin {
+        for line in std::io::stdin().lines().map_while(Result::ok) {
+            check(&line);
         }
-        Err(RepError::NoCommitsInBranch(branch)) => {
-            println!(
-                "fatal: your current branch '{}' does not have any commits yet",
-                branch
-            );
+    }
+}
+
+fn blame(args: Vec<String>, opts: blame::Options) {
+    let rep = load_repository();
+    let (rev, file) = match args.as_slice() {
+        [file] => (None, file),
+        [rev, file] => (Some(rev.as_str()), file),
+        _ => unreachable!("clap only allows one or two arguments"),
+    };
+
+    match blame::blame(&rep, rev, file, opts) {
+        Ok(()) => {}
+        Err(RepError::NoSuchPath(path, rev)) => fatal(format!("no such path {} in {}", path, rev)),
+        Err(RepError::InvalidRange(range)) => fatal(format!("invalid -L range: {}", range)),
+        Err(RepError::InvalidReference(rev)) => {
+            fatal(format!("ambiguous argument '{}': unknown revision", rev))
         }
-        Err(e) => {
-            println!("fatal: {:?}", e);
+        Err(e) => fail(e),
+    }
+}
+
+fn archive(
+    tree_ish: String,
+    paths: Vec<String>,
+    format: Option<String>,
+    prefix: String,
+    output: Option<String>,
+) {
+    let rep = load_repository();
+    let format = match (&format, &output) {
+        (Some(name), _) => match archive::Format::parse(name) {
+            Some(format) => format,
+            None => fatal(format!("Unknown archive format '{}'", name)),
+        },
+        (None, Some(file)) => archive::Format::from_filename(file).unwrap_or_default(),
+        (None, None) => archive::Format::Tar,
+    };
+    let opts = archive::Options { format, prefix };
+
+    // the archive is streamed to the file or stdout
+    let mut out: Box<dyn std::io::Write> = match output {
+        Some(file) => match std::fs::File::create(&file) {
+            Ok(f) => Box::new(std::io::BufWriter::new(f)),
+            Err(e) => fatal(format!("could not create archive file '{}': {}", file, e)),
+        },
+        None => Box::new(std::io::BufWriter::new(std::io::stdout().lock())),
+    };
+    match archive::archive(&rep, &tree_ish, &paths, &opts, &mut out) {
+        Ok(()) => {}
+        Err(RepError::InvalidReference(_)) => fatal(format!("not a tree object: {}", tree_ish)),
+        Err(RepError::InvalidPathspec(spec)) => {
+            fatal(format!("pathspec '{}' did not match any files", spec))
         }
+        Err(RepError::InvalidArgument(message)) => fatal(message),
+        Err(e) => fail(e),
+    }
+}
+
+fn bisect(args: Vec<String>) {
+    let rep = load_repository();
+    match bisect::bisect(&rep, &args) {
+        Ok(()) => {}
+        Err(RepError::InvalidArgument(message)) => error(message),
+        Err(RepError::InvalidReference(rev)) => fatal(format!("Not a valid object name {}", rev)),
+        Err(e) => fail(e),
+    }
+}
+
+fn fsck(objects: Vec<String>, opts: fsck::Options) {
+    let rep = load_repository();
+    match fsck::fsck(&rep, &objects, &opts) {
+        Ok(0) => {}
+        Ok(errors) => std::process::exit(errors as i32),
+        Err(RepError::InvalidReference(name)) => fatal(format!("invalid object name '{}'", name)),
+        Err(e) => fail(e),
+    }
+}
+
+fn count_objects(verbose: bool, human: bool) {
+    let rep = load_repository();
+    prune::count_objects(&rep, verbose, human).unwrap_or_else(|e| fail(e));
+}
+
+fn prune(heads: Vec<String>, dry_run: bool, verbose: bool, expire: Option<String>) {
+    let rep = load_repository();
+    let now = chrono::Utc::now().timestamp();
+    let expire = match expire.map(|e| (signature::parse_expiry(&e, now), e)) {
+        Some((Some(expire), _)) => Some(expire),
+        Some((None, e)) => fatal(format!("malformed expiration date '{}'", e)),
+        None => None,
+    };
+
+    let opts = prune::Options {
+        dry_run,
+        verbose,
+        expire,
+    };
+    match prune::prune(&rep, &heads, &opts) {
+        Ok(()) => {}
+        Err(RepError::InvalidReference(name)) => fatal(format!("unrecognized argument: {}", name)),
+        Err(RepError::BadObject(object_type, hash)) if object_type == "object" => {
+            fatal(format!("bad object {}", hash))
+        }
+        Err(RepError::BadObject(object_type, hash)) => {
+            fatal(format!("bad {} object {}", object_type, hash))
+        }
+        Err(e) => fail(e),
+    }
+}
+
+fn clean(paths: Vec<String>, opts: clean::Options) {
+    let rep = load_repository();
+    match clean::clean(&rep, &paths, &opts) {
+        Ok(()) => {}
+        Err(RepError::InvalidArgument(message)) => fatal(message),
+        Err(RepError::InvalidPathspec(spec)) => fatal(format!("invalid pathspec '{}'", spec)),
+        Err(e) => fail(e),
+    }
+}
+
+fn grep(args: Vec<String>) {
+    let rep = load_repository();
+    let (opts, rest) = grep::parse_args(&args).unwrap_or_else(|e| {
+        let _ = e.print();
+        std::process::exit(USAGE);
+    });
+    match grep::grep(&rep, &opts, &rest) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(RepError::InvalidArgument(message)) => fatal(message),
+        Err(RepError::InvalidPathspec(spec)) => fatal(format!("invalid pathspec '{}'", spec)),
+        Err(e) => fail(e),
+    }
+}
+
+fn describe(commits: Vec<String>, opts: describe::Options) {
+    let rep = load_repository();
+    print_describe_error(describe::describe(&rep, &commits, opts));
+}
+
+fn name_rev(commits: Vec<String>, opts: describe::NameOptions) {
+    let rep = load_repository();
+    print_describe_error(describe::name_rev(&rep, &commits, opts));
+}
+
+fn print_describe_error(result: Result<(), RepError>) {
+    match result {
+        Ok(()) => {}
+        Err(RepError::NoNames(message)) | Err(RepError::InvalidArgument(message)) => fatal(message),
+        Err(RepError::InvalidReference(rev)) => fatal(format!("Not a valid object name {}", rev)),
+        Err(e) => fail(e),
+    }
+}
+
+fn shortlog(
+    summary: bool,
+    numbered: bool,
+    email: bool,
+    committer: bool,
+    group: Vec<String>,
+    revisions: Vec<String>,
+) {
+    let mut groups = vec![];
+    for name in group.iter() {
+        match shortlog::Group::parse(name) {
+            Some(g) => groups.push(g),
+            None => fatal(format!("unknown group type: {}", name)),
+        }
+    }
+    if committer {
+        groups.push(shortlog::Group::Committer);
+    }
+    let opts = shortlog::Options {
+        summary,
+        numbered,
+        email,
+        groups,
+    };
+
+    let rep = Repository::load(None).ok();
+    if revisions.is_empty() && (rep.is_none() || !std::io::stdin().is_terminal()) {
+        shortlog::shortlog_stdin(rep.as_ref(), opts).unwrap_or_else(|e| fail(e));
+        return;
+    }
+
+    let rep = match rep {
+        Some(rep) => rep,
+        None => fatal("not a git repository (or any of the parent directories): .git"),
+    };
+    match shortlog::shortlog(&rep, &revisions, opts) {
+        Ok(()) => {}
+        Err(RepError::InvalidReference(rev)) => {
+            fatal(format!("ambiguous argument '{}': unknown revision", rev))
+        }
+        Err(e) => fail(e),
+    }
+}
+
+fn log(commit: String, date: String) {
+    let date_format = match signature::DateFormat::parse(&date) {
+        Some(format) => format,
+        None => fatal(format!("unknown date format {}", date)),
+    };
+    let rep = load_repository();
+    let hash = rep.ref_resolve(&commit).unwrap_or_else(|e| fail(e));
+
+    let commit = match Object::load(&rep, &hash) {
+        Ok(Object::Commit(c)) => c,
+        Ok(_) => fatal(RepError::BadObject("commit".to_string(), hash.to_string())),
+        Err(e) => fail(e),
+    };
+    if let Err(e) = logscreen::display_log(commit, rep, &date_format) {
+        fail(e);
     }
 }
 
 fn show_ref(reference: Option<String>) {
-    let rep = Repository::load(None).unwrap();
+    let rep = load_repository();
 
     if reference.is_none() {
         let refs = rep.get_refs();
         for (refname, hash) in refs {
-            println!("{}\t{}", hash, refname);
+            outln!("{}\t{}", hash, refname);
         }
         return;
     }
@@ -177,43 +1360,186 @@ fn show_ref(reference: Option<String>) {
 
     let r = match r {
         Ok(r) => r,
-        Err(e) => match e {
-            _ => format!("fatal: {:?}", e),
-        },
+        Err(e) => fail(e),
     };
 
-    println!("{}\t{}", r, reference);
+    outln!("{}\t{}", r, reference);
 }
 
-fn checkout(commit_or_ref: String, folder: Option<String>) {
-    let pwd = std::env::current_dir().unwrap();
-    let path_to_checkout = pwd.to_string_lossy();
-    let mut path_to_checkout =
-        path_to_checkout.to_string() + "/" + &folder.unwrap_or("".to_string());
+fn checkout(commit_or_ref: String, paths: Vec<String>) {
+    let rep = load_repository();
+    match checkout::checkout(&rep, &commit_or_ref, &paths) {
+        Ok(()) => {}
+        Err(RepError::InvalidPathspec(spec)) => {
+            fatal(format!("Invalid pathspec magic in '{}'", spec))
+        }
+        Err(RepError::InvalidReference(name)) => fatal(format!("invalid reference: {}", name)),
+        Err(RepError::InvalidArgument(message)) => error(message),
+        Err(e) => fail(e),
+    }
+}
 
-    if std::path::Path::new(&path_to_checkout).is_dir() && !path_to_checkout.ends_with("/") {
-        path_to_checkout.push_str("/");
+fn add(files: Vec<String>) {
+    let rep = load_repository();
+    match add::add(&rep, &files) {
+        Ok(()) => {}
+        Err(RepError::InvalidPathspec(file)) => {
+            fatal(format!("pathspec '{}' did not match any files", file))
+        }
+        Err(e) => fail(e),
     }
+}
 
-    // if files_or_folders is None, checkout the whole commit
+fn sequence(action: Action, args: SequencerArgs, record_origin: bool) {
+    let rep = load_repository();
 
-    let rep = Repository::load(None).unwrap();
-    let commit = rep
-        .ref_resolve(&commit_or_ref)
-        .expect("Invalid Reference / Commit");
-    let commit = Object::load(&rep, &commit);
+    let result = if args.continue_ {
+        sequencer::continue_(&rep)
+    } else if args.skip {
+        sequencer::skip(&rep)
+    } else if args.abort {
+        sequencer::abort(&rep)
+    } else if args.commits.is_empty() {
+        fatal("no commits given");
+    } else {
+        let opts = sequencer::Options {
+            record_origin,
+            no_commit: args.no_commit,
+        };
+        sequencer::start(&rep, action, &args.commits, opts)
+    };
 
-    let tree = match commit {
-        Object::Commit(c) => {
-            let t = Object::load(&rep, &c.get_tree());
-            match t {
-                Object::Tree(t) => t,
-                _ => panic!("head should be a commit object"),
-            }
+    match result {
+        Ok(()) => {}
+        Err(RepError::IdentityUnknown) => identity_unknown(),
+        Err(RepError::InvalidArgument(message)) => {
+            errln!("error: {}", message);
+            fatal(format!("{} failed", action.command()))
+        }
+        Err(e) => fail(e),
+    }
+}
+
+fn rebase(
+    upstream: Option<String>,
+    branch: Option<String>,
+    opts: rebase::Options,
+    continue_: bool,
+    skip: bool,
+    abort: bool,
+) {
+    let rep = load_repository();
+
+    let result = if continue_ {
+        rebase::continue_(&rep)
+    } else if skip {
+        rebase::skip(&rep)
+    } else if abort {
+        rebase::abort(&rep)
+    } else {
+        match upstream {
+            Some(upstream) => rebase::start(&rep, &upstream, branch, opts),
+            None => fatal("no upstream given"),
+        }
+    };
+
+    match result {
+        Ok(()) => {}
+        Err(RepError::IdentityUnknown) => identity_unknown(),
+        Err(e) => fail(e),
+    }
+}
+
+fn stash(command: StashCommand) {
+    let rep = load_repository();
+    // a stash which couldn't be applied cleanly fails the command
+    let applied = |clean: bool| {
+        if !clean {
+            std::process::exit(1);
         }
-        Object::Tree(t) => t,
-        _ => panic!("head should be a commit object"),
     };
 
-    tree.checkout(rep.get_workdir().to_owned(), path_to_checkout.to_string());
+    let result = match command {
+        StashCommand::Push(args) => stash::push(
+            &rep,
+            stash::PushOptions {
+                include_untracked: args.include_untracked,
+                all: args.all,
+                keep_index: args.keep_index,
+                message: args.message,
+            },
+        ),
+        StashCommand::List => stash::list(&rep),
+        StashCommand::Show { patch, stash } => stash::show(&rep, stash, patch),
+        StashCommand::Apply { index, stash } => stash::apply(&rep, stash, index).map(applied),
+        StashCommand::Pop { index, stash } => stash::pop(&rep, stash, index).map(applied),
+        StashCommand::Drop { stash } => stash::drop(&rep, stash),
+        StashCommand::Branch { name, stash } => stash::branch(&rep, &name, stash).map(applied),
+        StashCommand::Clear => {
+            stash::clear(&rep);
+            Ok(())
+        }
+    };
+
+    match result {
+        Ok(()) => {}
+        Err(RepError::NoCommitsInBranch(_)) => {
+            outln!("You do not have the initial commit yet");
+        }
+        Err(RepError::IdentityUnknown) => identity_unknown(),
+        Err(RepError::InvalidArgument(message)) => error(message),
+        Err(e) => fail(e),
+    }
+}
+
+fn reset(mode: reset::Mode, mut targets: Vec<String>, mut paths: Vec<String>) {
+    let rep = load_repository();
+
+    // the first argument is the commit unless it is a known file, everything else are paths
+    let is_file = |path: &str| {
+        let index = Index::load(&rep).unwrap_or_else(|e| fail(e));
+        let path = rep.relative_path(path);
+        std::path::Path::new(&(rep.get_workdir().clone() + "/" + &path)).exists()
+            || index.get_entries().iter().any(|e| e.get_name() == path)
+    };
+    let rev = match targets.first() {
+        Some(first) if rep.ref_resolve(first).is_ok() || !is_file(first) => targets.remove(0),
+        _ => "HEAD".to_string(),
+    };
+    targets.append(&mut paths);
+
+    let result = match targets.is_empty() {
+        true => reset::reset(&rep, &rev, mode),
+        false => Pathspec::parse(&rep, &targets)
+            .and_then(|pathspec| reset::reset_paths(&rep, &rev, mode, &pathspec)),
+    };
+
+    match result {
+        Ok(()) => {}
+        Err(RepError::NoCommitsInBranch(_)) | Err(RepError::InvalidReference(_)) => {
+            fatal(format!("ambiguous argument '{}': unknown revision", rev))
+        }
+        Err(RepError::InvalidPathspec(spec)) => {
+            fatal(format!("Invalid pathspec magic in '{}'", spec))
+        }
+        Err(e) => fail(e),
+    }
+}
+
+fn restore(source: Option<String>, staged: bool, worktree: bool, paths: Vec<String>) {
+    let rep = load_repository();
+    let result = Pathspec::parse(&rep, &paths)
+        .and_then(|pathspec| reset::restore(&rep, source.as_deref(), staged, worktree, &pathspec));
+
+    match result {
+        Ok(()) => {}
+        Err(RepError::InvalidPathspec(spec)) => {
+            fatal(format!("Invalid pathspec magic in '{}'", spec))
+        }
+        Err(RepError::NoCommitsInBranch(_)) | Err(RepError::InvalidReference(_)) => fatal(format!(
+            "could not resolve {}",
+            source.unwrap_or("HEAD".to_string())
+        )),
+        Err(e) => fail(e),
+    }
 }
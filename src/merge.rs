@@ -0,0 +1,294 @@
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::diff::{matches, split_lines};
+use crate::index::{Index, IndexEntry};
+use crate::objects::{Blob, Object};
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+
+/// the result of a three way merge of trees
+pub struct MergeResult {
+    /// the merged index, conflicting paths have stage 1-3 entries
+    pub index: Index,
+    /// the content with conflict markers that should be written to the working directory
+    pub conflicts: BTreeMap<String, Vec<u8>>,
+}
+
+/// names shown in the conflict markers
+pub struct MergeLabels<'a> {
+    pub base: &'a str,
+    pub ours: &'a str,
+    pub theirs: &'a str,
+}
+
+/// the result of merging a single file
+pub enum FileMerge {
+    Clean(Vec<u8>),
+    Conflict(Vec<u8>),
+}
+
+/// path -> (mode, sha) of every file in a tree, None is the empty tree
+pub fn tree_files(
+    rep: &Repository,
+    tree: Option<&ObjectId>,
+) -> Result<BTreeMap<String, (String, ObjectId)>, RepError> {
+    let tree = match tree {
+        Some(tree) => tree,
+        None => return Ok(BTreeMap::new()),
+    };
+
+    match Object::load(rep, tree)? {
+        Object::Tree(t) => Ok(t
+            .flatten(rep, "")?
+            .into_iter()
+            .map(|(path, mode, sha)| (path, (mode, sha)))
+            .collect()),
+        _ => Err(RepError::BadObject("tree".to_string(), tree.to_string())),
+    }
+}
+
+/// the numeric mode of a tree entry, an error for a broken tree
+pub fn parse_mode(mode: &str, sha: &ObjectId) -> Result<u32, RepError> {
+    u32::from_str_radix(mode, 8).map_err(|_| RepError::BadObjectHeader(sha.to_string()))
+}
+
+/// merge the changes from base to theirs into ours
+pub fn merge_trees(
+    rep: &Repository,
+    base: Option<&ObjectId>,
+    ours: &ObjectId,
+    theirs: &ObjectId,
+    labels: &MergeLabels,
+) -> Result<MergeResult, RepError> {
+    let base = tree_files(rep, base)?;
+    let ours = tree_files(rep, Some(ours))?;
+    let theirs = tree_files(rep, Some(theirs))?;
+
+    let paths = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect::<BTreeSet<&String>>();
+
+    let mut index = Index::new();
+    let mut conflicts = BTreeMap::new();
+
+    for path in paths {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+
+        let stage = |index: &mut Index, entry: Option<&(String, ObjectId)>, stage: u8| {
+            if let Some((mode, sha)) = entry {
+                index.add(IndexEntry::new(path, parse_mode(mode, sha)?, sha, stage));
+            }
+            Ok::<(), RepError>(())
+        };
+
+        // one side didnt change anything (or both did the same), take the other one
+        if o == t || b == t {
+            stage(&mut index, o, 0)?;
+            continue;
+        }
+        if b == o {
+            stage(&mut index, t, 0)?;
+            continue;
+        }
+
+        // both sides changed the file in a different way
+        if let (Some((o_mode, o_sha)), Some((t_mode, t_sha))) = (o, t) {
+            let mode = match b {
+                Some((b_mode, _)) if b_mode == o_mode => t_mode,
+                _ => o_mode,
+            };
+
+            if o_sha == t_sha {
+                stage(&mut index, Some(&(mode.clone(), *o_sha)), 0)?;
+                continue;
+            }
+
+            let read = |sha: &ObjectId| -> Result<Vec<u8>, RepError> {
+                Ok(Object::load(rep, sha)?.get_data().clone())
+            };
+
+            if mode != "160000" {
+                let base_data = match b {
+                    Some((_, sha)) => read(sha)?,
+                    None => vec![],
+                };
+                match merge_file(&base_data, &read(o_sha)?, &read(t_sha)?, labels) {
+                    FileMerge::Clean(data) => {
+                        let sha = Blob::from_data(data).save(rep)?;
+                        stage(&mut index, Some(&(mode.clone(), sha)), 0)?;
+                        continue;
+                    }
+                    FileMerge::Conflict(data) => {
+                        conflicts.insert(path.clone(), data);
+                    }
+                }
+            }
+        } else if let Some((_, sha)) = o.or(t) {
+            // modified on one side and deleted on the other, the modified version stays in the workdir
+            conflicts.insert(path.clone(), Object::load(rep, sha)?.get_data().clone());
+        }
+
+        stage(&mut index, b, 1)?;
+        stage(&mut index, o, 2)?;
+        stage(&mut index, t, 3)?;
+    }
+
+    Ok(MergeResult { index, conflicts })
+}
+
+/// merge the content of a file with the diff3 algorithm
+pub fn merge_file(base: &[u8], ours: &[u8], theirs: &[u8], labels: &MergeLabels) -> FileMerge {
+    let base = split_lines(base);
+    let ours = split_lines(ours);
+    let theirs = split_lines(theirs);
+
+    let match_ours = matches(&base, &ours);
+    let match_theirs = matches(&base, &theirs);
+
+    let mut result: Vec<u8> = vec![];
+    let mut conflict = false;
+    let (mut i, mut j, mut k) = (0, 0, 0);
+
+    loop {
+        // lines which are unchanged on both sides
+        let mut n = 0;
+        while i + n < base.len()
+            && match_ours[i + n] == Some(j + n)
+            && match_theirs[i + n] == Some(k + n)
+        {
+            result.extend_from_slice(base[i + n]);
+            n += 1;
+        }
+        i += n;
+        j += n;
+        k += n;
+
+        if i == base.len() && j == ours.len() && k == theirs.len() {
+            break;
+        }
+
+        // the next base line which is kept on both sides ends the changed chunk
+        let (i2, j2, k2) = (i..base.len())
+            .find_map(|x| match (match_ours[x], match_theirs[x]) {
+                (Some(y), Some(z)) => Some((x, y, z)),
+                _ => None,
+            })
+            .unwrap_or((base.len(), ours.len(), theirs.len()));
+
+        let (b, o, t) = (&base[i..i2], &ours[j..j2], &theirs[k..k2]);
+
+        if o == b {
+            t.iter().for_each(|l| result.extend_from_slice(l));
+        } else if t == b || o == t {
+            o.iter().for_each(|l| result.extend_from_slice(l));
+        } else {
+            conflict = true;
+            let push_lines = |result: &mut Vec<u8>, lines: &[&[u8]]| {
+                for l in lines {
+                    result.extend_from_slice(l);
+                    if !l.ends_with(b"\n") {
+                        result.push(b'\n');
+                    }
+                }
+            };
+            result.extend_from_slice(format!("<<<<<<< {}\n", labels.ours).as_bytes());
+            push_lines(&mut result, o);
+            result.extend_from_slice(b"=======\n");
+            push_lines(&mut result, t);
+            result.extend_from_slice(format!(">>>>>>> {}\n", labels.theirs).as_bytes());
+        }
+
+        i = i2;
+        j = j2;
+        k = k2;
+    }
+
+    if conflict {
+        FileMerge::Conflict(result)
+    } else {
+        FileMerge::Clean(result)
+    }
+}
+
+/// write the merged files into the working directory and return the index to save
+/// from is the index which currently represents the working directory
+pub fn apply_merge(rep: &Repository, from: &Index, result: MergeResult) -> Result<Index, RepError> {
+    let mut index = result.index;
+    crate::index::update_workdir(rep, from, &mut index)?;
+
+    for (path, data) in result.conflicts {
+        let file = rep.get_workdir().clone() + "/" + &path;
+        if let Some(parent) = std::path::Path::new(&file).parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        std::fs::write(&file, data).map_err(|e| RepError::Io(file.clone(), e))?;
+    }
+
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LABELS: MergeLabels = MergeLabels {
+        base: "base",
+        ours: "HEAD",
+        theirs: "topic",
+    };
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> (bool, String) {
+        match merge_file(base.as_bytes(), ours.as_bytes(), theirs.as_bytes(), &LABELS) {
+            FileMerge::Clean(data) => (true, String::from_utf8(data).unwrap()),
+            FileMerge::Conflict(data) => (false, String::from_utf8(data).unwrap()),
+        }
+    }
+
+    #[test]
+    fn changes_in_different_places_are_combined() {
+        let base = "a\nb\nc\nd\ne\n";
+        assert_eq!(
+            merge(base, "A\nb\nc\nd\ne\n", "a\nb\nc\nd\nE\n"),
+            (true, "A\nb\nc\nd\nE\n".to_string())
+        );
+        assert_eq!(
+            merge(base, "a\nb\nc\nd\ne\nf\n", "b\nc\nd\ne\n"),
+            (true, "b\nc\nd\ne\nf\n".to_string())
+        );
+    }
+
+    #[test]
+    fn the_same_change_on_both_sides_is_clean() {
+        assert_eq!(
+            merge("a\nb\nc\n", "a\nB\nc\n", "a\nB\nc\n"),
+            (true, "a\nB\nc\n".to_string())
+        );
+        assert_eq!(merge("a\n", "a\n", "a\n"), (true, "a\n".to_string()));
+    }
+
+    #[test]
+    fn overlapping_changes_conflict() {
+        assert_eq!(
+            merge("a\nb\nc\n", "a\nours\nc\n", "a\ntheirs\nc\n"),
+            (
+                false,
+                "a\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> topic\nc\n".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn conflict_markers_start_on_their_own_line() {
+        assert_eq!(
+            merge("a", "b", "c"),
+            (
+                false,
+                "<<<<<<< HEAD\nb\n=======\nc\n>>>>>>> topic\n".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,118 @@
+#![allow(dead_code)]
+
+use std::io::Read;
+
+use crate::objects::{Object, Tree};
+use crate::oid::ObjectId;
+use crate::pathspec::unquote_path;
+use crate::repository::{RepError, Repository};
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// the entries are terminated with NUL and paths aren't quoted (-z)
+    pub null_terminated: bool,
+    /// don't check that the objects exist (--missing)
+    pub allow_missing: bool,
+    /// an empty line ends a tree and starts the next one (--batch)
+    pub batch: bool,
+}
+
+/// parse a line in the format of ls-tree, "<mode> SP <type> SP <hash> TAB <path>"
+fn parse_entry(
+    rep: &Repository,
+    line: &str,
+    opts: &Options,
+) -> Result<(String, String, ObjectId), RepError> {
+    let error = |message: String| RepError::InvalidArgument(message);
+    let format_error = || error(format!("input format error: {}", line));
+
+    let (info, path) = line.split_once('\t').ok_or_else(format_error)?;
+    let path = match opts.null_terminated {
+        true => path.to_string(),
+        false => unquote_path(path),
+    };
+    if path.contains('/') {
+        return Err(error(format!("path {} contains slash", path)));
+    }
+
+    let parts = info.split(' ').collect::<Vec<&str>>();
+    let (mode, object_type, hash) = match parts.as_slice() {
+        [mode, object_type, hash] => (mode.trim_start_matches('0'), *object_type, *hash),
+        _ => return Err(format_error()),
+    };
+    let hash = hash.parse::<ObjectId>().map_err(|_| format_error())?;
+    let mode_type = match mode {
+        "40000" => "tree",
+        "160000" => "commit",
+        _ => "blob",
+    };
+    if mode_type != object_type {
+        return Err(error(format!(
+            "entry '{}' object type ({}) doesn't match mode type ({})",
+            path, object_type, mode_type
+        )));
+    }
+
+    // submodule commits are never in this repository
+    if !opts.allow_missing && mode_type != "commit" {
+        if !Object::exists(rep, &hash) {
+            return Err(error(format!(
+                "entry '{}' object {} is unavailable",
+                path, hash
+            )));
+        }
+        let actual = Object::load(rep, &hash)?;
+        if actual.get_type() != object_type {
+            return Err(error(format!(
+                "entry '{}' object {} is a {} but specified type was ({})",
+                path,
+                hash,
+                actual.get_type(),
+                object_type
+            )));
+        }
+    }
+
+    Ok((mode.to_string(), path, hash))
+}
+
+/// build trees from ls-tree lines read from stdin and print their ids
+pub fn mktree(rep: &Repository, opts: &Options) -> Result<(), RepError> {
+    let mut input = vec![];
+    std::io::stdin()
+        .read_to_end(&mut input)
+        .map_err(|e| RepError::Io("stdin".to_string(), e))?;
+    let separator = if opts.null_terminated { 0 } else { b'\n' };
+    if input.last() == Some(&separator) {
+        input.pop();
+    }
+
+    let mut entries = vec![];
+    for line in input
+        .split(|&b| b == separator)
+        .filter(|_| !input.is_empty())
+    {
+        let line = String::from_utf8_lossy(line);
+
+        // empty lines end a tree in batch mode
+        if line.is_empty() {
+            if !opts.batch {
+                return Err(RepError::InvalidArgument(
+                    "input format error: (blank line only valid in batch mode)".to_string(),
+                ));
+            }
+            outln!(
+                "{}",
+                Tree::from_entries(std::mem::take(&mut entries)).save(rep)?
+            )?;
+            continue;
+        }
+
+        entries.push(parse_entry(rep, &line, opts)?);
+    }
+
+    if !opts.batch || !entries.is_empty() {
+        outln!("{}", Tree::from_entries(entries).save(rep)?)?;
+    }
+    Ok(())
+}
@@ -1,124 +1,403 @@
 #![allow(dead_code)]
 
-use flate2::read::ZlibDecoder;
-use flate2::write::ZlibEncoder;
-use flate2::Compression;
-use sha1::{Digest, Sha1};
-use std::{collections::HashMap, io::prelude::*};
-
-use crate::repository::Repository;
+use crate::oid::ObjectId;
+use crate::pathspec::{quote_bytes, Pathspec};
+use crate::repository::{RepError, Repository};
+use crate::signature::Signature;
 
+/// a commit with its headers (tree, parents, author, ...) and message, read them with [KVLM]
 #[derive(Clone)]
 pub struct Commit {
     data: Vec<u8>,
     size: usize,
+    tree: ObjectId,
+    parents: Vec<ObjectId>,
 }
 
+/// the content of a file
 pub struct Blob {
     data: Vec<u8>,
     size: usize,
 }
 
 impl Blob {
-    pub fn from_file(path: &str) -> Object {
-        let data = std::fs::read(path).expect("Couldnt read file");
-        let size = data.len();
+    pub fn from_file(path: &str) -> Result<Object, RepError> {
+        let data = std::fs::read(path).map_err(|e| RepError::Io(path.to_string(), e))?;
 
-        let blob = Blob { data, size };
+        Ok(Self::from_data(data))
+    }
+
+    pub fn from_data(data: Vec<u8>) -> Object {
+        let size = data.len();
 
-        Object::Blob(blob)
+        Object::Blob(Blob { data, size })
     }
 }
 
+/// a directory listing of names with the modes and hashes of their objects
 pub struct Tree {
     data: Vec<u8>,
     size: usize,
     objects: Vec<TreeEntry>,
 }
 
+/// an entry of a tree, the object it points to is only loaded when it is needed
 pub struct TreeEntry {
     mode: String,
+    /// the name as a path, bytes which aren't UTF-8 are replaced
     name: String,
-    sha: String,
-    object: Object,
+    /// the name exactly as it is stored in the tree
+    raw_name: Vec<u8>,
+    sha: ObjectId,
 }
 
 impl TreeEntry {
-    pub fn get_object(&self) -> &Object {
-        &self.object
+    /// load the object the entry points to from the repository
+    pub fn load(&self, rep: &Repository) -> Result<Object, RepError> {
+        Object::load(rep, &self.sha)
+    }
+    pub fn get_mode(&self) -> &str {
+        &self.mode
     }
     pub fn get_name(&self) -> &str {
         &self.name
     }
-    pub fn get_hash(&self) -> &str {
+    pub fn get_raw_name(&self) -> &[u8] {
+        &self.raw_name
+    }
+    pub fn get_hash(&self) -> &ObjectId {
         &self.sha
     }
+    /// the type of the object, known from the mode without loading it
+    pub fn get_type(&self) -> &str {
+        match self.mode.as_str() {
+            "40000" | "040000" => "tree",
+            "160000" => "commit",
+            _ => "blob",
+        }
+    }
+    pub fn is_tree(&self) -> bool {
+        self.get_type() == "tree"
+    }
+    /// load the entry as a tree, None if it is not a tree
+    pub fn load_tree(&self, rep: &Repository) -> Result<Option<Tree>, RepError> {
+        if !self.is_tree() {
+            return Ok(None);
+        }
+        match self.load(rep)? {
+            Object::Tree(t) => Ok(Some(t)),
+            _ => Err(RepError::BadObject(
+                "tree".to_string(),
+                self.sha.to_string(),
+            )),
+        }
+    }
 }
 
+/// an annotated tag pointing to another object, read its headers with [KVLM]
 pub struct Tag {
     data: Vec<u8>,
     size: usize,
+    object: ObjectId,
 }
 
 impl Commit {
-    pub fn hash(&self) -> String {
-        Object::Commit(self.clone()).hash()
+    pub fn id(&self) -> ObjectId {
+        ObjectId::hash("commit", &self.data)
     }
-}
 
-pub trait KVLM {
-    fn get_data(&self) -> &Vec<u8>;
+    pub fn get_tree(&self) -> ObjectId {
+        self.tree
+    }
 
-    fn get_parents(&self) -> Vec<String> {
-        let (_, parents) = self.to_kvlm();
-        parents
+    pub fn get_parents(&self) -> &[ObjectId] {
+        &self.parents
     }
 
-    fn get_tree(&self) -> String {
-        let (kvlm, _) = self.to_kvlm();
-        kvlm.get("tree").expect("No tree").to_string()
+    /// build a new commit object
+    pub fn create(
+        tree: &ObjectId,
+        parents: &[ObjectId],
+        author: &Signature,
+        committer: &Signature,
+        message: &str,
+    ) -> Object {
+        let mut kvlm = Kvlm::new();
+        kvlm.push("tree", tree.to_string().as_bytes());
+        for parent in parents {
+            kvlm.push("parent", parent.to_string().as_bytes());
+        }
+        kvlm.push("author", author.to_string().as_bytes());
+        kvlm.push("committer", committer.to_string().as_bytes());
+
+        let mut message = message.as_bytes().to_vec();
+        if !message.ends_with(b"\n") {
+            message.push(b'\n');
+        }
+        kvlm.set_message(message);
+
+        let data = kvlm.serialize();
+        Object::Commit(Commit {
+            size: data.len(),
+            data,
+            tree: *tree,
+            parents: parents.to_vec(),
+        })
     }
 
-    fn get_message(&self) -> String {
-        let (kvlm, _) = self.to_kvlm();
-        kvlm.get("message").expect("No message").to_string()
+    /// build a commit object from headers and a message, None if the tree or a parent
+    /// is not a valid hash
+    pub fn from_kvlm(kvlm: &Kvlm) -> Option<Object> {
+        Some(Object::Commit(Self::from_data(kvlm.serialize())?))
     }
 
-    fn get_author(&self) -> String {
-        let (kvlm, _) = self.to_kvlm();
-        kvlm.get("author").expect("No author").to_string()
+    fn from_data(data: Vec<u8>) -> Option<Self> {
+        let kvlm = Kvlm::parse(&data);
+        let tree = parse_id(kvlm.get("tree")?)?;
+        let parents = kvlm
+            .get_all("parent")
+            .into_iter()
+            .map(parse_id)
+            .collect::<Option<Vec<ObjectId>>>()?;
+
+        Some(Commit {
+            size: data.len(),
+            data,
+            tree,
+            parents,
+        })
     }
+}
 
-    fn to_kvlm(&self) -> (HashMap<String, String>, Vec<String>) {
-        let data = std::str::from_utf8(self.get_data()).expect("Invalid utf8 data");
-        let data = data.replace("\n ", "");
+impl Tag {
+    pub fn get_object(&self) -> ObjectId {
+        self.object
+    }
 
-        let message = data.split("\n\n").collect::<Vec<&str>>()[1];
+    /// build a tag object from headers and a message, None if the tagged object is not
+    /// a valid hash
+    pub fn from_kvlm(kvlm: &Kvlm) -> Option<Object> {
+        Some(Object::Tag(Self::from_data(kvlm.serialize())?))
+    }
 
-        let data = data.split("\n\n").collect::<Vec<&str>>()[0];
+    fn from_data(data: Vec<u8>) -> Option<Self> {
+        let object = parse_id(Kvlm::parse(&data).get("object")?)?;
 
-        let mut parents = vec![];
+        Some(Tag {
+            size: data.len(),
+            data,
+            object,
+        })
+    }
+}
 
-        let mut kvlm = data
-            .split("\n")
-            .map(|line| {
-                let mut parts = line.split(' ');
-                let key = parts.next().expect("No key");
-                let value = parts.collect::<Vec<&str>>().join(" ");
+/// a hash in the headers of a commit or tag
+fn parse_id(value: &[u8]) -> Option<ObjectId> {
+    std::str::from_utf8(value).ok()?.parse().ok()
+}
 
-                if key == "parent" {
-                    parents.push(value.to_string());
+/// the headers and the message of a commit or tag in the order they are stored
+///
+/// values of continuation lines (like gpgsig or mergetag) are joined with newlines,
+/// serializing gives back exactly the parsed bytes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Kvlm {
+    headers: Vec<Header>,
+    /// None if there is no empty line after the headers
+    message: Option<Vec<u8>>,
+}
 
-                    // dont continue
-                    return (String::new(), String::new());
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Header {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    /// the lines as they were parsed, None once the header is built or changed
+    raw: Option<Vec<u8>>,
+}
+
+impl Kvlm {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(data: &[u8]) -> Self {
+        let mut kvlm = Self::new();
+        let mut rest = data;
+
+        while !rest.is_empty() {
+            let end = rest.iter().position(|&b| b == b'\n');
+            let raw = &rest[..end.map(|e| e + 1).unwrap_or(rest.len())];
+            let line = raw.strip_suffix(b"\n").unwrap_or(raw);
+            rest = &rest[raw.len()..];
+
+            if line.is_empty() {
+                kvlm.message = Some(rest.to_vec());
+                break;
+            }
+
+            match (line.strip_prefix(b" "), kvlm.headers.last_mut()) {
+                (Some(continuation), Some(header)) => {
+                    header.value.push(b'\n');
+                    header.value.extend_from_slice(continuation);
+                    if let Some(lines) = &mut header.raw {
+                        lines.extend_from_slice(raw);
+                    }
                 }
-                (key.to_string(), value.to_string())
-            })
-            .collect::<HashMap<String, String>>();
+                _ => {
+                    let space = line.iter().position(|&b| b == b' ').unwrap_or(line.len());
+                    let value = line.get(space + 1..).unwrap_or(&[]);
+                    kvlm.headers.push(Header {
+                        key: line[..space].to_vec(),
+                        value: value.to_vec(),
+                        raw: Some(raw.to_vec()),
+                    });
+                }
+            }
+        }
+
+        kvlm
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = vec![];
+
+        for header in self.headers.iter() {
+            if let Some(raw) = &header.raw {
+                data.extend_from_slice(raw);
+                continue;
+            }
+            data.extend_from_slice(&header.key);
+            data.push(b' ');
+            for (i, line) in header.value.split(|&b| b == b'\n').enumerate() {
+                if i > 0 {
+                    data.extend_from_slice(b"\n ");
+                }
+                data.extend_from_slice(line);
+            }
+            data.push(b'\n');
+        }
+
+        if let Some(message) = &self.message {
+            data.push(b'\n');
+            data.extend_from_slice(message);
+        }
+
+        data
+    }
+
+    /// all headers as (key, value) in their order
+    pub fn headers(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.headers
+            .iter()
+            .map(|h| (h.key.as_slice(), h.value.as_slice()))
+    }
+
+    /// the value of the first header with this key
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.get_all(key).into_iter().next()
+    }
+
+    /// the values of all headers with this key, like the parents of a merge
+    pub fn get_all(&self, key: &str) -> Vec<&[u8]> {
+        self.headers
+            .iter()
+            .filter(|h| h.key == key.as_bytes())
+            .map(|h| h.value.as_slice())
+            .collect()
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<String> {
+        self.get(key)
+            .map(|v| String::from_utf8_lossy(v).into_owned())
+    }
+
+    /// add a header after all other headers
+    pub fn push(&mut self, key: &str, value: &[u8]) {
+        self.headers.push(Header {
+            key: key.as_bytes().to_vec(),
+            value: value.to_vec(),
+            raw: None,
+        });
+    }
+
+    /// replace all headers with this key by one value at the position of the first one
+    pub fn set(&mut self, key: &str, value: &[u8]) {
+        match self.headers.iter().position(|h| h.key == key.as_bytes()) {
+            Some(i) => {
+                self.headers[i].value = value.to_vec();
+                self.headers[i].raw = None;
+                let mut seen = 0;
+                self.headers.retain(|h| {
+                    seen += (h.key == key.as_bytes()) as usize;
+                    h.key != key.as_bytes() || seen == 1
+                });
+            }
+            None => self.push(key, value),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.headers.retain(|h| h.key != key.as_bytes());
+    }
+
+    pub fn message(&self) -> &[u8] {
+        self.message.as_deref().unwrap_or(&[])
+    }
+
+    pub fn set_message(&mut self, message: Vec<u8>) {
+        self.message = Some(message);
+    }
+
+    /// the encoding of the message, git assumes UTF-8 without an encoding header
+    pub fn encoding(&self) -> Option<String> {
+        self.get_str("encoding")
+    }
+
+    /// the message as text, decoded from latin-1 if the encoding header says so
+    pub fn message_str(&self) -> String {
+        let latin1 = self.encoding().is_some_and(|e| {
+            matches!(
+                e.to_lowercase().as_str(),
+                "iso-8859-1" | "iso8859-1" | "latin1" | "latin-1"
+            )
+        });
+        match latin1 {
+            true => self.message().iter().map(|&b| b as char).collect(),
+            false => String::from_utf8_lossy(self.message()).into_owned(),
+        }
+    }
+}
+
+/// access to the headers and message of the objects stored as key value list with message
+#[allow(clippy::upper_case_acronyms)]
+pub trait KVLM {
+    fn get_data(&self) -> &Vec<u8>;
+
+    fn get_message(&self) -> String {
+        self.to_kvlm().message_str()
+    }
+
+    fn get_author(&self) -> Signature {
+        Signature::parse(&self.to_kvlm().get_str("author").unwrap_or_default())
+    }
+
+    fn get_committer(&self) -> Signature {
+        Signature::parse(&self.to_kvlm().get_str("committer").unwrap_or_default())
+    }
+
+    /// the tagger of a tag, very old tags dont have one
+    fn get_tagger(&self) -> Option<Signature> {
+        self.to_kvlm()
+            .get_str("tagger")
+            .map(|t| Signature::parse(&t))
+    }
 
-        kvlm.insert("message".to_string(), message.to_string());
+    fn get_encoding(&self) -> Option<String> {
+        self.to_kvlm().encoding()
+    }
 
-        (kvlm, parents)
+    fn to_kvlm(&self) -> Kvlm {
+        Kvlm::parse(self.get_data())
     }
 }
 
@@ -134,6 +413,7 @@ impl KVLM for Tag {
     }
 }
 
+/// any object of the object store, loaded by its hash with [Object::load]
 pub enum Object {
     Commit(Commit),
     Blob(Blob),
@@ -146,37 +426,113 @@ impl Tree {
         &self.objects
     }
 
-    pub fn checkout(&self, path: String, path_to_checkout: String) {
-        // the paths differ somewhere and are not subfolders of each other
-        if !path_to_checkout.contains(&path)
-            && !path.contains(&path_to_checkout)
-            && path_to_checkout != path
-        {
-            return;
-        }
-
-        for object in self.objects.iter() {
-            match object.get_object() {
-                Object::Tree(t) => {
-                    t.checkout(
-                        path.clone() + "/" + object.get_name(),
-                        path_to_checkout.clone(),
-                    );
+    /// the files selected by the pathspec as (path, mode, sha)
+    /// prefix is the path of this tree relative to the root
+    pub fn matching(
+        &self,
+        rep: &Repository,
+        prefix: &str,
+        pathspec: &Pathspec,
+    ) -> Result<Vec<(String, String, ObjectId)>, RepError> {
+        let mut files = vec![];
+
+        for entry in self.objects.iter() {
+            let path = prefix.to_string() + entry.get_name();
+            if entry.is_tree() {
+                // folders without any matching path are not loaded at all
+                if !pathspec.matches_dir(&path) {
+                    continue;
                 }
-                Object::Blob(_) => {
-                    let p = path.clone() + "/" + object.get_name();
-                    if !p.contains(&path_to_checkout) {
-                        continue;
-                    } else {
-                        // override the file
-                        std::fs::write(&p, object.get_object().cat()).expect("Couldnt write file");
-
-                        println!("checked out: {}", p);
-                    }
+                if let Some(t) = entry.load_tree(rep)? {
+                    files.extend(t.matching(rep, &(path + "/"), pathspec)?);
                 }
-                _ => unimplemented!("Not implemented Commits / Tags in trees"),
+            } else if pathspec.matches(&path) {
+                files.push((path, entry.mode.clone(), entry.sha));
             }
         }
+
+        Ok(files)
+    }
+
+    /// (mode, sha) of the non tree entry at a path below this tree, only the trees
+    /// along the path are loaded
+    pub fn find(
+        &self,
+        rep: &Repository,
+        path: &str,
+    ) -> Result<Option<(String, ObjectId)>, RepError> {
+        let (name, rest) = match path.split_once('/') {
+            Some((name, rest)) => (name, Some(rest)),
+            None => (path, None),
+        };
+        let entry = match self.objects.iter().find(|e| e.name == name) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        match (rest, entry.load_tree(rep)?) {
+            (Some(rest), Some(tree)) => tree.find(rep, rest),
+            (None, None) => Ok(Some((entry.mode.clone(), entry.sha))),
+            _ => Ok(None),
+        }
+    }
+
+    /// all blobs (and other non tree entries) below this tree as (path, mode, sha)
+    pub fn flatten(
+        &self,
+        rep: &Repository,
+        prefix: &str,
+    ) -> Result<Vec<(String, String, ObjectId)>, RepError> {
+        let mut result = vec![];
+
+        for entry in self.objects.iter() {
+            let path = prefix.to_string() + &entry.name;
+            match entry.load_tree(rep)? {
+                Some(t) => result.extend(t.flatten(rep, &(path + "/"))?),
+                None => result.push((path, entry.mode.clone(), entry.sha)),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// build a tree object from (mode, name, sha) entries
+    pub fn from_entries(entries: Vec<(String, String, ObjectId)>) -> Object {
+        // git writes the mode of trees without the leading zero
+        let mut entries = entries
+            .into_iter()
+            .map(|(mode, name, sha)| match mode.as_str() {
+                "040000" => ("40000".to_string(), name, sha),
+                _ => (mode, name, sha),
+            })
+            .collect::<Vec<(String, String, ObjectId)>>();
+
+        // git sorts trees as if their name had a trailing slash
+        let sort_key = |mode: &str, name: &str| {
+            if mode == "40000" {
+                name.to_string() + "/"
+            } else {
+                name.to_string()
+            }
+        };
+        entries.sort_by_key(|(mode, name, _)| sort_key(mode, name));
+
+        let mut data = vec![];
+        for (mode, name, sha) in entries {
+            data.extend_from_slice(mode.as_bytes());
+            data.push(0x20);
+            data.extend_from_slice(name.as_bytes());
+            data.push(0x00);
+            data.extend_from_slice(sha.as_bytes());
+        }
+
+        let size = data.len();
+        // the entries get resolved when the tree is loaded from the repository again
+        Object::Tree(Tree {
+            data,
+            size,
+            objects: vec![],
+        })
     }
 
     pub fn display_objects(&self) -> String {
@@ -187,47 +543,37 @@ impl Tree {
         result
     }
 
-    fn from_data(data: &[u8], size: usize) -> Self {
+    /// parse the entries of a tree, None if they are truncated or malformed
+    fn from_data(data: &[u8], size: usize) -> Option<Self> {
         let mut objects = vec![];
         let mut data_to_process = data;
 
-        let repository = Repository::load(None).expect("should be a repository");
-
         while !data_to_process.is_empty() {
-            let space = data_to_process.iter().position(|&x| x == 0x20).unwrap();
+            let space = data_to_process.iter().position(|&x| x == 0x20)?;
             let mode = &data_to_process[0..space];
-            let mode = std::str::from_utf8(mode).expect("Invalid mode");
+            let mode = std::str::from_utf8(mode).ok()?;
             data_to_process = &data_to_process[space + 1..];
 
-            let null = data_to_process.iter().position(|&x| x == 0x00).unwrap();
-            let name = &data_to_process[0..null];
-            let name = std::str::from_utf8(name).expect("Invalid name");
+            let null = data_to_process.iter().position(|&x| x == 0x00)?;
+            let raw_name = data_to_process[0..null].to_vec();
             data_to_process = &data_to_process[null + 1..];
 
-            let sha = &data_to_process[0..20];
-            // binary decode the sha
-            let sha = sha
-                .iter()
-                .map(|b| format!("{:02x}", b))
-                .collect::<Vec<String>>()
-                .join("");
+            let sha = ObjectId::from_bytes(data_to_process.get(0..20)?)?;
             data_to_process = &data_to_process[20..];
 
-            let object = Object::load(&repository, &sha);
-
             objects.push(TreeEntry {
                 mode: mode.to_string(),
-                name: name.to_string(),
+                name: String::from_utf8_lossy(&raw_name).to_string(),
+                raw_name,
                 sha,
-                object,
             });
         }
 
-        Tree {
+        Some(Tree {
             data: data.to_vec(),
             size,
             objects,
-        }
+        })
     }
 }
 
@@ -241,32 +587,24 @@ impl Object {
         }
     }
 
-    pub fn cat(&self) -> String {
-        let data = self.get_data();
-
+    /// the content like cat-file -p shows it, trees are listed like ls-tree
+    pub fn pretty(&self) -> Vec<u8> {
         match self {
-            Object::Blob(_) | Object::Commit(_) | Object::Tag(_) => match std::str::from_utf8(data)
-            {
-                Ok(s) => s.to_string(),
-                Err(_) => format!("failed to decode content: {:?}", data),
-            },
-            Object::Tree(tree) => {
-                // parse the tree object
-
-                tree.objects
-                    .iter()
-                    .map(|entry| {
-                        format!(
-                            "{}\t{}\t{}\t{}\n",
-                            entry.mode,
-                            entry.object.get_type(),
-                            entry.sha,
-                            entry.name
-                        )
-                    })
-                    .collect::<Vec<String>>()
-                    .join("")
-            }
+            Object::Tree(tree) => tree
+                .objects
+                .iter()
+                .map(|entry| {
+                    format!(
+                        "{:0>6} {} {}\t{}\n",
+                        entry.mode,
+                        entry.get_type(),
+                        entry.sha,
+                        quote_bytes(&entry.raw_name)
+                    )
+                })
+                .collect::<String>()
+                .into_bytes(),
+            _ => self.get_data().clone(),
         }
     }
 
@@ -288,124 +626,205 @@ impl Object {
         }
     }
 
-    pub fn load(repository: &Repository, sha: &str) -> Self {
-        let path = repository.get_object_path(sha);
-        let data = Self::read(&path);
+    /// check if an object is stored in the repository
+    pub fn exists(repository: &Repository, id: &ObjectId) -> bool {
+        repository.objects().exists(id)
+    }
 
-        Self::deserialize(data)
+    /// load an object from the repository by its id
+    pub fn load(repository: &Repository, id: &ObjectId) -> Result<Self, RepError> {
+        let (object_type, data) = repository.objects().read(id)?;
+
+        Self::from_parts(&object_type, data)
+            .ok_or_else(|| RepError::BadObjectHeader(id.to_string()))
     }
 
-    /// deserialize an object from the repository by its uncompressed data
-    fn deserialize(data: Vec<u8>) -> Self {
-        // the data consists of the object type, a space 0x20, the object size, a null byte 0x00, and the object content
-        let mut data = data.as_slice();
-        let space = data.iter().position(|&x| x == 0x20).unwrap();
-        let obj_type = &data[0..space];
-        let obj_type = std::str::from_utf8(obj_type).expect("Invalid Object Type");
+    /// build an object from its type and content, None if the type is unknown, the content
+    /// of a tree is malformed or a commit or tag points to an invalid hash
+    pub fn from_parts(object_type: &str, data: Vec<u8>) -> Option<Self> {
+        let size = data.len();
 
-        data = &data[space + 1..];
-        let null = data.iter().position(|&x| x == 0x00).unwrap();
-        let size = &data[0..null];
-        let size = std::str::from_utf8(size).expect("Invalid Object Size");
+        let object = match object_type {
+            "commit" => Object::Commit(Commit::from_data(data)?),
+            "blob" => Object::Blob(Blob { data, size }),
+            "tree" => Object::Tree(Tree::from_data(&data, size)?),
+            "tag" => Object::Tag(Tag::from_data(data)?),
+            _ => return None,
+        };
+        Some(object)
+    }
 
-        data = &data[null + 1..];
-        let size = size.parse::<usize>().expect("Failed to parse object size");
-        let content = &data[0..size];
+    /// save an object to the repository
+    pub fn save(&self, repository: &Repository) -> Result<ObjectId, RepError> {
+        Self::save_raw(repository, self.get_type(), self.get_data())
+    }
 
-        match obj_type {
-            "commit" => {
-                let commit = Commit {
-                    data: content.to_vec(),
-                    size,
-                };
-                Object::Commit(commit)
-            }
-            "blob" => {
-                let blob = Blob {
-                    data: content.to_vec(),
-                    size,
-                };
-                Object::Blob(blob)
-            }
-            "tree" => Object::Tree(Tree::from_data(data, size)),
-            "tag" => {
-                let tag = Tag {
-                    data: content.to_vec(),
-                    size,
-                };
-                Object::Tag(tag)
-            }
-            _ => unimplemented!("Non Known Object Type"),
-        }
+    /// save data as an object of any type without checking the content
+    pub fn save_raw(
+        repository: &Repository,
+        object_type: &str,
+        data: &[u8],
+    ) -> Result<ObjectId, RepError> {
+        repository.objects().write(object_type, data)
     }
 
-    /// read an object file with a given path and decrompress it with zlib
-    fn read(path: &str) -> Vec<u8> {
-        let data = std::fs::read(path).expect("Failed to read object file");
+    /// the id of the object, without saving it
+    pub fn id(&self) -> ObjectId {
+        ObjectId::hash(self.get_type(), self.get_data())
+    }
+}
 
-        let mut z = ZlibDecoder::new(&data[..]);
-        let mut s = Vec::new();
-        //println!("{:?}", z.into_inner().read_to_string(buf));
-        z.read_to_end(&mut s).expect("Failed to decompress object");
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNED: &[u8] = b"tree 9d4c3a46a6a04d5624fdb47ed91eeba7dfd0c950
+parent 70ce96057278e39600d477912c672b9ef28dccf4
+author T <t@x> 1700000000 +0000
+committer T <t@x> 1700000000 +0000
+gpgsig -----BEGIN PGP SIGNATURE-----
+ 
+ iQEzBAABCAAdFiEE
+ =abcd
+ -----END PGP SIGNATURE-----
+
+subject
+
+body after a blank line
+";
+
+    const MERGE: &[u8] = b"tree 9d4c3a46a6a04d5624fdb47ed91eeba7dfd0c950
+parent 70ce96057278e39600d477912c672b9ef28dccf4
+parent 97a65e7c70a850289d91c42a852f5409debe7d16
+author T <t@x> 1700000000 +0000
+committer T <t@x> 1700000000 +0000
+mergetag object 97a65e7c70a850289d91c42a852f5409debe7d16
+ type commit
+ tag v1.0
+ tagger T <t@x> 1700000000 +0000
+ 
+ release
+ -----BEGIN PGP SIGNATURE-----
+ =efgh
+ -----END PGP SIGNATURE-----
+
+Merge tag 'v1.0'
+";
+
+    #[test]
+    fn kvlm_round_trip_is_byte_exact() {
+        for data in [
+            SIGNED,
+            MERGE,
+            b"tree 9d4c3a46a6a04d5624fdb47ed91eeba7dfd0c950\nnospace\n\nmessage\n",
+            b"tree 9d4c3a46a6a04d5624fdb47ed91eeba7dfd0c950\nauthor T <t@x> 1 +0000",
+            b"tree 9d4c3a46a6a04d5624fdb47ed91eeba7dfd0c950\n\n\n\nmessage after blank lines",
+            b"",
+        ] {
+            assert_eq!(Kvlm::parse(data).serialize(), data);
+        }
+    }
 
-        s
+    #[test]
+    fn kvlm_joins_continuation_lines() {
+        let kvlm = Kvlm::parse(SIGNED);
+        assert_eq!(
+            kvlm.get("gpgsig").unwrap(),
+            b"-----BEGIN PGP SIGNATURE-----\n\niQEzBAABCAAdFiEE\n=abcd\n-----END PGP SIGNATURE-----"
+        );
+        assert_eq!(kvlm.message(), b"subject\n\nbody after a blank line\n");
+
+        let kvlm = Kvlm::parse(MERGE);
+        assert_eq!(kvlm.get_all("parent").len(), 2);
+        assert!(kvlm
+            .get("mergetag")
+            .unwrap()
+            .starts_with(b"object 97a65e7c70a850289d91c42a852f5409debe7d16\ntype commit\n"));
     }
 
-    /// serialize an object to a byte array
-    fn serialize(&self) -> Vec<u8> {
-        let t = match self {
-            Object::Tag(_) => "tag",
-            Object::Commit(_) => "commit",
-            Object::Tree(_) => "tree",
-            Object::Blob(_) => "blob",
-        };
+    #[test]
+    fn kvlm_changed_headers_are_serialized_again() {
+        let mut kvlm = Kvlm::parse(SIGNED);
+        kvlm.remove("gpgsig");
+        kvlm.set("tree", b"0000000000000000000000000000000000000000");
+        kvlm.push("encoding", b"latin1");
+
+        let data = kvlm.serialize();
+        assert!(data.starts_with(b"tree 0000000000000000000000000000000000000000\nparent "));
+        assert!(!data.windows(6).any(|w| w == b"gpgsig"));
+        assert_eq!(Kvlm::parse(&data).serialize(), data);
+    }
 
-        let data = self.get_data();
-        let size = data.len();
+    #[test]
+    fn kvlm_builds_new_commits() {
+        let mut kvlm = Kvlm::new();
+        kvlm.push("tree", b"9d4c3a46a6a04d5624fdb47ed91eeba7dfd0c950");
+        kvlm.push("gpgsig", b"line 1\n\nline 3");
+        kvlm.set_message(b"message\n".to_vec());
+        assert_eq!(
+            kvlm.serialize(),
+            b"tree 9d4c3a46a6a04d5624fdb47ed91eeba7dfd0c950\ngpgsig line 1\n \n line 3\n\nmessage\n"
+        );
+    }
 
-        let mut s = Vec::new();
-        s.extend_from_slice(t.as_bytes());
-        s.push(0x20);
-        s.extend_from_slice(size.to_string().as_bytes());
-        s.push(0x00);
-        s.extend_from_slice(data);
+    #[test]
+    fn trees_are_built_from_ids() {
+        let blob = ObjectId::hash("blob", b"");
+        assert_eq!(blob.to_string(), "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391");
+        let tree = Tree::from_entries(vec![("100644".into(), "a".into(), blob)]);
+        assert_eq!(
+            tree.id().to_string(),
+            "496d6428b9cf92981dc9495211e6e1120fb6f2ba"
+        );
+    }
 
-        s
+    #[test]
+    fn commits_with_malformed_hashes_are_not_loaded() {
+        let data = b"tree e69de29b\nauthor A <a@x> 0 +0000\n\nmessage\n".to_vec();
+        assert!(Commit::from_data(data).is_none());
     }
 
-    /// save an object to the repository
-    pub fn save(&self, repository: &Repository) -> String {
-        let data = self.serialize();
-        let hash = self.hash();
-        let path = repository.get_object_path(&hash);
-
-        Self::write(&data, &path);
-        hash
-    }
-
-    /// compress and byte array and write it to a file
-    fn write(data: &Vec<u8>, path: &str) {
-        let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
-        z.write_all(data).expect("Failed to compress object");
-        let compressed = z.finish().expect("Failed to finish compression");
-
-        // create the folder
-        let folder = path
-            .split("/")
-            .take(path.split("/").count() - 1)
-            .collect::<Vec<&str>>()
-            .join("/");
-        let _ = std::fs::create_dir_all(folder);
-
-        std::fs::write(path, compressed).expect("Failed to write object file");
-    }
-
-    /// get the hash of an object
-    pub fn hash(&self) -> String {
-        let data = self.serialize();
-        let mut hasher = Sha1::new();
-        hasher.update(data);
-        let result = hasher.finalize();
-        result.iter().map(|b| format!("{:02x}", b)).collect()
+    #[test]
+    fn tree_names_which_arent_utf8_are_kept() {
+        let rep = Repository::in_memory();
+        let blob = Object::save_raw(&rep, "blob", b"").unwrap();
+        let mut data = b"100644 caf\xe9\0".to_vec();
+        data.extend_from_slice(blob.as_bytes());
+        let tree = Object::save_raw(&rep, "tree", &data).unwrap();
+
+        let object = Object::load(&rep, &tree).unwrap();
+        assert_eq!(object.get_data(), &data);
+        let tree = match &object {
+            Object::Tree(tree) => tree,
+            _ => panic!("expected a tree"),
+        };
+        assert_eq!(tree.get_objects()[0].get_raw_name(), b"caf\xe9");
+        assert_eq!(tree.get_objects()[0].get_name(), "caf\u{fffd}");
+        assert!(String::from_utf8(object.pretty())
+            .unwrap()
+            .ends_with("\t\"caf\\351\"\n"));
+    }
+
+    #[test]
+    fn tree_modes_with_a_leading_zero_are_written_without_it() {
+        let rep = Repository::in_memory();
+        let sub = Tree::from_entries(vec![]).save(&rep).unwrap();
+        let blob = Object::save_raw(&rep, "blob", b"").unwrap();
+
+        let entries = |mode: &str| {
+            vec![
+                (mode.to_string(), "a".to_string(), sub),
+                ("100644".to_string(), "a.txt".to_string(), blob),
+            ]
+        };
+        let tree = Tree::from_entries(entries("040000")).save(&rep).unwrap();
+        assert_eq!(
+            tree,
+            Tree::from_entries(entries("40000")).save(&rep).unwrap()
+        );
+        // the tree sorts like "a/", after "a.txt"
+        let data = Object::load(&rep, &tree).unwrap().get_data().clone();
+        assert!(data.starts_with(b"100644 a.txt\0"));
+        assert!(data.windows(8).any(|w| w == b"40000 a\0"));
     }
 }
@@ -0,0 +1,707 @@
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::collections::BTreeMap;
+use std::io::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::oid::ObjectId;
+use crate::pack::Pack;
+use crate::repository::RepError;
+
+/// alternates listing alternates are followed up to this depth like git does
+const MAX_ALTERNATE_DEPTH: usize = 5;
+
+/// makes the names of temporary object files unique within the process
+static TEMPORARY_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// where a copy of an object is kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    /// a file in objects/xx/
+    Loose(String),
+    /// in the pack at the path, at an offset
+    Packed(String, u64),
+    /// only in memory
+    Memory,
+}
+
+/// an object as it is stored, an object which is both loose and packed is stored twice
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredObject {
+    pub id: ObjectId,
+    pub location: Location,
+}
+
+/// where objects are stored, the repository reads and writes all objects through one
+pub trait ObjectDatabase: Send + Sync {
+    /// the type and content of an object
+    fn read(&self, id: &ObjectId) -> Result<(String, Vec<u8>), RepError>;
+
+    /// store content as an object of a type and return its id
+    fn write(&self, object_type: &str, data: &[u8]) -> Result<ObjectId, RepError>;
+
+    fn exists(&self, id: &ObjectId) -> bool;
+
+    /// the ids of all objects, sorted
+    fn iterate(&self) -> Vec<ObjectId>;
+
+    /// the ids of the objects whose hex digits start with a lowercase abbreviation, sorted
+    fn find_prefix(&self, prefix: &str) -> Vec<ObjectId> {
+        self.iterate()
+            .into_iter()
+            .filter(|id| id.starts_with(prefix))
+            .collect()
+    }
+
+    /// the type and size of an object, without its content where the storage allows it
+    fn read_header(&self, id: &ObjectId) -> Result<(String, usize), RepError> {
+        self.read(id)
+            .map(|(object_type, data)| (object_type, data.len()))
+    }
+
+    /// every stored copy of every object, for commands which look at the storage itself like
+    /// fsck, prune and count-objects
+    fn stored(&self) -> Vec<StoredObject>;
+
+    /// the type and content of one stored copy, ObjectNotFound if this database doesn't hold it
+    fn read_stored(&self, object: &StoredObject) -> Result<(String, Vec<u8>), RepError>;
+
+    /// delete one stored copy, ObjectNotFound if this database doesn't hold it
+    fn remove_stored(&self, object: &StoredObject) -> Result<(), RepError>;
+
+    /// problems of the storage which aren't about a single object, like pack checksums
+    fn verify(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// the zlib compressed files in .git/objects/xx/yyyy...
+pub struct LooseDatabase {
+    /// the objects directory
+    dir: String,
+}
+
+impl LooseDatabase {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: dir.to_string(),
+        }
+    }
+
+    /// the file an object is stored in
+    pub fn path(&self, id: &ObjectId) -> String {
+        let hex = id.to_string();
+        format!("{}/{}/{}", self.dir, &hex[..2], &hex[2..])
+    }
+
+    /// a decoder for the file of an object
+    fn open(path: &str, id: &ObjectId) -> Result<ZlibDecoder<std::fs::File>, RepError> {
+        match std::fs::File::open(path) {
+            Ok(file) => Ok(ZlibDecoder::new(file)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(RepError::ObjectNotFound(id.to_string()))
+            }
+            Err(e) => Err(RepError::Io(path.to_string(), e)),
+        }
+    }
+
+    /// the type and content of the object in a file
+    fn read_file(path: &str, id: &ObjectId) -> Result<(String, Vec<u8>), RepError> {
+        let mut data = vec![];
+        Self::open(path, id)?
+            .read_to_end(&mut data)
+            .map_err(|_| RepError::CorruptObject(id.to_string(), path.to_string()))?;
+
+        let (object_type, _, content) = split_header(&data)
+            .filter(|(_, size, content)| *size == content.len())
+            .ok_or_else(|| RepError::BadObjectHeader(id.to_string()))?;
+        Ok((object_type.to_string(), content.to_vec()))
+    }
+}
+
+impl ObjectDatabase for LooseDatabase {
+    fn read(&self, id: &ObjectId) -> Result<(String, Vec<u8>), RepError> {
+        Self::read_file(&self.path(id), id)
+    }
+
+    fn write(&self, object_type: &str, data: &[u8]) -> Result<ObjectId, RepError> {
+        let id = ObjectId::hash(object_type, data);
+        if self.exists(&id) {
+            return Ok(id);
+        }
+
+        let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
+        let path = self.path(&id);
+        let io_error = |e| RepError::Io(path.clone(), e);
+        z.write_all(format!("{} {}\0", object_type, data.len()).as_bytes())
+            .map_err(io_error)?;
+        z.write_all(data).map_err(io_error)?;
+        let compressed = z.finish().map_err(io_error)?;
+
+        // the object is written to a temporary file first and renamed, so a reader never
+        // sees half of it, prune removes the leftovers of interrupted writes
+        let folder = format!("{}/{}", self.dir, &id.to_string()[..2]);
+        std::fs::create_dir_all(&folder).map_err(|e| RepError::Io(folder.clone(), e))?;
+        let temporary = format!(
+            "{}/tmp_obj_{}_{}",
+            folder,
+            std::process::id(),
+            TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed)
+        );
+        std::fs::write(&temporary, compressed).map_err(|e| RepError::Io(temporary.clone(), e))?;
+        if let Err(e) = std::fs::rename(&temporary, &path) {
+            let _ = std::fs::remove_file(&temporary);
+            return Err(io_error(e));
+        }
+        Ok(id)
+    }
+
+    fn exists(&self, id: &ObjectId) -> bool {
+        std::path::Path::new(&self.path(id)).is_file()
+    }
+
+    fn iterate(&self) -> Vec<ObjectId> {
+        let mut objects = vec![];
+
+        let dirs = match std::fs::read_dir(&self.dir) {
+            Ok(dirs) => dirs.filter_map(|e| e.ok()).collect::<Vec<_>>(),
+            Err(_) => return objects,
+        };
+        for dir in dirs {
+            let prefix = dir.file_name().to_string_lossy().to_string();
+            if prefix.len() != 2 {
+                continue;
+            }
+            for file in std::fs::read_dir(dir.path())
+                .into_iter()
+                .flatten()
+                .flatten()
+            {
+                let hex = prefix.clone() + &file.file_name().to_string_lossy();
+                if let Ok(id) = hex.parse() {
+                    objects.push(id);
+                }
+            }
+        }
+
+        objects.sort();
+        objects
+    }
+
+    /// only the folder of the first two digits is read
+    fn find_prefix(&self, prefix: &str) -> Vec<ObjectId> {
+        if prefix.len() < 2 {
+            return self
+                .iterate()
+                .into_iter()
+                .filter(|id| id.starts_with(prefix))
+                .collect();
+        }
+
+        let (dir, rest) = prefix.split_at(2);
+        let mut objects = std::fs::read_dir(format!("{}/{}", self.dir, dir))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|file| file.file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with(rest))
+            .filter_map(|name| (dir.to_string() + &name).parse().ok())
+            .collect::<Vec<ObjectId>>();
+        objects.sort();
+        objects
+    }
+
+    /// only the start of the file is decompressed
+    fn read_header(&self, id: &ObjectId) -> Result<(String, usize), RepError> {
+        let mut header = vec![];
+        Self::open(&self.path(id), id)?
+            .take(64)
+            .read_to_end(&mut header)
+            .map_err(|_| RepError::CorruptObject(id.to_string(), self.path(id)))?;
+
+        match split_header(&header) {
+            Some((object_type, size, _)) => Ok((object_type.to_string(), size)),
+            None => Err(RepError::BadObjectHeader(id.to_string())),
+        }
+    }
+
+    fn stored(&self) -> Vec<StoredObject> {
+        self.iterate()
+            .into_iter()
+            .map(|id| StoredObject {
+                id,
+                location: Location::Loose(self.path(&id)),
+            })
+            .collect()
+    }
+
+    fn read_stored(&self, object: &StoredObject) -> Result<(String, Vec<u8>), RepError> {
+        match &object.location {
+            Location::Loose(path) if *path == self.path(&object.id) => {
+                Self::read_file(path, &object.id)
+            }
+            _ => Err(RepError::ObjectNotFound(object.id.to_string())),
+        }
+    }
+
+    /// the folder of the file goes away too once it is empty
+    fn remove_stored(&self, object: &StoredObject) -> Result<(), RepError> {
+        let path = match &object.location {
+            Location::Loose(path) if *path == self.path(&object.id) => path,
+            _ => return Err(RepError::ObjectNotFound(object.id.to_string())),
+        };
+        std::fs::remove_file(path).map_err(|e| RepError::Io(path.clone(), e))?;
+
+        if let Some(dir) = std::path::Path::new(path).parent() {
+            let io_error = |e| RepError::Io(dir.to_string_lossy().to_string(), e);
+            if std::fs::read_dir(dir).map_err(io_error)?.next().is_none() {
+                std::fs::remove_dir(dir).map_err(io_error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// the packs in .git/objects/pack, they are only read when an object is first looked up
+pub struct PackDatabase {
+    /// the pack directory
+    dir: String,
+    packs: OnceLock<Vec<Pack>>,
+}
+
+impl PackDatabase {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: dir.to_string(),
+            packs: OnceLock::new(),
+        }
+    }
+
+    fn all(&self) -> &Vec<Pack> {
+        self.packs.get_or_init(|| Pack::all_in(&self.dir))
+    }
+
+    fn find(&self, id: &ObjectId) -> Option<&Pack> {
+        self.all().iter().find(|pack| pack.contains(id))
+    }
+}
+
+impl ObjectDatabase for PackDatabase {
+    fn read(&self, id: &ObjectId) -> Result<(String, Vec<u8>), RepError> {
+        let pack = self
+            .find(id)
+            .ok_or_else(|| RepError::ObjectNotFound(id.to_string()))?;
+        pack.read(id)
+            .ok_or_else(|| RepError::CorruptObject(id.to_string(), pack.get_path().to_string()))
+    }
+
+    /// objects are never added to existing packs
+    fn write(&self, _object_type: &str, _data: &[u8]) -> Result<ObjectId, RepError> {
+        Err(RepError::InvalidArgument(format!(
+            "cannot write objects to the packs in {}",
+            self.dir
+        )))
+    }
+
+    fn exists(&self, id: &ObjectId) -> bool {
+        self.find(id).is_some()
+    }
+
+    fn iterate(&self) -> Vec<ObjectId> {
+        let mut objects = self
+            .all()
+            .iter()
+            .flat_map(|pack| pack.entries().iter().map(|(id, _)| *id))
+            .collect::<Vec<ObjectId>>();
+        objects.sort();
+        objects.dedup();
+        objects
+    }
+
+    fn find_prefix(&self, prefix: &str) -> Vec<ObjectId> {
+        let mut objects = self
+            .all()
+            .iter()
+            .flat_map(|pack| pack.find_prefix(prefix))
+            .collect::<Vec<ObjectId>>();
+        objects.sort();
+        objects.dedup();
+        objects
+    }
+
+    /// the objects of every pack in the order of its index
+    fn stored(&self) -> Vec<StoredObject> {
+        self.all()
+            .iter()
+            .flat_map(|pack| {
+                pack.entries().iter().map(|(id, offset)| StoredObject {
+                    id: *id,
+                    location: Location::Packed(pack.get_path().to_string(), *offset),
+                })
+            })
+            .collect()
+    }
+
+    fn read_stored(&self, object: &StoredObject) -> Result<(String, Vec<u8>), RepError> {
+        let not_found = || RepError::ObjectNotFound(object.id.to_string());
+        let (path, offset) = match &object.location {
+            Location::Packed(path, offset) => (path, *offset),
+            _ => return Err(not_found()),
+        };
+        let pack = self
+            .all()
+            .iter()
+            .find(|pack| pack.get_path() == path)
+            .ok_or_else(not_found)?;
+        pack.read_at(offset)
+            .ok_or_else(|| RepError::CorruptObject(object.id.to_string(), path.clone()))
+    }
+
+    /// packs are only ever replaced as a whole
+    fn remove_stored(&self, object: &StoredObject) -> Result<(), RepError> {
+        match &object.location {
+            Location::Packed(path, _) if self.all().iter().any(|p| p.get_path() == path) => {
+                Err(RepError::InvalidArgument(format!(
+                    "cannot remove {} from the pack {}",
+                    object.id, path
+                )))
+            }
+            _ => Err(RepError::ObjectNotFound(object.id.to_string())),
+        }
+    }
+
+    fn verify(&self) -> Vec<String> {
+        self.all()
+            .iter()
+            .filter_map(|pack| pack.verify_checksum().err())
+            .collect()
+    }
+}
+
+/// objects which only live as long as the database, e.g. for tests
+#[derive(Default)]
+pub struct MemoryDatabase {
+    objects: Mutex<BTreeMap<ObjectId, (String, Vec<u8>)>>,
+}
+
+impl MemoryDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn objects(&self) -> std::sync::MutexGuard<'_, BTreeMap<ObjectId, (String, Vec<u8>)>> {
+        // the map stays consistent even if a thread panicked while holding the lock
+        self.objects.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ObjectDatabase for MemoryDatabase {
+    fn read(&self, id: &ObjectId) -> Result<(String, Vec<u8>), RepError> {
+        self.objects()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| RepError::ObjectNotFound(id.to_string()))
+    }
+
+    fn write(&self, object_type: &str, data: &[u8]) -> Result<ObjectId, RepError> {
+        let id = ObjectId::hash(object_type, data);
+        self.objects()
+            .insert(id, (object_type.to_string(), data.to_vec()));
+        Ok(id)
+    }
+
+    fn exists(&self, id: &ObjectId) -> bool {
+        self.objects().contains_key(id)
+    }
+
+    fn iterate(&self) -> Vec<ObjectId> {
+        self.objects().keys().copied().collect()
+    }
+
+    fn find_prefix(&self, prefix: &str) -> Vec<ObjectId> {
+        let start = match ObjectId::prefix_start(prefix) {
+            Some(start) => start,
+            None => return vec![],
+        };
+        self.objects()
+            .range(start..)
+            .map(|(id, _)| *id)
+            .take_while(|id| id.starts_with(prefix))
+            .collect()
+    }
+
+    fn stored(&self) -> Vec<StoredObject> {
+        self.iterate()
+            .into_iter()
+            .map(|id| StoredObject {
+                id,
+                location: Location::Memory,
+            })
+            .collect()
+    }
+
+    fn read_stored(&self, object: &StoredObject) -> Result<(String, Vec<u8>), RepError> {
+        match object.location {
+            Location::Memory => self.read(&object.id),
+            _ => Err(RepError::ObjectNotFound(object.id.to_string())),
+        }
+    }
+
+    fn remove_stored(&self, object: &StoredObject) -> Result<(), RepError> {
+        let removed = match object.location {
+            Location::Memory => self.objects().remove(&object.id),
+            _ => None,
+        };
+        match removed {
+            Some(_) => Ok(()),
+            None => Err(RepError::ObjectNotFound(object.id.to_string())),
+        }
+    }
+}
+
+/// several databases searched in order, new objects are written to the first one
+#[derive(Default)]
+pub struct CompositeDatabase {
+    databases: Vec<Box<dyn ObjectDatabase>>,
+}
+
+impl CompositeDatabase {
+    pub fn new(databases: Vec<Box<dyn ObjectDatabase>>) -> Self {
+        Self { databases }
+    }
+
+    /// the loose objects and packs of an objects directory followed by the ones of
+    /// the directories listed in its info/alternates
+    pub fn open(dir: &str) -> Self {
+        let mut composite = Self::default();
+        composite.add_dir(dir, 0);
+        composite
+    }
+
+    pub fn push(&mut self, database: Box<dyn ObjectDatabase>) {
+        self.databases.push(database);
+    }
+
+    fn add_dir(&mut self, dir: &str, depth: usize) {
+        self.push(Box::new(LooseDatabase::new(dir)));
+        self.push(Box::new(PackDatabase::new(&format!("{}/pack", dir))));
+
+        if depth >= MAX_ALTERNATE_DEPTH {
+            return;
+        }
+        let alternates = std::fs::read_to_string(format!("{}/info/alternates", dir));
+        for line in alternates.unwrap_or_default().lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // relative alternates are relative to the objects directory
+            match line.starts_with('/') {
+                true => self.add_dir(line, depth + 1),
+                false => self.add_dir(&format!("{}/{}", dir, line), depth + 1),
+            }
+        }
+    }
+
+    fn find(&self, id: &ObjectId) -> Result<&dyn ObjectDatabase, RepError> {
+        self.databases
+            .iter()
+            .find(|database| database.exists(id))
+            .map(|database| database.as_ref())
+            .ok_or_else(|| RepError::ObjectNotFound(id.to_string()))
+    }
+}
+
+impl ObjectDatabase for CompositeDatabase {
+    fn read(&self, id: &ObjectId) -> Result<(String, Vec<u8>), RepError> {
+        self.find(id)?.read(id)
+    }
+
+    fn write(&self, object_type: &str, data: &[u8]) -> Result<ObjectId, RepError> {
+        match self.databases.first() {
+            Some(database) => database.write(object_type, data),
+            None => Err(RepError::InvalidArgument(
+                "no object database to write to".to_string(),
+            )),
+        }
+    }
+
+    fn exists(&self, id: &ObjectId) -> bool {
+        self.databases.iter().any(|database| database.exists(id))
+    }
+
+    fn iterate(&self) -> Vec<ObjectId> {
+        let mut objects = self
+            .databases
+            .iter()
+            .flat_map(|database| database.iterate())
+            .collect::<Vec<ObjectId>>();
+        objects.sort();
+        objects.dedup();
+        objects
+    }
+
+    fn find_prefix(&self, prefix: &str) -> Vec<ObjectId> {
+        let mut objects = self
+            .databases
+            .iter()
+            .flat_map(|database| database.find_prefix(prefix))
+            .collect::<Vec<ObjectId>>();
+        objects.sort();
+        objects.dedup();
+        objects
+    }
+
+    fn read_header(&self, id: &ObjectId) -> Result<(String, usize), RepError> {
+        self.find(id)?.read_header(id)
+    }
+
+    /// the objects of the repository come before the ones of its alternates
+    fn stored(&self) -> Vec<StoredObject> {
+        self.databases
+            .iter()
+            .flat_map(|database| database.stored())
+            .collect()
+    }
+
+    fn read_stored(&self, object: &StoredObject) -> Result<(String, Vec<u8>), RepError> {
+        for database in self.databases.iter() {
+            match database.read_stored(object) {
+                Err(RepError::ObjectNotFound(_)) => continue,
+                result => return result,
+            }
+        }
+        Err(RepError::ObjectNotFound(object.id.to_string()))
+    }
+
+    fn remove_stored(&self, object: &StoredObject) -> Result<(), RepError> {
+        for database in self.databases.iter() {
+            match database.remove_stored(object) {
+                Err(RepError::ObjectNotFound(_)) => continue,
+                result => return result,
+            }
+        }
+        Err(RepError::ObjectNotFound(object.id.to_string()))
+    }
+
+    fn verify(&self) -> Vec<String> {
+        self.databases
+            .iter()
+            .flat_map(|database| database.verify())
+            .collect()
+    }
+}
+
+/// split uncompressed object data into its type, the size from the header and the content,
+/// None if the header is invalid
+pub fn split_header(data: &[u8]) -> Option<(&str, usize, &[u8])> {
+    // the data consists of the object type, a space 0x20, the object size, a null byte 0x00, and the object content
+    let space = data.iter().position(|&x| x == 0x20)?;
+    let null = data.iter().position(|&x| x == 0x00)?;
+    let object_type = std::str::from_utf8(data.get(..space)?).ok()?;
+    let size = std::str::from_utf8(data.get(space + 1..null)?).ok()?;
+
+    Some((object_type, size.parse().ok()?, &data[null + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{Object, Tree};
+    use crate::repository::Repository;
+
+    #[test]
+    fn objects_are_written_and_read_in_memory() {
+        let rep = Repository::in_memory();
+        let blob = Object::save_raw(&rep, "blob", b"hi\n").unwrap();
+        assert_eq!(blob.to_string(), "45b983be36b73c0788dc9cbcb76cbb80fc7bb057");
+
+        let tree = Tree::from_entries(vec![("100644".into(), "a".into(), blob)])
+            .save(&rep)
+            .unwrap();
+        match Object::load(&rep, &tree).unwrap() {
+            Object::Tree(tree) => assert_eq!(tree.get_objects().len(), 1),
+            _ => panic!("expected a tree"),
+        }
+        assert_eq!(
+            rep.objects().read(&blob).unwrap(),
+            ("blob".into(), b"hi\n".to_vec())
+        );
+        assert_eq!(
+            rep.objects().read_header(&blob).unwrap(),
+            ("blob".into(), 3)
+        );
+        assert!(rep.objects().read(&ObjectId::NULL).is_err());
+    }
+
+    #[test]
+    fn memory_objects_are_iterated_sorted() {
+        let rep = Repository::in_memory();
+        let mut ids = ["a", "b", "c"]
+            .iter()
+            .map(|data| Object::save_raw(&rep, "blob", data.as_bytes()).unwrap())
+            .collect::<Vec<ObjectId>>();
+        // writing an object again doesn't add it twice
+        Object::save_raw(&rep, "blob", b"a").unwrap();
+
+        ids.sort();
+        assert_eq!(rep.objects().iterate(), ids);
+        assert!(ids.iter().all(|id| rep.objects().exists(id)));
+    }
+
+    #[test]
+    fn abbreviated_hashes_are_found_by_prefix() {
+        let rep = Repository::in_memory();
+        let blob = Object::save_raw(&rep, "blob", b"hi\n").unwrap();
+        Object::save_raw(&rep, "blob", b"").unwrap();
+
+        assert_eq!(rep.objects().find_prefix("45b9"), vec![blob]);
+        assert_eq!(rep.objects().find_prefix(""), rep.objects().iterate());
+        assert!(rep.objects().find_prefix("45ba").is_empty());
+        assert_eq!(rep.ref_resolve("45b983b").unwrap(), blob);
+    }
+
+    #[test]
+    fn stored_memory_objects_are_read_and_removed() {
+        let rep = Repository::in_memory();
+        let blob = Object::save_raw(&rep, "blob", b"hi\n").unwrap();
+
+        let stored = rep.objects().stored();
+        assert_eq!(
+            stored,
+            vec![StoredObject {
+                id: blob,
+                location: Location::Memory,
+            }]
+        );
+        assert_eq!(
+            rep.objects().read_stored(&stored[0]).unwrap(),
+            ("blob".into(), b"hi\n".to_vec())
+        );
+
+        let packed = StoredObject {
+            id: blob,
+            location: Location::Packed("pack-1.pack".into(), 12),
+        };
+        assert!(matches!(
+            rep.objects().read_stored(&packed),
+            Err(RepError::ObjectNotFound(_))
+        ));
+
+        rep.objects().remove_stored(&stored[0]).unwrap();
+        assert!(!rep.objects().exists(&blob));
+        assert!(rep.objects().remove_stored(&stored[0]).is_err());
+    }
+
+    #[test]
+    fn headers_are_split_from_the_content() {
+        assert_eq!(
+            split_header(b"blob 3\0hi\n"),
+            Some(("blob", 3, &b"hi\n"[..]))
+        );
+        assert_eq!(split_header(b"blob\0"), None);
+        assert_eq!(split_header(b"blob x\0"), None);
+    }
+}
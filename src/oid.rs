@@ -0,0 +1,129 @@
+use sha1::{Digest, Sha1};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::repository::RepError;
+
+/// the sha1 name of an object, shown and parsed as 40 hex digits
+///
+/// the default is the all zero id git uses for an object that doesn't exist
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId([u8; 20]);
+
+impl ObjectId {
+    /// the all zero id, e.g. the old value in the reflog of a new ref
+    pub const NULL: ObjectId = ObjectId([0; 20]);
+
+    /// the id from the 20 raw bytes like they are stored in trees and the index
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(ObjectId(bytes.try_into().ok()?))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 20] {
+        &self.0
+    }
+
+    /// the id data gets when it is stored as an object of a type
+    pub fn hash(object_type: &str, data: &[u8]) -> Self {
+        let mut hasher = Sha1::new();
+        hasher.update(object_type.as_bytes());
+        hasher.update(format!(" {}\0", data.len()).as_bytes());
+        hasher.update(data);
+        ObjectId(hasher.finalize().into())
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.0 == [0; 20]
+    }
+
+    /// the smallest id starting with the hex digits of an abbreviation, the ids with that
+    /// prefix follow it when sorted, None if the abbreviation isn't hex or too long
+    pub fn prefix_start(prefix: &str) -> Option<Self> {
+        match prefix.len() <= 40 {
+            true => format!("{:0<40}", prefix).parse().ok(),
+            false => None,
+        }
+    }
+
+    /// whether the hex digits start with an abbreviation in lowercase
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        self.to_string().starts_with(prefix)
+    }
+}
+
+/// a precision abbreviates the hex digits, e.g. `{:.7}`
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = self
+            .0
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        f.pad(&hex)
+    }
+}
+
+impl fmt::Debug for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ObjectId({})", self)
+    }
+}
+
+/// only full hashes are accepted, abbreviations need the repository to be resolved
+impl FromStr for ObjectId {
+    type Err = RepError;
+
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        let invalid = || RepError::InvalidReference(hex.to_string());
+        if hex.len() != 40 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        let mut bytes = [0; 20];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(ObjectId(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEX: &str = "45b983be36b73c0788dc9cbcb76cbb80fc7bb057";
+
+    #[test]
+    fn ids_are_parsed_and_shown_as_hex() {
+        let id = HEX.parse::<ObjectId>().unwrap();
+        assert_eq!(id.to_string(), HEX);
+        assert_eq!(format!("{:.7}", id), "45b983b");
+        assert_eq!(ObjectId::from_bytes(id.as_bytes()), Some(id));
+        assert_eq!(HEX.to_uppercase().parse::<ObjectId>().unwrap(), id);
+    }
+
+    #[test]
+    fn only_full_hashes_are_parsed() {
+        assert!("45b983b".parse::<ObjectId>().is_err());
+        assert!((HEX.to_string() + "0").parse::<ObjectId>().is_err());
+        assert!(HEX.replace('4', "g").parse::<ObjectId>().is_err());
+        assert!(ObjectId::from_bytes(&[0; 19]).is_none());
+    }
+
+    #[test]
+    fn objects_are_hashed_with_their_header() {
+        assert_eq!(ObjectId::hash("blob", b"hi\n").to_string(), HEX);
+        assert!(ObjectId::NULL.is_null());
+        assert_eq!(ObjectId::default(), ObjectId::NULL);
+    }
+
+    #[test]
+    fn prefixes_start_at_the_smallest_id() {
+        let start = ObjectId::prefix_start("45b9").unwrap();
+        assert_eq!(start.to_string(), format!("{:0<40}", "45b9"));
+        assert!(start <= HEX.parse().unwrap());
+        assert!(HEX.parse::<ObjectId>().unwrap().starts_with("45b9"));
+        assert!(ObjectId::prefix_start("xyz").is_none());
+        assert!(ObjectId::prefix_start(&(HEX.to_string() + "0")).is_none());
+    }
+}
@@ -0,0 +1,426 @@
+#![allow(dead_code)]
+
+use flate2::read::ZlibDecoder;
+use sha1::{Digest, Sha1};
+use std::io::prelude::*;
+
+use crate::oid::ObjectId;
+
+/// deltas pointing to deltas are followed up to this depth
+const MAX_DELTA_DEPTH: usize = 10000;
+
+/// a packfile in .git/objects/pack together with its version 2 index
+pub struct Pack {
+    /// the path of the .pack file
+    path: String,
+    data: Vec<u8>,
+    /// (hash, offset) sorted by hash like in the index
+    entries: Vec<(ObjectId, u64)>,
+    /// the pack checksum the index was written for
+    index_checksum: Vec<u8>,
+}
+
+impl Pack {
+    /// every pack of a pack directory sorted by name, packs which can't be read are skipped
+    pub fn all_in(dir: &str) -> Vec<Pack> {
+        let mut indexes = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.path().to_string_lossy().to_string())
+            .filter(|p| p.ends_with(".idx"))
+            .collect::<Vec<String>>();
+        indexes.sort();
+
+        indexes
+            .iter()
+            .filter_map(|idx| Pack::open(idx).ok())
+            .collect()
+    }
+
+    /// read a pack by the path of its index
+    pub fn open(idx_path: &str) -> Result<Self, String> {
+        let path = idx_path.trim_end_matches(".idx").to_string() + ".pack";
+        let index = std::fs::read(idx_path).map_err(|e| format!("{}: {}", idx_path, e))?;
+        let data = std::fs::read(&path).map_err(|e| format!("{}: {}", path, e))?;
+        let bad_index = || format!("{} is not a valid pack index", idx_path);
+
+        if index.len() < 8 + 256 * 4 + 40 || index[..8] != [0xff, b't', b'O', b'c', 0, 0, 0, 2] {
+            return Err(bad_index());
+        }
+        if data.len() < 32 || &data[..4] != b"PACK" {
+            return Err(format!("{} is not a valid pack", path));
+        }
+
+        let u32_at = |pos: usize| u32::from_be_bytes(index[pos..pos + 4].try_into().unwrap());
+        let count = u32_at(8 + 255 * 4) as usize;
+        let hashes = 8 + 256 * 4;
+        let offsets = hashes + count * 24;
+        let large_offsets = offsets + count * 4;
+        if index.len() < large_offsets + 40 {
+            return Err(bad_index());
+        }
+
+        let mut entries = Vec::with_capacity(count);
+        for i in 0..count {
+            let hash = ObjectId::from_bytes(&index[hashes + i * 20..hashes + i * 20 + 20])
+                .ok_or_else(bad_index)?;
+            let offset = u32_at(offsets + i * 4);
+            // the most significant bit marks offsets stored in the table of 8 byte offsets
+            let offset = match offset & 0x8000_0000 {
+                0 => offset as u64,
+                _ => {
+                    let pos = large_offsets + (offset & 0x7fff_ffff) as usize * 8;
+                    let bytes = index.get(pos..pos + 8).ok_or_else(bad_index)?;
+                    u64::from_be_bytes(bytes.try_into().unwrap())
+                }
+            };
+            entries.push((hash, offset));
+        }
+
+        let end = index.len() - 40;
+        Ok(Self {
+            path,
+            data,
+            entries,
+            index_checksum: index[end..end + 20].to_vec(),
+        })
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    /// the hashes and offsets of all objects in the pack, sorted by hash
+    pub fn entries(&self) -> &Vec<(ObjectId, u64)> {
+        &self.entries
+    }
+
+    pub fn contains(&self, id: &ObjectId) -> bool {
+        self.offset(id).is_some()
+    }
+
+    /// the ids starting with a lowercase abbreviation, found with a binary search of the index
+    pub fn find_prefix(&self, prefix: &str) -> Vec<ObjectId> {
+        let start = match ObjectId::prefix_start(prefix) {
+            Some(start) => start,
+            None => return vec![],
+        };
+        let first = self.entries.partition_point(|(id, _)| *id < start);
+        self.entries[first..]
+            .iter()
+            .map(|(id, _)| *id)
+            .take_while(|id| id.starts_with(prefix))
+            .collect()
+    }
+
+    fn offset(&self, id: &ObjectId) -> Option<u64> {
+        let pos = self.entries.binary_search_by(|(e, _)| e.cmp(id)).ok()?;
+        Some(self.entries[pos].1)
+    }
+
+    /// check the checksum at the end of the pack and that the index belongs to it
+    pub fn verify_checksum(&self) -> Result<(), String> {
+        let end = self.data.len() - 20;
+        let checksum = Sha1::digest(&self.data[..end]);
+        if checksum.as_slice() != &self.data[end..] {
+            return Err(format!("{} pack checksum mismatch", self.path));
+        }
+        if self.index_checksum != self.data[end..] {
+            return Err(format!(
+                "{} pack checksum does not match its index",
+                self.path
+            ));
+        }
+        Ok(())
+    }
+
+    /// the type and content of an object, None if it isn't in the pack or can't be unpacked
+    pub fn read(&self, id: &ObjectId) -> Option<(String, Vec<u8>)> {
+        self.read_at(self.offset(id)?)
+    }
+
+    /// the type and content of the object at an offset with all deltas applied
+    pub fn read_at(&self, offset: u64) -> Option<(String, Vec<u8>)> {
+        let mut deltas = vec![];
+        let mut offset = offset as usize;
+
+        let (object_type, mut content) = loop {
+            if deltas.len() > MAX_DELTA_DEPTH {
+                return None;
+            }
+            let (object_type, start) = self.entry_header(offset)?;
+            match object_type {
+                // the base is given by its distance to this object
+                6 => {
+                    let mut pos = start;
+                    let mut byte = *self.data.get(pos)?;
+                    let mut distance = (byte & 0x7f) as usize;
+                    while byte & 0x80 != 0 {
+                        pos += 1;
+                        byte = *self.data.get(pos)?;
+                        distance = ((distance + 1) << 7) | (byte & 0x7f) as usize;
+                    }
+                    deltas.push(self.inflate(pos + 1)?);
+                    offset = offset.checked_sub(distance)?;
+                }
+                // the base is given by its hash and has to be in this pack too
+                7 => {
+                    let base = ObjectId::from_bytes(self.data.get(start..start + 20)?)?;
+                    deltas.push(self.inflate(start + 20)?);
+                    offset = self.offset(&base)? as usize;
+                }
+                1..=4 => break (object_type, self.inflate(start)?),
+                _ => return None,
+            }
+        };
+
+        while let Some(delta) = deltas.pop() {
+            content = apply_delta(&content, &delta)?;
+        }
+
+        let name = match object_type {
+            1 => "commit",
+            2 => "tree",
+            3 => "blob",
+            _ => "tag",
+        };
+        Some((name.to_string(), content))
+    }
+
+    /// the type of the entry at an offset and where its data starts
+    fn entry_header(&self, offset: usize) -> Option<(u8, usize)> {
+        let mut pos = offset;
+        let mut byte = *self.data.get(pos)?;
+        let object_type = (byte >> 4) & 0x07;
+        // the size follows, it is not needed as zlib knows where the data ends
+        while byte & 0x80 != 0 {
+            pos += 1;
+            byte = *self.data.get(pos)?;
+        }
+        Some((object_type, pos + 1))
+    }
+
+    fn inflate(&self, start: usize) -> Option<Vec<u8>> {
+        let mut content = vec![];
+        ZlibDecoder::new(self.data.get(start..)?)
+            .read_to_end(&mut content)
+            .ok()?;
+        Some(content)
+    }
+}
+
+/// build an object from its base and a delta of copy and insert instructions
+fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = 0;
+    let mut varint = || {
+        let mut value = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = *delta.get(pos)?;
+            pos += 1;
+            value |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+    };
+    if varint()? != base.len() {
+        return None;
+    }
+    let size = varint()?;
+
+    let mut result = Vec::with_capacity(size);
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+
+        if op & 0x80 == 0 {
+            // insert the next op bytes
+            if op == 0 {
+                return None;
+            }
+            result.extend_from_slice(delta.get(pos..pos + op as usize)?);
+            pos += op as usize;
+            continue;
+        }
+
+        // copy from the base, the bits of op tell which bytes of offset and size follow
+        let mut offset = 0usize;
+        let mut length = 0usize;
+        for i in 0..7 {
+            if op & (1 << i) == 0 {
+                continue;
+            }
+            let byte = *delta.get(pos)? as usize;
+            pos += 1;
+            match i {
+                0..=3 => offset |= byte << (i * 8),
+                _ => length |= byte << ((i - 4) * 8),
+            }
+        }
+        if length == 0 {
+            length = 0x10000;
+        }
+        result.extend_from_slice(base.get(offset..offset + length)?);
+    }
+
+    match result.len() == size {
+        true => Some(result),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    const BASE: &[u8] = b"hello world\n";
+    /// "hello " from the base followed by "there\n"
+    const OFS_DELTA: &[u8] = b"\x0c\x0c\x90\x06\x06there\n";
+    /// "hello " from the base followed by "again\n"
+    const REF_DELTA: &[u8] = b"\x0c\x0c\x90\x06\x06again\n";
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
+        z.write_all(data).unwrap();
+        z.finish().unwrap()
+    }
+
+    /// the type and size of an entry, 4 bits of the size in the first byte and 7 in the next ones
+    fn entry_header(object_type: u8, size: usize) -> Vec<u8> {
+        let mut header = vec![];
+        let mut byte = (object_type << 4) | (size & 0x0f) as u8;
+        let mut size = size >> 4;
+        while size > 0 {
+            header.push(byte | 0x80);
+            byte = (size & 0x7f) as u8;
+            size >>= 7;
+        }
+        header.push(byte);
+        header
+    }
+
+    /// the distance to the base of an ofs delta, most significant byte first
+    fn ofs_distance(mut distance: usize) -> Vec<u8> {
+        let mut bytes = vec![(distance & 0x7f) as u8];
+        distance >>= 7;
+        while distance > 0 {
+            distance -= 1;
+            bytes.insert(0, 0x80 | (distance & 0x7f) as u8);
+            distance >>= 7;
+        }
+        bytes
+    }
+
+    /// write a pack with a blob, an ofs delta and a ref delta against it and its index,
+    /// returns the pack and the ids of the three blobs
+    fn write_pack(name: &str) -> (Pack, [ObjectId; 3]) {
+        let ids = [
+            ObjectId::hash("blob", BASE),
+            ObjectId::hash("blob", b"hello there\n"),
+            ObjectId::hash("blob", b"hello again\n"),
+        ];
+
+        let mut data = b"PACK\0\0\0\x02\0\0\0\x03".to_vec();
+        let base = data.len();
+        data.extend(entry_header(3, BASE.len()));
+        data.extend(deflate(BASE));
+        let ofs = data.len();
+        data.extend(entry_header(6, OFS_DELTA.len()));
+        data.extend(ofs_distance(ofs - base));
+        data.extend(deflate(OFS_DELTA));
+        let refd = data.len();
+        data.extend(entry_header(7, REF_DELTA.len()));
+        data.extend(ids[0].as_bytes());
+        data.extend(deflate(REF_DELTA));
+        let checksum = Sha1::digest(&data).to_vec();
+        data.extend(&checksum);
+
+        let mut entries = [(ids[0], base), (ids[1], ofs), (ids[2], refd)];
+        entries.sort();
+        let mut index = vec![0xff, b't', b'O', b'c', 0, 0, 0, 2];
+        for byte in 0..=255u8 {
+            let count = entries
+                .iter()
+                .filter(|(id, _)| id.as_bytes()[0] <= byte)
+                .count();
+            index.extend((count as u32).to_be_bytes());
+        }
+        entries
+            .iter()
+            .for_each(|(id, _)| index.extend(id.as_bytes()));
+        entries.iter().for_each(|_| index.extend([0; 4]));
+        entries
+            .iter()
+            .for_each(|(_, offset)| index.extend((*offset as u32).to_be_bytes()));
+        index.extend(&checksum);
+        let index_checksum = Sha1::digest(&index).to_vec();
+        index.extend(index_checksum);
+
+        let dir = std::env::temp_dir().join(format!("kyu-pack-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let idx_path = dir.join("pack-test.idx").to_string_lossy().to_string();
+        std::fs::write(&idx_path, index).unwrap();
+        std::fs::write(dir.join("pack-test.pack"), data).unwrap();
+
+        let pack = Pack::open(&idx_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        (pack, ids)
+    }
+
+    #[test]
+    fn objects_and_deltas_are_read() {
+        let (pack, [base, ofs, refd]) = write_pack("read");
+        assert_eq!(pack.read(&base), Some(("blob".into(), BASE.to_vec())));
+        assert_eq!(
+            pack.read(&ofs),
+            Some(("blob".into(), b"hello there\n".to_vec()))
+        );
+        assert_eq!(
+            pack.read(&refd),
+            Some(("blob".into(), b"hello again\n".to_vec()))
+        );
+        assert_eq!(pack.read(&ObjectId::NULL), None);
+        assert!(pack.verify_checksum().is_ok());
+    }
+
+    #[test]
+    fn the_index_is_sorted_and_searched_by_prefix() {
+        let (pack, ids) = write_pack("index");
+        let mut sorted = ids.to_vec();
+        sorted.sort();
+        let listed = pack.entries().iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        assert_eq!(listed, sorted);
+
+        assert!(ids.iter().all(|id| pack.contains(id)));
+        assert_eq!(pack.find_prefix(&format!("{:.6}", ids[1])), vec![ids[1]]);
+        assert_eq!(pack.find_prefix(""), sorted);
+    }
+
+    #[test]
+    fn damaged_packs_are_detected() {
+        let (mut pack, [base, ..]) = write_pack("damaged");
+        let offset = pack.entries().iter().find(|(id, _)| *id == base).unwrap().1;
+        pack.data[offset as usize + 4] ^= 0xff;
+        assert!(pack.verify_checksum().is_err());
+        assert_eq!(pack.read(&base), None);
+
+        assert!(Pack::open("/nonexistent/pack-none.idx").is_err());
+    }
+
+    #[test]
+    fn deltas_copy_and_insert() {
+        assert_eq!(
+            apply_delta(BASE, OFS_DELTA),
+            Some(b"hello there\n".to_vec())
+        );
+        // the size of the base has to match
+        assert_eq!(apply_delta(b"hello", OFS_DELTA), None);
+        // copies outside of the base are rejected
+        assert_eq!(apply_delta(BASE, b"\x0c\x0d\x91\x06\x0d"), None);
+    }
+}
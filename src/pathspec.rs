@@ -0,0 +1,380 @@
+#![allow(dead_code)]
+
+use crate::ignore::wildmatch;
+use crate::repository::{RepError, Repository};
+
+/// a single pattern of a pathspec with its magic
+#[derive(Debug, Clone)]
+struct Item {
+    /// the pattern as given on the command line for error messages
+    original: String,
+    /// the pattern relative to the root of the repository
+    pattern: String,
+    /// :(exclude) / :! / :^ removes matching paths again
+    exclude: bool,
+    /// :(icase) matches case insensitive
+    icase: bool,
+    /// :(literal) treats wildcards as normal characters
+    literal: bool,
+    /// :(glob) makes * stop at slashes and enables **
+    glob: bool,
+}
+
+impl Item {
+    fn parse(rep: &Repository, spec: &str) -> Result<Self, RepError> {
+        let mut item = Item {
+            original: spec.to_string(),
+            pattern: String::new(),
+            exclude: false,
+            icase: false,
+            literal: false,
+            glob: false,
+        };
+        let mut top = false;
+
+        let pattern = if let Some(rest) = spec.strip_prefix(":(") {
+            // long form like :(top,icase)pattern
+            let (magic, pattern) = rest
+                .split_once(')')
+                .ok_or(RepError::InvalidPathspec(spec.to_string()))?;
+            for word in magic.split(',').map(str::trim) {
+                match word {
+                    "top" => top = true,
+                    "exclude" => item.exclude = true,
+                    "icase" => item.icase = true,
+                    "literal" => item.literal = true,
+                    "glob" => item.glob = true,
+                    _ => return Err(RepError::InvalidPathspec(spec.to_string())),
+                }
+            }
+            pattern
+        } else if let Some(rest) = spec.strip_prefix(':') {
+            // short form like :/pattern or :!pattern, optionally ended by another colon
+            let mut rest = rest;
+            while let Some(c) = rest.chars().next() {
+                match c {
+                    '/' => top = true,
+                    '!' | '^' => item.exclude = true,
+                    ':' => {
+                        rest = &rest[1..];
+                        break;
+                    }
+                    _ => break,
+                }
+                rest = &rest[1..];
+            }
+            rest
+        } else {
+            spec
+        };
+
+        if item.literal && item.glob {
+            return Err(RepError::InvalidPathspec(spec.to_string()));
+        }
+
+        item.pattern = match top {
+            true => pattern
+                .trim_matches('/')
+                .trim_start_matches("./")
+                .to_string(),
+            false => rep.relative_path(pattern),
+        };
+        if item.pattern == "." {
+            item.pattern = String::new();
+        }
+
+        Ok(item)
+    }
+
+    fn has_wildcard(&self) -> bool {
+        !self.literal && self.pattern.contains(['*', '?', '[', '\\'])
+    }
+
+    fn fold(&self, text: &str) -> String {
+        match self.icase {
+            true => text.to_lowercase(),
+            false => text.to_string(),
+        }
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        if self.pattern.is_empty() {
+            return true;
+        }
+
+        let pattern = self.fold(&self.pattern);
+        let path = self.fold(path);
+
+        // the path itself or something inside of the folder
+        if path == pattern
+            || path
+                .strip_prefix(&pattern)
+                .map(|rest| rest.starts_with('/'))
+                .unwrap_or(false)
+        {
+            return true;
+        }
+
+        self.has_wildcard() && wildmatch(&pattern, &path, self.glob)
+    }
+
+    /// check if something inside of a folder could match, used to skip folders while walking
+    fn matches_dir(&self, dir: &str) -> bool {
+        if self.pattern.is_empty() {
+            return true;
+        }
+
+        let pattern = self.fold(&self.pattern);
+        let dir = self.fold(dir) + "/";
+
+        let prefix = match self.has_wildcard() {
+            true => pattern[..pattern.find(['*', '?', '[', '\\']).unwrap()].to_string(),
+            false => pattern + "/",
+        };
+
+        prefix.starts_with(&dir) || dir.starts_with(&prefix)
+    }
+}
+
+/// the paths given to a command, relative to the current directory unless :(top) is used
+#[derive(Debug, Clone, Default)]
+pub struct Pathspec {
+    items: Vec<Item>,
+}
+
+impl Pathspec {
+    /// a pathspec matching everything
+    pub fn all() -> Self {
+        Self { items: vec![] }
+    }
+
+    pub fn parse(rep: &Repository, specs: &[String]) -> Result<Self, RepError> {
+        let items = specs
+            .iter()
+            .map(|spec| Item::parse(rep, spec))
+            .collect::<Result<Vec<Item>, RepError>>()?;
+
+        Ok(Self { items })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// check if a path relative to the root is selected
+    /// without any positive pattern every path is selected unless it is excluded
+    pub fn matches(&self, path: &str) -> bool {
+        let mut includes = self.items.iter().filter(|i| !i.exclude).peekable();
+        let included = includes.peek().is_none() || includes.any(|i| i.matches(path));
+
+        included && !self.items.iter().any(|i| i.exclude && i.matches(path))
+    }
+
+    /// check if a folder has to be entered because something inside of it could match
+    pub fn matches_dir(&self, dir: &str) -> bool {
+        let mut includes = self.items.iter().filter(|i| !i.exclude).peekable();
+        let included = includes.peek().is_none() || includes.any(|i| i.matches_dir(dir));
+
+        // a folder can only be skipped if it is excluded as a whole
+        included && !self.items.iter().any(|i| i.exclude && i.matches(dir))
+    }
+
+    /// the positive patterns which dont match any of the paths, for "did not match" errors
+    pub fn unmatched<'a>(&self, paths: impl Iterator<Item = &'a str> + Clone) -> Vec<String> {
+        self.items
+            .iter()
+            .filter(|i| !i.exclude && !paths.clone().any(|p| i.matches(p)))
+            .map(|i| i.original.clone())
+            .collect()
+    }
+}
+
+/// quote a path like git does with core.quotePath when it contains special characters
+pub fn quote_path(path: &str) -> String {
+    quote_bytes(path.as_bytes())
+}
+
+/// quote a path which may not be valid UTF-8, the bytes outside of ASCII are shown in octal
+pub fn quote_bytes(path: &[u8]) -> String {
+    let needs_quotes = path
+        .iter()
+        .any(|&b| !(0x20..0x7f).contains(&b) || b == b'"' || b == b'\\');
+    if !needs_quotes {
+        return path.iter().map(|&b| b as char).collect();
+    }
+
+    let mut quoted = String::from("\"");
+    for &b in path {
+        match b {
+            0x07 => quoted.push_str("\\a"),
+            0x08 => quoted.push_str("\\b"),
+            b'\t' => quoted.push_str("\\t"),
+            b'\n' => quoted.push_str("\\n"),
+            0x0b => quoted.push_str("\\v"),
+            0x0c => quoted.push_str("\\f"),
+            b'\r' => quoted.push_str("\\r"),
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b if !(0x20..0x7f).contains(&b) => quoted.push_str(&format!("\\{:03o}", b)),
+            b => quoted.push(b as char),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// a path relative to the root shown relative to a folder (the current directory) like git does
+pub fn display_path(path: &str, prefix: &str) -> String {
+    let path_parts = path.split('/').collect::<Vec<&str>>();
+    let prefix_parts = prefix
+        .split('/')
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<&str>>();
+    let common = path_parts
+        .iter()
+        .zip(prefix_parts.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let relative = "../".repeat(prefix_parts.len() - common) + &path_parts[common..].join("/");
+    match relative.is_empty() {
+        true => ".".to_string(),
+        false => relative,
+    }
+}
+
+/// undo the quoting of quote_path, paths without quotes are returned as they are
+pub fn unquote_path(path: &str) -> String {
+    let inner = match path.strip_prefix('"').and_then(|p| p.strip_suffix('"')) {
+        Some(inner) => inner.as_bytes(),
+        None => return path.to_string(),
+    };
+
+    let mut bytes = vec![];
+    let mut i = 0;
+    while i < inner.len() {
+        if inner[i] != b'\\' || i + 1 >= inner.len() {
+            bytes.push(inner[i]);
+            i += 1;
+            continue;
+        }
+
+        i += 1;
+        let escaped = match inner[i] {
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'v' => 0x0b,
+            b'f' => 0x0c,
+            b'r' => b'\r',
+            b'0'..=b'3' if i + 2 < inner.len() => {
+                let octal = std::str::from_utf8(&inner[i..i + 3]).unwrap_or("");
+                match u8::from_str_radix(octal, 8) {
+                    Ok(b) => {
+                        i += 2;
+                        b
+                    }
+                    Err(_) => inner[i],
+                }
+            }
+            c => c,
+        };
+        bytes.push(escaped);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// top magic keeps the patterns independent of the current directory
+    fn pathspec(specs: &[&str]) -> Pathspec {
+        let specs = specs.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+        Pathspec::parse(&Repository::in_memory(), &specs).unwrap()
+    }
+
+    fn invalid(spec: &str) -> bool {
+        let specs = [spec.to_string()];
+        matches!(
+            Pathspec::parse(&Repository::in_memory(), &specs),
+            Err(RepError::InvalidPathspec(s)) if s == spec
+        )
+    }
+
+    #[test]
+    fn paths_match_themselves_and_their_contents() {
+        let spec = pathspec(&[":(top)src"]);
+        assert!(spec.matches("src"));
+        assert!(spec.matches("src/main.rs"));
+        assert!(!spec.matches("srcx"));
+        assert!(pathspec(&[":/"]).matches("anything"));
+    }
+
+    #[test]
+    fn wildcards_depend_on_literal_and_glob_magic() {
+        assert!(pathspec(&[":/*.rs"]).matches("src/main.rs"));
+        assert!(!pathspec(&[":(top,glob)*.rs"]).matches("src/main.rs"));
+        assert!(pathspec(&[":(top,glob)**/*.rs"]).matches("src/main.rs"));
+        assert!(!pathspec(&[":(top,literal)*.rs"]).matches("main.rs"));
+        assert!(pathspec(&[":(top,literal)*.rs"]).matches("*.rs"));
+    }
+
+    #[test]
+    fn icase_ignores_the_case() {
+        assert!(pathspec(&[":(top,icase)README"]).matches("readme"));
+        assert!(!pathspec(&[":/README"]).matches("readme"));
+    }
+
+    #[test]
+    fn excludes_remove_paths_again() {
+        let spec = pathspec(&[":/src", ":/!src/gen", ":(top,exclude)*.txt"]);
+        assert!(spec.matches("src/main.rs"));
+        assert!(!spec.matches("src/gen/table.rs"));
+        assert!(!spec.matches("src/notes.txt"));
+        assert!(!spec.matches("doc/a.md"));
+
+        // without positive patterns everything else is selected
+        let spec = pathspec(&[":/^doc"]);
+        assert!(spec.matches("src/main.rs"));
+        assert!(!spec.matches("doc/a.md"));
+        assert!(!spec.matches_dir("doc"));
+    }
+
+    #[test]
+    fn folders_are_only_entered_if_something_inside_can_match() {
+        let spec = pathspec(&[":/src/*.rs"]);
+        assert!(spec.matches_dir("src"));
+        assert!(!spec.matches_dir("doc"));
+        assert!(pathspec(&[":/src/main.rs"]).matches_dir("src"));
+    }
+
+    #[test]
+    fn unmatched_patterns_are_reported_as_given() {
+        let spec = pathspec(&[":/src", ":(top)doc", ":/!src/gen"]);
+        assert_eq!(
+            spec.unmatched(["src/main.rs"].into_iter()),
+            vec![":(top)doc"]
+        );
+    }
+
+    #[test]
+    fn unknown_or_conflicting_magic_is_invalid() {
+        assert!(invalid(":(bogus)x"));
+        assert!(invalid(":(top"));
+        assert!(invalid(":(literal,glob)x"));
+    }
+
+    #[test]
+    fn paths_are_quoted_like_git() {
+        let path = "a \"b\"\t\u{e9}";
+        assert_eq!(quote_path("plain/path"), "plain/path");
+        assert_eq!(quote_path(path), "\"a \\\"b\\\"\\t\\303\\251\"");
+        assert_eq!(unquote_path(&quote_path(path)), path);
+        assert_eq!(display_path("src/main.rs", "doc/"), "../src/main.rs");
+        assert_eq!(display_path("src", "src"), ".");
+    }
+}
@@ -0,0 +1,516 @@
+#![allow(dead_code)]
+
+use std::collections::HashSet;
+use std::os::unix::fs::MetadataExt;
+
+use crate::fsck::links;
+use crate::index::Index;
+use crate::odb::{Location, StoredObject};
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// only show what would be removed (-n)
+    pub dry_run: bool,
+    /// show the removed objects (-v)
+    pub verbose: bool,
+    /// only loose objects older than this are removed, None removes all unreachable ones
+    pub expire: Option<i64>,
+}
+
+/// paths are shown relative to the root of the working tree like git does
+fn display_path(rep: &Repository, path: &str) -> String {
+    match path.strip_prefix(&(rep.get_workdir().clone() + "/")) {
+        Some(relative) => relative.to_string(),
+        None => path.to_string(),
+    }
+}
+
+/// sizes like git shows them with -H: 2.26 KiB, rounded to two digits
+fn human_bytes(bytes: u64) -> String {
+    let units = [
+        (1 << 30, 5368709, "GiB"),
+        (1 << 20, 5243, "MiB"),
+        (1 << 10, 5, "KiB"),
+    ];
+    for (size, rounding, unit) in units {
+        if bytes > size {
+            let bytes = bytes + rounding;
+            let fraction = (bytes % size) * 100 / size;
+            return format!("{}.{:02} {}", bytes / size, fraction, unit);
+        }
+    }
+    match bytes {
+        1 => "1 byte".to_string(),
+        _ => format!("{} bytes", bytes),
+    }
+}
+
+/// the modification time of a file in seconds since the epoch
+fn mtime(path: &str) -> i64 {
+    std::fs::metadata(path).map(|m| m.mtime()).unwrap_or(0)
+}
+
+/// the stored objects of the repository itself, the ones of its alternates are left alone
+fn own_objects(rep: &Repository) -> Vec<StoredObject> {
+    let dir = rep.get_gitdir().clone() + "/objects/";
+    rep.objects()
+        .stored()
+        .into_iter()
+        .filter(|object| match &object.location {
+            Location::Loose(path) | Location::Packed(path, _) => path.starts_with(&dir),
+            Location::Memory => true,
+        })
+        .collect()
+}
+
+/// the packs the objects are in, in the order they are stored
+fn pack_paths(stored: &[StoredObject]) -> Vec<&str> {
+    let mut paths: Vec<&str> = vec![];
+    for object in stored {
+        if let Location::Packed(path, _) = &object.location {
+            if !paths.contains(&path.as_str()) {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
+
+fn packed_ids(stored: &[StoredObject]) -> HashSet<ObjectId> {
+    stored
+        .iter()
+        .filter(|object| matches!(object.location, Location::Packed(..)))
+        .map(|object| object.id)
+        .collect()
+}
+
+/// the files in the folders of loose objects which aren't objects as (path, name)
+fn loose_cruft(rep: &Repository) -> Vec<(String, String)> {
+    let mut cruft = vec![];
+    let objects = rep.get_gitdir().clone() + "/objects";
+    let mut dirs = std::fs::read_dir(&objects)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| name.len() == 2 && name.chars().all(|c| c.is_ascii_hexdigit()))
+        .collect::<Vec<String>>();
+    dirs.sort();
+
+    for dir in dirs {
+        let mut files = std::fs::read_dir(objects.clone() + "/" + &dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|name| name.len() != 38 || !name.chars().all(|c| c.is_ascii_hexdigit()))
+            .collect::<Vec<String>>();
+        files.sort();
+        for name in files {
+            cruft.push((objects.clone() + "/" + &dir + "/" + &name, name));
+        }
+    }
+
+    cruft
+}
+
+/// the files in objects/pack which don't belong to a pack as (path, reason)
+fn pack_garbage(rep: &Repository) -> Vec<(String, &'static str)> {
+    let dir = rep.get_gitdir().clone() + "/objects/pack/";
+    let mut names = std::fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect::<Vec<String>>();
+    names.sort();
+    let exists = |name: &str| names.iter().any(|n| n == name);
+
+    let mut garbage = vec![];
+    for name in names.iter() {
+        let (base, extension) = match name.rsplit_once('.') {
+            Some(split) => split,
+            None => {
+                garbage.push((dir.clone() + name, "garbage found"));
+                continue;
+            }
+        };
+        let problem = match extension {
+            "pack" if !exists(&(base.to_string() + ".idx")) => Some("no corresponding .idx"),
+            "idx" if !exists(&(base.to_string() + ".pack")) => Some("no corresponding .pack"),
+            "pack" | "idx" => None,
+            "keep" | "bitmap" | "promisor" | "mtimes" | "rev"
+                if exists(&(base.to_string() + ".pack")) =>
+            {
+                None
+            }
+            _ => Some("garbage found"),
+        };
+        if let Some(problem) = problem {
+            garbage.push((dir.clone() + name, problem));
+        }
+    }
+
+    garbage
+}
+
+/// show the number of loose objects and the disk space they use, with verbose also packs and garbage
+pub fn count_objects(rep: &Repository, verbose: bool, human: bool) -> Result<(), RepError> {
+    let stored = own_objects(rep);
+    let packs = pack_paths(&stored);
+    let packed = packed_ids(&stored);
+    let paths = loose_paths(&stored);
+
+    // loose objects are counted with the blocks they occupy, everything else by its size
+    let mut loose_size = 0;
+    let mut packable = 0;
+    for (hash, path) in paths.iter() {
+        if let Ok(meta) = std::fs::metadata(path) {
+            loose_size += meta.blocks() * 512;
+        }
+        if packed.contains(hash) {
+            packable += 1;
+        }
+    }
+
+    let size = |bytes: u64| match human {
+        true => human_bytes(bytes),
+        false => (bytes / 1024).to_string(),
+    };
+    if !verbose {
+        match human {
+            true => outln!("{} objects, {}", paths.len(), size(loose_size))?,
+            false => outln!("{} objects, {} kilobytes", paths.len(), size(loose_size))?,
+        }
+        return Ok(());
+    }
+
+    let file_size = |path: &str| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let mut garbage = pack_garbage(rep);
+    for (path, _) in loose_cruft(rep) {
+        garbage.push((path, "garbage found"));
+    }
+    for (path, problem) in garbage.iter() {
+        errln!("warning: {}: {}", problem, display_path(rep, path));
+    }
+
+    let pack_size = packs
+        .iter()
+        .map(|p| file_size(p) + file_size(&p.replace(".pack", ".idx")))
+        .sum();
+    let garbage_size = garbage.iter().map(|(path, _)| file_size(path)).sum();
+
+    outln!("count: {}", paths.len())?;
+    outln!("size: {}", size(loose_size))?;
+    outln!(
+        "in-pack: {}",
+        stored
+            .iter()
+            .filter(|object| matches!(object.location, Location::Packed(..)))
+            .count()
+    )?;
+    outln!("packs: {}", packs.len())?;
+    outln!("size-pack: {}", size(pack_size))?;
+    outln!("prune-packable: {}", packable)?;
+    outln!("garbage: {}", garbage.len())?;
+    outln!("size-garbage: {}", size(garbage_size))?;
+    Ok(())
+}
+
+/// the ids of the loose objects with the files they are stored in
+fn loose_paths(stored: &[StoredObject]) -> Vec<(ObjectId, String)> {
+    stored
+        .iter()
+        .filter_map(|object| match &object.location {
+            Location::Loose(path) => Some((object.id, path.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// walks objects from starting points
+struct Reachability<'a> {
+    rep: &'a Repository,
+    reachable: HashSet<ObjectId>,
+}
+
+impl Reachability<'_> {
+    /// mark everything reachable from the roots given as (hash, expected type), a missing tree,
+    /// commit or tag is an error unless missing objects are allowed
+    fn walk(
+        &mut self,
+        roots: Vec<(ObjectId, String)>,
+        allow_missing: bool,
+    ) -> Result<(), RepError> {
+        let mut pending = roots;
+
+        while let Some((hash, object_type)) = pending.pop() {
+            if !self.reachable.insert(hash) {
+                continue;
+            }
+            match self.rep.objects().read(&hash) {
+                Ok((object_type, data)) => pending.extend(links(&object_type, &data)),
+                // blobs don't link to anything, so a missing one doesn't hide other objects
+                Err(_) if allow_missing || object_type == "blob" => {}
+                Err(_) => return Err(RepError::BadObject(object_type, hash.to_string())),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// the starting points of the walk: HEAD, the refs, their reflogs and the index,
+/// stashes are kept by the reflog of refs/stash
+/// a ref or HEAD which can't be resolved is an error, pruning without it could lose its objects
+fn default_roots(rep: &Repository) -> Result<Vec<(ObjectId, String)>, RepError> {
+    let mut roots = vec![];
+    let object = |hash: ObjectId| (hash, "object".to_string());
+    let resolve = |name: &str| {
+        rep.ref_resolve(name)
+            .map_err(|_| RepError::BadObject("object".to_string(), name.to_string()))
+    };
+
+    // the branch of a new repository doesn't exist yet and has nothing to keep
+    let unborn = rep
+        .get_head_ref()
+        .is_some_and(|head| rep.full_ref_name(&head).is_none());
+    if !unborn {
+        roots.push(object(resolve("HEAD")?));
+    }
+    for name in rep.ref_names() {
+        roots.push(object(resolve(&name)?));
+    }
+
+    for name in rep.reflog_names() {
+        for entry in rep.read_reflog(&name) {
+            for hash in [entry.old, entry.new] {
+                if !hash.is_null() {
+                    roots.push(object(hash));
+                }
+            }
+        }
+    }
+
+    let index = Index::load(rep)?;
+    for entry in index.get_entries() {
+        if entry.get_mode() != "160000" {
+            roots.push((*entry.get_hash(), "blob".to_string()));
+        }
+    }
+
+    Ok(roots)
+}
+
+/// remove a file of the objects folder and the folder of loose objects it is in if it is empty now,
+/// used for the files which aren't objects
+fn remove_object_file(path: &str) -> Result<(), RepError> {
+    std::fs::remove_file(path).map_err(|e| RepError::Io(path.to_string(), e))?;
+
+    let dir = match std::path::Path::new(path).parent() {
+        Some(dir) => dir,
+        None => return Ok(()),
+    };
+    let name = dir.file_name().map(|n| n.to_string_lossy().to_string());
+    if !name.is_some_and(|n| n.len() == 2 && n.chars().all(|c| c.is_ascii_hexdigit())) {
+        return Ok(());
+    }
+    let io_error = |e| RepError::Io(dir.to_string_lossy().to_string(), e);
+    if std::fs::read_dir(dir).map_err(io_error)?.next().is_none() {
+        std::fs::remove_dir(dir).map_err(io_error)?;
+    }
+    Ok(())
+}
+
+/// remove the loose objects which are unreachable from the refs, reflogs, index and the heads
+/// given, also loose objects which are in a pack anyway
+pub fn prune(rep: &Repository, heads: &[String], opts: &Options) -> Result<(), RepError> {
+    let mut roots = default_roots(rep)?;
+    for head in heads {
+        roots.push((rep.ref_resolve(head)?, "object".to_string()));
+    }
+
+    let mut walk = Reachability {
+        rep,
+        reachable: HashSet::new(),
+    };
+    walk.walk(roots, false)?;
+
+    // unreachable objects which are too new to be removed keep the objects they link to,
+    // objects kept in memory have no age
+    let expired = |path: &str| opts.expire.is_none_or(|expire| mtime(path) <= expire);
+    let stored = own_objects(rep);
+    let unpacked = stored
+        .iter()
+        .filter(|object| !matches!(object.location, Location::Packed(..)))
+        .collect::<Vec<&StoredObject>>();
+    let recent = unpacked
+        .iter()
+        .filter(|object| !walk.reachable.contains(&object.id))
+        .filter(|object| match &object.location {
+            Location::Loose(path) => !expired(path),
+            _ => false,
+        })
+        .map(|object| (object.id, "object".to_string()))
+        .collect::<Vec<(ObjectId, String)>>();
+    walk.walk(recent, true)?;
+
+    let mut remaining = vec![];
+    for object in unpacked {
+        if walk.reachable.contains(&object.id) {
+            remaining.push(object);
+            continue;
+        }
+
+        if opts.dry_run || opts.verbose {
+            let object_type = rep
+                .objects()
+                .read_header(&object.id)
+                .map(|(object_type, _)| object_type)
+                .unwrap_or("unknown".to_string());
+            outln!("{} {}", object.id, object_type)?;
+        }
+        if !opts.dry_run {
+            rep.objects().remove_stored(object)?;
+        }
+    }
+
+    // loose copies of packed objects aren't needed
+    let packed = packed_ids(&stored);
+    for object in remaining {
+        if !packed.contains(&object.id) {
+            continue;
+        }
+        match (opts.dry_run, &object.location) {
+            (true, Location::Loose(path)) => outln!("rm -f {}", display_path(rep, path))?,
+            (true, _) => outln!("rm -f {}", object.id)?,
+            (false, _) => rep.objects().remove_stored(object)?,
+        }
+    }
+
+    // leftovers of interrupted writes
+    let mut temporary = vec![];
+    for (path, name) in loose_cruft(rep) {
+        match name.starts_with("tmp_obj_") {
+            true => temporary.push(path),
+            false => errln!("bad sha1 file: {}", display_path(rep, &path)),
+        }
+    }
+    for dir in ["/objects/", "/objects/pack/"] {
+        let dir = rep.get_gitdir().clone() + dir;
+        let mut files = std::fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|e| e.path().is_file())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with("tmp_"))
+            .map(|name| dir.clone() + &name)
+            .collect::<Vec<String>>();
+        files.sort();
+        temporary.extend(files);
+    }
+    for path in temporary {
+        if !expired(&path) {
+            continue;
+        }
+        if opts.dry_run || opts.verbose {
+            outln!("Removing stale temporary file {}", display_path(rep, &path))?;
+        }
+        if !opts.dry_run {
+            remove_object_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::Blob;
+
+    fn blob(rep: &Repository, content: &str) -> ObjectId {
+        Blob::from_data(content.as_bytes().to_vec())
+            .save(rep)
+            .unwrap()
+    }
+
+    #[test]
+    fn only_unreachable_objects_are_removed() {
+        let rep = Repository::temporary("prune-unreachable");
+        let commit = rep.commit_files(&[("a.txt", "a\n")], "first");
+        let dangling = blob(&rep, "dangling\n");
+        std::fs::write(rep.get_workdir().clone() + "/b.txt", "staged\n").unwrap();
+        crate::add::add(&rep, &[rep.get_workdir().clone() + "/b.txt"]).unwrap();
+        let staged = blob(&rep, "staged\n");
+
+        let opts = Options {
+            dry_run: true,
+            ..Default::default()
+        };
+        prune(&rep, &[], &opts).unwrap();
+        assert!(rep.objects().exists(&dangling));
+
+        // objects newer than the expiry date are kept
+        let opts = Options {
+            expire: Some(0),
+            ..Default::default()
+        };
+        prune(&rep, &[], &opts).unwrap();
+        assert!(rep.objects().exists(&dangling));
+
+        prune(&rep, &[], &Options::default()).unwrap();
+        assert!(!rep.objects().exists(&dangling));
+        assert!(rep.objects().exists(&commit) && rep.objects().exists(&staged));
+        let tree = rep.peel(&commit, "tree").unwrap();
+        assert!(rep.objects().exists(&tree));
+    }
+
+    #[test]
+    fn heads_and_reflogs_keep_objects() {
+        let rep = Repository::temporary("prune-reflogs");
+        let first = rep.commit_files(&[("a.txt", "a\n")], "first");
+        let kept = blob(&rep, "kept\n");
+        let second = rep.commit_files(&[("a.txt", "b\n")], "second");
+        rep.update_head(&first).unwrap();
+        rep.append_reflog("HEAD", &second, &first, "reset: moving to HEAD~1")
+            .unwrap();
+
+        prune(&rep, &[kept.to_string()], &Options::default()).unwrap();
+        assert!(rep.objects().exists(&kept) && rep.objects().exists(&second));
+
+        prune(&rep, &[], &Options::default()).unwrap();
+        assert!(!rep.objects().exists(&kept) && rep.objects().exists(&second));
+    }
+
+    #[test]
+    fn broken_roots_stop_the_prune() {
+        let rep = Repository::temporary("prune-broken");
+        rep.commit_files(&[("a.txt", "a\n")], "first");
+        let dangling = blob(&rep, "dangling\n");
+
+        let broken = rep.get_gitdir().clone() + "/refs/heads/broken";
+        std::fs::write(&broken, "nonsense\n").unwrap();
+        assert!(matches!(
+            prune(&rep, &[], &Options::default()),
+            Err(RepError::BadObject(..))
+        ));
+        assert!(rep.objects().exists(&dangling));
+        std::fs::remove_file(&broken).unwrap();
+
+        std::fs::write(rep.get_gitdir().clone() + "/index", "DIRC broken").unwrap();
+        assert!(prune(&rep, &[], &Options::default()).is_err());
+        assert!(rep.objects().exists(&dangling));
+    }
+
+    #[test]
+    fn sizes_are_shown_like_git() {
+        assert_eq!(human_bytes(1), "1 byte");
+        assert_eq!(human_bytes(1000), "1000 bytes");
+        assert_eq!(human_bytes(2314), "2.26 KiB");
+        assert_eq!(human_bytes(3 << 20), "3.00 MiB");
+    }
+}
@@ -0,0 +1,893 @@
+#![allow(dead_code)]
+
+use crate::index::{reset_workdir, Index};
+use crate::objects::{Commit, KVLM};
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+use crate::revwalk::{resolve_commit, rev_list};
+use crate::sequencer::{
+    commit_tree, load_commit, merge_commit, short, subject, write_file, Action,
+};
+use crate::signature::Signature;
+
+/// the commands of a rebase todo list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Pick,
+    Reword,
+    Edit,
+    Squash,
+    Fixup,
+    Drop,
+    Exec,
+    Break,
+}
+
+impl Command {
+    fn name(&self) -> &str {
+        match self {
+            Command::Pick => "pick",
+            Command::Reword => "reword",
+            Command::Edit => "edit",
+            Command::Squash => "squash",
+            Command::Fixup => "fixup",
+            Command::Drop => "drop",
+            Command::Exec => "exec",
+            Command::Break => "break",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "p" | "pick" => Command::Pick,
+            "r" | "reword" => Command::Reword,
+            "e" | "edit" => Command::Edit,
+            "s" | "squash" => Command::Squash,
+            "f" | "fixup" => Command::Fixup,
+            "d" | "drop" => Command::Drop,
+            "x" | "exec" => Command::Exec,
+            "b" | "break" => Command::Break,
+            _ => return None,
+        })
+    }
+
+    /// commands which take a commit as argument
+    fn takes_commit(&self) -> bool {
+        !matches!(self, Command::Exec | Command::Break)
+    }
+}
+
+/// a line of the todo list, commit is null for the commands which don't take one
+/// and arg is the command to execute
+#[derive(Debug, Clone)]
+struct TodoItem {
+    command: Command,
+    commit: ObjectId,
+    arg: String,
+}
+
+impl TodoItem {
+    fn to_line(&self, rep: &Repository, abbrev: bool) -> String {
+        if !self.command.takes_commit() {
+            return format!("{} {}", self.command.name(), self.arg)
+                .trim_end()
+                .to_string();
+        }
+
+        let hash = match abbrev {
+            true => short(&self.commit),
+            false => self.commit.to_string(),
+        };
+        format!(
+            "{} {} {}",
+            self.command.name(),
+            hash,
+            subject(rep, &self.commit)
+        )
+    }
+}
+
+pub struct Options {
+    /// the new base, defaults to the upstream
+    pub onto: Option<String>,
+    /// let the user edit the todo list with the sequence editor
+    pub interactive: bool,
+    /// move fixup! / squash! commits after the commit they belong to
+    pub autosquash: bool,
+    /// commands to run after every commit
+    pub exec: Vec<String>,
+}
+
+/// the state of a running rebase in .git/rebase-merge (the same layout stock git uses)
+struct State<'a> {
+    rep: &'a Repository,
+    dir: String,
+}
+
+impl<'a> State<'a> {
+    fn new(rep: &'a Repository) -> Self {
+        Self {
+            rep,
+            dir: rep.get_gitdir().clone() + "/rebase-merge",
+        }
+    }
+
+    fn in_progress(&self) -> bool {
+        std::path::Path::new(&self.dir).exists()
+    }
+
+    fn path(&self, file: &str) -> String {
+        self.dir.clone() + "/" + file
+    }
+
+    fn read(&self, file: &str) -> Option<String> {
+        std::fs::read_to_string(self.path(file))
+            .ok()
+            .map(|s| s.trim().to_string())
+    }
+
+    fn write(&self, file: &str, content: &str) -> Result<(), RepError> {
+        write_file(self.path(file), content.to_string() + "\n")
+    }
+
+    fn remove(&self, file: &str) {
+        let _ = std::fs::remove_file(self.path(file));
+    }
+
+    fn read_todo(&self, file: &str) -> Result<Vec<TodoItem>, String> {
+        let todo = std::fs::read_to_string(self.path(file)).unwrap_or_default();
+        parse_todo(self.rep, &todo)
+    }
+
+    fn write_todo(&self, file: &str, todo: &[TodoItem]) -> Result<(), RepError> {
+        let todo = todo
+            .iter()
+            .map(|item| item.to_line(self.rep, false) + "\n")
+            .collect::<String>();
+        write_file(self.path(file), todo)
+    }
+
+    /// move the next item from the todo list to the done list
+    fn pop_todo(&self) -> Result<Option<TodoItem>, RepError> {
+        let mut todo = self.read_todo("git-rebase-todo").unwrap_or_default();
+        if todo.is_empty() {
+            return Ok(None);
+        }
+        let item = todo.remove(0);
+        self.write_todo("git-rebase-todo", &todo)?;
+
+        let mut done = std::fs::read_to_string(self.path("done")).unwrap_or_default();
+        done.push_str(&(item.to_line(self.rep, false) + "\n"));
+        write_file(self.path("done"), done)?;
+
+        let msgnum = self.read("msgnum").and_then(|n| n.parse::<usize>().ok());
+        self.write("msgnum", &(msgnum.unwrap_or(0) + 1).to_string())?;
+
+        Ok(Some(item))
+    }
+
+    /// the item which was executed last
+    fn last_done(&self) -> Option<TodoItem> {
+        self.read_todo("done").ok()?.pop()
+    }
+
+    fn cleanup(&self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+        for file in ["REBASE_HEAD", "MERGE_MSG"] {
+            let _ = std::fs::remove_file(self.rep.get_gitdir().clone() + "/" + file);
+        }
+    }
+}
+
+fn parse_todo(rep: &Repository, todo: &str) -> Result<Vec<TodoItem>, String> {
+    let mut items = vec![];
+
+    for line in todo.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let command = Command::parse(name).ok_or_else(|| format!("invalid command '{}'", name))?;
+
+        let item = if command.takes_commit() {
+            let hash = rest.split_whitespace().next().unwrap_or("");
+            TodoItem {
+                command,
+                commit: resolve_commit(rep, hash)
+                    .map_err(|_| format!("invalid commit '{}'", hash))?,
+                arg: String::new(),
+            }
+        } else {
+            TodoItem {
+                command,
+                commit: ObjectId::NULL,
+                arg: rest.trim().to_string(),
+            }
+        };
+
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
+/// reorder fixup! / squash! commits so they follow the commit they refer to
+fn autosquash(rep: &Repository, todo: Vec<TodoItem>) -> Vec<TodoItem> {
+    let mut result: Vec<TodoItem> = vec![];
+    let mut fixups: Vec<(usize, TodoItem)> = vec![];
+
+    for item in todo {
+        let subj = subject(rep, &item.commit);
+        let target = [
+            ("fixup! ", Command::Fixup),
+            ("amend! ", Command::Fixup),
+            ("squash! ", Command::Squash),
+        ]
+        .iter()
+        .find_map(|(prefix, command)| subj.strip_prefix(prefix).map(|t| (t.to_string(), *command)));
+
+        let position = target.as_ref().and_then(|(target, _)| {
+            result.iter().position(|i| {
+                i.command == Command::Pick
+                    && (subject(rep, &i.commit) == *target
+                        || i.commit.to_string().starts_with(target.as_str()))
+            })
+        });
+
+        match (target, position) {
+            (Some((_, command)), Some(pos)) => fixups.push((pos, TodoItem { command, ..item })),
+            _ => result.push(item),
+        }
+    }
+
+    // insert from the back so the positions stay valid, fixups for the same commit keep their order
+    fixups.sort_by_key(|(pos, _)| *pos);
+    for (pos, item) in fixups.into_iter().rev() {
+        result.insert(pos + 1, item);
+    }
+
+    result
+}
+
+/// add the exec commands after every commit (and its fixups)
+fn add_exec(todo: Vec<TodoItem>, exec: &[String]) -> Vec<TodoItem> {
+    let mut result = vec![];
+
+    for i in 0..todo.len() {
+        result.push(todo[i].clone());
+
+        let next_is_fixup = todo
+            .get(i + 1)
+            .map(|n| matches!(n.command, Command::Fixup | Command::Squash))
+            .unwrap_or(false);
+        if todo[i].command.takes_commit() && !next_is_fixup {
+            result.extend(exec.iter().map(|cmd| TodoItem {
+                command: Command::Exec,
+                commit: ObjectId::NULL,
+                arg: cmd.clone(),
+            }));
+        }
+    }
+
+    result
+}
+
+/// open a file in the configured editor, returns false if the editor failed
+fn launch_editor(rep: &Repository, path: &str, sequence: bool) -> bool {
+    let editor = rep.get_editor(sequence);
+
+    std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(&editor)
+        .arg(path)
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// let the user edit a commit message, comment lines are removed afterwards
+fn edit_message(rep: &Repository, message: &str) -> Result<String, RepError> {
+    let path = rep.get_gitdir().clone() + "/COMMIT_EDITMSG";
+    write_file(path.clone(), message.to_string())?;
+
+    if !launch_editor(rep, &path, false) {
+        return Ok(strip_comments(message));
+    }
+
+    let edited = std::fs::read_to_string(&path).map_err(|e| RepError::Io(path, e))?;
+    Ok(strip_comments(&edited))
+}
+
+fn strip_comments(message: &str) -> String {
+    let message = message
+        .lines()
+        .filter(|l| !l.starts_with('#'))
+        .map(|l| l.trim_end())
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    message.trim().to_string() + "\n"
+}
+
+fn help_text(upstream: &ObjectId, head: &ObjectId, onto: &ObjectId, count: usize) -> String {
+    format!(
+        "
+# Rebase {}..{} onto {} ({} commands)
+#
+# Commands:
+# p, pick <commit> = use commit
+# r, reword <commit> = use commit, but edit the commit message
+# e, edit <commit> = use commit, but stop for amending
+# s, squash <commit> = use commit, but meld into previous commit
+# f, fixup <commit> = like \"squash\" but keep only the previous
+#                    commit's log message
+# x, exec <command> = run command (the rest of the line) using shell
+# b, break = stop here (continue rebase later with 'kyu rebase --continue')
+# d, drop <commit> = remove commit
+#
+# These lines can be re-ordered; they are executed from top to bottom.
+#
+# If you remove a line here THAT COMMIT WILL BE LOST.
+#
+# However, if you remove everything, the rebase will be aborted.
+#
+",
+        short(upstream),
+        short(head),
+        short(onto),
+        count
+    )
+}
+
+/// start replaying the commits of the current branch which are not in upstream
+pub fn start(
+    rep: &Repository,
+    upstream: &str,
+    branch: Option<String>,
+    opts: Options,
+) -> Result<(), RepError> {
+    let state = State::new(rep);
+    if state.in_progress() {
+        return Err(RepError::InvalidArgument(
+            "It seems that there is already a rebase-merge directory.\nUse 'kyu rebase (--continue | --abort | --skip)' to deal with it."
+                .to_string(),
+        ));
+    }
+
+    let index = Index::load(rep)?;
+    let head = rep.ref_resolve("HEAD")?;
+    if !index.get_conflicts().is_empty()
+        || !index.get_modified(rep).is_empty()
+        || Some(index.write_tree(rep)?) != rep.peel(&head, "tree")
+    {
+        errln!("error: cannot rebase: You have unstaged or uncommitted changes.");
+        errln!("error: Please commit or stash them.");
+        return Ok(());
+    }
+
+    // rebase another branch: switch to it first
+    if let Some(branch) = branch {
+        let hash = resolve_commit(rep, &branch)?;
+        let refname = "refs/heads/".to_string() + &branch;
+        if std::path::Path::new(&(rep.get_gitdir().clone() + "/" + &refname)).exists() {
+            rep.attach_head(&refname)?;
+        } else {
+            rep.detach_head(&hash)?;
+        }
+        reset_workdir(rep, &commit_tree(rep, &hash)?)?;
+    }
+
+    let head = rep.ref_resolve("HEAD")?;
+    let head_name = rep.get_head_ref().unwrap_or("detached HEAD".to_string());
+    let upstream = resolve_commit(rep, upstream)?;
+    let onto = match &opts.onto {
+        Some(onto) => resolve_commit(rep, onto)?,
+        None => upstream,
+    };
+
+    if !opts.interactive
+        && opts.exec.is_empty()
+        && onto == upstream
+        && is_linear_on(rep, &head, &onto)
+    {
+        outln!(
+            "Current branch {} is up to date.",
+            head_name.trim_start_matches("refs/heads/")
+        )?;
+        return Ok(());
+    }
+
+    // merge commits are dropped like git does without --rebase-merges
+    let mut todo = vec![];
+    let commits = rev_list(
+        rep,
+        std::slice::from_ref(&head),
+        std::slice::from_ref(&upstream),
+    );
+    for commit in commits.into_iter().rev() {
+        if load_commit(rep, &commit)?.get_parents().len() <= 1 {
+            todo.push(TodoItem {
+                command: Command::Pick,
+                commit,
+                arg: String::new(),
+            });
+        }
+    }
+
+    let autosquash_config = rep
+        .config_get("rebase", "autosquash")
+        .map(|v| v == "true")
+        .unwrap_or(false);
+    if opts.autosquash || (opts.interactive && autosquash_config) {
+        todo = autosquash(rep, todo);
+    }
+    if !opts.exec.is_empty() {
+        todo = add_exec(todo, &opts.exec);
+    }
+
+    std::fs::create_dir_all(&state.dir).map_err(|e| RepError::Io(state.dir.clone(), e))?;
+
+    if opts.interactive {
+        let path = state.path("git-rebase-todo");
+        let mut content = todo
+            .iter()
+            .map(|item| item.to_line(rep, true) + "\n")
+            .collect::<String>();
+        content.push_str(&help_text(&upstream, &head, &onto, todo.len()));
+        write_file(path.clone(), content)?;
+
+        if !launch_editor(rep, &path, true) {
+            errln!("error: there was a problem with the editor");
+            state.cleanup();
+            return Ok(());
+        }
+
+        todo = match state.read_todo("git-rebase-todo") {
+            Ok(todo) => todo,
+            Err(e) => {
+                errln!("error: {}", e);
+                state.cleanup();
+                return Ok(());
+            }
+        };
+        if todo.is_empty() {
+            outln!("Nothing to do")?;
+            state.cleanup();
+            return Ok(());
+        }
+        if let Some(first) = todo.first() {
+            if matches!(first.command, Command::Squash | Command::Fixup) {
+                errln!(
+                    "error: cannot '{}' without a previous commit",
+                    first.command.name()
+                );
+                state.cleanup();
+                return Ok(());
+            }
+        }
+        write_file(state.path("interactive"), String::new())?;
+    }
+
+    state.write_todo("git-rebase-todo", &todo)?;
+    write_file(state.path("done"), String::new())?;
+    state.write("head-name", &head_name)?;
+    state.write("onto", &onto.to_string())?;
+    state.write("orig-head", &head.to_string())?;
+    state.write("end", &todo.len().to_string())?;
+    state.write("msgnum", "0")?;
+    write_file(
+        rep.get_gitdir().clone() + "/ORIG_HEAD",
+        format!("{}\n", head),
+    )?;
+
+    rep.detach_head(&onto)?;
+    reset_workdir(rep, &commit_tree(rep, &onto)?)?;
+
+    run(rep, &state)
+}
+
+/// true if following the first parents from head reaches onto
+fn is_linear_on(rep: &Repository, head: &ObjectId, onto: &ObjectId) -> bool {
+    let mut commit = *head;
+    loop {
+        if commit == *onto {
+            return true;
+        }
+        let parent = load_commit(rep, &commit)
+            .ok()
+            .and_then(|c| c.get_parents().first().copied());
+        match parent {
+            Some(parent) => commit = parent,
+            None => return false,
+        }
+    }
+}
+
+/// commit the resolved conflicts (or amend an edited commit) and go on
+pub fn continue_(rep: &Repository) -> Result<(), RepError> {
+    let state = State::new(rep);
+    if !state.in_progress() {
+        return Err(RepError::InvalidArgument(
+            "No rebase in progress?".to_string(),
+        ));
+    }
+
+    let index = Index::load(rep)?;
+    if !index.get_conflicts().is_empty() {
+        errln!("error: Committing is not possible because you have unmerged files.");
+        errln!("hint: Fix them up in the work tree, and then use 'kyu add <file>'");
+        errln!("hint: as appropriate to mark resolution.");
+        return Ok(());
+    }
+
+    let rebase_head = std::fs::read_to_string(rep.get_gitdir().clone() + "/REBASE_HEAD")
+        .ok()
+        .map(|h| h.trim().to_string());
+
+    if let (Some(_), Some(item)) = (rebase_head, state.last_done()) {
+        let _ = std::fs::remove_file(rep.get_gitdir().clone() + "/REBASE_HEAD");
+        let _ = std::fs::remove_file(rep.get_gitdir().clone() + "/MERGE_MSG");
+        state.remove("author-script");
+        state.remove("message");
+
+        commit_item(rep, &state, &item)?;
+        if item.command == Command::Edit {
+            stop_for_edit(rep, &state, &item)?;
+            return Ok(());
+        }
+    } else if state.read("amend").is_some() {
+        // amend the commit the rebase stopped at with the staged changes
+        let head = rep.ref_resolve("HEAD")?;
+        let tree = index.write_tree(rep)?;
+        if rep.peel(&head, "tree") != Some(tree) {
+            let commit = load_commit(rep, &head)?;
+            let new = Commit::create(
+                &tree,
+                commit.get_parents(),
+                &commit.get_author(),
+                &rep.get_signature("COMMITTER")?,
+                &commit.get_message(),
+            )
+            .save(rep)?;
+            rep.detach_head(&new)?;
+        }
+        state.remove("amend");
+    }
+
+    run(rep, &state)
+}
+
+/// drop the commit which could not be applied
+pub fn skip(rep: &Repository) -> Result<(), RepError> {
+    let state = State::new(rep);
+    if !state.in_progress() {
+        return Err(RepError::InvalidArgument(
+            "No rebase in progress?".to_string(),
+        ));
+    }
+
+    let head = rep.ref_resolve("HEAD")?;
+    reset_workdir(rep, &commit_tree(rep, &head)?)?;
+    let _ = std::fs::remove_file(rep.get_gitdir().clone() + "/REBASE_HEAD");
+    let _ = std::fs::remove_file(rep.get_gitdir().clone() + "/MERGE_MSG");
+    state.remove("amend");
+
+    run(rep, &state)
+}
+
+/// go back to the branch as it was before the rebase
+pub fn abort(rep: &Repository) -> Result<(), RepError> {
+    let state = State::new(rep);
+    let orig_head = match state
+        .read("orig-head")
+        .and_then(|h| h.parse::<ObjectId>().ok())
+    {
+        Some(orig_head) => orig_head,
+        None => {
+            return Err(RepError::InvalidArgument(
+                "No rebase in progress?".to_string(),
+            ));
+        }
+    };
+    let head_name = state.read("head-name").unwrap_or_default();
+
+    reset_workdir(rep, &commit_tree(rep, &orig_head)?)?;
+    if head_name.starts_with("refs/") {
+        rep.update_ref(&head_name, &orig_head)?;
+        rep.attach_head(&head_name)?;
+    } else {
+        rep.detach_head(&orig_head)?;
+    }
+    state.cleanup();
+
+    Ok(())
+}
+
+/// execute the todo list until it is empty or something needs the user
+fn run(rep: &Repository, state: &State) -> Result<(), RepError> {
+    while let Some(item) = state.pop_todo()? {
+        match item.command {
+            Command::Drop => continue,
+            Command::Break => {
+                outln!("Stopped at {}", short(&rep.ref_resolve("HEAD")?))?;
+                return Ok(());
+            }
+            Command::Exec => {
+                outln!("Executing: {}", item.arg)?;
+                let status = std::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(&item.arg)
+                    .current_dir(rep.get_workdir())
+                    .status();
+
+                if !status.map(|s| s.success()).unwrap_or(false) {
+                    errln!("warning: execution failed: {}", item.arg);
+                    errln!("You can fix the problem, and then run");
+                    errln!();
+                    errln!("  kyu rebase --continue");
+                    return Ok(());
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let head = rep.ref_resolve("HEAD")?;
+        let commit = load_commit(rep, &item.commit)?;
+
+        // the commit is already based on HEAD, it can be reused as it is
+        let fast_forward = matches!(item.command, Command::Pick | Command::Edit)
+            && commit.get_parents().first() == Some(&head);
+        if fast_forward {
+            reset_workdir(rep, &commit.get_tree())?;
+            rep.detach_head(&item.commit)?;
+        } else {
+            let conflicts = merge_commit(rep, Action::Pick, &item.commit)?;
+            if !conflicts.is_empty() {
+                stop_for_conflicts(rep, state, &item, &conflicts)?;
+                return Ok(());
+            }
+            commit_item(rep, state, &item)?;
+        }
+
+        if item.command == Command::Edit {
+            stop_for_edit(rep, state, &item)?;
+            return Ok(());
+        }
+    }
+
+    finish(rep, state)
+}
+
+/// commit the current index for a todo item
+fn commit_item(rep: &Repository, state: &State, item: &TodoItem) -> Result<(), RepError> {
+    let head = rep.ref_resolve("HEAD")?;
+    let tree = Index::load(rep)?.write_tree(rep)?;
+    let commit = load_commit(rep, &item.commit)?;
+    let committer = rep.get_signature("COMMITTER")?;
+
+    let new = match item.command {
+        Command::Squash | Command::Fixup => {
+            let previous = load_commit(rep, &head)?;
+            let combined = format!(
+                "# This is a combination of 2 commits.\n# This is the 1st commit message:\n\n{}\n\n# This is the commit message #2:\n\n{}",
+                previous.get_message().trim_end(),
+                commit.get_message().trim_end()
+            );
+            // only an interactive rebase asks for the message, otherwise both are kept
+            let message = match item.command {
+                Command::Squash if state.read("interactive").is_some() => {
+                    edit_message(rep, &combined)?
+                }
+                Command::Squash => strip_comments(&combined),
+                _ => previous.get_message(),
+            };
+
+            Commit::create(
+                &tree,
+                previous.get_parents(),
+                &previous.get_author(),
+                &committer,
+                &message,
+            )
+        }
+        _ => {
+            if rep.peel(&head, "tree") == Some(tree) {
+                outln!(
+                    "dropping {} {} -- patch contents already upstream",
+                    item.commit,
+                    subject(rep, &item.commit)
+                )?;
+                return Ok(());
+            }
+
+            let message = match item.command {
+                Command::Reword => edit_message(rep, &commit.get_message())?,
+                _ => commit.get_message(),
+            };
+            Commit::create(&tree, &[head], &commit.get_author(), &committer, &message)
+        }
+    };
+
+    rep.detach_head(&new.save(rep)?)?;
+    Ok(())
+}
+
+fn stop_for_edit(rep: &Repository, state: &State, item: &TodoItem) -> Result<(), RepError> {
+    let head = rep.ref_resolve("HEAD")?;
+    state.write("amend", &head.to_string())?;
+    state.write("stopped-sha", &item.commit.to_string())?;
+
+    outln!(
+        "Stopped at {}...  {}",
+        short(&item.commit),
+        subject(rep, &item.commit)
+    )?;
+    outln!("You can amend the commit now, by staging your changes with")?;
+    outln!()?;
+    outln!("  kyu add <files>")?;
+    outln!()?;
+    outln!("Once you are satisfied with your changes, run")?;
+    outln!()?;
+    outln!("  kyu rebase --continue")?;
+    Ok(())
+}
+
+fn stop_for_conflicts(
+    rep: &Repository,
+    state: &State,
+    item: &TodoItem,
+    conflicts: &[String],
+) -> Result<(), RepError> {
+    let commit = load_commit(rep, &item.commit)?;
+    let message = commit.get_message();
+
+    state.write("stopped-sha", &item.commit.to_string())?;
+    state.write("message", message.trim_end())?;
+    state.write("author-script", &author_script(&commit.get_author()))?;
+    write_file(
+        rep.get_gitdir().clone() + "/REBASE_HEAD",
+        format!("{}\n", item.commit),
+    )?;
+    write_file(rep.get_gitdir().clone() + "/MERGE_MSG", message)?;
+
+    for path in conflicts {
+        outln!("CONFLICT (content): Merge conflict in {}", path)?;
+    }
+    errln!(
+        "error: could not apply {}... {}",
+        short(&item.commit),
+        subject(rep, &item.commit)
+    );
+    errln!("hint: Resolve all conflicts manually, mark them as resolved with");
+    errln!("hint: \"kyu add <conflicted_files>\", then run \"kyu rebase --continue\".");
+    errln!("hint: You can instead skip this commit: run \"kyu rebase --skip\".");
+    errln!("hint: To abort and get back to the state before \"kyu rebase\", run \"kyu rebase --abort\".");
+    Ok(())
+}
+
+/// the author of a commit in the shell format git stores in rebase-merge/author-script
+fn author_script(author: &Signature) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "'\\''"));
+
+    format!(
+        "GIT_AUTHOR_NAME={}\nGIT_AUTHOR_EMAIL={}\nGIT_AUTHOR_DATE={}",
+        quote(&author.name),
+        quote(&author.email),
+        quote(&format!("@{} {}", author.seconds, author.timezone()))
+    )
+}
+
+/// move the branch to the rebased commits and attach HEAD again
+fn finish(rep: &Repository, state: &State) -> Result<(), RepError> {
+    let head = rep.ref_resolve("HEAD")?;
+    let head_name = state.read("head-name").unwrap_or_default();
+
+    if head_name.starts_with("refs/") {
+        rep.update_ref(&head_name, &head)?;
+        rep.attach_head(&head_name)?;
+        outln!("Successfully rebased and updated {}.", head_name)?;
+    } else {
+        outln!("Successfully rebased.")?;
+    }
+    state.cleanup();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reset::{reset, Mode};
+
+    fn options() -> Options {
+        Options {
+            onto: None,
+            interactive: false,
+            autosquash: false,
+            exec: vec![],
+        }
+    }
+
+    fn read(rep: &Repository, name: &str) -> Option<String> {
+        std::fs::read_to_string(rep.get_workdir().clone() + "/" + name).ok()
+    }
+
+    /// master gets a commit changing a.txt, topic one changing the file given
+    fn diverge(rep: &Repository, topic_file: (&str, &str)) -> (ObjectId, ObjectId) {
+        let first = rep.commit_files(&[("a.txt", "a\n")], "first");
+        rep.update_ref("refs/heads/topic", &first).unwrap();
+        let master = rep.commit_files(&[("a.txt", "master\n")], "on master");
+        rep.attach_head("refs/heads/topic").unwrap();
+        reset(rep, "topic", Mode::Hard).unwrap();
+        let topic = rep.commit_files(&[topic_file], "on topic");
+        (master, topic)
+    }
+
+    #[test]
+    fn commits_are_replayed_on_the_upstream() {
+        let rep = Repository::temporary("rebase-replay");
+        let (master, _) = diverge(&rep, ("b.txt", "b\n"));
+
+        start(&rep, "master", None, options()).unwrap();
+
+        let head = rep.ref_resolve("HEAD").unwrap();
+        assert_eq!(rep.get_head_ref(), Some("refs/heads/topic".to_string()));
+        assert_eq!(load_commit(&rep, &head).unwrap().get_parents(), &[master]);
+        assert_eq!(subject(&rep, &head), "on topic");
+        assert_eq!(read(&rep, "a.txt").unwrap(), "master\n");
+        assert_eq!(read(&rep, "b.txt").unwrap(), "b\n");
+        assert!(!State::new(&rep).in_progress());
+    }
+
+    #[test]
+    fn fixups_are_squashed_into_their_commit() {
+        let rep = Repository::temporary("rebase-autosquash");
+        let first = rep.commit_files(&[("a.txt", "a\n")], "first");
+        rep.commit_files(&[("b.txt", "b\n")], "add b");
+        rep.commit_files(&[("c.txt", "c\n")], "add c");
+        rep.commit_files(&[("b.txt", "fixed\n")], "fixup! add b");
+
+        // the commits are only replayed on a linear history with a command to run
+        let opts = Options {
+            autosquash: true,
+            exec: vec!["true".to_string()],
+            ..options()
+        };
+        start(&rep, &first.to_string(), None, opts).unwrap();
+
+        let head = rep.ref_resolve("HEAD").unwrap();
+        assert_eq!(subject(&rep, &head), "add c");
+        let parent = load_commit(&rep, &head).unwrap().get_parents()[0];
+        assert_eq!(subject(&rep, &parent), "add b");
+        assert_eq!(load_commit(&rep, &parent).unwrap().get_parents(), &[first]);
+        assert_eq!(read(&rep, "b.txt").unwrap(), "fixed\n");
+        assert!(!State::new(&rep).in_progress());
+    }
+
+    #[test]
+    fn conflicts_can_be_aborted_or_skipped() {
+        let rep = Repository::temporary("rebase-conflict");
+        let (master, topic) = diverge(&rep, ("a.txt", "topic\n"));
+
+        start(&rep, "master", None, options()).unwrap();
+        assert!(State::new(&rep).in_progress());
+        assert!(read(&rep, "a.txt").unwrap().starts_with("<<<<<<< "));
+
+        abort(&rep).unwrap();
+        assert!(!State::new(&rep).in_progress());
+        assert_eq!(rep.get_head_ref(), Some("refs/heads/topic".to_string()));
+        assert_eq!(rep.ref_resolve("HEAD").unwrap(), topic);
+        assert_eq!(read(&rep, "a.txt").unwrap(), "topic\n");
+
+        start(&rep, "master", None, options()).unwrap();
+        skip(&rep).unwrap();
+        assert!(!State::new(&rep).in_progress());
+        assert_eq!(rep.ref_resolve("topic").unwrap(), master);
+        assert_eq!(read(&rep, "a.txt").unwrap(), "master\n");
+        assert!(matches!(skip(&rep), Err(RepError::InvalidArgument(_))));
+    }
+}
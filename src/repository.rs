@@ -2,20 +2,38 @@
 
 use ini::Ini;
 use std::env;
+use std::fmt;
 
-// Repository
-#[derive(Debug)]
+use crate::objects::Object;
+use crate::odb::{CompositeDatabase, MemoryDatabase, ObjectDatabase};
+use crate::oid::ObjectId;
+use crate::signature::{parse_date, Signature};
+
+/// a working directory with its .git directory and config, refs are read from the disk
+/// whenever they are needed and objects through the object database
 pub struct Repository {
     workdir: String,
     gitdir: String,
     config: Config,
+    objects: Box<dyn ObjectDatabase>,
+}
+
+impl fmt::Debug for Repository {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Repository")
+            .field("workdir", &self.workdir)
+            .field("gitdir", &self.gitdir)
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Repository {
+    /// create an empty repository in path or the current directory
     pub fn init(path: Option<String>) -> Result<Self, RepError> {
         let workdir = match path {
             Some(p) => p,
-            None => env::current_dir().unwrap().to_str().unwrap().to_string(),
+            None => current_dir()?,
         };
 
         let gitdir = workdir.clone() + "/.git";
@@ -25,17 +43,18 @@ impl Repository {
         }
 
         let s = Self {
+            objects: Box::new(CompositeDatabase::open(&(gitdir.clone() + "/objects"))),
             workdir,
             gitdir,
             config: Config::default(),
         };
 
-        std::fs::create_dir(&s.gitdir).unwrap();
+        std::fs::create_dir(&s.gitdir).map_err(|e| RepError::Io(s.gitdir.clone(), e))?;
 
-        s.mkdir(vec!["objects"]);
-        s.mkdir(vec!["refs", "heads"]);
-        s.mkdir(vec!["refs", "tags"]);
-        s.mkdir(vec!["branches"]);
+        s.mkdir(vec!["objects"])?;
+        s.mkdir(vec!["refs", "heads"])?;
+        s.mkdir(vec!["refs", "tags"])?;
+        s.mkdir(vec!["branches"])?;
 
         // write the description file
         let description_path = s.gitdir.clone() + "/description";
@@ -43,66 +62,175 @@ impl Repository {
             &description_path,
             "Unnamed repository; edit this file 'description' to name the repository.\n",
         )
-        .unwrap();
+        .map_err(|e| RepError::Io(description_path, e))?;
 
         // write the HEAD file
         let head_path = s.gitdir.clone() + "/HEAD";
-        std::fs::write(&head_path, "ref: refs/heads/master\n").unwrap();
+        std::fs::write(&head_path, "ref: refs/heads/master\n")
+            .map_err(|e| RepError::Io(head_path, e))?;
 
-        s.config.dump(&(s.gitdir.clone() + "/config"));
+        let config_path = s.gitdir.clone() + "/config";
+        s.config
+            .dump(&config_path)
+            .map_err(|e| RepError::Io(config_path, e))?;
 
         Ok(s)
     }
 
-    pub fn get_refs(&self) -> Vec<(String, String)> {
-        let mut refs = vec![];
+    /// every ref below refs/ as (refname, id) sorted by name, symbolic refs are resolved,
+    /// refs which can't be resolved are left out
+    pub fn get_refs(&self) -> Vec<(String, ObjectId)> {
+        self.ref_names()
+            .into_iter()
+            .filter_map(|name| Some((name.clone(), self.ref_resolve(&name).ok()?)))
+            .collect()
+    }
 
-        let heads = std::fs::read_dir(self.gitdir.clone() + "/refs/heads").unwrap();
-        for head in heads {
-            let head = head.unwrap();
-            let head = head.file_name().into_string().unwrap();
-            let head = head.trim().to_string();
-            let hash =
-                std::fs::read_to_string(self.gitdir.clone() + "/refs/heads/" + &head).unwrap();
-            let hash = hash.trim().to_string();
-            refs.push((format!("refs/heads/{}", head), hash));
+    /// the names of all refs below refs/ and in packed-refs, sorted
+    pub fn ref_names(&self) -> Vec<String> {
+        let mut names = vec![];
+        let mut dirs = vec!["refs".to_string()];
+
+        while let Some(dir) = dirs.pop() {
+            let entries = match std::fs::read_dir(self.gitdir.clone() + "/" + &dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let name = dir.clone() + "/" + &entry.file_name().to_string_lossy();
+                match entry.path().is_dir() {
+                    true => dirs.push(name),
+                    false => names.push(name),
+                }
+            }
         }
+        // loose refs take precedence over the ones in packed-refs
+        let packed = self.packed_refs();
+        names.extend(
+            packed
+                .iter()
+                .map(|(name, _)| name.clone())
+                .filter(|name| !names.contains(name))
+                .collect::<Vec<String>>(),
+        );
+        names.sort();
+        names
+    }
 
-        let tags = std::fs::read_dir(self.gitdir.clone() + "/refs/tags").unwrap();
-        for tag in tags {
-            let tag = tag.unwrap();
-            let tag = tag.file_name().into_string().unwrap();
-            let tag = tag.trim().to_string();
-            let hash = std::fs::read_to_string(self.gitdir.clone() + "/refs/tags/" + &tag).unwrap();
-            let hash = hash.trim().to_string();
-            refs.push((format!("refs/tags/{}", tag), hash));
-        }
+    /// the refs in .git/packed-refs as (refname, id), the peeled lines of tags are skipped
+    pub fn packed_refs(&self) -> Vec<(String, ObjectId)> {
+        let content = std::fs::read_to_string(self.gitdir.clone() + "/packed-refs");
+
+        content
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+            .filter_map(|line| {
+                let (hash, name) = line.split_once(' ')?;
+                Some((name.trim().to_string(), hash.parse().ok()?))
+            })
+            .collect()
+    }
 
-        refs
+    fn packed_ref(&self, refname: &str) -> Option<ObjectId> {
+        self.packed_refs()
+            .into_iter()
+            .find(|(name, _)| name == refname)
+            .map(|(_, id)| id)
     }
 
     fn get_last_commit_hash(&self) -> Result<String, RepError> {
-        let head_path = self.gitdir.clone() + "/HEAD";
-        let head = std::fs::read_to_string(&head_path).unwrap();
-        let head = head.trim().split(':').collect::<Vec<&str>>()[1].trim();
+        let head = match self.get_head_ref() {
+            Some(head) => head,
+            // detached HEAD, the file contains the commit hash itself
+            None => {
+                let path = self.gitdir.clone() + "/HEAD";
+                let head = std::fs::read_to_string(&path).map_err(|e| RepError::Io(path, e))?;
+                return Ok(head.trim().to_string());
+            }
+        };
 
-        let head_path = self.gitdir.clone() + "/" + head;
-        let head = std::fs::read_to_string(&head_path);
+        let head_path = self.gitdir.clone() + "/" + &head;
+        let head = std::fs::read_to_string(&head_path)
+            .or_else(|e| self.packed_ref(&head).map(|id| id.to_string()).ok_or(e));
 
-        if head.is_err() {
-            let branch = head_path.split('/').collect::<Vec<&str>>();
-            let branch = branch.last().unwrap();
-            return Err(RepError::NoCommitsInBranch(branch.to_string()));
+        match head {
+            Ok(head) => Ok(head.trim().to_string()),
+            Err(_) => {
+                let branch = head_path.rsplit('/').next().unwrap_or_default();
+                Err(RepError::NoCommitsInBranch(branch.to_string()))
+            }
         }
-        let head = head.unwrap();
+    }
 
+    /// the ref HEAD points to (e.g. refs/heads/master) or None if HEAD is detached
+    pub fn get_head_ref(&self) -> Option<String> {
+        let head = std::fs::read_to_string(self.gitdir.clone() + "/HEAD").ok()?;
         let head = head.trim();
 
-        Ok(head.to_string())
+        head.strip_prefix("ref:").map(|r| r.trim().to_string())
     }
 
-    pub fn ref_resolve(&self, reference: &str) -> Result<String, RepError> {
-        if reference == "HEAD" || reference == "HEAD~" {
+    /// point the current branch (or a detached HEAD) to a new commit
+    pub fn update_head(&self, id: &ObjectId) -> Result<(), RepError> {
+        match self.get_head_ref() {
+            Some(head) => self.update_ref(&head, id),
+            None => self.detach_head(id),
+        }
+    }
+
+    /// point HEAD directly to a commit instead of a branch
+    pub fn detach_head(&self, id: &ObjectId) -> Result<(), RepError> {
+        write_file(&(self.gitdir.clone() + "/HEAD"), format!("{}\n", id))
+    }
+
+    /// point HEAD to a branch again, e.g. refs/heads/master
+    pub fn attach_head(&self, refname: &str) -> Result<(), RepError> {
+        write_file(
+            &(self.gitdir.clone() + "/HEAD"),
+            format!("ref: {}\n", refname),
+        )
+    }
+
+    /// write an id to a ref file like refs/heads/master
+    pub fn update_ref(&self, refname: &str, id: &ObjectId) -> Result<(), RepError> {
+        write_file(&(self.gitdir.clone() + "/" + refname), format!("{}\n", id))
+    }
+
+    /// the object a revision like HEAD~2, main, v1.0^{tree}, an abbreviated hash
+    /// or HEAD:path names
+    pub fn ref_resolve(&self, reference: &str) -> Result<ObjectId, RepError> {
+        // a path inside of a tree like HEAD:src/main.rs
+        if let Some((rev, path)) = reference.split_once(':') {
+            if !rev.is_empty() {
+                return self.resolve_tree_path(rev, path);
+            }
+        }
+
+        // revision suffixes like HEAD~2, main^2 or v1.0^{commit}
+        if let Some(pos) = reference.rfind(['~', '^']) {
+            if pos > 0 {
+                return self.resolve_suffix(&reference[..pos], &reference[pos..]);
+            }
+        }
+
+        // reflog entries like stash@{1}
+        if let Some((name, n)) = reference
+            .strip_suffix('}')
+            .and_then(|r| r.rsplit_once("@{"))
+        {
+            let invalid = || RepError::InvalidReference(reference.to_string());
+            let n = n.parse::<usize>().map_err(|_| invalid())?;
+            let refname = self.full_ref_name(name).ok_or_else(invalid)?;
+            let reflog = self.read_reflog(&refname);
+
+            return match reflog.len().checked_sub(n + 1) {
+                Some(pos) => Ok(reflog[pos].new),
+                None => Err(invalid()),
+            };
+        }
+
+        if reference == "HEAD" {
             let c = self.get_last_commit_hash();
             if c.is_err() {
                 return Err(c.err().unwrap());
@@ -112,33 +240,23 @@ impl Repository {
                 let c = c.split(':').collect::<Vec<&str>>()[1].trim();
                 return self.ref_resolve(c);
             } else {
-                return Ok(c);
+                return c.parse();
             }
         }
 
         let reference = reference.to_string();
 
         if reference.starts_with("refs/") || reference.len() != 40 {
-            let mut head_path = self.gitdir.clone() + "/" + &reference;
-
-            if !std::path::Path::new(&head_path).exists() {
-                // check if the path exists with a refs/ before
-                // a refs/heads/ or a refs/tags/
-                let t_head = self.gitdir.clone() + "/refs/" + &reference;
-                let t2_head = self.gitdir.clone() + "/refs/heads/" + &reference;
-                let t3_head = self.gitdir.clone() + "/refs/tags/" + &reference;
-                if std::path::Path::new(&t_head).exists() {
-                    head_path = t_head;
-                } else if std::path::Path::new(&t2_head).exists() {
-                    head_path = t2_head;
-                } else if std::path::Path::new(&t3_head).exists() {
-                    head_path = t3_head;
-                } else {
-                    return Err(RepError::InvalidReference(reference));
-                }
-            }
+            let refname = match self.full_ref_name(&reference) {
+                Some(refname) => refname,
+                None => match self.expand_hash(&reference) {
+                    Some(hash) => return Ok(hash),
+                    None => return Err(RepError::InvalidReference(reference)),
+                },
+            };
 
-            let head = std::fs::read_to_string(&head_path);
+            let head = std::fs::read_to_string(self.gitdir.clone() + "/" + &refname)
+                .or_else(|e| self.packed_ref(&refname).map(|id| id.to_string()).ok_or(e));
 
             if head.is_err() {
                 return Err(RepError::InvalidReference(reference));
@@ -149,21 +267,238 @@ impl Repository {
 
             if head.starts_with("ref: ") {
                 let head = head.split(':').collect::<Vec<&str>>()[1].trim();
-                return self.ref_resolve(head);
+                self.ref_resolve(head)
             } else {
-                return Ok(head.to_string());
+                head.parse()
             }
         } else {
-            Ok(reference)
+            reference.parse()
         }
     }
 
-    pub fn get_object_path(&self, sha: &str) -> String {
-        let mut path = self.gitdir.clone() + "/objects/";
-        path.push_str(&sha[..2]);
-        path.push('/');
-        path.push_str(&sha[2..]);
-        path
+    /// the full name of a ref like master -> refs/heads/master
+    /// like git the name is tried as it is and below refs/, refs/heads/ and refs/tags/
+    pub fn full_ref_name(&self, name: &str) -> Option<String> {
+        let packed = self.packed_refs();
+        ["", "refs/", "refs/heads/", "refs/tags/"]
+            .iter()
+            .map(|prefix| prefix.to_string() + name)
+            .find(|r| {
+                std::path::Path::new(&(self.gitdir.clone() + "/" + r)).is_file()
+                    || packed.iter().any(|(packed, _)| packed == r)
+            })
+    }
+
+    /// remove a ref together with its reflog, also from packed-refs
+    pub fn delete_ref(&self, refname: &str) {
+        let _ = std::fs::remove_file(self.gitdir.clone() + "/" + refname);
+        let _ = std::fs::remove_file(self.gitdir.clone() + "/logs/" + refname);
+
+        let path = self.gitdir.clone() + "/packed-refs";
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => return,
+        };
+        if self.packed_ref(refname).is_none() {
+            return;
+        }
+        // the peeled line of a deleted tag follows it and goes away too
+        let mut deleted = false;
+        let content = content
+            .lines()
+            .filter(|line| {
+                if line.starts_with('^') {
+                    return !deleted;
+                }
+                deleted = line
+                    .split_once(' ')
+                    .is_some_and(|(_, name)| name == refname);
+                !deleted
+            })
+            .map(|line| line.to_string() + "\n")
+            .collect::<String>();
+        let _ = std::fs::write(&path, content);
+    }
+
+    /// the reflog of a ref, the oldest entry comes first
+    pub fn read_reflog(&self, refname: &str) -> Vec<ReflogEntry> {
+        let log = std::fs::read_to_string(self.gitdir.clone() + "/logs/" + refname);
+
+        log.unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (info, message) = line.split_once('\t').unwrap_or((line, ""));
+                let mut parts = info.splitn(3, ' ');
+                Some(ReflogEntry {
+                    old: parts.next()?.parse().ok()?,
+                    new: parts.next()?.parse().ok()?,
+                    committer: parts.next()?.to_string(),
+                    message: message.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// the names of all refs with a reflog, HEAD last like git
+    pub fn reflog_names(&self) -> Vec<String> {
+        let logs = self.gitdir.clone() + "/logs/";
+        let mut names = vec![];
+        let mut dirs = vec![logs.clone()];
+
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+                let path = entry.path().to_string_lossy().to_string();
+                match entry.path().is_dir() {
+                    true => dirs.push(path),
+                    false => names.push(path[logs.len()..].to_string()),
+                }
+            }
+        }
+        names.sort_by_key(|name| (name == "HEAD", name.clone()));
+        names
+    }
+
+    pub fn write_reflog(&self, refname: &str, entries: &[ReflogEntry]) -> Result<(), RepError> {
+        let log = entries
+            .iter()
+            .map(|e| format!("{} {} {}\t{}\n", e.old, e.new, e.committer, e.message))
+            .collect::<String>();
+        write_file(&(self.gitdir.clone() + "/logs/" + refname), log)
+    }
+
+    /// add an entry for a ref update to its reflog
+    pub fn append_reflog(
+        &self,
+        refname: &str,
+        old: &ObjectId,
+        new: &ObjectId,
+        message: &str,
+    ) -> Result<(), RepError> {
+        let committer = self
+            .get_signature("COMMITTER")
+            .map(|s| s.to_string())
+            .unwrap_or("unknown <unknown> 0 +0000".to_string());
+
+        let mut entries = self.read_reflog(refname);
+        entries.push(ReflogEntry {
+            old: *old,
+            new: *new,
+            committer,
+            message: message.to_string(),
+        });
+        self.write_reflog(refname, &entries)
+    }
+
+    /// resolve the part of a revision after the last ~ or ^
+    fn resolve_suffix(&self, base: &str, suffix: &str) -> Result<ObjectId, RepError> {
+        let invalid = || RepError::InvalidReference(base.to_string() + suffix);
+        let hash = self.ref_resolve(base)?;
+
+        if let Some(peel) = suffix.strip_prefix("^{") {
+            let peel = peel.strip_suffix('}').ok_or_else(invalid)?;
+            return self.peel(&hash, peel).ok_or_else(invalid);
+        }
+
+        let n = match &suffix[1..] {
+            "" => 1,
+            n => n.parse::<usize>().map_err(|_| invalid())?,
+        };
+
+        let commit = self.peel(&hash, "commit").ok_or_else(invalid)?;
+        if suffix.starts_with('^') {
+            if n == 0 {
+                return Ok(commit);
+            }
+            return self
+                .get_parents(&commit)
+                .get(n - 1)
+                .cloned()
+                .ok_or_else(invalid);
+        }
+
+        let mut commit = commit;
+        for _ in 0..n {
+            commit = self
+                .get_parents(&commit)
+                .first()
+                .cloned()
+                .ok_or_else(invalid)?;
+        }
+        Ok(commit)
+    }
+
+    /// the object at a path of the tree of a revision
+    fn resolve_tree_path(&self, rev: &str, path: &str) -> Result<ObjectId, RepError> {
+        let invalid = || RepError::InvalidReference(format!("{}:{}", rev, path));
+        let mut hash = self.ref_resolve(rev)?;
+        hash = self.peel(&hash, "tree").ok_or_else(invalid)?;
+
+        for name in path.split('/').filter(|n| !n.is_empty() && *n != ".") {
+            let tree = match Object::load(self, &hash)? {
+                Object::Tree(t) => t,
+                _ => return Err(invalid()),
+            };
+            hash = tree
+                .get_objects()
+                .iter()
+                .find(|e| e.get_name() == name)
+                .map(|e| *e.get_hash())
+                .ok_or_else(invalid)?;
+        }
+
+        Ok(hash)
+    }
+
+    fn get_parents(&self, id: &ObjectId) -> Vec<ObjectId> {
+        match Object::load(self, id) {
+            Ok(Object::Commit(c)) => c.get_parents().to_vec(),
+            _ => vec![],
+        }
+    }
+
+    /// follow tags (and commits to their trees) until an object of the given type is found
+    /// an empty type peels tags until a non tag object is reached
+    pub fn peel(&self, id: &ObjectId, object_type: &str) -> Option<ObjectId> {
+        let mut id = *id;
+
+        loop {
+            let obj = Object::load(self, &id).ok()?;
+            if obj.get_type() == object_type || (object_type.is_empty() && obj.get_type() != "tag")
+            {
+                return Some(id);
+            }
+
+            id = match obj {
+                Object::Tag(t) => t.get_object(),
+                Object::Commit(c) if object_type == "tree" => c.get_tree(),
+                _ => return None,
+            };
+        }
+    }
+
+    /// expand an abbreviated object hash, returns None if it is not unique
+    fn expand_hash(&self, prefix: &str) -> Option<ObjectId> {
+        if prefix.len() < 4 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let prefix = prefix.to_lowercase();
+
+        let matches = self.objects.find_prefix(&prefix);
+
+        match matches.len() {
+            1 => Some(matches[0]),
+            _ => None,
+        }
+    }
+
+    /// the database all objects are read from and written to
+    pub fn objects(&self) -> &dyn ObjectDatabase {
+        self.objects.as_ref()
+    }
+
+    /// use another object database instead of the one in .git/objects, e.g. one in memory
+    pub fn set_object_database(&mut self, objects: Box<dyn ObjectDatabase>) {
+        self.objects = objects;
     }
 
     pub fn load(path: Option<String>) -> Result<Self, RepError> {
@@ -171,14 +506,15 @@ impl Repository {
             Some(p) => p,
             None => {
                 // check if we are in a git repository / subdirectory
-                let mut pwd = env::current_dir().unwrap().to_str().unwrap().to_string();
+                let mut pwd = current_dir()?;
 
                 let mut gitdir = pwd.clone() + "/.git";
 
-                while pwd != "/" && !std::path::Path::new(&gitdir).exists() {
-                    let p = std::path::Path::new(&pwd);
-                    let parent = p.parent().unwrap();
-                    pwd = parent.to_str().unwrap().to_string();
+                while !std::path::Path::new(&gitdir).exists() {
+                    pwd = match std::path::Path::new(&pwd).parent() {
+                        Some(parent) => parent.to_string_lossy().to_string(),
+                        None => return Err(RepError::NotARepository),
+                    };
                     gitdir = pwd.clone() + "/.git";
                 }
 
@@ -197,45 +533,208 @@ impl Repository {
             return Err(RepError::ConfigFileMissing);
         }
 
-        let config = Config::load(&config_path);
-
-        if config.is_err() {
-            return Err(RepError::ConfigError(config.err().unwrap()));
-        }
+        let config = Config::load(&config_path).map_err(RepError::ConfigError)?;
 
         let s = Self {
+            objects: Box::new(CompositeDatabase::open(&(gitdir.clone() + "/objects"))),
             workdir,
             gitdir,
-            config: config.ok().unwrap(),
+            config,
         };
 
         Ok(s)
     }
 
-    fn mkdir(&self, path: Vec<&str>) {
+    /// a repository without any files whose objects only live in memory, e.g. for tests,
+    /// it has no refs, index or working directory
+    pub fn in_memory() -> Self {
+        Self {
+            workdir: String::new(),
+            gitdir: String::new(),
+            config: Config::default(),
+            objects: Box::new(MemoryDatabase::new()),
+        }
+    }
+
+    fn mkdir(&self, path: Vec<&str>) -> Result<(), RepError> {
         let mut dir = self.gitdir.clone();
         dir.push('/');
         for p in path {
             dir.push_str(p);
             dir.push('/');
-            let r = std::fs::create_dir(&dir);
-            if r.is_err() {
-                if r.err().unwrap().kind() == std::io::ErrorKind::AlreadyExists {
-                    continue;
+            match std::fs::create_dir(&dir) {
+                Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => {
+                    return Err(RepError::Io(dir, e));
                 }
-                println!("Failed to create directory: {:?}", dir);
+                _ => {}
             }
         }
+        Ok(())
     }
 
     pub fn get_workdir(&self) -> &String {
         &self.workdir
     }
+
+    pub fn get_gitdir(&self) -> &String {
+        &self.gitdir
+    }
+
+    /// look up a config value like user.name, the repository config wins over ~/.gitconfig
+    pub fn config_get(&self, section: &str, key: &str) -> Option<String> {
+        if let Some(v) = self.config.get(section, key) {
+            return Some(v);
+        }
+
+        let home = env::var("HOME").ok()?;
+        let global = Ini::load_from_file(home + "/.gitconfig").ok()?;
+        Config::lookup(&global, section, key)
+    }
+
+    /// the identity line for an author or committer: "Name <email> timestamp timezone"
+    /// role is either "AUTHOR" or "COMMITTER" like in the GIT_AUTHOR_NAME variables
+    pub fn get_signature(&self, role: &str) -> Result<Signature, RepError> {
+        let name = env::var(format!("GIT_{}_NAME", role))
+            .ok()
+            .or_else(|| self.config_get("user", "name"));
+        let email = env::var(format!("GIT_{}_EMAIL", role))
+            .ok()
+            .or_else(|| self.config_get("user", "email"));
+
+        let (name, email) = match (name, email) {
+            (Some(name), Some(email)) => (name, email),
+            _ => return Err(RepError::IdentityUnknown),
+        };
+
+        let mut signature = Signature::now(&name, &email);
+        if let Ok(date) = env::var(format!("GIT_{}_DATE", role)) {
+            let (seconds, offset) = parse_date(&date).ok_or(RepError::InvalidDate(date))?;
+            signature.seconds = seconds;
+            signature.offset = offset;
+        }
+
+        Ok(signature)
+    }
+
+    /// the editor to use, the sequence editor is used for todo lists of rebase -i
+    pub fn get_editor(&self, sequence: bool) -> String {
+        let sequence_editor = match sequence {
+            true => env::var("GIT_SEQUENCE_EDITOR")
+                .ok()
+                .or_else(|| self.config_get("sequence", "editor")),
+            false => None,
+        };
+
+        sequence_editor
+            .or_else(|| env::var("GIT_EDITOR").ok())
+            .or_else(|| self.config_get("core", "editor"))
+            .or_else(|| env::var("VISUAL").ok())
+            .or_else(|| env::var("EDITOR").ok())
+            .unwrap_or("vi".to_string())
+    }
+
+    /// path of a file relative to the root of the working directory
+    pub fn relative_path(&self, path: &str) -> String {
+        let pwd = env::current_dir().unwrap_or_else(|_| self.workdir.clone().into());
+        let path = pwd.join(path);
+
+        // normalize . and .. without requiring the file to exist
+        let mut parts: Vec<String> = vec![];
+        for c in path.components() {
+            match c {
+                std::path::Component::ParentDir => {
+                    parts.pop();
+                }
+                std::path::Component::Normal(p) => parts.push(p.to_string_lossy().to_string()),
+                _ => {}
+            }
+        }
+        let path = "/".to_string() + &parts.join("/");
+
+        path.strip_prefix(&self.workdir)
+            .unwrap_or(&path)
+            .trim_start_matches('/')
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+impl Repository {
+    /// a new repository in the temporary directory with an identity for commits,
+    /// one of the same name left by an earlier run is removed first
+    pub(crate) fn temporary(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("kyu-test-{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let workdir = dir.to_string_lossy().to_string();
+
+        Self::init(Some(workdir.clone())).unwrap();
+        let mut config = std::fs::OpenOptions::new()
+            .append(true)
+            .open(workdir.clone() + "/.git/config")
+            .unwrap();
+        std::io::Write::write_all(&mut config, b"[user]\n\tname = T\n\temail = t@x\n").unwrap();
+        Self::load(Some(workdir)).unwrap()
+    }
+
+    /// write files to the working directory, stage all of it and commit it on HEAD
+    pub(crate) fn commit_files(&self, files: &[(&str, &str)], message: &str) -> ObjectId {
+        for (name, content) in files {
+            let path = std::path::Path::new(&self.workdir).join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        crate::add::add(self, std::slice::from_ref(&self.workdir)).unwrap();
+
+        let tree = crate::index::Index::load(self)
+            .unwrap()
+            .write_tree(self)
+            .unwrap();
+        let parents = self.ref_resolve("HEAD").into_iter().collect::<Vec<_>>();
+        let signature = self.get_signature("AUTHOR").unwrap();
+        let commit = crate::objects::Commit::create(
+            &tree,
+            &parents,
+            &signature,
+            &signature,
+            &(message.to_string() + "\n"),
+        );
+        let id = commit.save(self).unwrap();
+        self.update_head(&id).unwrap();
+        id
+    }
+}
+
+/// write a file of the git directory, the folders it is in are created first
+fn write_file(path: &str, content: String) -> Result<(), RepError> {
+    let io_error = |e| RepError::Io(path.to_string(), e);
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(parent).map_err(io_error)?;
+    }
+    std::fs::write(path, content).map_err(io_error)
+}
+
+/// the current directory, an error if it was deleted
+fn current_dir() -> Result<String, RepError> {
+    env::current_dir()
+        .map(|dir| dir.to_string_lossy().to_string())
+        .map_err(|e| RepError::Io(".".to_string(), e))
+}
+
+/// a line of a reflog in .git/logs
+#[derive(Debug, Clone)]
+pub struct ReflogEntry {
+    pub old: ObjectId,
+    pub new: ObjectId,
+    /// identity and time of the update: "Name <email> timestamp timezone"
+    pub committer: String,
+    pub message: String,
 }
 
 // Config
 #[derive(Debug)]
 struct Config {
+    ini: Ini,
     bare: bool,
     repository_format_version: i32,
     file_mode: bool,
@@ -247,7 +746,8 @@ struct Config {
 impl Config {
     fn default() -> Self {
         Self {
-            bare: false,                  // bare -> no working directory, only the .git directory
+            ini: Ini::new(),
+            bare: false, // bare -> no working directory, only the .git directory
             repository_format_version: 0, // 0 -> without extensions in the git directory, 1 -> with extensions
             file_mode: false,             // tracking file mode changes (permissions)
                                           //ignore_case: false,
@@ -257,62 +757,54 @@ impl Config {
     }
 
     fn load(config_path: &str) -> Result<Self, ConfigError> {
-        // TODO: make the config loadable
-
-        let config = Ini::load_from_file(config_path).unwrap();
-        //  .get_from(Option<"core">, "repositoryformatversion");
-
-        let repository_format_version = config
-            .get_from(Some("core"), "repositoryformatversion")
-            .unwrap()
-            .parse::<i32>()
-            .unwrap();
+        let config = match Ini::load_from_file(config_path) {
+            Ok(config) => config,
+            Err(ini::Error::Parse(e)) => return Err(ConfigError::BadLine(e.line)),
+            Err(ini::Error::Io(e)) => return Err(ConfigError::Unreadable(e)),
+        };
 
+        // missing or unparsable values fall back to the defaults like in git
+        let get = |key: &str| Self::lookup(&config, "core", key);
+        let repository_format_version = get("repositoryformatversion")
+            .and_then(|v| v.parse::<i32>().ok())
+            .unwrap_or(0);
         if repository_format_version != 0 {
             return Err(ConfigError::UnsupportedRepositoryFormatVersion);
         }
 
-        let bare = config
-            .get_from(Some("core"), "bare")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap();
-
-        let file_mode = config
-            .get_from(Some("core"), "filemode")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap();
-
-        /*let ignore_case = config
-            .get_from(Some("core"), "ignorecase")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap();
-
-        let precompose_unicode = config
-            .get_from(Some("core"), "precomposeunicode")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap();
-
-        let logal_lref_updates = config
-            .get_from(Some("core"), "logallrefupdates")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap();*/
+        let bare = get("bare")
+            .and_then(|v| v.parse::<bool>().ok())
+            .unwrap_or(false);
+        let file_mode = get("filemode")
+            .and_then(|v| v.parse::<bool>().ok())
+            .unwrap_or(true);
 
         Ok(Self {
+            ini: config,
             bare,
             repository_format_version,
             file_mode,
-            /*ignore_case,
-            precompose_unicode,
-            logal_lref_updates,*/
         })
     }
 
-    fn dump(&self, path: &str) {
+    fn get(&self, section: &str, key: &str) -> Option<String> {
+        Self::lookup(&self.ini, section, key)
+    }
+
+    /// git config keys are case insensitive, so compare them that way
+    fn lookup(ini: &Ini, section: &str, key: &str) -> Option<String> {
+        let props = ini
+            .iter()
+            .rfind(|(s, _)| s.map(|s| s.eq_ignore_ascii_case(section)).unwrap_or(false))?
+            .1;
+
+        props
+            .iter()
+            .rfind(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.to_string())
+    }
+
+    fn dump(&self, path: &str) -> std::io::Result<()> {
         let mut conf = Ini::new();
         conf.with_section(Some("core"))
             .set("bare", self.bare.to_string())
@@ -325,11 +817,11 @@ impl Config {
             .set("precomposeunicode", self.precompose_unicode.to_string())
             .set("logallrefupdates", self.logal_lref_updates.to_string())*/
             ;
-        conf.write_to_file(path).unwrap();
+        conf.write_to_file(path)
     }
 }
 
-// Errors
+/// everything that can go wrong in kyu, shown like git shows its errors
 #[derive(Debug)]
 pub enum RepError {
     AlreadyExists,
@@ -338,9 +830,120 @@ pub enum RepError {
     ConfigError(ConfigError),
     NoCommitsInBranch(String),
     InvalidReference(String),
+    IdentityUnknown,
+    InvalidPathspec(String),
+    InvalidObjectType(String),
+    InvalidDate(String),
+    InvalidRange(String),
+    /// content which would make a malformed object, with the problem fsck found
+    FailsFsck(String),
+    /// a branch which should be created but is already there
+    BranchExists(String),
+    /// a path which doesnt exist in a revision as (path, revision)
+    NoSuchPath(String, String),
+    /// an invalid combination of options
+    InvalidArgument(String),
+    /// describe and name-rev found nothing to name a commit with
+    NoNames(String),
+    /// an object which is missing or can't be read as (type, hash)
+    BadObject(String, String),
+    /// a hash which isn't in the object store
+    ObjectNotFound(String),
+    /// an object which can't be decompressed or unpacked as (hash, path of its file)
+    CorruptObject(String, String),
+    /// an object without a valid "<type> <size>" header or content
+    BadObjectHeader(String),
+    /// reading or writing a file failed as (path, error)
+    Io(String, std::io::Error),
+    /// an index file which is truncated or has no DIRC signature
+    CorruptIndex,
+    /// an index in a format version which can't be read, like version 4
+    UnsupportedIndexVersion(u32),
+    /// writing the output failed, e.g. because the pipe it goes to was closed
+    Output(std::io::Error),
+    /// an io error where the path isn't known
+    IoError(std::io::Error),
+}
+
+impl From<std::io::Error> for RepError {
+    fn from(e: std::io::Error) -> Self {
+        RepError::IoError(e)
+    }
+}
+
+/// the messages like git shows them after "fatal: "
+impl fmt::Display for RepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepError::AlreadyExists => write!(f, "a git repository already exists here"),
+            RepError::NotARepository => write!(
+                f,
+                "not a git repository (or any of the parent directories): .git"
+            ),
+            RepError::ConfigFileMissing => write!(f, "unable to read config file '.git/config'"),
+            RepError::ConfigError(e) => write!(f, "{}", e),
+            RepError::NoCommitsInBranch(branch) => write!(
+                f,
+                "your current branch '{}' does not have any commits yet",
+                branch
+            ),
+            RepError::InvalidReference(name) => write!(f, "Not a valid object name {}", name),
+            RepError::IdentityUnknown => write!(f, "unable to auto-detect email address"),
+            RepError::InvalidPathspec(spec) => write!(f, "invalid pathspec '{}'", spec),
+            RepError::InvalidObjectType(name) => write!(f, "invalid object type \"{}\"", name),
+            RepError::InvalidDate(date) => write!(f, "invalid date format: {}", date),
+            RepError::InvalidRange(range) => write!(f, "invalid -L range: {}", range),
+            RepError::FailsFsck(problem) => write!(f, "object fails fsck: {}", problem),
+            RepError::BranchExists(name) => write!(f, "a branch named '{}' already exists", name),
+            RepError::NoSuchPath(path, rev) => write!(f, "no such path {} in {}", path, rev),
+            RepError::InvalidArgument(message) | RepError::NoNames(message) => {
+                write!(f, "{}", message)
+            }
+            RepError::BadObject(object_type, hash) if object_type == "object" => {
+                write!(f, "bad object {}", hash)
+            }
+            RepError::BadObject(object_type, hash) => {
+                write!(f, "bad {} object {}", object_type, hash)
+            }
+            RepError::ObjectNotFound(hash) => write!(f, "unable to read {}", hash),
+            RepError::CorruptObject(hash, path) if path.ends_with(".pack") => {
+                write!(f, "packed object {} (stored in {}) is corrupt", hash, path)
+            }
+            RepError::CorruptObject(hash, path) => {
+                write!(f, "loose object {} (stored in {}) is corrupt", hash, path)
+            }
+            RepError::BadObjectHeader(hash) => write!(f, "unable to parse {} header", hash),
+            RepError::Io(path, e) => write!(f, "unable to access '{}': {}", path, e),
+            RepError::CorruptIndex => write!(f, "index file corrupt"),
+            RepError::Output(e) => write!(f, "write failure on standard output: {}", e),
+            RepError::IoError(e) => write!(f, "{}", e),
+            RepError::UnsupportedIndexVersion(version) => {
+                write!(f, "index file version {} is not supported", version)
+            }
+        }
+    }
 }
 
+impl std::error::Error for RepError {}
+
 #[derive(Debug)]
 pub enum ConfigError {
     UnsupportedRepositoryFormatVersion,
+    /// a line of .git/config which can't be parsed
+    BadLine(usize),
+    Unreadable(std::io::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::UnsupportedRepositoryFormatVersion => {
+                write!(f, "unsupported repository format version")
+            }
+            ConfigError::BadLine(line) => write!(f, "bad config line {} in file .git/config", line),
+            ConfigError::Unreadable(e) => {
+                write!(f, "unable to read config file '.git/config': {}", e)
+            }
+        }
+    }
 }
@@ -0,0 +1,370 @@
+#![allow(dead_code)]
+
+use crate::index::{remove_workdir_file, write_workdir_file, Index, IndexEntry};
+use crate::merge::{parse_mode, tree_files};
+use crate::oid::ObjectId;
+use crate::pathspec::Pathspec;
+use crate::repository::{RepError, Repository};
+use crate::sequencer::{short, subject};
+
+/// how much of the repository reset updates besides the branch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// only move the branch
+    Soft,
+    /// move the branch and reset the index
+    Mixed,
+    /// move the branch and reset the index and the working directory
+    Hard,
+}
+
+impl Mode {
+    fn name(&self) -> &str {
+        match self {
+            Mode::Soft => "soft",
+            Mode::Mixed => "mixed",
+            Mode::Hard => "hard",
+        }
+    }
+}
+
+/// the tree of a revision, an error if it is not a commit or tree
+fn resolve_tree(rep: &Repository, rev: &str) -> Result<ObjectId, RepError> {
+    let hash = rep.ref_resolve(rev)?;
+    rep.peel(&hash, "tree")
+        .ok_or(RepError::InvalidReference(rev.to_string()))
+}
+
+/// the state files of a merge, cherry-pick or revert which are stale after a reset
+fn remove_branch_state(rep: &Repository) {
+    for file in [
+        "MERGE_HEAD",
+        "MERGE_MSG",
+        "MERGE_MODE",
+        "CHERRY_PICK_HEAD",
+        "REVERT_HEAD",
+    ] {
+        let _ = std::fs::remove_file(rep.get_gitdir().clone() + "/" + file);
+    }
+}
+
+/// print the files whose working directory content differs from the index
+fn print_unstaged(rep: &Repository, index: &Index) -> Result<(), RepError> {
+    let changes = index
+        .get_entries()
+        .iter()
+        .filter(|e| e.is_modified(rep))
+        .map(|e| {
+            let exists = std::fs::symlink_metadata(rep.get_workdir().clone() + "/" + e.get_name());
+            let status = if exists.is_ok() { 'M' } else { 'D' };
+            format!("{}\t{}", status, e.get_name())
+        })
+        .collect::<Vec<String>>();
+
+    if !changes.is_empty() {
+        outln!("Unstaged changes after reset:")?;
+        for change in changes {
+            outln!("{}", change)?;
+        }
+    }
+    Ok(())
+}
+
+/// an index with the files of a tree, entries which didnt change keep their stat information
+fn index_from_tree(rep: &Repository, current: &Index, tree: &ObjectId) -> Result<Index, RepError> {
+    let mut index = Index::new();
+
+    for (path, (mode, sha)) in tree_files(rep, Some(tree))? {
+        match current.get(&path) {
+            Some(entry) if entry.get_mode() == mode && *entry.get_hash() == sha => {
+                index.add(entry.clone())
+            }
+            _ => index.add(IndexEntry::new(&path, parse_mode(&mode, &sha)?, &sha, 0)),
+        }
+    }
+
+    Ok(index)
+}
+
+/// point the current branch to a commit and update the index / working directory depending on the mode
+pub fn reset(rep: &Repository, rev: &str, mode: Mode) -> Result<(), RepError> {
+    let target = rep.ref_resolve(rev)?;
+    let target = rep
+        .peel(&target, "commit")
+        .ok_or(RepError::InvalidReference(rev.to_string()))?;
+    let tree = rep
+        .peel(&target, "tree")
+        .ok_or_else(|| RepError::BadObject("commit".to_string(), target.to_string()))?;
+
+    let index = Index::load(rep)?;
+    let merging = ["MERGE_HEAD", "CHERRY_PICK_HEAD", "REVERT_HEAD"]
+        .iter()
+        .any(|f| std::path::Path::new(&(rep.get_gitdir().clone() + "/" + f)).exists());
+    if mode == Mode::Soft && (merging || !index.get_conflicts().is_empty()) {
+        return Err(RepError::InvalidArgument(
+            "Cannot do a soft reset in the middle of a merge.".to_string(),
+        ));
+    }
+
+    // HEAD may not exist yet in a fresh repository
+    if let Ok(head) = rep.ref_resolve("HEAD") {
+        let path = rep.get_gitdir().clone() + "/ORIG_HEAD";
+        std::fs::write(&path, format!("{}\n", head)).map_err(|e| RepError::Io(path, e))?;
+    }
+    rep.update_head(&target)?;
+
+    match mode {
+        Mode::Soft => {}
+        Mode::Mixed => {
+            let new = index_from_tree(rep, &index, &tree)?;
+            new.save(rep)?;
+            print_unstaged(rep, &new)?;
+        }
+        Mode::Hard => {
+            crate::index::reset_workdir(rep, &tree)?;
+            outln!(
+                "HEAD is now at {} {}",
+                short(&target),
+                subject(rep, &target)
+            )?;
+        }
+    }
+
+    remove_branch_state(rep);
+    Ok(())
+}
+
+/// set the index entries of some paths to their version in a revision, the working directory stays untouched
+pub fn reset_paths(
+    rep: &Repository,
+    rev: &str,
+    mode: Mode,
+    pathspec: &Pathspec,
+) -> Result<(), RepError> {
+    if mode != Mode::Mixed {
+        return Err(RepError::InvalidArgument(format!(
+            "Cannot do {} reset with paths.",
+            mode.name()
+        )));
+    }
+
+    let source = match resolve_tree(rep, rev) {
+        Ok(tree) => Some(tree),
+        // resetting paths before the first commit unstages them
+        Err(RepError::NoCommitsInBranch(_)) if rev == "HEAD" => None,
+        Err(e) => return Err(e),
+    };
+    let files = tree_files(rep, source.as_ref())?;
+
+    let mut index = Index::load(rep)?;
+    let matching = index
+        .get_entries()
+        .iter()
+        .map(|e| e.get_name().to_string())
+        .filter(|p| pathspec.matches(p))
+        .collect::<Vec<String>>();
+    for path in matching {
+        index.remove(&path);
+    }
+
+    for (path, (mode, sha)) in files.iter().filter(|(p, _)| pathspec.matches(p)) {
+        index.add(IndexEntry::new(path, parse_mode(mode, sha)?, sha, 0));
+    }
+
+    index.save(rep)?;
+    print_unstaged(rep, &index)?;
+    Ok(())
+}
+
+/// restore files in the working directory and / or the index
+/// the source defaults to the index for the working directory and to HEAD for --staged
+pub fn restore(
+    rep: &Repository,
+    source: Option<&str>,
+    staged: bool,
+    worktree: bool,
+    pathspec: &Pathspec,
+) -> Result<(), RepError> {
+    let mut index = Index::load(rep)?;
+
+    // (mode, sha) of every matching file in the source
+    let files = match (source, staged) {
+        (Some(rev), _) => tree_files(rep, Some(&resolve_tree(rep, rev)?))?,
+        (None, true) => match resolve_tree(rep, "HEAD") {
+            Ok(tree) => tree_files(rep, Some(&tree))?,
+            Err(RepError::NoCommitsInBranch(_)) => Default::default(),
+            Err(e) => return Err(e),
+        },
+        (None, false) => index.to_map(),
+    };
+    let files = files
+        .into_iter()
+        .filter(|(p, _)| pathspec.matches(p))
+        .collect::<std::collections::BTreeMap<String, (String, ObjectId)>>();
+
+    // files which are tracked but not in the source get removed
+    let tracked = index
+        .get_entries()
+        .iter()
+        .map(|e| e.get_name().to_string())
+        .filter(|p| pathspec.matches(p))
+        .collect::<Vec<String>>();
+
+    let unmatched = pathspec.unmatched(files.keys().chain(tracked.iter()).map(|p| p.as_str()));
+    if !unmatched.is_empty() {
+        for spec in unmatched {
+            errln!(
+                "error: pathspec '{}' did not match any file(s) known to git",
+                spec
+            );
+        }
+        return Ok(());
+    }
+
+    if worktree && !staged {
+        let conflicts = index
+            .get_conflicts()
+            .into_iter()
+            .filter(|p| pathspec.matches(p))
+            .collect::<Vec<String>>();
+        if source.is_none() && !conflicts.is_empty() {
+            for path in conflicts {
+                errln!("error: path '{}' is unmerged", path);
+            }
+            return Ok(());
+        }
+    }
+
+    if worktree {
+        for path in tracked.iter().filter(|p| !files.contains_key(*p)) {
+            remove_workdir_file(rep, path);
+        }
+        for (path, (mode, sha)) in files.iter() {
+            write_workdir_file(rep, path, mode, sha)?;
+        }
+    }
+
+    if staged {
+        for path in tracked.iter().filter(|p| !files.contains_key(*p)) {
+            index.remove(path);
+        }
+    }
+
+    for (path, (mode, sha)) in files.iter() {
+        let written = worktree && mode != "160000";
+        if written && (staged || index.get(path).map(|e| e.get_hash()) == Some(sha)) {
+            // the file was just written, so the stat information can be refreshed
+            index.add(IndexEntry::from_workdir(rep, path, sha));
+        } else if staged {
+            index.add(IndexEntry::new(path, parse_mode(mode, sha)?, sha, 0));
+        }
+    }
+
+    index.save(rep)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(rep: &Repository, name: &str, content: &str) {
+        std::fs::write(rep.get_workdir().clone() + "/" + name, content).unwrap();
+    }
+
+    fn read(rep: &Repository, name: &str) -> String {
+        std::fs::read_to_string(rep.get_workdir().clone() + "/" + name).unwrap()
+    }
+
+    fn index_tree(rep: &Repository) -> ObjectId {
+        Index::load(rep).unwrap().write_tree(rep).unwrap()
+    }
+
+    fn pathspec(rep: &Repository, spec: &str) -> Pathspec {
+        Pathspec::parse(rep, &[spec.to_string()]).unwrap()
+    }
+
+    #[test]
+    fn modes_update_more_and_more() {
+        let rep = Repository::temporary("reset-modes");
+        let first = rep.commit_files(&[("a.txt", "a\n")], "first");
+        let second = rep.commit_files(&[("a.txt", "b\n")], "second");
+        let first_tree = resolve_tree(&rep, &first.to_string()).unwrap();
+        let second_tree = resolve_tree(&rep, &second.to_string()).unwrap();
+
+        reset(&rep, "HEAD~1", Mode::Soft).unwrap();
+        assert_eq!(rep.ref_resolve("HEAD").unwrap(), first);
+        assert_eq!(rep.ref_resolve("ORIG_HEAD").unwrap(), second);
+        assert_eq!(index_tree(&rep), second_tree);
+
+        reset(&rep, &first.to_string(), Mode::Mixed).unwrap();
+        assert_eq!(index_tree(&rep), first_tree);
+        assert_eq!(read(&rep, "a.txt"), "b\n");
+
+        reset(&rep, &first.to_string(), Mode::Hard).unwrap();
+        assert_eq!(read(&rep, "a.txt"), "a\n");
+        assert!(Index::load(&rep).unwrap().get_modified(&rep).is_empty());
+        assert!(matches!(
+            reset(&rep, "nothing", Mode::Hard),
+            Err(RepError::InvalidReference(_))
+        ));
+    }
+
+    #[test]
+    fn soft_resets_are_refused_while_merging() {
+        let rep = Repository::temporary("reset-merging");
+        rep.commit_files(&[("a.txt", "a\n")], "first");
+        let second = rep.commit_files(&[("a.txt", "b\n")], "second");
+        let pick_head = rep.get_gitdir().clone() + "/CHERRY_PICK_HEAD";
+        std::fs::write(&pick_head, format!("{}\n", second)).unwrap();
+
+        assert!(matches!(
+            reset(&rep, "HEAD~1", Mode::Soft),
+            Err(RepError::InvalidArgument(_))
+        ));
+        assert_eq!(rep.ref_resolve("HEAD").unwrap(), second);
+
+        reset(&rep, "HEAD", Mode::Hard).unwrap();
+        assert!(!std::path::Path::new(&pick_head).exists());
+    }
+
+    #[test]
+    fn paths_are_unstaged() {
+        let rep = Repository::temporary("reset-paths");
+        let first = rep.commit_files(&[("a.txt", "a\n"), ("b.txt", "b\n")], "first");
+        write(&rep, "a.txt", "changed\n");
+        write(&rep, "b.txt", "changed\n");
+        crate::add::add(&rep, std::slice::from_ref(rep.get_workdir())).unwrap();
+
+        let spec = pathspec(&rep, ":(top)a.txt");
+        reset_paths(&rep, "HEAD", Mode::Mixed, &spec).unwrap();
+        let index = Index::load(&rep).unwrap();
+        let files =
+            tree_files(&rep, Some(&resolve_tree(&rep, &first.to_string()).unwrap())).unwrap();
+        assert_eq!(index.get("a.txt").unwrap().get_hash(), &files["a.txt"].1);
+        assert_ne!(index.get("b.txt").unwrap().get_hash(), &files["b.txt"].1);
+        assert_eq!(read(&rep, "a.txt"), "changed\n");
+
+        assert!(matches!(
+            reset_paths(&rep, "HEAD", Mode::Hard, &spec),
+            Err(RepError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn restore_takes_files_from_the_index_or_a_source() {
+        let rep = Repository::temporary("reset-restore");
+        let first = rep.commit_files(&[("a.txt", "a\n")], "first");
+        rep.commit_files(&[("a.txt", "b\n"), ("new.txt", "new\n")], "second");
+        write(&rep, "a.txt", "changed\n");
+
+        restore(&rep, None, false, true, &pathspec(&rep, ":/a.txt")).unwrap();
+        assert_eq!(read(&rep, "a.txt"), "b\n");
+
+        let first = first.to_string();
+        restore(&rep, Some(&first), true, true, &pathspec(&rep, ":/")).unwrap();
+        assert_eq!(read(&rep, "a.txt"), "a\n");
+        assert!(!std::path::Path::new(&(rep.get_workdir().clone() + "/new.txt")).exists());
+        assert_eq!(index_tree(&rep), resolve_tree(&rep, &first).unwrap());
+    }
+}
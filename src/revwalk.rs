@@ -0,0 +1,172 @@
+#![allow(dead_code)]
+
+use std::collections::{BinaryHeap, HashSet};
+
+use crate::objects::{Object, KVLM};
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+
+/// split revision arguments like `A..B`, `^A` and `B` into the commits to include and exclude
+pub fn parse_revisions(
+    rep: &Repository,
+    args: &[String],
+) -> Result<(Vec<ObjectId>, Vec<ObjectId>), RepError> {
+    let mut include = vec![];
+    let mut exclude = vec![];
+
+    for arg in args {
+        if let Some((from, to)) = arg.split_once("..") {
+            let from = if from.is_empty() { "HEAD" } else { from };
+            let to = if to.is_empty() { "HEAD" } else { to };
+            exclude.push(resolve_commit(rep, from)?);
+            include.push(resolve_commit(rep, to)?);
+        } else if let Some(rev) = arg.strip_prefix('^') {
+            exclude.push(resolve_commit(rep, rev)?);
+        } else {
+            include.push(resolve_commit(rep, arg)?);
+        }
+    }
+
+    Ok((include, exclude))
+}
+
+/// resolve a revision and peel it to a commit
+pub fn resolve_commit(rep: &Repository, rev: &str) -> Result<ObjectId, RepError> {
+    let id = rep.ref_resolve(rev)?;
+    rep.peel(&id, "commit")
+        .ok_or_else(|| RepError::InvalidReference(rev.to_string()))
+}
+
+/// the commit time of a commit, used to order the walk
+pub fn commit_time(rep: &Repository, id: &ObjectId) -> i64 {
+    match Object::load(rep, id) {
+        Ok(Object::Commit(c)) => c.get_committer().seconds,
+        _ => 0,
+    }
+}
+
+fn parents(rep: &Repository, id: &ObjectId) -> Vec<ObjectId> {
+    match Object::load(rep, id) {
+        Ok(Object::Commit(c)) => c.get_parents().to_vec(),
+        _ => vec![],
+    }
+}
+
+/// every commit reachable from the given commits (including themselves)
+pub fn ancestors(rep: &Repository, commits: &[ObjectId]) -> HashSet<ObjectId> {
+    let mut seen = HashSet::new();
+    let mut stack = commits.to_vec();
+
+    while let Some(commit) = stack.pop() {
+        if seen.insert(commit) {
+            stack.extend(parents(rep, &commit));
+        }
+    }
+
+    seen
+}
+
+/// list the commits reachable from include but not from exclude, newest first like git rev-list
+pub fn rev_list(rep: &Repository, include: &[ObjectId], exclude: &[ObjectId]) -> Vec<ObjectId> {
+    let excluded = ancestors(rep, exclude);
+
+    let mut result = vec![];
+    let mut seen = HashSet::new();
+    let mut queue = BinaryHeap::new();
+
+    for commit in include {
+        if seen.insert(*commit) {
+            queue.push((commit_time(rep, commit), *commit));
+        }
+    }
+
+    while let Some((_, commit)) = queue.pop() {
+        if excluded.contains(&commit) {
+            continue;
+        }
+
+        for parent in parents(rep, &commit) {
+            if seen.insert(parent) {
+                queue.push((commit_time(rep, &parent), parent));
+            }
+        }
+        result.push(commit);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{Commit, Tree};
+    use crate::signature::Signature;
+
+    /// a commit made at a time, so the walk order is known
+    fn commit(rep: &Repository, parents: &[ObjectId], seconds: i64) -> ObjectId {
+        let tree = Tree::from_entries(vec![]).save(rep).unwrap();
+        let signature = Signature {
+            name: "T".to_string(),
+            email: "t@x".to_string(),
+            seconds,
+            offset: 0,
+        };
+        Commit::create(&tree, parents, &signature, &signature, &seconds.to_string())
+            .save(rep)
+            .unwrap()
+    }
+
+    /// root - a - b - merge with a side branch root - c merged into it
+    fn history(rep: &Repository) -> [ObjectId; 5] {
+        let root = commit(rep, &[], 1);
+        let a = commit(rep, &[root], 2);
+        let b = commit(rep, &[a], 3);
+        let c = commit(rep, &[root], 4);
+        let merge = commit(rep, &[b, c], 5);
+        [root, a, b, c, merge]
+    }
+
+    #[test]
+    fn commits_are_listed_newest_first() {
+        let rep = Repository::in_memory();
+        let [root, a, b, c, merge] = history(&rep);
+        assert_eq!(rev_list(&rep, &[merge], &[]), vec![merge, c, b, a, root]);
+        assert_eq!(rev_list(&rep, &[b, c], &[]), vec![c, b, a, root]);
+    }
+
+    #[test]
+    fn excluded_commits_hide_their_ancestors() {
+        let rep = Repository::in_memory();
+        let [root, a, b, c, merge] = history(&rep);
+        assert_eq!(ancestors(&rep, &[b]), HashSet::from([root, a, b]));
+        assert_eq!(rev_list(&rep, &[merge], &[b]), vec![merge, c]);
+        assert!(rev_list(&rep, &[a], &[merge]).is_empty());
+    }
+
+    #[test]
+    fn ranges_and_negations_are_split() {
+        let rep = Repository::in_memory();
+        let [_, a, b, c, merge] = history(&rep);
+        let args = [
+            format!("{}..{}", b, merge),
+            format!("^{:.7}", a),
+            c.to_string(),
+        ];
+        assert_eq!(
+            parse_revisions(&rep, &args).unwrap(),
+            (vec![merge, c], vec![b, a])
+        );
+    }
+
+    #[test]
+    fn only_commits_are_resolved() {
+        let rep = Repository::in_memory();
+        let [root, ..] = history(&rep);
+        assert_eq!(resolve_commit(&rep, &root.to_string()).unwrap(), root);
+
+        let tree = Tree::from_entries(vec![]).save(&rep).unwrap();
+        assert!(resolve_commit(&rep, &tree.to_string()).is_err());
+        assert_eq!(commit_time(&rep, &root), 1);
+        assert_eq!(commit_time(&rep, &tree), 0);
+    }
+}
@@ -0,0 +1,655 @@
+#![allow(dead_code)]
+
+use ini::Ini;
+
+use crate::index::{reset_workdir, Index};
+use crate::merge::{apply_merge, merge_trees, MergeLabels};
+use crate::objects::{Commit, Object, Tree, KVLM};
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+use crate::revwalk::{parse_revisions, resolve_commit, rev_list};
+
+/// cherry-pick and revert share everything except the direction the changes are applied in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Pick,
+    Revert,
+}
+
+impl Action {
+    fn name(&self) -> &str {
+        match self {
+            Action::Pick => "pick",
+            Action::Revert => "revert",
+        }
+    }
+
+    pub fn command(&self) -> &str {
+        match self {
+            Action::Pick => "cherry-pick",
+            Action::Revert => "revert",
+        }
+    }
+
+    /// the pseudo ref pointing to the commit that is currently applied
+    fn head_file(&self) -> &str {
+        match self {
+            Action::Pick => "CHERRY_PICK_HEAD",
+            Action::Revert => "REVERT_HEAD",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// append "(cherry picked from commit ...)" to the message (-x)
+    pub record_origin: bool,
+    /// only update the index and working directory (--no-commit)
+    pub no_commit: bool,
+}
+
+/// the state of a running cherry-pick / revert stored in .git/sequencer
+struct Sequencer<'a> {
+    rep: &'a Repository,
+    dir: String,
+}
+
+impl<'a> Sequencer<'a> {
+    fn new(rep: &'a Repository) -> Self {
+        Self {
+            rep,
+            dir: rep.get_gitdir().clone() + "/sequencer",
+        }
+    }
+
+    fn in_progress(&self) -> bool {
+        std::path::Path::new(&self.dir).exists()
+    }
+
+    fn read_todo(&self) -> Vec<(Action, ObjectId)> {
+        let todo = std::fs::read_to_string(self.dir.clone() + "/todo").unwrap_or_default();
+
+        todo.lines()
+            .filter(|l| !l.trim().is_empty() && !l.starts_with('#'))
+            .filter_map(|l| {
+                let mut parts = l.split_whitespace();
+                let action = match parts.next()? {
+                    "pick" | "p" => Action::Pick,
+                    "revert" => Action::Revert,
+                    _ => return None,
+                };
+                Some((action, parts.next()?.parse().ok()?))
+            })
+            .collect()
+    }
+
+    fn write_todo(&self, todo: &[(Action, ObjectId)]) -> Result<(), RepError> {
+        let todo = todo
+            .iter()
+            .map(|(action, hash)| {
+                format!("{} {} {}\n", action.name(), hash, subject(self.rep, hash))
+            })
+            .collect::<String>();
+
+        write_file(self.dir.clone() + "/todo", todo)
+    }
+
+    fn read_opts(&self) -> Options {
+        let ini = match Ini::load_from_file(self.dir.clone() + "/opts") {
+            Ok(ini) => ini,
+            Err(_) => return Options::default(),
+        };
+        let flag = |key| ini.get_from(Some("options"), key) == Some("true");
+
+        Options {
+            record_origin: flag("record-origin"),
+            no_commit: flag("no-commit"),
+        }
+    }
+
+    fn write_opts(&self, opts: &Options) -> Result<(), RepError> {
+        let mut ini = Ini::new();
+        if opts.record_origin {
+            ini.with_section(Some("options"))
+                .set("record-origin", "true");
+        }
+        if opts.no_commit {
+            ini.with_section(Some("options")).set("no-commit", "true");
+        }
+        let path = self.dir.clone() + "/opts";
+        ini.write_to_file(&path).map_err(|e| RepError::Io(path, e))
+    }
+
+    fn read(&self, file: &str) -> Option<String> {
+        std::fs::read_to_string(self.dir.clone() + "/" + file)
+            .ok()
+            .map(|s| s.trim().to_string())
+    }
+
+    fn write(&self, file: &str, content: &str) -> Result<(), RepError> {
+        write_file(self.dir.clone() + "/" + file, content.to_string() + "\n")
+    }
+
+    fn cleanup(&self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+        remove_pseudo_refs(self.rep);
+    }
+}
+
+/// write a file of the sequencer (or rebase) state
+pub fn write_file(path: String, content: String) -> Result<(), RepError> {
+    std::fs::write(&path, content).map_err(|e| RepError::Io(path, e))
+}
+
+/// the tree of a commit, an error if the commit is broken
+pub fn commit_tree(rep: &Repository, hash: &ObjectId) -> Result<ObjectId, RepError> {
+    rep.peel(hash, "tree")
+        .ok_or_else(|| RepError::BadObject("commit".to_string(), hash.to_string()))
+}
+
+/// merge commits can only be picked with -m, which isn't supported
+fn check_not_merge(rep: &Repository, hash: &ObjectId) -> Result<(), RepError> {
+    if load_commit(rep, hash)?.get_parents().len() > 1 {
+        return Err(RepError::InvalidArgument(format!(
+            "commit {} is a merge but no -m option was given.",
+            hash
+        )));
+    }
+    Ok(())
+}
+
+fn remove_pseudo_refs(rep: &Repository) {
+    for file in ["CHERRY_PICK_HEAD", "REVERT_HEAD", "MERGE_MSG"] {
+        let _ = std::fs::remove_file(rep.get_gitdir().clone() + "/" + file);
+    }
+}
+
+pub fn load_commit(rep: &Repository, hash: &ObjectId) -> Result<Commit, RepError> {
+    match Object::load(rep, hash)? {
+        Object::Commit(c) => Ok(c),
+        _ => Err(RepError::BadObject("commit".to_string(), hash.to_string())),
+    }
+}
+
+/// the first line of a commit message, empty if the commit can't be read
+pub fn subject(rep: &Repository, hash: &ObjectId) -> String {
+    let message = load_commit(rep, hash)
+        .map(|c| c.get_message())
+        .unwrap_or_default();
+    message.lines().next().unwrap_or("").to_string()
+}
+
+pub fn short(hash: &ObjectId) -> String {
+    format!("{:.7}", hash)
+}
+
+/// start applying the given commits (or ranges) on top of HEAD
+pub fn start(
+    rep: &Repository,
+    action: Action,
+    revisions: &[String],
+    opts: Options,
+) -> Result<(), RepError> {
+    let sequencer = Sequencer::new(rep);
+    if sequencer.in_progress() {
+        errln!("error: a cherry-pick or revert is already in progress");
+        errln!(
+            "hint: try \"kyu {} (--continue | --skip | --abort)\"",
+            action.command()
+        );
+        return Ok(());
+    }
+
+    let head = rep.ref_resolve("HEAD")?;
+    let commits = if revisions
+        .iter()
+        .any(|r| r.contains("..") || r.starts_with('^'))
+    {
+        let (include, exclude) = parse_revisions(rep, revisions)?;
+        let mut commits = rev_list(rep, &include, &exclude);
+        // picks are replayed from the oldest commit on, reverts undo the newest first
+        if action == Action::Pick {
+            commits.reverse();
+        }
+        commits
+    } else {
+        revisions
+            .iter()
+            .map(|r| resolve_commit(rep, r))
+            .collect::<Result<Vec<ObjectId>, RepError>>()?
+    };
+
+    if commits.is_empty() {
+        errln!("error: empty commit set passed");
+        return Ok(());
+    }
+    for hash in commits.iter() {
+        check_not_merge(rep, hash)?;
+    }
+
+    let index = Index::load(rep)?;
+    let head_tree = commit_tree(rep, &head)?;
+    let dirty = !index.get_conflicts().is_empty()
+        || !index.get_modified(rep).is_empty()
+        || (!opts.no_commit && index.write_tree(rep)? != head_tree);
+    if dirty {
+        errln!(
+            "error: your local changes would be overwritten by {}.",
+            action.command()
+        );
+        errln!("hint: commit your changes or stash them to proceed.");
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(&sequencer.dir).map_err(|e| RepError::Io(sequencer.dir.clone(), e))?;
+    sequencer.write("head", &head.to_string())?;
+    sequencer.write("abort-safety", &head.to_string())?;
+    sequencer.write_opts(&opts)?;
+    sequencer.write_todo(
+        &commits
+            .into_iter()
+            .map(|c| (action, c))
+            .collect::<Vec<(Action, ObjectId)>>(),
+    )?;
+
+    run(rep, &sequencer)
+}
+
+/// commit the resolved conflicts and go on with the remaining commits
+pub fn continue_(rep: &Repository) -> Result<(), RepError> {
+    let sequencer = Sequencer::new(rep);
+    if !sequencer.in_progress() {
+        errln!("error: no cherry-pick or revert in progress");
+        return Ok(());
+    }
+
+    let index = Index::load(rep)?;
+    if !index.get_conflicts().is_empty() {
+        errln!("error: Committing is not possible because you have unmerged files.");
+        errln!("hint: Fix them up in the work tree, and then use 'kyu add <file>'");
+        errln!("hint: as appropriate to mark resolution.");
+        return Ok(());
+    }
+
+    let mut todo = sequencer.read_todo();
+    if let Some((action, hash)) = todo.first().cloned() {
+        let gitdir = rep.get_gitdir().clone();
+        let pseudo_ref = std::path::Path::new(&gitdir).join(action.head_file());
+
+        if pseudo_ref.exists() {
+            if !sequencer.read_opts().no_commit {
+                let message = std::fs::read_to_string(gitdir.clone() + "/MERGE_MSG")
+                    .unwrap_or_default()
+                    .lines()
+                    .filter(|l| !l.starts_with('#'))
+                    .collect::<Vec<&str>>()
+                    .join("\n");
+                create_commit(rep, &sequencer, action, &hash, &index, message.trim_end())?;
+            }
+            remove_pseudo_refs(rep);
+            todo.remove(0);
+            sequencer.write_todo(&todo)?;
+        }
+    }
+
+    run(rep, &sequencer)
+}
+
+/// drop the commit which could not be applied and go on with the next one
+pub fn skip(rep: &Repository) -> Result<(), RepError> {
+    let sequencer = Sequencer::new(rep);
+    if !sequencer.in_progress() {
+        errln!("error: no cherry-pick or revert in progress");
+        return Ok(());
+    }
+
+    let head = rep.ref_resolve("HEAD")?;
+    reset_workdir(rep, &commit_tree(rep, &head)?)?;
+    remove_pseudo_refs(rep);
+
+    let mut todo = sequencer.read_todo();
+    if !todo.is_empty() {
+        todo.remove(0);
+    }
+    sequencer.write_todo(&todo)?;
+
+    run(rep, &sequencer)
+}
+
+/// go back to the state before the cherry-pick / revert started
+pub fn abort(rep: &Repository) -> Result<(), RepError> {
+    let sequencer = Sequencer::new(rep);
+    let head = match sequencer.read("head").and_then(|h| h.parse().ok()) {
+        Some(head) => head,
+        None => {
+            errln!("error: no cherry-pick or revert in progress");
+            return Ok(());
+        }
+    };
+
+    reset_workdir(rep, &commit_tree(rep, &head)?)?;
+    rep.update_head(&head)?;
+    sequencer.cleanup();
+
+    Ok(())
+}
+
+/// apply the todo list until it is empty or a conflict stops it
+fn run(rep: &Repository, sequencer: &Sequencer) -> Result<(), RepError> {
+    let opts = sequencer.read_opts();
+
+    loop {
+        let mut todo = sequencer.read_todo();
+        let (action, hash) = match todo.first() {
+            Some(next) => *next,
+            None => {
+                sequencer.cleanup();
+                return Ok(());
+            }
+        };
+
+        if !apply(rep, sequencer, action, &hash, &opts)? {
+            return Ok(());
+        }
+
+        todo.remove(0);
+        sequencer.write_todo(&todo)?;
+        sequencer.write("abort-safety", &rep.ref_resolve("HEAD")?.to_string())?;
+    }
+}
+
+/// the message of the commit created by a pick / revert
+fn build_message(
+    rep: &Repository,
+    action: Action,
+    hash: &ObjectId,
+    opts: &Options,
+) -> Result<String, RepError> {
+    let message = match action {
+        Action::Pick => {
+            let message = load_commit(rep, hash)?.get_message();
+            if opts.record_origin {
+                format!(
+                    "{}\n\n(cherry picked from commit {})\n",
+                    message.trim_end(),
+                    hash
+                )
+            } else {
+                message
+            }
+        }
+        Action::Revert => format!(
+            "Revert \"{}\"\n\nThis reverts commit {}.\n",
+            subject(rep, hash),
+            hash
+        ),
+    };
+    Ok(message)
+}
+
+/// merge the changes of a commit (or their inverse for a revert) into the index and working directory
+/// returns the paths which have conflicts
+pub fn merge_commit(
+    rep: &Repository,
+    action: Action,
+    hash: &ObjectId,
+) -> Result<Vec<String>, RepError> {
+    let picked = load_commit(rep, hash)?;
+    let tree = picked.get_tree();
+    let parent_tree = match picked.get_parents().first() {
+        Some(parent) => commit_tree(rep, parent)?,
+        None => Tree::from_entries(vec![]).save(rep)?,
+    };
+    let (base, theirs) = match action {
+        Action::Pick => (parent_tree, tree),
+        Action::Revert => (tree, parent_tree),
+    };
+
+    let index = Index::load(rep)?;
+    let ours = index.write_tree(rep)?;
+
+    let label = format!("{}... {}", short(hash), subject(rep, hash));
+    let base_label = format!("parent of {}", label);
+    let labels = match action {
+        Action::Pick => MergeLabels {
+            base: &base_label,
+            ours: "HEAD",
+            theirs: &label,
+        },
+        Action::Revert => MergeLabels {
+            base: &label,
+            ours: "HEAD",
+            theirs: &base_label,
+        },
+    };
+
+    let result = merge_trees(rep, Some(&base), &ours, &theirs, &labels)?;
+    let conflicts = result.conflicts.keys().cloned().collect::<Vec<String>>();
+    let index = apply_merge(rep, &index, result)?;
+    index.save(rep)?;
+
+    Ok(conflicts)
+}
+
+/// merge a single commit into the index, returns false if there were conflicts
+fn apply(
+    rep: &Repository,
+    sequencer: &Sequencer,
+    action: Action,
+    hash: &ObjectId,
+    opts: &Options,
+) -> Result<bool, RepError> {
+    check_not_merge(rep, hash)?;
+
+    let conflicts = merge_commit(rep, action, hash)?;
+
+    let message = build_message(rep, action, hash, opts)?;
+
+    if !conflicts.is_empty() {
+        let mut merge_msg = message.trim_end().to_string() + "\n\n# Conflicts:\n";
+        for path in conflicts.iter() {
+            merge_msg.push_str(&format!("#\t{}\n", path));
+        }
+        write_file(rep.get_gitdir().clone() + "/MERGE_MSG", merge_msg)?;
+        write_file(
+            rep.get_gitdir().clone() + "/" + action.head_file(),
+            format!("{}\n", hash),
+        )?;
+
+        errln!(
+            "error: could not {} {}... {}",
+            action.name(),
+            short(hash),
+            subject(rep, hash)
+        );
+        for path in conflicts.iter() {
+            outln!("CONFLICT (content): Merge conflict in {}", path)?;
+        }
+        errln!("hint: after resolving the conflicts, mark the corrected paths");
+        errln!(
+            "hint: with 'kyu add <paths>' and run 'kyu {} --continue'",
+            action.command()
+        );
+        return Ok(false);
+    }
+
+    if !opts.no_commit {
+        create_commit(rep, sequencer, action, hash, &Index::load(rep)?, &message)?;
+    }
+
+    Ok(true)
+}
+
+/// create the commit for a picked / reverted commit from the current index
+fn create_commit(
+    rep: &Repository,
+    sequencer: &Sequencer,
+    action: Action,
+    hash: &ObjectId,
+    index: &Index,
+    message: &str,
+) -> Result<(), RepError> {
+    let head = rep.ref_resolve("HEAD")?;
+    let tree = index.write_tree(rep)?;
+
+    if rep.peel(&head, "tree") == Some(tree) {
+        outln!(
+            "The previous {} of {} is now empty, skipping it.",
+            action.command(),
+            short(hash)
+        )?;
+        return Ok(());
+    }
+
+    // a cherry-pick keeps the original author, a revert is authored by whoever reverts
+    let author = match action {
+        Action::Pick => load_commit(rep, hash)?.get_author(),
+        Action::Revert => rep.get_signature("AUTHOR")?,
+    };
+    let committer = rep.get_signature("COMMITTER")?;
+
+    let new = Commit::create(&tree, &[head], &author, &committer, message).save(rep)?;
+    rep.update_head(&new)?;
+    sequencer.write("abort-safety", &new.to_string())?;
+
+    let branch = rep
+        .get_head_ref()
+        .map(|r| r.trim_start_matches("refs/heads/").to_string())
+        .unwrap_or("detached HEAD".to_string());
+    outln!(
+        "[{} {}] {}",
+        branch,
+        short(&new),
+        message.lines().next().unwrap_or("")
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reset::{reset, Mode};
+
+    fn read(rep: &Repository, name: &str) -> String {
+        std::fs::read_to_string(rep.get_workdir().clone() + "/" + name).unwrap()
+    }
+
+    fn exists(rep: &Repository, name: &str) -> bool {
+        std::path::Path::new(&(rep.get_gitdir().clone() + "/" + name)).exists()
+    }
+
+    #[test]
+    fn revert_undoes_a_commit() {
+        let rep = Repository::temporary("sequencer-revert");
+        let first = rep.commit_files(&[("a.txt", "a\n")], "first");
+        let second = rep.commit_files(&[("a.txt", "b\n")], "second");
+
+        start(
+            &rep,
+            Action::Revert,
+            &["HEAD".to_string()],
+            Options::default(),
+        )
+        .unwrap();
+
+        let head = rep.ref_resolve("HEAD").unwrap();
+        let commit = load_commit(&rep, &head).unwrap();
+        assert_eq!(commit.get_parents(), &[second]);
+        assert_eq!(commit.get_tree(), commit_tree(&rep, &first).unwrap());
+        assert!(commit.get_message().starts_with("Revert \"second\"\n"));
+        assert_eq!(read(&rep, "a.txt"), "a\n");
+        assert!(!exists(&rep, "sequencer"));
+    }
+
+    #[test]
+    fn ranges_are_picked_oldest_first() {
+        let rep = Repository::temporary("sequencer-range");
+        let first = rep.commit_files(&[("a.txt", "a\n")], "first");
+        rep.commit_files(&[("b.txt", "b\n")], "second");
+        let third = rep.commit_files(&[("c.txt", "c\n")], "third");
+        reset(&rep, &first.to_string(), Mode::Hard).unwrap();
+
+        let range = format!("{}..{}", first, third);
+        let opts = Options {
+            record_origin: true,
+            ..Default::default()
+        };
+        start(&rep, Action::Pick, &[range], opts).unwrap();
+
+        let head = rep.ref_resolve("HEAD").unwrap();
+        let commit = load_commit(&rep, &head).unwrap();
+        assert_eq!(
+            commit.get_message(),
+            format!("third\n\n(cherry picked from commit {})\n", third)
+        );
+        let parent = load_commit(&rep, &commit.get_parents()[0]).unwrap();
+        assert_eq!(subject(&rep, &commit.get_parents()[0]), "second");
+        assert_eq!(parent.get_parents(), &[first]);
+        assert_eq!(
+            (read(&rep, "b.txt"), read(&rep, "c.txt")),
+            ("b\n".into(), "c\n".into())
+        );
+    }
+
+    #[test]
+    fn conflicts_stop_until_they_are_continued_or_aborted() {
+        let rep = Repository::temporary("sequencer-conflict");
+        let first = rep.commit_files(&[("a.txt", "a\n")], "first");
+        let second = rep.commit_files(&[("a.txt", "b\n")], "second");
+        reset(&rep, &first.to_string(), Mode::Hard).unwrap();
+        let third = rep.commit_files(&[("a.txt", "c\n")], "third");
+
+        let pick = || {
+            start(
+                &rep,
+                Action::Pick,
+                &[second.to_string()],
+                Options::default(),
+            )
+        };
+        pick().unwrap();
+        assert!(exists(&rep, "sequencer") && exists(&rep, "CHERRY_PICK_HEAD"));
+        assert_eq!(rep.ref_resolve("HEAD").unwrap(), third);
+        assert!(read(&rep, "a.txt").starts_with("<<<<<<< HEAD\nc\n"));
+
+        abort(&rep).unwrap();
+        assert!(!exists(&rep, "sequencer") && !exists(&rep, "CHERRY_PICK_HEAD"));
+        assert_eq!(rep.ref_resolve("HEAD").unwrap(), third);
+        assert_eq!(read(&rep, "a.txt"), "c\n");
+
+        pick().unwrap();
+        std::fs::write(rep.get_workdir().clone() + "/a.txt", "resolved\n").unwrap();
+        crate::add::add(&rep, &[rep.get_workdir().clone() + "/a.txt"]).unwrap();
+        continue_(&rep).unwrap();
+
+        let head = rep.ref_resolve("HEAD").unwrap();
+        let commit = load_commit(&rep, &head).unwrap();
+        assert_eq!(commit.get_parents(), &[third]);
+        assert_eq!(subject(&rep, &head), "second");
+        assert!(!exists(&rep, "sequencer"));
+    }
+
+    #[test]
+    fn merge_commits_are_refused_before_any_state_is_written() {
+        let rep = Repository::temporary("sequencer-merge");
+        let first = rep.commit_files(&[("a.txt", "a\n")], "first");
+        let second = rep.commit_files(&[("b.txt", "b\n")], "second");
+        let commit = load_commit(&rep, &second).unwrap();
+        let signature = rep.get_signature("AUTHOR").unwrap();
+        let merge = Commit::create(
+            &commit.get_tree(),
+            &[second, first],
+            &signature,
+            &signature,
+            "merge\n",
+        )
+        .save(&rep)
+        .unwrap();
+
+        let result = start(&rep, Action::Pick, &[merge.to_string()], Options::default());
+        assert!(matches!(result, Err(RepError::InvalidArgument(_))));
+        assert!(!exists(&rep, "sequencer"));
+        assert_eq!(rep.ref_resolve("HEAD").unwrap(), second);
+    }
+}
@@ -0,0 +1,306 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+use crate::mailmap::Mailmap;
+use crate::objects::{Object, KVLM};
+use crate::repository::{RepError, Repository};
+use crate::revwalk::{parse_revisions, rev_list};
+use crate::signature::Signature;
+
+/// what the commits are grouped by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Group {
+    Author,
+    Committer,
+    /// the values of a trailer like Co-authored-by
+    Trailer(String),
+}
+
+impl Group {
+    /// parse the value of --group
+    pub fn parse(group: &str) -> Option<Self> {
+        match group {
+            "author" => Some(Self::Author),
+            "committer" => Some(Self::Committer),
+            _ => group
+                .strip_prefix("trailer:")
+                .filter(|key| !key.is_empty())
+                .map(|key| Self::Trailer(key.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// only show the number of commits (-s)
+    pub summary: bool,
+    /// sort by the number of commits instead of the name (-n)
+    pub numbered: bool,
+    /// show the email of the identities (-e)
+    pub email: bool,
+    /// groups to count commits in, the author if empty
+    pub groups: Vec<Group>,
+}
+
+/// collects the subjects of commits per identity
+struct Shortlog {
+    opts: Options,
+    mailmap: Mailmap,
+    /// identity -> subjects, newest first
+    entries: BTreeMap<String, Vec<String>>,
+}
+
+impl Shortlog {
+    fn new(opts: Options, mailmap: Mailmap) -> Self {
+        let mut opts = opts;
+        if opts.groups.is_empty() {
+            opts.groups.push(Group::Author);
+        }
+        Self {
+            opts,
+            mailmap,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// the identity as it is shown, mapped with the mailmap
+    fn format_ident(&self, signature: &Signature) -> String {
+        let signature = self.mailmap.apply(signature);
+        match self.opts.email {
+            true => signature.identity(),
+            false => signature.name,
+        }
+    }
+
+    fn insert(&mut self, ident: String, subject: &str) {
+        let subject = match subject.is_empty() {
+            true => "<none>".to_string(),
+            false => subject.to_string(),
+        };
+        self.entries.entry(ident).or_default().push(subject);
+    }
+
+    fn add_commit(&mut self, author: &Signature, committer: &Signature, message: &str) {
+        let subject = subject(message);
+        let mut idents: Vec<String> = vec![];
+
+        for group in self.opts.groups.clone() {
+            match group {
+                Group::Author => idents.push(self.format_ident(author)),
+                Group::Committer => idents.push(self.format_ident(committer)),
+                Group::Trailer(key) => {
+                    for value in trailers(message, &key) {
+                        // trailers naming a person are mapped like authors
+                        match value.contains('<') && value.ends_with('>') {
+                            true => idents.push(self.format_ident(&Signature::parse(&value))),
+                            false => idents.push(value),
+                        }
+                    }
+                }
+            }
+        }
+
+        // a commit is counted only once per identity, even if it shows up in several groups
+        let mut seen = vec![];
+        for ident in idents {
+            if !seen.contains(&ident) {
+                self.insert(ident.clone(), &subject);
+                seen.push(ident);
+            }
+        }
+    }
+
+    fn output(&self) -> Result<(), RepError> {
+        let mut entries: Vec<(&String, &Vec<String>)> = self.entries.iter().collect();
+        if self.opts.numbered {
+            entries.sort_by_key(|(_, subjects)| std::cmp::Reverse(subjects.len()));
+        }
+
+        for (ident, subjects) in entries {
+            if self.opts.summary {
+                outln!("{:>6}\t{}", subjects.len(), ident)?;
+                continue;
+            }
+
+            outln!("{} ({}):", ident, subjects.len())?;
+            for subject in subjects.iter().rev() {
+                outln!("      {}", subject)?;
+            }
+            outln!()?;
+        }
+        Ok(())
+    }
+}
+
+/// the first paragraph of a message on one line
+fn subject(message: &str) -> String {
+    message
+        .trim_start_matches('\n')
+        .split("\n\n")
+        .next()
+        .unwrap_or("")
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// the values of a trailer in the last paragraph of a message, the key is case insensitive
+fn trailers(message: &str, key: &str) -> Vec<String> {
+    let paragraphs: Vec<&str> = message
+        .trim_end()
+        .split("\n\n")
+        .filter(|p| !p.trim().is_empty())
+        .collect();
+    // the subject can never be a trailer block
+    if paragraphs.len() < 2 {
+        return vec![];
+    }
+
+    let block = paragraphs[paragraphs.len() - 1];
+    let mut values: Vec<(String, String)> = vec![];
+    for line in block.lines() {
+        if line.starts_with(char::is_whitespace) {
+            // continuation of the previous trailer
+            match values.last_mut() {
+                Some((_, value)) => {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                    continue;
+                }
+                None => return vec![],
+            }
+        }
+        match line.split_once(':') {
+            Some((k, v)) if !k.is_empty() && k.chars().all(|c| c.is_alphanumeric() || c == '-') => {
+                values.push((k.to_string(), v.trim().to_string()))
+            }
+            _ => return vec![],
+        }
+    }
+
+    values
+        .into_iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v)
+        .collect()
+}
+
+/// summarize the commits of the revisions, HEAD if none are given
+pub fn shortlog(rep: &Repository, revisions: &[String], opts: Options) -> Result<(), RepError> {
+    let revisions = match revisions.is_empty() {
+        true => vec!["HEAD".to_string()],
+        false => revisions.to_vec(),
+    };
+    let (include, exclude) = parse_revisions(rep, &revisions)?;
+
+    let mut shortlog = Shortlog::new(opts, Mailmap::load(rep));
+    for hash in rev_list(rep, &include, &exclude) {
+        if let Object::Commit(c) = Object::load(rep, &hash)? {
+            shortlog.add_commit(&c.get_author(), &c.get_committer(), &c.get_message());
+        }
+    }
+    shortlog.output()?;
+
+    Ok(())
+}
+
+/// summarize the output of git log read from stdin
+pub fn shortlog_stdin(rep: Option<&Repository>, opts: Options) -> Result<(), RepError> {
+    // the log only contains the author (or the committer with --pretty=fuller)
+    let prefix = match opts.groups.first() {
+        Some(Group::Committer) => "Commit: ",
+        _ => "Author: ",
+    };
+    let mailmap = rep.map(Mailmap::load).unwrap_or_default();
+    let mut shortlog = Shortlog::new(opts, mailmap);
+
+    let mut lines = std::io::stdin().lock().lines().map_while(Result::ok);
+    while let Some(line) = lines.next() {
+        let ident = match line.strip_prefix(prefix) {
+            Some(ident) => Signature::parse(ident.trim()),
+            None => continue,
+        };
+
+        // skip the other headers up to the empty line before the message
+        for line in lines.by_ref() {
+            if line.trim().is_empty() {
+                break;
+            }
+        }
+        let subject = lines
+            .by_ref()
+            .map(|l| l.trim().to_string())
+            .find(|l| !l.is_empty())
+            .unwrap_or_default();
+
+        let ident = shortlog.format_ident(&ident);
+        shortlog.insert(ident, &subject);
+    }
+
+    shortlog.output()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &str = "fix the parser\nfor real\n\nlonger explanation\n\nCo-authored-by: Other <other@x>\nco-authored-by: Third <third@x>\nReviewed-by: R\n  continued\n";
+
+    fn signature(name: &str, email: &str) -> Signature {
+        Signature::parse(&format!("{} <{}> 1700000000 +0000", name, email))
+    }
+
+    #[test]
+    fn subjects_and_trailers_are_extracted() {
+        assert_eq!(subject(MESSAGE), "fix the parser for real");
+        assert_eq!(subject("\n\n"), "");
+        assert_eq!(
+            trailers(MESSAGE, "Co-Authored-By"),
+            vec!["Other <other@x>", "Third <third@x>"]
+        );
+        assert_eq!(trailers(MESSAGE, "reviewed-by"), vec!["R continued"]);
+        // a message without a body has no trailers, neither has a block with other lines
+        assert!(trailers("Key: value\n", "key").is_empty());
+        assert!(trailers("subject\n\nKey: value\nnot a trailer\n", "key").is_empty());
+    }
+
+    #[test]
+    fn commits_are_grouped_once_per_identity() {
+        let mut mailmap = Mailmap::new();
+        mailmap.add_data("Proper Name <old@x>\n");
+        let opts = Options {
+            email: true,
+            groups: vec![
+                Group::Author,
+                Group::Committer,
+                Group::parse("trailer:co-authored-by").unwrap(),
+            ],
+            ..Default::default()
+        };
+        let mut shortlog = Shortlog::new(opts, mailmap);
+
+        let author = signature("Old Name", "old@x");
+        shortlog.add_commit(&author, &author, MESSAGE);
+        shortlog.add_commit(&author, &signature("Other", "other@x"), "\n");
+
+        let counts = shortlog
+            .entries
+            .iter()
+            .map(|(ident, subjects)| (ident.as_str(), subjects.len()))
+            .collect::<Vec<(&str, usize)>>();
+        assert_eq!(
+            counts,
+            vec![
+                ("Other <other@x>", 2),
+                ("Proper Name <old@x>", 2),
+                ("Third <third@x>", 1)
+            ]
+        );
+        assert_eq!(shortlog.entries["Other <other@x>"][1], "<none>");
+        assert!(Group::parse("trailer:").is_none());
+    }
+}
@@ -0,0 +1,333 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use std::fmt;
+
+/// an identity with a timestamp like "Name <email> 1700000000 +0100"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    /// seconds since the epoch
+    pub seconds: i64,
+    /// offset of the timezone in minutes east of UTC
+    pub offset: i32,
+}
+
+/// how dates are shown, selected with --date=<format>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DateFormat {
+    /// Tue Nov 14 17:43:20 2023 -0530
+    Default,
+    /// 2023-11-14 17:43:20 -0530
+    Iso,
+    /// 2023-11-14T17:43:20-05:30
+    IsoStrict,
+    /// Tue, 14 Nov 2023 17:43:20 -0530
+    Rfc,
+    /// 3 weeks ago
+    Relative,
+    /// 2023-11-14
+    Short,
+    /// 1700000000
+    Unix,
+    /// 1700000000 -0530
+    Raw,
+    /// strftime format, format:%Y
+    Format(String),
+}
+
+impl DateFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        if let Some(format) = name.strip_prefix("format:") {
+            return Some(Self::Format(format.to_string()));
+        }
+        let format = match name {
+            "default" => Self::Default,
+            "iso" | "iso8601" => Self::Iso,
+            "iso-strict" | "iso8601-strict" => Self::IsoStrict,
+            "rfc" | "rfc2822" => Self::Rfc,
+            "relative" => Self::Relative,
+            "short" => Self::Short,
+            "unix" => Self::Unix,
+            "raw" => Self::Raw,
+            _ => return None,
+        };
+        Some(format)
+    }
+}
+
+impl Signature {
+    /// parse an identity line, missing or invalid dates become 0 +0000 like git shows them
+    pub fn parse(line: &str) -> Self {
+        let (name, rest) = line.split_once('<').unwrap_or((line, ""));
+        let (email, date) = match rest.rfind('>') {
+            Some(close) => (&rest[..close], &rest[close + 1..]),
+            None => (rest, ""),
+        };
+        let (seconds, offset) = parse_raw_date(date).unwrap_or((0, 0));
+
+        Self {
+            name: name.trim().to_string(),
+            email: email.to_string(),
+            seconds,
+            offset,
+        }
+    }
+
+    /// an identity at the current time in the local timezone
+    pub fn now(name: &str, email: &str) -> Self {
+        let now = Local::now();
+        Self {
+            name: name.to_string(),
+            email: email.to_string(),
+            seconds: now.timestamp(),
+            offset: now.offset().local_minus_utc() / 60,
+        }
+    }
+
+    /// the timezone as stored in objects, like +0100 or -0530
+    pub fn timezone(&self) -> String {
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let minutes = self.offset.abs();
+        format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+    }
+
+    /// "Name <email>" without the date
+    pub fn identity(&self) -> String {
+        format!("{} <{}>", self.name, self.email)
+    }
+
+    fn datetime(&self) -> DateTime<FixedOffset> {
+        let offset =
+            FixedOffset::east_opt(self.offset * 60).unwrap_or(FixedOffset::east_opt(0).unwrap());
+        Utc.timestamp_opt(self.seconds, 0)
+            .single()
+            .unwrap_or_default()
+            .with_timezone(&offset)
+    }
+
+    /// the date of the signature in its own timezone
+    pub fn format_date(&self, format: &DateFormat) -> String {
+        let date = self.datetime();
+        match format {
+            DateFormat::Default => date.format("%a %b %-d %H:%M:%S %Y %z").to_string(),
+            DateFormat::Iso => date.format("%Y-%m-%d %H:%M:%S %z").to_string(),
+            DateFormat::IsoStrict => date.format("%Y-%m-%dT%H:%M:%S%:z").to_string(),
+            DateFormat::Rfc => date.format("%a, %-d %b %Y %H:%M:%S %z").to_string(),
+            DateFormat::Relative => relative_date(Utc::now().timestamp() - self.seconds),
+            DateFormat::Short => date.format("%Y-%m-%d").to_string(),
+            DateFormat::Unix => self.seconds.to_string(),
+            DateFormat::Raw => format!("{} {}", self.seconds, self.timezone()),
+            DateFormat::Format(format) => {
+                let mut result = String::new();
+                // an invalid format string makes chrono fail while writing
+                match std::fmt::write(&mut result, format_args!("{}", date.format(format))) {
+                    Ok(_) => result,
+                    Err(_) => format.clone(),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} <{}> {} {}",
+            self.name,
+            self.email,
+            self.seconds,
+            self.timezone()
+        )
+    }
+}
+
+/// parse "1700000000 +0100" into seconds and the offset in minutes
+fn parse_raw_date(date: &str) -> Option<(i64, i32)> {
+    let mut parts = date.split_whitespace();
+    let seconds = parts.next()?.parse::<i64>().ok()?;
+    let offset = match parts.next() {
+        Some(tz) => parse_timezone(tz)?,
+        None => 0,
+    };
+    Some((seconds, offset))
+}
+
+/// parse a timezone like -0530 into minutes
+fn parse_timezone(tz: &str) -> Option<i32> {
+    let (sign, digits) = match tz.as_bytes().first()? {
+        b'+' => (1, &tz[1..]),
+        b'-' => (-1, &tz[1..]),
+        _ => return None,
+    };
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours = digits[..2].parse::<i32>().ok()?;
+    let minutes = digits[2..].parse::<i32>().ok()?;
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// parse a date given in GIT_AUTHOR_DATE and GIT_COMMITTER_DATE
+/// supports the raw format (with an optional @), rfc 2822 and iso 8601
+pub fn parse_date(date: &str) -> Option<(i64, i32)> {
+    let date = date.trim();
+    if let Some(raw) = parse_raw_date(date.trim_start_matches('@')) {
+        return Some(raw);
+    }
+
+    let parsed = DateTime::parse_from_rfc2822(date)
+        .or_else(|_| DateTime::parse_from_rfc3339(date))
+        .or_else(|_| DateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S %z"));
+    if let Ok(parsed) = parsed {
+        return Some((parsed.timestamp(), parsed.offset().local_minus_utc() / 60));
+    }
+
+    // without a timezone the date is in the local timezone
+    let naive = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S"))
+        .ok()?;
+    let local = Local.from_local_datetime(&naive).earliest()?;
+    Some((local.timestamp(), local.offset().local_minus_utc() / 60))
+}
+
+/// parse an expiry date like "2.weeks.ago", "3 days ago", "now", "never" or an absolute date
+/// into seconds, months and years are approximated as 30 and 365 days
+pub fn parse_expiry(date: &str, now: i64) -> Option<i64> {
+    let date = date.trim();
+    match date {
+        "now" | "all" => return Some(now),
+        "never" | "false" => return Some(0),
+        _ => {}
+    }
+
+    let parts = date.split(['.', ' ']).collect::<Vec<&str>>();
+    if let [n, unit, "ago"] = parts.as_slice() {
+        let n = n.parse::<i64>().ok()?;
+        let seconds = match unit.trim_end_matches('s') {
+            "second" => 1,
+            "minute" => 60,
+            "hour" => 60 * 60,
+            "day" => 24 * 60 * 60,
+            "week" => 7 * 24 * 60 * 60,
+            "month" => 30 * 24 * 60 * 60,
+            "year" => 365 * 24 * 60 * 60,
+            _ => return None,
+        };
+        return Some(now - n * seconds);
+    }
+
+    parse_date(date).map(|(seconds, _)| seconds)
+}
+
+/// a duration in seconds like git shows relative dates
+fn relative_date(diff: i64) -> String {
+    let plural = |n: i64, unit: &str| match n {
+        1 => format!("{} {}", n, unit),
+        _ => format!("{} {}s", n, unit),
+    };
+
+    if diff < 0 {
+        return "in the future".to_string();
+    }
+    if diff < 90 {
+        return plural(diff, "second") + " ago";
+    }
+    let minutes = (diff + 30) / 60;
+    if minutes < 90 {
+        return plural(minutes, "minute") + " ago";
+    }
+    let hours = (minutes + 30) / 60;
+    if hours < 36 {
+        return plural(hours, "hour") + " ago";
+    }
+    let days = (hours + 12) / 24;
+    if days < 14 {
+        return plural(days, "day") + " ago";
+    }
+    if days < 70 {
+        return plural((days + 3) / 7, "week") + " ago";
+    }
+    if days < 365 {
+        return plural((days + 15) / 30, "month") + " ago";
+    }
+    if days < 1825 {
+        let total_months = (days * 12 * 2 + 365) / (365 * 2);
+        let (years, months) = (total_months / 12, total_months % 12);
+        return match months {
+            0 => plural(years, "year") + " ago",
+            _ => format!("{}, {} ago", plural(years, "year"), plural(months, "month")),
+        };
+    }
+    plural((days + 183) / 365, "year") + " ago"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: &str = "A U Thor <author@example.com> 1700000000 -0530";
+
+    #[test]
+    fn identity_lines_are_parsed_and_written_back() {
+        let signature = Signature::parse(LINE);
+        assert_eq!(signature.name, "A U Thor");
+        assert_eq!(signature.email, "author@example.com");
+        assert_eq!((signature.seconds, signature.offset), (1700000000, -330));
+        assert_eq!(signature.to_string(), LINE);
+        assert_eq!(signature.identity(), "A U Thor <author@example.com>");
+
+        // broken dates are shown as the epoch like git does
+        let broken = Signature::parse("Name <mail> yesterday");
+        assert_eq!((broken.seconds, broken.offset), (0, 0));
+        assert_eq!(broken.timezone(), "+0000");
+    }
+
+    #[test]
+    fn dates_are_formatted_in_their_timezone() {
+        let signature = Signature::parse(LINE);
+        let format = |name: &str| signature.format_date(&DateFormat::parse(name).unwrap());
+        assert_eq!(format("default"), "Tue Nov 14 16:43:20 2023 -0530");
+        assert_eq!(format("iso"), "2023-11-14 16:43:20 -0530");
+        assert_eq!(format("iso-strict"), "2023-11-14T16:43:20-05:30");
+        assert_eq!(format("rfc"), "Tue, 14 Nov 2023 16:43:20 -0530");
+        assert_eq!(format("short"), "2023-11-14");
+        assert_eq!(format("raw"), "1700000000 -0530");
+        assert_eq!(format("format:%Y/%m"), "2023/11");
+        assert!(DateFormat::parse("tomorrow").is_none());
+    }
+
+    #[test]
+    fn dates_are_parsed_in_the_formats_git_accepts() {
+        let expected = Some((1700000000, -330));
+        assert_eq!(parse_date("1700000000 -0530"), expected);
+        assert_eq!(parse_date("@1700000000 -0530"), expected);
+        assert_eq!(parse_date("Tue, 14 Nov 2023 16:43:20 -0530"), expected);
+        assert_eq!(parse_date("2023-11-14T16:43:20-05:30"), expected);
+        assert_eq!(parse_date("2023-11-14 16:43:20 -0530"), expected);
+        assert_eq!(parse_date("1700000000 +5"), None);
+        assert_eq!(parse_date("soon"), None);
+    }
+
+    #[test]
+    fn expiry_dates_are_relative_to_now() {
+        let now = 1700000000;
+        assert_eq!(parse_expiry("now", now), Some(now));
+        assert_eq!(parse_expiry("never", now), Some(0));
+        assert_eq!(parse_expiry("2.weeks.ago", now), Some(now - 14 * 24 * 3600));
+        assert_eq!(parse_expiry("3 days ago", now), Some(now - 3 * 24 * 3600));
+        assert_eq!(parse_expiry("@1600000000", now), Some(1600000000));
+        assert_eq!(parse_expiry("2.fortnights.ago", now), None);
+    }
+
+    #[test]
+    fn relative_dates_are_rounded_like_git() {
+        assert_eq!(relative_date(-5), "in the future");
+        assert_eq!(relative_date(1), "1 second ago");
+        assert_eq!(relative_date(3 * 24 * 3600), "3 days ago");
+        assert_eq!(relative_date(400 * 24 * 3600), "1 year, 1 month ago");
+        assert_eq!(relative_date(10 * 365 * 24 * 3600), "10 years ago");
+    }
+}
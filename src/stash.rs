@@ -0,0 +1,473 @@
+#![allow(dead_code)]
+
+use crate::diff::{diff_trees, format_patch, format_stat};
+use crate::index::{
+    read_workdir_blob, remove_workdir_file, reset_workdir, untracked_files, write_workdir_file,
+    Index, IndexEntry,
+};
+use crate::merge::{apply_merge, merge_trees, tree_files, MergeLabels};
+use crate::objects::Commit;
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+use crate::sequencer::{load_commit, short, subject};
+
+const STASH_REF: &str = "refs/stash";
+
+#[derive(Debug, Default)]
+pub struct PushOptions {
+    /// also stash untracked files (-u)
+    pub include_untracked: bool,
+    /// also stash untracked and ignored files (-a)
+    pub all: bool,
+    /// leave the staged changes in the index and working directory
+    pub keep_index: bool,
+    pub message: Option<String>,
+}
+
+/// find the position and commit of a stash entry given as stash@{n}, n or nothing (the latest)
+fn resolve_stash(rep: &Repository, stash: Option<String>) -> Option<(usize, ObjectId)> {
+    let stash = stash.unwrap_or("0".to_string());
+    let n = match stash.parse::<usize>() {
+        Ok(n) => n,
+        Err(_) => stash
+            .strip_prefix("stash@{")
+            .and_then(|s| s.strip_suffix('}'))
+            .and_then(|s| s.parse::<usize>().ok())?,
+    };
+
+    let reflog = rep.read_reflog(STASH_REF);
+    let pos = reflog.len().checked_sub(n + 1)?;
+    Some((n, reflog[pos].new))
+}
+
+/// the stash entry or an error if there is none
+fn find_stash(rep: &Repository, stash: Option<String>) -> Result<(usize, ObjectId), RepError> {
+    resolve_stash(rep, stash)
+        .ok_or_else(|| RepError::InvalidArgument("No stash entries found.".to_string()))
+}
+
+/// a parent of a stash commit: 0 is HEAD, 1 the index and 2 the untracked files
+fn stash_parent(stash: &Commit, hash: &ObjectId, n: usize) -> Result<ObjectId, RepError> {
+    stash
+        .get_parents()
+        .get(n)
+        .copied()
+        .ok_or_else(|| RepError::InvalidArgument(format!("'{}' is not a stash-like commit", hash)))
+}
+
+fn commit_tree(rep: &Repository, hash: &ObjectId) -> Result<ObjectId, RepError> {
+    rep.peel(hash, "tree")
+        .ok_or_else(|| RepError::BadObject("commit".to_string(), hash.to_string()))
+}
+
+/// the current branch name for the stash messages
+fn branch_name(rep: &Repository) -> String {
+    rep.get_head_ref()
+        .map(|r| r.trim_start_matches("refs/heads/").to_string())
+        .unwrap_or("(no branch)".to_string())
+}
+
+/// save the local changes as stash commits and reset the working directory to HEAD
+pub fn push(rep: &Repository, opts: PushOptions) -> Result<(), RepError> {
+    let head = rep.ref_resolve("HEAD")?;
+    let head_tree = commit_tree(rep, &head)?;
+
+    let index = Index::load(rep)?;
+    if !index.get_conflicts().is_empty() {
+        return Err(RepError::InvalidArgument(
+            "could not save stash: needs merge".to_string(),
+        ));
+    }
+
+    let index_tree = index.write_tree(rep)?;
+    let modified = index.get_modified(rep);
+    let untracked = match opts.include_untracked || opts.all {
+        true => untracked_files(rep, &index, opts.all),
+        false => vec![],
+    };
+
+    if index_tree == head_tree && modified.is_empty() && untracked.is_empty() {
+        outln!("No local changes to save")?;
+        return Ok(());
+    }
+
+    let branch = branch_name(rep);
+    let description = format!("{}: {} {}", branch, short(&head), subject(rep, &head));
+    let author = rep.get_signature("AUTHOR")?;
+    let committer = rep.get_signature("COMMITTER")?;
+    let commit = |tree: &ObjectId, parents: &[ObjectId], message: &str| {
+        Commit::create(tree, parents, &author, &committer, message).save(rep)
+    };
+
+    // the stash commit has HEAD, the index and the untracked files as parents
+    let mut parents = vec![head];
+    parents.push(commit(
+        &index_tree,
+        std::slice::from_ref(&head),
+        &format!("index on {}\n", description),
+    )?);
+
+    if !untracked.is_empty() {
+        let mut untracked_index = Index::new();
+        for path in untracked.iter() {
+            let sha = read_workdir_blob(rep, path)?.save(rep)?;
+            untracked_index.add(IndexEntry::from_workdir(rep, path, &sha));
+        }
+        let tree = untracked_index.write_tree(rep)?;
+        parents.push(commit(
+            &tree,
+            &[],
+            &format!("untracked files on {}\n", description),
+        )?);
+    }
+
+    let mut worktree = index.clone();
+    for path in modified.iter() {
+        let full = rep.get_workdir().clone() + "/" + path;
+        if std::fs::symlink_metadata(&full).is_ok() {
+            let sha = read_workdir_blob(rep, path)?.save(rep)?;
+            worktree.add(IndexEntry::from_workdir(rep, path, &sha));
+        } else {
+            worktree.remove(path);
+        }
+    }
+
+    let message = match opts.message {
+        Some(message) => format!("On {}: {}", branch, message),
+        None => format!("WIP on {}", description),
+    };
+    let stash = commit(
+        &worktree.write_tree(rep)?,
+        &parents,
+        &(message.clone() + "\n"),
+    )?;
+
+    let old = rep.ref_resolve(STASH_REF).unwrap_or(ObjectId::NULL);
+    rep.update_ref(STASH_REF, &stash)?;
+    rep.append_reflog(STASH_REF, &old, &stash, &message)?;
+
+    reset_workdir(rep, &head_tree)?;
+    if opts.keep_index {
+        reset_workdir(rep, &index_tree)?;
+    }
+    for path in untracked.iter() {
+        remove_workdir_file(rep, path);
+    }
+
+    outln!("Saved working directory and index state {}", message)?;
+    Ok(())
+}
+
+pub fn list(rep: &Repository) -> Result<(), RepError> {
+    for (i, entry) in rep.read_reflog(STASH_REF).iter().rev().enumerate() {
+        outln!("stash@{{{}}}: {}", i, entry.message)?;
+    }
+    Ok(())
+}
+
+/// print the changes of a stash entry compared to the commit it was based on
+pub fn show(rep: &Repository, stash: Option<String>, patch: bool) -> Result<(), RepError> {
+    let (_, hash) = find_stash(rep, stash)?;
+
+    let commit = load_commit(rep, &hash)?;
+    let base = rep.peel(&stash_parent(&commit, &hash, 0)?, "tree");
+    let changes = diff_trees(rep, base.as_ref(), Some(&commit.get_tree()))?;
+
+    if patch {
+        out!("{}", format_patch(rep, &changes)?)?;
+    } else {
+        out!("{}", format_stat(rep, &changes)?)?;
+    }
+    Ok(())
+}
+
+/// merge a stash entry into the working directory (and the index with restore_index)
+/// returns true if the stash was applied without conflicts
+pub fn apply(
+    rep: &Repository,
+    stash: Option<String>,
+    restore_index: bool,
+) -> Result<bool, RepError> {
+    let (_, hash) = find_stash(rep, stash)?;
+
+    let stash = load_commit(rep, &hash)?;
+    let base_tree = commit_tree(rep, &stash_parent(&stash, &hash, 0)?)?;
+
+    let index = Index::load(rep)?;
+    if !index.get_conflicts().is_empty() {
+        return Err(RepError::InvalidArgument(
+            "Cannot apply a stash in the middle of a merge".to_string(),
+        ));
+    }
+    let ours = index.write_tree(rep)?;
+
+    let labels = MergeLabels {
+        base: "Stash base",
+        ours: "Updated upstream",
+        theirs: "Stashed changes",
+    };
+
+    let staged = match restore_index {
+        true => {
+            let index_tree = commit_tree(rep, &stash_parent(&stash, &hash, 1)?)?;
+            let result = merge_trees(rep, Some(&base_tree), &ours, &index_tree, &labels)?;
+            if !result.conflicts.is_empty() {
+                outln!("Conflicts in index. Try without --index.")?;
+                return Ok(false);
+            }
+            Some(result.index)
+        }
+        false => None,
+    };
+
+    let untracked = match stash.get_parents().get(2) {
+        Some(u) => tree_files(rep, Some(&commit_tree(rep, u)?))?,
+        None => Default::default(),
+    };
+    for path in untracked.keys() {
+        if std::fs::symlink_metadata(rep.get_workdir().clone() + "/" + path).is_ok() {
+            return Err(RepError::InvalidArgument(format!(
+                "{} already exists, no checkout\nerror: could not restore untracked files from stash",
+                path
+            )));
+        }
+    }
+
+    let result = merge_trees(rep, Some(&base_tree), &ours, &stash.get_tree(), &labels)?;
+
+    // local changes which the merge would overwrite
+    let current = index.to_map();
+    let merged = result.index.to_map();
+    let overwritten = index
+        .get_modified(rep)
+        .into_iter()
+        .filter(|p| current.get(p) != merged.get(p) || result.conflicts.contains_key(p))
+        .collect::<Vec<String>>();
+    if !overwritten.is_empty() {
+        let mut message =
+            "Your local changes to the following files would be overwritten by merge:\n"
+                .to_string();
+        for path in overwritten {
+            message.push_str(&format!("\t{}\n", path));
+        }
+        message.push_str("Please commit your changes or stash them before you merge.\nAborting");
+        return Err(RepError::InvalidArgument(message));
+    }
+
+    let conflicts = result.conflicts.keys().cloned().collect::<Vec<String>>();
+    let merged = apply_merge(rep, &index, result)?;
+
+    for (path, (mode, sha)) in untracked.iter() {
+        write_workdir_file(rep, path, mode, sha)?;
+    }
+
+    if !conflicts.is_empty() {
+        merged.save(rep)?;
+        for path in conflicts {
+            outln!("CONFLICT (content): Merge conflict in {}", path)?;
+        }
+        outln!("The stash entry is kept in case you need it again.")?;
+        return Ok(false);
+    }
+
+    // without --index the changes stay unstaged, only new files get added to the index
+    let mut result = Index::new();
+    let new_index = staged.as_ref().unwrap_or(&index);
+    for entry in new_index.get_entries() {
+        match merged.get(entry.get_name()) {
+            Some(m) if m.get_hash() == entry.get_hash() => result.add(m.clone()),
+            _ => result.add(entry.clone()),
+        }
+    }
+    for entry in merged.get_entries() {
+        if !current.contains_key(entry.get_name()) && result.get(entry.get_name()).is_none() {
+            result.add(entry.clone());
+        }
+    }
+    result.save(rep)?;
+
+    Ok(true)
+}
+
+/// apply a stash entry and drop it if there were no conflicts
+/// returns true if the stash was applied without conflicts
+pub fn pop(rep: &Repository, stash: Option<String>, restore_index: bool) -> Result<bool, RepError> {
+    let applied = apply(rep, stash.clone(), restore_index)?;
+    if applied {
+        drop(rep, stash)?;
+    }
+    Ok(applied)
+}
+
+/// remove a stash entry from the reflog of refs/stash
+pub fn drop(rep: &Repository, stash: Option<String>) -> Result<(), RepError> {
+    let (n, hash) = find_stash(rep, stash)?;
+
+    let mut reflog = rep.read_reflog(STASH_REF);
+    reflog.remove(reflog.len() - 1 - n);
+
+    match reflog.last() {
+        Some(latest) => {
+            rep.update_ref(STASH_REF, &latest.new)?;
+            rep.write_reflog(STASH_REF, &reflog)?;
+        }
+        None => rep.delete_ref(STASH_REF),
+    }
+
+    outln!("Dropped refs/stash@{{{}}} ({})", n, hash)?;
+    Ok(())
+}
+
+/// create a branch at the commit the stash was based on and apply the stash there
+/// returns true if the stash was applied without conflicts
+pub fn branch(rep: &Repository, name: &str, stash: Option<String>) -> Result<bool, RepError> {
+    let (_, hash) = find_stash(rep, stash.clone())?;
+
+    let refname = "refs/heads/".to_string() + name;
+    if std::path::Path::new(&(rep.get_gitdir().clone() + "/" + &refname)).exists() {
+        return Err(RepError::BranchExists(name.to_string()));
+    }
+
+    // the checkout of the base would lose staged and unstaged changes
+    let index = Index::load(rep)?;
+    let head_tree = commit_tree(rep, &rep.ref_resolve("HEAD")?)?;
+    if !index.get_modified(rep).is_empty()
+        || !index.get_conflicts().is_empty()
+        || index.write_tree(rep)? != head_tree
+    {
+        return Err(RepError::InvalidArgument(
+            "Your local changes would be overwritten by checkout.".to_string(),
+        ));
+    }
+
+    let base = stash_parent(&load_commit(rep, &hash)?, &hash, 0)?;
+    rep.update_ref(&refname, &base)?;
+    rep.attach_head(&refname)?;
+    reset_workdir(rep, &commit_tree(rep, &base)?)?;
+    outln!("Switched to a new branch '{}'", name)?;
+
+    pop(rep, stash, true)
+}
+
+/// remove all stash entries
+pub fn clear(rep: &Repository) {
+    rep.delete_ref(STASH_REF);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(rep: &Repository, name: &str, content: &str) {
+        std::fs::write(rep.get_workdir().clone() + "/" + name, content).unwrap();
+    }
+
+    fn read(rep: &Repository, name: &str) -> Option<String> {
+        std::fs::read_to_string(rep.get_workdir().clone() + "/" + name).ok()
+    }
+
+    fn stage(rep: &Repository, name: &str) {
+        crate::add::add(rep, &[rep.get_workdir().clone() + "/" + name]).unwrap();
+    }
+
+    fn push_message(rep: &Repository, message: &str) {
+        let opts = PushOptions {
+            message: Some(message.to_string()),
+            ..Default::default()
+        };
+        push(rep, opts).unwrap();
+    }
+
+    #[test]
+    fn push_and_pop_restore_the_changes() {
+        let rep = Repository::temporary("stash-pop");
+        rep.commit_files(&[("a.txt", "a\n")], "first");
+        write(&rep, "a.txt", "changed\n");
+        write(&rep, "b.txt", "staged\n");
+        stage(&rep, "b.txt");
+        write(&rep, "u.txt", "untracked\n");
+
+        let opts = PushOptions {
+            include_untracked: true,
+            ..Default::default()
+        };
+        push(&rep, opts).unwrap();
+        assert_eq!(read(&rep, "a.txt").unwrap(), "a\n");
+        assert_eq!((read(&rep, "b.txt"), read(&rep, "u.txt")), (None, None));
+        assert!(Index::load(&rep).unwrap().get("b.txt").is_none());
+        assert_eq!(rep.read_reflog(STASH_REF).len(), 1);
+
+        assert!(pop(&rep, None, true).unwrap());
+        assert_eq!(read(&rep, "a.txt").unwrap(), "changed\n");
+        assert_eq!(read(&rep, "u.txt").unwrap(), "untracked\n");
+        let index = Index::load(&rep).unwrap();
+        assert!(index.get("b.txt").is_some());
+        assert!(index.get_modified(&rep).contains(&"a.txt".to_string()));
+        assert!(rep.read_reflog(STASH_REF).is_empty());
+        assert!(rep.ref_resolve(STASH_REF).is_err());
+    }
+
+    #[test]
+    fn entries_are_counted_from_the_newest() {
+        let rep = Repository::temporary("stash-entries");
+        rep.commit_files(&[("a.txt", "a\n")], "first");
+        write(&rep, "a.txt", "one\n");
+        push_message(&rep, "one");
+        write(&rep, "a.txt", "two\n");
+        push_message(&rep, "two");
+
+        let subject_of = |n: &str| subject(&rep, &resolve_stash(&rep, Some(n.into())).unwrap().1);
+        assert_eq!(subject_of("stash@{0}"), "On master: two");
+        assert_eq!(subject_of("1"), "On master: one");
+        assert!(resolve_stash(&rep, Some("2".to_string())).is_none());
+
+        drop(&rep, Some("stash@{0}".to_string())).unwrap();
+        assert_eq!(subject_of("0"), "On master: one");
+        assert!(apply(&rep, None, false).unwrap());
+        assert_eq!(read(&rep, "a.txt").unwrap(), "one\n");
+        assert_eq!(rep.read_reflog(STASH_REF).len(), 1);
+    }
+
+    #[test]
+    fn conflicts_keep_the_entry() {
+        let rep = Repository::temporary("stash-conflict");
+        rep.commit_files(&[("a.txt", "a\n")], "first");
+        write(&rep, "a.txt", "stashed\n");
+        push_message(&rep, "change");
+        rep.commit_files(&[("a.txt", "committed\n")], "second");
+
+        assert!(!pop(&rep, None, false).unwrap());
+        assert!(read(&rep, "a.txt")
+            .unwrap()
+            .contains("<<<<<<< Updated upstream"));
+        assert_eq!(rep.read_reflog(STASH_REF).len(), 1);
+    }
+
+    #[test]
+    fn branch_refuses_local_changes() {
+        let rep = Repository::temporary("stash-branch");
+        let first = rep.commit_files(&[("a.txt", "a\n")], "first");
+        write(&rep, "a.txt", "stashed\n");
+        push_message(&rep, "change");
+        rep.commit_files(&[("b.txt", "b\n")], "second");
+
+        write(&rep, "b.txt", "staged\n");
+        stage(&rep, "b.txt");
+        assert!(matches!(
+            branch(&rep, "topic", None),
+            Err(RepError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            branch(&rep, "master", None),
+            Err(RepError::BranchExists(_))
+        ));
+        assert!(rep.ref_resolve("refs/heads/topic").is_err());
+
+        crate::reset::reset(&rep, "HEAD", crate::reset::Mode::Hard).unwrap();
+        assert!(branch(&rep, "topic", None).unwrap());
+        assert_eq!(rep.get_head_ref(), Some("refs/heads/topic".to_string()));
+        assert_eq!(rep.ref_resolve("HEAD").unwrap(), first);
+        assert_eq!(read(&rep, "a.txt").unwrap(), "stashed\n");
+        assert!(rep.read_reflog(STASH_REF).is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+#![allow(dead_code)]
+
+use crate::index::{Index, IndexEntry};
+use crate::objects::Object;
+use crate::oid::ObjectId;
+use crate::repository::{RepError, Repository};
+
+/// store the index as trees and return the root tree, or the tree of the folder prefix,
+/// unmerged entries and (without missing_ok) missing objects are errors
+pub fn write_tree(
+    rep: &Repository,
+    prefix: Option<&str>,
+    missing_ok: bool,
+) -> Result<ObjectId, RepError> {
+    let index = Index::load(rep)?;
+    let error = || RepError::InvalidArgument("git-write-tree: error building trees".to_string());
+
+    if !index.get_conflicts().is_empty() {
+        for entry in index.get_entries().iter().filter(|e| e.get_stage() != 0) {
+            errln!(
+                "error: {}: unmerged ({})",
+                entry.get_name(),
+                entry.get_hash()
+            );
+        }
+        return Err(error());
+    }
+
+    if !missing_ok {
+        let missing = index
+            .get_entries()
+            .iter()
+            .filter(|e| e.get_mode() != "160000" && !Object::exists(rep, e.get_hash()))
+            .collect::<Vec<&IndexEntry>>();
+        for entry in missing.iter() {
+            errln!(
+                "error: invalid object {} {} for '{}'",
+                entry.get_mode(),
+                entry.get_hash(),
+                entry.get_name()
+            );
+        }
+        if !missing.is_empty() {
+            return Err(error());
+        }
+    }
+
+    let tree = index.write_tree(rep)?;
+    match prefix {
+        Some(prefix) => rep
+            .ref_resolve(&format!("{}:{}", tree, prefix))
+            .map_err(|_| {
+                RepError::InvalidArgument(format!("git-write-tree: prefix {} not found", prefix))
+            }),
+        None => Ok(tree),
+    }
+}